@@ -0,0 +1,99 @@
+//! Example 11. Headless rendering.
+//!
+//! Difficulty: Easy.
+//!
+//! This example shows how to render a model thumbnail without a window and save it to a file.
+//! It works even on machines without a display or GPU, Mesa's software rasterizer is enough.
+
+extern crate rg3d;
+
+use rg3d::{
+    core::{
+        algebra::{UnitQuaternion, Vector3},
+        color::Color,
+    },
+    gui::node::StubNode,
+    scene::{
+        base::BaseBuilder,
+        camera::CameraBuilder,
+        light::{BaseLightBuilder, PointLightBuilder},
+        node::Node,
+        transform::TransformBuilder,
+        Scene,
+    },
+};
+
+type GameEngine = rg3d::engine::Engine<(), StubNode>;
+
+fn main() {
+    let mut engine = GameEngine::new_headless((256, 256)).unwrap();
+
+    engine
+        .resource_manager
+        .state()
+        .set_textures_path("examples/data");
+
+    let mut scene = Scene::new();
+
+    scene.graph.add_node(Node::Camera(
+        CameraBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(0.0, 5.0, -8.0))
+                    .with_local_rotation(UnitQuaternion::from_axis_angle(
+                        &Vector3::x_axis(),
+                        20.0f32.to_radians(),
+                    ))
+                    .build(),
+            ),
+        )
+        .build(),
+    ));
+
+    scene.graph.add_node(
+        PointLightBuilder::new(BaseLightBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(0.0, 6.0, -4.0))
+                    .build(),
+            ),
+        ))
+        .with_radius(20.0)
+        .build_node(),
+    );
+
+    // Model is loaded synchronously, there is no game loop which could wait for it.
+    let model = rg3d::futures::executor::block_on(
+        engine
+            .resource_manager
+            .request_model("examples/data/mutant.FBX"),
+    )
+    .unwrap();
+    let model_handle = model.instantiate_geometry(&mut scene);
+    scene.graph[model_handle]
+        .local_transform_mut()
+        .set_scale(Vector3::new(0.05, 0.05, 0.05));
+
+    // Textures of the model are still loading in background, wait for all of them.
+    let textures = engine
+        .resource_manager
+        .state()
+        .textures()
+        .iter()
+        .map(|entry| entry.value.clone())
+        .collect::<Vec<_>>();
+    rg3d::futures::executor::block_on(rg3d::futures::future::join_all(textures));
+
+    engine.scenes.add(scene);
+    engine
+        .renderer
+        .set_ambient_color(Color::opaque(200, 200, 200));
+
+    // Automatic exposure adapts over time, so render a few frames before taking a picture.
+    for _ in 0..10 {
+        engine.update(1.0 / 60.0);
+        engine.render(1.0 / 60.0).unwrap();
+    }
+
+    engine.renderer.read_pixels().save("thumbnail.png").unwrap();
+}
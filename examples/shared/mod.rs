@@ -779,5 +779,6 @@ pub fn fix_shadows_distance(mut quality: QualitySettings) -> QualitySettings {
     // Scale distance because game world has different scale.
     quality.spot_shadows_distance *= 2.0;
     quality.point_shadows_distance *= 2.0;
+    quality.directional_shadows_distance *= 2.0;
     quality
 }
@@ -0,0 +1,131 @@
+//! Piece-wise linear curve of scalar values, it is a scalar analog of color gradient.
+
+use crate::{
+    math::lerpf,
+    visitor::{Visit, VisitResult, Visitor},
+};
+use std::cmp::Ordering;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CurvePoint {
+    location: f32,
+    value: f32,
+}
+
+impl CurvePoint {
+    pub fn new(location: f32, value: f32) -> Self {
+        Self { location, value }
+    }
+
+    pub fn location(&self) -> f32 {
+        self.location
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+impl Visit for CurvePoint {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.location.visit("Location", visitor)?;
+        self.value.visit("Value", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Curve {
+    points: Vec<CurvePoint>,
+}
+
+impl Visit for Curve {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.points.visit("Points", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+impl Curve {
+    pub fn new() -> Self {
+        Self { points: Vec::new() }
+    }
+
+    /// Creates curve that goes linearly from `begin` value at 0.0 to `end` value at 1.0.
+    pub fn linear(begin: f32, end: f32) -> Self {
+        Self {
+            points: vec![CurvePoint::new(0.0, begin), CurvePoint::new(1.0, end)],
+        }
+    }
+
+    pub fn with_point(mut self, point: CurvePoint) -> Self {
+        self.add_point(point);
+        self
+    }
+
+    pub fn add_point(&mut self, point: CurvePoint) {
+        self.points.push(point);
+        self.points.sort_by(|a, b| {
+            a.location
+                .partial_cmp(&b.location)
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+
+    pub fn points(&self) -> &[CurvePoint] {
+        &self.points
+    }
+
+    /// Returns value of the curve at given location, values outside of the curve are clamped
+    /// to values of first and last points. Empty curve has value of 1.0 everywhere.
+    pub fn value_at(&self, location: f32) -> f32 {
+        let first = match self.points.first() {
+            Some(first) => first,
+            None => return 1.0,
+        };
+        if location <= first.location {
+            return first.value;
+        }
+        let last = self.points.last().unwrap();
+        if location >= last.location {
+            return last.value;
+        }
+        let right = self
+            .points
+            .partition_point(|point| point.location <= location);
+        let (a, b) = (&self.points[right - 1], &self.points[right]);
+        let span = b.location - a.location;
+        if span <= f32::EPSILON {
+            b.value
+        } else {
+            lerpf(a.value, b.value, (location - a.location) / span)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::curve::{Curve, CurvePoint};
+
+    #[test]
+    fn curve_value_at() {
+        assert_eq!(Curve::new().value_at(0.5), 1.0);
+
+        let curve = Curve::linear(0.0, 2.0).with_point(CurvePoint::new(0.5, 4.0));
+        assert_eq!(curve.value_at(-1.0), 0.0);
+        assert_eq!(curve.value_at(0.25), 2.0);
+        assert_eq!(curve.value_at(0.5), 4.0);
+        assert_eq!(curve.value_at(0.75), 3.0);
+        assert_eq!(curve.value_at(2.0), 2.0);
+    }
+}
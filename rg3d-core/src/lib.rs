@@ -15,6 +15,7 @@ use std::{
 
 pub mod color;
 pub mod color_gradient;
+pub mod curve;
 pub mod math;
 pub mod numeric_range;
 pub mod octree;
@@ -127,6 +127,12 @@ pub struct Data<'a> {
     vec: &'a mut Vec<u8>,
 }
 
+impl<'a> Data<'a> {
+    pub fn new(vec: &'a mut Vec<u8>) -> Self {
+        Self { vec }
+    }
+}
+
 impl_field_data!(u64, FieldKind::U64);
 impl_field_data!(i64, FieldKind::I64);
 impl_field_data!(u32, FieldKind::U32);
@@ -6,7 +6,7 @@
 //!
 
 use crate::{
-    device::run_device,
+    device::{run_device, run_dummy_device, FeedCallback},
     effects::{Effect, EffectRenderTrait},
     error::SoundError,
     listener::Listener,
@@ -95,6 +95,19 @@ impl Context {
     /// sound source and send samples to default output device. This method returns Arc<Mutex<Context>>
     /// because separate thread also uses context.
     pub fn new() -> Result<Arc<Mutex<Self>>, SoundError> {
+        Self::with_device(run_device)
+    }
+
+    /// Creates new instance of context which renders sound sources as usual, but does not send
+    /// samples to any output device. Useful when there is no sound device at all or sound is not
+    /// needed, for example on servers or when engine works in headless mode.
+    pub fn new_dummy() -> Result<Arc<Mutex<Self>>, SoundError> {
+        Self::with_device(run_dummy_device)
+    }
+
+    fn with_device(
+        run: fn(u32, Box<FeedCallback>) -> Result<(), SoundError>,
+    ) -> Result<Arc<Mutex<Self>>, SoundError> {
         let context = Self {
             sources: Pool::new(),
             listener: Listener::new(),
@@ -109,7 +122,7 @@ impl Context {
 
         // Run device with a mixer callback. Mixer callback will mix samples
         // from source with a fixed rate.
-        run_device(4 * Self::SAMPLES_PER_CHANNEL as u32, {
+        run(4 * Self::SAMPLES_PER_CHANNEL as u32, {
             let context = context.clone();
             Box::new(move |buf| {
                 if let Ok(mut context) = context.lock() {
@@ -1,13 +1,16 @@
 use crate::{
+    context::SAMPLE_RATE,
     device::{Device, FeedCallback, MixContext, NativeSample},
     error::SoundError,
 };
-use std::mem::size_of;
+use std::{mem::size_of, time::Duration};
 
 pub struct DummySoundDevice {
     callback: Box<FeedCallback>,
     out_data: Vec<NativeSample>,
     mix_buffer: Vec<(f32, f32)>,
+    // Time it takes real device to play whole buffer.
+    buffer_duration: Duration,
 }
 
 impl DummySoundDevice {
@@ -17,6 +20,9 @@ impl DummySoundDevice {
             callback,
             out_data: vec![Default::default(); samples_per_channel],
             mix_buffer: vec![(0.0, 0.0); samples_per_channel],
+            buffer_duration: Duration::from_secs_f64(
+                samples_per_channel as f64 / f64::from(SAMPLE_RATE),
+            ),
         })
     }
 }
@@ -33,6 +39,7 @@ impl Device for DummySoundDevice {
     fn run(&mut self) {
         loop {
             self.mix();
+            std::thread::sleep(self.buffer_duration);
         }
     }
 }
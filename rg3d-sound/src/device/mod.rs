@@ -17,7 +17,6 @@ mod alsa;
 mod coreaudio;
 
 // The dummy target works on all platforms
-#[cfg(not(any(target_os = "windows", target_os = "linux")))]
 mod dummy;
 
 #[repr(C)]
@@ -98,3 +97,16 @@ pub(in crate) fn run_device(
     });
     Ok(())
 }
+
+/// Same as [`run_device`], but samples are not sent to any physical device, callback is
+/// still called with the rate of real device so sound sources are played as usual.
+pub(in crate) fn run_dummy_device(
+    buffer_len_bytes: u32,
+    callback: Box<FeedCallback>,
+) -> Result<(), SoundError> {
+    std::thread::spawn(move || {
+        let mut device = dummy::DummySoundDevice::new(buffer_len_bytes, callback).unwrap();
+        device.run()
+    });
+    Ok(())
+}
@@ -1,11 +1,22 @@
 //! Engine is container for all subsystems (renderer, ui, sound, resource manager). It also
-//! creates a window and an OpenGL context.
+//! creates a window and an OpenGL context. Engine could also work without a window (headless
+//! mode), this is useful for automated rendering tests or to render thumbnails on machines
+//! without a display.
 
 #![warn(missing_docs)]
 
 pub mod error;
 pub mod resource_manager;
 
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod surfaceless;
+
 use crate::core::algebra::Vector2;
 use crate::resource::texture::TextureKind;
 use crate::{
@@ -17,7 +28,7 @@ use crate::{
     scene::SceneContainer,
     sound::context::Context,
     window::{Window, WindowBuilder},
-    Api, GlProfile, GlRequest, NotCurrent, PossiblyCurrent, WindowedContext,
+    Api, ContextBuilder, GlProfile, GlRequest, NotCurrent, PossiblyCurrent, WindowedContext,
 };
 use rg3d_ui::message::MessageData;
 use std::{
@@ -25,9 +36,42 @@ use std::{
     time::{self, Duration},
 };
 
+enum GraphicsContext {
+    Windowed(glutin::WindowedContext<PossiblyCurrent>),
+    // Context is not used directly, but it must live as long as the renderer.
+    Headless(#[allow(dead_code)] HeadlessContext),
+}
+
+enum HeadlessContext {
+    Glutin(glutin::Context<PossiblyCurrent>),
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    Surfaceless(Box<surfaceless::SurfacelessContext>),
+}
+
+impl HeadlessContext {
+    fn get_proc_address(&self, symbol: &str) -> *const std::os::raw::c_void {
+        match self {
+            HeadlessContext::Glutin(context) => context.get_proc_address(symbol) as *const _,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            HeadlessContext::Surfaceless(context) => context.get_proc_address(symbol),
+        }
+    }
+}
+
 /// See module docs.
 pub struct Engine<M: MessageData, C: Control<M, C>> {
-    context: glutin::WindowedContext<PossiblyCurrent>,
     /// Current renderer. You should call at least [render] method to see your scene on screen.
     pub renderer: Renderer,
     /// User interface allows you to build interface of any kind. UI itself is *not* thread-safe,
@@ -49,6 +93,17 @@ pub struct Engine<M: MessageData, C: Control<M, C>> {
     /// for such statistics, probably it is best to make separate structure to hold all
     /// such data.
     pub ui_time: Duration,
+    // Must be the last field: it is dropped after everything that may free GPU resources.
+    context: GraphicsContext,
+}
+
+fn make_current(
+    context: glutin::Context<NotCurrent>,
+) -> Result<glutin::Context<PossiblyCurrent>, EngineError> {
+    match unsafe { context.make_current() } {
+        Ok(context) => Ok(context),
+        Err((_, e)) => Err(EngineError::from(e)),
+    }
 }
 
 impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
@@ -82,7 +137,7 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
             .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
             .build_windowed(window_builder, events_loop)?;
 
-        let mut context = match unsafe { context_wrapper.make_current() } {
+        let context = match unsafe { context_wrapper.make_current() } {
             Ok(context) => context,
             Err((_, e)) => return Err(EngineError::from(e)),
         };
@@ -90,7 +145,7 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
         let client_size = context.window().inner_size();
 
         Ok(Self {
-            renderer: Renderer::new(&mut context, client_size.into())?,
+            renderer: Renderer::new(&context, client_size.into())?,
             resource_manager: ResourceManager::new(),
             sound_context: Context::new()?,
             scenes: SceneContainer::new(),
@@ -99,23 +154,120 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
                 client_size.height as f32,
             )),
             ui_time: Default::default(),
-            context,
+            context: GraphicsContext::Windowed(context),
+        })
+    }
+
+    /// Creates new instance of engine without a window, everything is rendered into an
+    /// offscreen framebuffer of given size. Use [Renderer::read_pixels] to get rendered frame.
+    ///
+    /// On Linux and BSD an EGL context on Mesa's surfaceless platform is used, it does not need
+    /// any display server and works with GPU drivers as well as with software rasterizer
+    /// (llvmpipe). If it can't be created, OSMesa context is used as a fallback. On other
+    /// platforms a context with hidden window is created.
+    ///
+    /// Headless engine does not use any sound device, sound sources are still updated but
+    /// nothing can be heard (see [Context::new_dummy]).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use rg3d::engine::Engine;
+    /// use rg3d::gui::node::StubNode;
+    ///
+    /// let mut engine: Engine<(), StubNode> = Engine::new_headless((256, 256)).unwrap();
+    /// engine.render(0.0).unwrap();
+    /// let frame = engine.renderer.read_pixels();
+    /// ```
+    pub fn new_headless(frame_size: (u32, u32)) -> Result<Self, EngineError> {
+        let frame_size = (frame_size.0.max(1), frame_size.1.max(1));
+
+        let context_builder = ContextBuilder::new()
+            .with_gl_profile(GlProfile::Core)
+            .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)));
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        let context = match surfaceless::SurfacelessContext::new() {
+            Ok(context) => HeadlessContext::Surfaceless(Box::new(context)),
+            Err(surfaceless_error) => {
+                use glutin::platform::unix::HeadlessContextExt;
+                match context_builder.build_osmesa(frame_size.into()) {
+                    Ok(context_wrapper) => HeadlessContext::Glutin(make_current(context_wrapper)?),
+                    Err(osmesa_error) => {
+                        return Err(EngineError::from(glutin::CreationError::CreationErrors(
+                            vec![Box::new(surfaceless_error), Box::new(osmesa_error)],
+                        )))
+                    }
+                }
+            }
+        };
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        )))]
+        let context = HeadlessContext::Glutin(make_current(
+            context_builder.build_headless(&EventLoop::new(), frame_size.into())?,
+        )?);
+
+        Ok(Self {
+            renderer: Renderer::new_headless(
+                |symbol| context.get_proc_address(symbol),
+                frame_size,
+            )?,
+            resource_manager: ResourceManager::new(),
+            sound_context: Context::new_dummy()?,
+            scenes: SceneContainer::new(),
+            user_interface: UserInterface::new(Vector2::new(
+                frame_size.0 as f32,
+                frame_size.1 as f32,
+            )),
+            ui_time: Default::default(),
+            context: GraphicsContext::Headless(context),
         })
     }
 
     /// Returns reference to main window. Could be useful to set fullscreen mode, change
     /// size of window, its title, etc.
+    ///
+    /// # Panics
+    ///
+    /// Panics if engine was created by [Engine::new_headless], use [Engine::is_headless]
+    /// to check it.
     #[inline]
     pub fn get_window(&self) -> &Window {
-        self.context.window()
+        match &self.context {
+            GraphicsContext::Windowed(context) => context.window(),
+            GraphicsContext::Headless(_) => panic!("headless engine does not have a window!"),
+        }
+    }
+
+    /// Returns `true` if engine was created without a window.
+    #[inline]
+    pub fn is_headless(&self) -> bool {
+        matches!(self.context, GraphicsContext::Headless(_))
     }
 
     /// Performs single update tick with given time delta. Engine internally will perform update
     /// of all scenes, sub-systems, user interface, etc. Must be called in order to get engine
     /// functioning.
     pub fn update(&mut self, dt: f32) {
-        let inner_size = self.context.window().inner_size();
-        let window_size = Vector2::new(inner_size.width as f32, inner_size.height as f32);
+        let window_size = match &self.context {
+            GraphicsContext::Windowed(context) => {
+                let inner_size = context.window().inner_size();
+                Vector2::new(inner_size.width as f32, inner_size.height as f32)
+            }
+            GraphicsContext::Headless(_) => self.renderer.get_frame_bounds(),
+        };
 
         self.resource_manager.state().update(dt);
 
@@ -129,6 +281,7 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
             });
 
             scene.update(frame_size, dt);
+            scene.update_streaming(&self.resource_manager);
         }
 
         let time = time::Instant::now();
@@ -141,12 +294,19 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
     #[inline]
     pub fn render(&mut self, dt: f32) -> Result<(), RendererError> {
         self.user_interface.draw();
-        self.renderer.render_and_swap_buffers(
-            &self.scenes,
-            &self.user_interface.get_drawing_context(),
-            &self.context,
-            dt,
-        )
+        match &self.context {
+            GraphicsContext::Windowed(context) => self.renderer.render_and_swap_buffers(
+                &self.scenes,
+                self.user_interface.get_drawing_context(),
+                context,
+                dt,
+            ),
+            GraphicsContext::Headless(_) => self.renderer.render_offscreen(
+                &self.scenes,
+                self.user_interface.get_drawing_context(),
+                dt,
+            ),
+        }
     }
 }
 
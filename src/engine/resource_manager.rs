@@ -4,6 +4,7 @@ use crate::resource::texture::{TextureError, TextureWrapMode};
 use crate::{
     core::visitor::{Visit, VisitResult, Visitor},
     resource::{
+        material::{Material, MaterialData},
         model::{Model, ModelData},
         texture::{
             Texture, TextureData, TextureMagnificationFilter, TextureMinificationFilter,
@@ -111,6 +112,7 @@ impl Into<Arc<Mutex<SoundBuffer>>> for SharedSoundBuffer {
 pub struct ResourceManagerState {
     textures: Vec<TimedEntry<Texture>>,
     models: Vec<TimedEntry<Model>>,
+    materials: Vec<TimedEntry<Material>>,
     sound_buffers: Vec<TimedEntry<SharedSoundBuffer>>,
     /// Path to textures, extensively used for resource files which stores path in weird
     /// format (either relative or absolute) which is obviously not good for engine.
@@ -124,6 +126,7 @@ impl Default for ResourceManagerState {
         Self {
             textures: Default::default(),
             models: Default::default(),
+            materials: Default::default(),
             sound_buffers: Default::default(),
             textures_path: Default::default(),
             textures_import_options: Default::default(),
@@ -400,6 +403,60 @@ impl ResourceManager {
         result
     }
 
+    /// Tries to load new material resource from given path or get instance of existing, if any.
+    /// This method is asynchronous, it immediately returns a material which can be shared across
+    /// multiple places, the loading may fail, but it is internal state of the material. Textures
+    /// used by the material are requested from this resource manager.
+    ///
+    /// # Async/.await
+    ///
+    /// Each material implements Future trait and can be used in async contexts.
+    ///
+    /// # Supported formats
+    ///
+    /// Only native binary format is supported, such files can be created using
+    /// `MaterialData::save`.
+    pub fn request_material<P: AsRef<Path>>(&self, path: P) -> Material {
+        let mut state = self.state();
+
+        if let Some(material) = state.find_material(path.as_ref()) {
+            return material;
+        }
+
+        let material = Material::new(ResourceState::new_pending(path.as_ref().to_owned()));
+        state.materials.push(TimedEntry {
+            value: material.clone(),
+            time_to_live: MAX_RESOURCE_TTL,
+        });
+        let result = material.clone();
+        let path = path.as_ref().to_owned();
+
+        let resource_manager = self.clone();
+
+        state.thread_pool.spawn_ok(async move {
+            match MaterialData::load(&path, resource_manager).await {
+                Ok(raw_material) => {
+                    Log::writeln(format!("Material {:?} is loaded!", path));
+
+                    material.state().commit(ResourceState::Ok(raw_material));
+                }
+                Err(error) => {
+                    Log::writeln(format!(
+                        "Unable to load material from {:?}! Reason {:?}",
+                        path, error
+                    ));
+
+                    material.state().commit(ResourceState::LoadError {
+                        path,
+                        error: Some(Arc::new(error)),
+                    });
+                }
+            }
+        });
+
+        result
+    }
+
     /// Tries to load new sound buffer from given path or get instance of existing, if any.
     /// This method is **blocking**, so it will block current thread until sound buffer is
     /// loading. On failure it returns None and prints failure reason to log.
@@ -551,6 +608,51 @@ impl ResourceManager {
         Log::write("All model resources reloaded!".to_owned());
     }
 
+    /// Reloads every loaded material. This method is asynchronous, internally it uses thread pool
+    /// to run reload on separate thread per material.
+    pub async fn reload_materials(&self) {
+        let materials = {
+            let this = self.clone();
+            let state = self.state();
+
+            let materials = state
+                .materials
+                .iter()
+                .map(|m| m.value.clone())
+                .collect::<Vec<Material>>();
+
+            for material in materials.iter().cloned() {
+                let this = this.clone();
+                let path = material.state().path().to_path_buf();
+                *material.state() = ResourceState::new_pending(path.clone());
+                state.thread_pool.spawn_ok(async move {
+                    match MaterialData::load(&path, this).await {
+                        Ok(data) => {
+                            Log::writeln(format!("Material {:?} successfully reloaded!", path,));
+
+                            material.state().commit(ResourceState::Ok(data));
+                        }
+                        Err(e) => {
+                            Log::writeln(format!(
+                                "Unable to reload {:?} material! Reason: {:?}",
+                                path, e
+                            ));
+
+                            material.state().commit(ResourceState::LoadError {
+                                path,
+                                error: Some(Arc::new(e)),
+                            })
+                        }
+                    };
+                })
+            }
+
+            materials
+        };
+
+        futures::future::join_all(materials).await;
+    }
+
     /// Reloads every loaded sound buffer. This method is asynchronous, internally it uses thread pool
     /// to run reload on separate thread per sound buffer.
     pub async fn reload_sound_buffers(&self) {
@@ -626,6 +728,7 @@ impl ResourceManager {
         futures::join!(
             self.reload_textures(),
             self.reload_models(),
+            self.reload_materials(),
             self.reload_sound_buffers()
         );
     }
@@ -636,6 +739,7 @@ impl ResourceManagerState {
         Self {
             textures: Vec::new(),
             models: Vec::new(),
+            materials: Vec::new(),
             sound_buffers: Vec::new(),
             textures_path: PathBuf::from("data/textures/"),
             textures_import_options: Default::default(),
@@ -681,6 +785,22 @@ impl ResourceManagerState {
         None
     }
 
+    /// Returns shared reference to list of available materials.
+    #[inline]
+    pub fn materials(&self) -> &[TimedEntry<Material>] {
+        &self.materials
+    }
+
+    /// Tries to find material by its path. Returns None if no such material was found.
+    pub fn find_material<P: AsRef<Path>>(&self, path: P) -> Option<Material> {
+        for material in self.materials.iter() {
+            if material.state().path() == path.as_ref() {
+                return Some(material.value.clone());
+            }
+        }
+        None
+    }
+
     /// Returns shared reference to list of sound buffers.
     #[inline]
     pub fn sound_buffers(&self) -> &[TimedEntry<SharedSoundBuffer>] {
@@ -719,6 +839,8 @@ impl ResourceManagerState {
         self.sound_buffers
             .retain(|buffer| buffer.value.use_count() > 1);
         self.models.retain(|buffer| buffer.value.use_count() > 1);
+        self.materials
+            .retain(|material| material.value.use_count() > 1);
         self.textures.retain(|buffer| buffer.value.use_count() > 1);
     }
 
@@ -762,6 +884,25 @@ impl ResourceManagerState {
         });
     }
 
+    fn update_materials(&mut self, dt: f32) {
+        for material in self.materials.iter_mut() {
+            material.time_to_live -= dt;
+            if material.use_count() > 1 {
+                material.time_to_live = MAX_RESOURCE_TTL;
+            }
+        }
+        self.materials.retain(|material| {
+            let retain = material.time_to_live > 0.0;
+            if !retain && material.state().path().exists() {
+                Log::writeln(format!(
+                    "Material resource {:?} destroyed because it not used anymore!",
+                    material.state().path()
+                ));
+            }
+            retain
+        });
+    }
+
     fn update_sound_buffers(&mut self, dt: f32) {
         for buffer in self.sound_buffers.iter_mut() {
             buffer.time_to_live -= dt;
@@ -784,6 +925,7 @@ impl ResourceManagerState {
     pub(in crate) fn update(&mut self, dt: f32) {
         self.update_textures(dt);
         self.update_model(dt);
+        self.update_materials(dt);
         self.update_sound_buffers(dt);
     }
 }
@@ -798,6 +940,9 @@ impl Visit for ResourceManagerState {
         futures::executor::block_on(futures::future::join_all(
             self.models.iter().map(|m| m.value.clone()),
         ));
+        futures::executor::block_on(futures::future::join_all(
+            self.materials.iter().map(|m| m.value.clone()),
+        ));
         futures::executor::block_on(futures::future::join_all(
             self.sound_buffers.iter().map(|m| m.value.clone()),
         ));
@@ -806,6 +951,7 @@ impl Visit for ResourceManagerState {
         self.textures.visit("Textures", visitor)?;
         self.models.visit("Models", visitor)?;
         self.sound_buffers.visit("SoundBuffers", visitor)?;
+        let _ = self.materials.visit("Materials", visitor);
 
         visitor.leave_region()
     }
@@ -0,0 +1,184 @@
+//! OpenGL context that does not need any display server or window. It uses EGL with Mesa's
+//! surfaceless platform (`EGL_MESA_platform_surfaceless`), so it works on headless servers
+//! and CI machines. Context has no default framebuffer, renderer must draw into its own
+//! framebuffers.
+
+use glutin::CreationError;
+use glutin_egl_sys::egl::{
+    self,
+    types::{EGLConfig, EGLContext, EGLDisplay, EGLenum, EGLint},
+    Egl,
+};
+use libloading::{Library, Symbol};
+use std::{
+    ffi::{c_void, CString},
+    os::raw::c_char,
+    ptr,
+};
+
+/// Defined by `EGL_MESA_platform_surfaceless` extension, generated bindings do not have it.
+const PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
+
+/// EGL context without any surface, it is current on the thread that created it.
+pub(in crate) struct SurfacelessContext {
+    egl: Egl,
+    display: EGLDisplay,
+    context: EGLContext,
+    // Entry points in `egl` point into the library, so it must live as long as the context.
+    _library: Library,
+}
+
+fn load_library() -> Result<Library, CreationError> {
+    Library::new("libEGL.so.1")
+        .or_else(|_| Library::new("libEGL.so"))
+        .map_err(|e| CreationError::NotSupported(format!("unable to load libEGL: {}", e)))
+}
+
+fn egl_error(egl: &Egl, what: &str) -> CreationError {
+    CreationError::OsError(format!("{} failed with error 0x{:x}", what, unsafe {
+        egl.GetError()
+    }))
+}
+
+impl SurfacelessContext {
+    /// Creates OpenGL 3.3 core context and makes it current.
+    pub fn new() -> Result<Self, CreationError> {
+        let library = load_library()?;
+
+        let get_proc_address = unsafe {
+            library
+                .get::<unsafe extern "C" fn(*const c_char) -> *const c_void>(b"eglGetProcAddress\0")
+                .map(|symbol| *symbol)
+                .map_err(|e| CreationError::NotSupported(e.to_string()))?
+        };
+
+        // Core functions are exported by the library, extensions are available only through
+        // eglGetProcAddress.
+        let egl = Egl::load_with(|name| {
+            let name = CString::new(name).unwrap();
+            unsafe {
+                library
+                    .get::<*const c_void>(name.as_bytes_with_nul())
+                    .map(|symbol: Symbol<*const c_void>| *symbol)
+                    .unwrap_or_else(|_| get_proc_address(name.as_ptr()))
+            }
+        });
+
+        unsafe {
+            let display = if egl.GetPlatformDisplay.is_loaded() {
+                egl.GetPlatformDisplay(
+                    PLATFORM_SURFACELESS_MESA,
+                    egl::DEFAULT_DISPLAY as *mut _,
+                    ptr::null(),
+                )
+            } else if egl.GetPlatformDisplayEXT.is_loaded() {
+                egl.GetPlatformDisplayEXT(
+                    PLATFORM_SURFACELESS_MESA,
+                    egl::DEFAULT_DISPLAY as *mut _,
+                    ptr::null(),
+                )
+            } else {
+                return Err(CreationError::NotSupported(
+                    "EGL does not support platform displays".to_owned(),
+                ));
+            };
+            if display == egl::NO_DISPLAY {
+                return Err(egl_error(&egl, "eglGetPlatformDisplay"));
+            }
+
+            let (mut major, mut minor) = (0, 0);
+            if egl.Initialize(display, &mut major, &mut minor) == egl::FALSE {
+                return Err(egl_error(&egl, "eglInitialize"));
+            }
+
+            match Self::create_context(&egl, display) {
+                Ok(context) => Ok(Self {
+                    egl,
+                    display,
+                    context,
+                    _library: library,
+                }),
+                Err(e) => {
+                    egl.Terminate(display);
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    unsafe fn create_context(egl: &Egl, display: EGLDisplay) -> Result<EGLContext, CreationError> {
+        if egl.BindAPI(egl::OPENGL_API) == egl::FALSE {
+            return Err(egl_error(egl, "eglBindAPI"));
+        }
+
+        // Surface type defaults to window, but surfaceless platform has only pbuffer configs.
+        let config_attributes = [
+            egl::SURFACE_TYPE as EGLint,
+            egl::PBUFFER_BIT as EGLint,
+            egl::RENDERABLE_TYPE as EGLint,
+            egl::OPENGL_BIT as EGLint,
+            egl::NONE as EGLint,
+        ];
+        let mut config: EGLConfig = ptr::null();
+        let mut config_count = 0;
+        if egl.ChooseConfig(
+            display,
+            config_attributes.as_ptr(),
+            &mut config,
+            1,
+            &mut config_count,
+        ) == egl::FALSE
+            || config_count == 0
+        {
+            return Err(CreationError::NoAvailablePixelFormat);
+        }
+
+        let context_attributes = [
+            egl::CONTEXT_MAJOR_VERSION as EGLint,
+            3,
+            egl::CONTEXT_MINOR_VERSION as EGLint,
+            3,
+            egl::CONTEXT_OPENGL_PROFILE_MASK as EGLint,
+            egl::CONTEXT_OPENGL_CORE_PROFILE_BIT as EGLint,
+            egl::NONE as EGLint,
+        ];
+        let context = egl.CreateContext(
+            display,
+            config,
+            egl::NO_CONTEXT,
+            context_attributes.as_ptr(),
+        );
+        if context == egl::NO_CONTEXT {
+            return Err(CreationError::OpenGlVersionNotSupported);
+        }
+
+        if egl.MakeCurrent(display, egl::NO_SURFACE, egl::NO_SURFACE, context) == egl::FALSE {
+            let error = egl_error(egl, "eglMakeCurrent");
+            egl.DestroyContext(display, context);
+            return Err(error);
+        }
+
+        Ok(context)
+    }
+
+    /// Returns address of OpenGL function with given name.
+    pub fn get_proc_address(&self, name: &str) -> *const c_void {
+        let name = CString::new(name).unwrap();
+        unsafe { self.egl.GetProcAddress(name.as_ptr()) as *const _ }
+    }
+}
+
+impl Drop for SurfacelessContext {
+    fn drop(&mut self) {
+        unsafe {
+            self.egl.MakeCurrent(
+                self.display,
+                egl::NO_SURFACE,
+                egl::NO_SURFACE,
+                egl::NO_CONTEXT,
+            );
+            self.egl.DestroyContext(self.display, self.context);
+            self.egl.Terminate(self.display);
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use crate::core::arrayvec::ArrayVec;
 use crate::{
     core::{algebra::Matrix4, color::Color, pool::Handle},
     renderer::{
@@ -7,9 +6,10 @@ use crate::{
             GpuTextureKind, MagnificationFilter, MinificationFilter, PixelKind,
         },
         framework::{gpu_texture::GpuTexture, state::PipelineState},
-        surface::SurfaceSharedData,
+        surface::{BlendMode, SurfaceSharedData},
         TextureCache,
     },
+    resource::material::Material,
     scene::{graph::Graph, node::Node},
 };
 use std::sync::RwLock;
@@ -17,29 +17,32 @@ use std::{
     cell::RefCell,
     collections::HashMap,
     fmt::{Debug, Formatter},
-    iter::FromIterator,
     rc::Rc,
     sync::Arc,
 };
 use crate::scene::mesh::Mesh;
 
-pub const BONE_MATRICES_COUNT: usize = 64;
-
 #[repr(C)]
 #[doc(hidden)]
 pub struct InstanceData {
     pub color: Color,
     pub world: Matrix4<f32>,
-    pub depth_offset: f32, // Does NOT include bone matrices, they simply won't fit into vertex attributes
-                           // limit and they'll be passed using texture.
+    pub depth_offset: f32,
+    pub decal_layer_index: f32,
+    // Does NOT include bone matrices, they simply won't fit into vertex attributes
+    // limit and they're passed using texture, only offset of bone palette is stored.
+    pub bone_matrices_offset: f32,
 }
 
 pub struct SurfaceInstance {
     pub owner: Handle<Node>,
     pub world_transform: Matrix4<f32>,
-    pub bone_matrices: ArrayVec<[Matrix4<f32>; BONE_MATRICES_COUNT]>,
+    /// Index of first bone matrix of the instance in bone matrices storage of batch storage.
+    /// Bone indices of vertices are relative to this offset.
+    pub bone_matrices_offset: usize,
     pub color: Color,
     pub depth_offset: f32,
+    pub decal_layer_index: u8,
 }
 
 pub struct Batch {
@@ -49,8 +52,13 @@ pub struct Batch {
     pub normal_texture: Rc<RefCell<GpuTexture>>,
     pub specular_texture: Rc<RefCell<GpuTexture>>,
     pub roughness_texture: Rc<RefCell<GpuTexture>>,
+    pub metallic_texture: Rc<RefCell<GpuTexture>>,
+    pub ambient_occlusion_texture: Rc<RefCell<GpuTexture>>,
+    pub emission_texture: Rc<RefCell<GpuTexture>>,
     pub lightmap_texture: Rc<RefCell<GpuTexture>>,
     pub is_skinned: bool,
+    pub blend_mode: BlendMode,
+    pub material: Option<Material>,
 }
 
 impl Debug for Batch {
@@ -64,15 +72,28 @@ impl Debug for Batch {
     }
 }
 
-#[derive(Default)]
 pub struct BatchStorage {
     buffers: Vec<Vec<SurfaceInstance>>,
-    inner: HashMap<u64, usize>,
+    inner: HashMap<(u64, BlendMode, usize), usize>,
     /// Sorted list of batches.
     pub batches: Vec<Batch>,
+    /// Bone palettes of all skinned instances, each instance has its own range of
+    /// matrices (see `SurfaceInstance::bone_matrices_offset`). Every pass that draws
+    /// skinned surfaces fetches bone matrices from this storage, so amount of bones per
+    /// surface is limited only by the size of bone indices of vertices (256).
+    pub bone_matrices: MatrixStorage,
 }
 
 impl BatchStorage {
+    pub fn new(state: &mut PipelineState) -> Result<Self, RendererError> {
+        Ok(Self {
+            buffers: Default::default(),
+            inner: Default::default(),
+            batches: Default::default(),
+            bone_matrices: MatrixStorage::new(state)?,
+        })
+    }
+
     pub(in crate) fn generate_batches(
         &mut self,
         state: &mut PipelineState,
@@ -91,6 +112,7 @@ impl BatchStorage {
 
         self.batches.clear();
         self.inner.clear();
+        self.bone_matrices.clear();
 
         for (handle, mesh) in nodes {
             for surface in mesh.surfaces().iter() {
@@ -120,6 +142,21 @@ impl BatchStorage {
                 let roughness_texture = surface
                     .roughness_texture()
                     .and_then(|texture| texture_cache.get(state, texture))
+                    .unwrap_or_else(|| white_dummy.clone());
+
+                let metallic_texture = surface
+                    .metallic_texture()
+                    .and_then(|texture| texture_cache.get(state, texture))
+                    .unwrap_or_else(|| black_dummy.clone());
+
+                let ambient_occlusion_texture = surface
+                    .ambient_occlusion_texture()
+                    .and_then(|texture| texture_cache.get(state, texture))
+                    .unwrap_or_else(|| white_dummy.clone());
+
+                let emission_texture = surface
+                    .emission_texture()
+                    .and_then(|texture| texture_cache.get(state, texture))
                     .unwrap_or_else(|| black_dummy.clone());
 
                 let lightmap_texture = surface
@@ -127,8 +164,16 @@ impl BatchStorage {
                     .and_then(|texture| texture_cache.get(state, texture))
                     .unwrap_or_else(|| black_dummy.clone());
 
+                let material = surface.material();
+
                 let data = surface.data();
-                let key = &*data as *const _ as u64;
+                // Surfaces with different blend modes or materials can't be drawn in one batch
+                // even if they share the same data, they may even be rendered in different passes.
+                let key = (
+                    &*data as *const _ as u64,
+                    surface.blend_mode(),
+                    material.as_ref().map_or(0, |m| m.key()),
+                );
                 let batch = if let Some(&batch_index) = self.inner.get(&key) {
                     self.batches.get_mut(batch_index).unwrap()
                 } else {
@@ -140,8 +185,13 @@ impl BatchStorage {
                         normal_texture: normal_texture.clone(),
                         specular_texture: specular_texture.clone(),
                         roughness_texture: roughness_texture.clone(),
+                        metallic_texture: metallic_texture.clone(),
+                        ambient_occlusion_texture: ambient_occlusion_texture.clone(),
+                        emission_texture: emission_texture.clone(),
                         lightmap_texture: lightmap_texture.clone(),
                         is_skinned: !surface.bones.is_empty(),
+                        blend_mode: surface.blend_mode(),
+                        material,
                     });
                     self.batches.last_mut().unwrap()
                 };
@@ -151,17 +201,25 @@ impl BatchStorage {
                 batch.normal_texture = normal_texture;
                 batch.specular_texture = specular_texture;
                 batch.roughness_texture = roughness_texture;
+                batch.metallic_texture = metallic_texture;
+                batch.ambient_occlusion_texture = ambient_occlusion_texture;
+                batch.emission_texture = emission_texture;
                 batch.lightmap_texture = lightmap_texture;
 
+                let bone_matrices_offset =
+                    self.bone_matrices
+                        .push(surface.bones.iter().map(|&bone_handle| {
+                            let bone_node = &graph[bone_handle];
+                            bone_node.global_transform() * bone_node.inv_bind_pose_transform()
+                        }));
+
                 batch.instances.push(SurfaceInstance {
                     world_transform: world,
-                    bone_matrices: ArrayVec::from_iter(surface.bones.iter().map(|&bone_handle| {
-                        let bone_node = &graph[bone_handle];
-                        bone_node.global_transform() * bone_node.inv_bind_pose_transform()
-                    })),
+                    bone_matrices_offset,
                     color: surface.color(),
                     owner: *handle,
                     depth_offset: mesh.depth_offset_factor(),
+                    decal_layer_index: mesh.decal_layer_index(),
                 });
             }
         }
@@ -170,12 +228,14 @@ impl BatchStorage {
         // state changes during the rendering.
         self.batches
             .sort_unstable_by_key(|b| (&*b.diffuse_texture.borrow()) as *const _ as u64);
+
+        self.bone_matrices.update(state);
     }
 }
 
 pub struct MatrixStorage {
-    // Generic storage for instancing, contains all matrices needed for instanced
-    // rendering. It has variable size, but it is always multiple of 4. Each pixel
+    // Generic storage of matrices, it is used to pass bone matrices of skinned surfaces
+    // to shaders. It has variable size, but it is always multiple of 4. Each pixel
     // has RGBA components as f32 so to store 4x4 matrix we need 4 pixels.
     //
     // Q: Why it uses textures instead of SSBO?
@@ -208,16 +268,19 @@ impl MatrixStorage {
         self.matrices.clear();
     }
 
-    pub fn push_slice(&mut self, matrices: &[Matrix4<f32>]) {
-        self.matrices.extend_from_slice(matrices);
-
-        // Pad rest with zeros because we can't use tight packing in this case.
-        for _ in 0..(BONE_MATRICES_COUNT - matrices.len()) {
-            self.matrices.push(Default::default());
-        }
+    /// Adds matrices to the end of the storage and returns index of the first one.
+    pub fn push<I: IntoIterator<Item = Matrix4<f32>>>(&mut self, matrices: I) -> usize {
+        let offset = self.matrices.len();
+        self.matrices.extend(matrices);
+        offset
     }
 
     pub fn update(&mut self, state: &mut PipelineState) {
+        // Texture can't be empty.
+        if self.matrices.is_empty() {
+            self.matrices.push(Matrix4::identity());
+        }
+
         // Select width for the texture by restricting width at 1024 pixels.
         let matrices_tex_size = 1024;
         let actual_matrices_pixel_count = self.matrices.len() * 4;
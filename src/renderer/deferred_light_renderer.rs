@@ -3,6 +3,7 @@ use crate::{
         algebra::{Matrix4, Point3, Vector2, Vector3},
         color::Color,
         math::{frustum::Frustum, Matrix4Ext, Rect, TriangleDefinition},
+        pool::Handle,
         scope_profile,
     },
     renderer::{
@@ -17,18 +18,23 @@ use crate::{
             state::{ColorMask, PipelineState, StencilFunc, StencilOp},
         },
         gbuffer::GBuffer,
+        ibl::{IblRenderer, PREFILTERED_MAP_MIP_COUNT},
         light_volume::LightVolumeRenderer,
         shadow_map_renderer::{
-            PointShadowMapRenderContext, PointShadowMapRenderer, SpotShadowMapRenderer,
+            CsmRenderContext, CsmRenderer, PointShadowMapRenderContext, PointShadowMapRenderer,
+            SpotShadowMapRenderer,
         },
         ssao::ScreenSpaceAmbientOcclusionRenderer,
+        ssr::{ScreenSpaceReflectionsRenderer, SsrRenderContext},
         surface::{SurfaceSharedData, Vertex},
-        GeometryCache, QualitySettings, RenderPassStatistics, TextureCache,
+        GeometryCache, QualitySettings, RenderPassStatistics, TextureCache, CSM_MAX_CASCADES,
     },
     scene::{camera::Camera, light::Light, node::Node, Scene},
+    utils::log::Log,
 };
 use std::{
     cell::RefCell,
+    collections::HashSet,
     fmt::{Display, Formatter},
     ops::AddAssign,
     rc::Rc,
@@ -41,6 +47,18 @@ struct AmbientLightShader {
     ambient_color: UniformLocation,
     ao_sampler: UniformLocation,
     ambient_texture: UniformLocation,
+    depth_texture: UniformLocation,
+    normal_texture: UniformLocation,
+    material_texture: UniformLocation,
+    emission_texture: UniformLocation,
+    irradiance_map: UniformLocation,
+    prefiltered_map: UniformLocation,
+    brdf_lut: UniformLocation,
+    inv_view_proj_matrix: UniformLocation,
+    camera_position: UniformLocation,
+    environment_enabled: UniformLocation,
+    prefiltered_max_lod: UniformLocation,
+    ssr_enabled: UniformLocation,
 }
 
 #[derive(Copy, Clone, Default)]
@@ -50,6 +68,7 @@ pub struct LightingStatistics {
     pub spot_lights_rendered: usize,
     pub spot_shadow_maps_rendered: usize,
     pub directional_lights_rendered: usize,
+    pub csm_rendered: usize,
 }
 
 impl AddAssign for LightingStatistics {
@@ -59,6 +78,7 @@ impl AddAssign for LightingStatistics {
         self.spot_lights_rendered += rhs.spot_lights_rendered;
         self.spot_shadow_maps_rendered += rhs.spot_shadow_maps_rendered;
         self.directional_lights_rendered += rhs.directional_lights_rendered;
+        self.csm_rendered += rhs.csm_rendered;
     }
 }
 
@@ -71,12 +91,14 @@ impl Display for LightingStatistics {
             \tSpot Lights: {}\n\
             \tDirectional Lights: {}\n\
             \tPoint Shadow Maps: {}\n\
-            \tSpot Shadow Maps: {}",
+            \tSpot Shadow Maps: {}\n\
+            \tCascaded Shadow Maps: {}",
             self.point_lights_rendered,
             self.spot_lights_rendered,
             self.directional_lights_rendered,
             self.point_shadow_maps_rendered,
             self.spot_shadow_maps_rendered,
+            self.csm_rendered,
         )
     }
 }
@@ -93,6 +115,18 @@ impl AmbientLightShader {
             ambient_color: program.uniform_location("ambientColor")?,
             ao_sampler: program.uniform_location("aoSampler")?,
             ambient_texture: program.uniform_location("ambientTexture")?,
+            depth_texture: program.uniform_location("depthTexture")?,
+            normal_texture: program.uniform_location("normalTexture")?,
+            material_texture: program.uniform_location("materialTexture")?,
+            emission_texture: program.uniform_location("emissionTexture")?,
+            irradiance_map: program.uniform_location("irradianceMap")?,
+            prefiltered_map: program.uniform_location("prefilteredMap")?,
+            brdf_lut: program.uniform_location("brdfLut")?,
+            inv_view_proj_matrix: program.uniform_location("invViewProj")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            environment_enabled: program.uniform_location("environmentEnabled")?,
+            prefiltered_max_lod: program.uniform_location("prefilteredMaxLod")?,
+            ssr_enabled: program.uniform_location("ssrEnabled")?,
             program,
         })
     }
@@ -104,6 +138,7 @@ struct SpotLightShader {
     depth_sampler: UniformLocation,
     color_sampler: UniformLocation,
     normal_sampler: UniformLocation,
+    material_sampler: UniformLocation,
     spot_shadow_texture: UniformLocation,
     cookie_enabled: UniformLocation,
     cookie_texture: UniformLocation,
@@ -120,6 +155,11 @@ struct SpotLightShader {
     inv_view_proj_matrix: UniformLocation,
     camera_position: UniformLocation,
     shadow_bias: UniformLocation,
+    view_proj_matrix: UniformLocation,
+    contact_shadows_enabled: UniformLocation,
+    contact_shadows_distance: UniformLocation,
+    contact_shadows_steps: UniformLocation,
+    contact_shadows_thickness: UniformLocation,
 }
 
 impl SpotLightShader {
@@ -133,6 +173,7 @@ impl SpotLightShader {
             depth_sampler: program.uniform_location("depthTexture")?,
             color_sampler: program.uniform_location("colorTexture")?,
             normal_sampler: program.uniform_location("normalTexture")?,
+            material_sampler: program.uniform_location("materialTexture")?,
             spot_shadow_texture: program.uniform_location("spotShadowTexture")?,
             cookie_enabled: program.uniform_location("cookieEnabled")?,
             cookie_texture: program.uniform_location("cookieTexture")?,
@@ -149,6 +190,11 @@ impl SpotLightShader {
             inv_view_proj_matrix: program.uniform_location("invViewProj")?,
             camera_position: program.uniform_location("cameraPosition")?,
             shadow_bias: program.uniform_location("shadowBias")?,
+            view_proj_matrix: program.uniform_location("viewProj")?,
+            contact_shadows_enabled: program.uniform_location("contactShadowsEnabled")?,
+            contact_shadows_distance: program.uniform_location("contactShadowsDistance")?,
+            contact_shadows_steps: program.uniform_location("contactShadowsSteps")?,
+            contact_shadows_thickness: program.uniform_location("contactShadowsThickness")?,
 
             program,
         })
@@ -161,6 +207,7 @@ struct PointLightShader {
     depth_sampler: UniformLocation,
     color_sampler: UniformLocation,
     normal_sampler: UniformLocation,
+    material_sampler: UniformLocation,
     point_shadow_texture: UniformLocation,
     shadows_enabled: UniformLocation,
     soft_shadows: UniformLocation,
@@ -170,6 +217,11 @@ struct PointLightShader {
     inv_view_proj_matrix: UniformLocation,
     camera_position: UniformLocation,
     shadow_bias: UniformLocation,
+    view_proj_matrix: UniformLocation,
+    contact_shadows_enabled: UniformLocation,
+    contact_shadows_distance: UniformLocation,
+    contact_shadows_steps: UniformLocation,
+    contact_shadows_thickness: UniformLocation,
 }
 
 impl PointLightShader {
@@ -183,6 +235,7 @@ impl PointLightShader {
             depth_sampler: program.uniform_location("depthTexture")?,
             color_sampler: program.uniform_location("colorTexture")?,
             normal_sampler: program.uniform_location("normalTexture")?,
+            material_sampler: program.uniform_location("materialTexture")?,
             point_shadow_texture: program.uniform_location("pointShadowTexture")?,
             shadows_enabled: program.uniform_location("shadowsEnabled")?,
             soft_shadows: program.uniform_location("softShadows")?,
@@ -192,6 +245,11 @@ impl PointLightShader {
             inv_view_proj_matrix: program.uniform_location("invViewProj")?,
             camera_position: program.uniform_location("cameraPosition")?,
             shadow_bias: program.uniform_location("shadowBias")?,
+            view_proj_matrix: program.uniform_location("viewProj")?,
+            contact_shadows_enabled: program.uniform_location("contactShadowsEnabled")?,
+            contact_shadows_distance: program.uniform_location("contactShadowsDistance")?,
+            contact_shadows_steps: program.uniform_location("contactShadowsSteps")?,
+            contact_shadows_thickness: program.uniform_location("contactShadowsThickness")?,
 
             program,
         })
@@ -204,10 +262,25 @@ struct DirectionalLightShader {
     depth_sampler: UniformLocation,
     color_sampler: UniformLocation,
     normal_sampler: UniformLocation,
+    material_sampler: UniformLocation,
+    shadow_cascades: [UniformLocation; CSM_MAX_CASCADES],
+    light_view_proj_matrices: UniformLocation,
+    cascade_distances: UniformLocation,
+    cascade_count: UniformLocation,
+    view_matrix: UniformLocation,
+    shadows_enabled: UniformLocation,
+    soft_shadows: UniformLocation,
+    shadow_map_inv_size: UniformLocation,
+    shadow_bias: UniformLocation,
     light_direction: UniformLocation,
     light_color: UniformLocation,
     inv_view_proj_matrix: UniformLocation,
     camera_position: UniformLocation,
+    view_proj_matrix: UniformLocation,
+    contact_shadows_enabled: UniformLocation,
+    contact_shadows_distance: UniformLocation,
+    contact_shadows_steps: UniformLocation,
+    contact_shadows_thickness: UniformLocation,
 }
 
 impl DirectionalLightShader {
@@ -221,10 +294,30 @@ impl DirectionalLightShader {
             depth_sampler: program.uniform_location("depthTexture")?,
             color_sampler: program.uniform_location("colorTexture")?,
             normal_sampler: program.uniform_location("normalTexture")?,
+            material_sampler: program.uniform_location("materialTexture")?,
+            shadow_cascades: [
+                program.uniform_location("shadowCascade0")?,
+                program.uniform_location("shadowCascade1")?,
+                program.uniform_location("shadowCascade2")?,
+                program.uniform_location("shadowCascade3")?,
+            ],
+            light_view_proj_matrices: program.uniform_location("lightViewProjMatrices")?,
+            cascade_distances: program.uniform_location("cascadeDistances")?,
+            cascade_count: program.uniform_location("cascadeCount")?,
+            view_matrix: program.uniform_location("viewMatrix")?,
+            shadows_enabled: program.uniform_location("shadowsEnabled")?,
+            soft_shadows: program.uniform_location("softShadows")?,
+            shadow_map_inv_size: program.uniform_location("shadowMapInvSize")?,
+            shadow_bias: program.uniform_location("shadowBias")?,
             light_direction: program.uniform_location("lightDirection")?,
             light_color: program.uniform_location("lightColor")?,
             inv_view_proj_matrix: program.uniform_location("invViewProj")?,
             camera_position: program.uniform_location("cameraPosition")?,
+            view_proj_matrix: program.uniform_location("viewProj")?,
+            contact_shadows_enabled: program.uniform_location("contactShadowsEnabled")?,
+            contact_shadows_distance: program.uniform_location("contactShadowsDistance")?,
+            contact_shadows_steps: program.uniform_location("contactShadowsSteps")?,
+            contact_shadows_thickness: program.uniform_location("contactShadowsThickness")?,
             program,
         })
     }
@@ -232,6 +325,7 @@ impl DirectionalLightShader {
 
 pub struct DeferredLightRenderer {
     pub ssao_renderer: ScreenSpaceAmbientOcclusionRenderer,
+    ssr_renderer: ScreenSpaceReflectionsRenderer,
     spot_light_shader: SpotLightShader,
     point_light_shader: PointLightShader,
     directional_light_shader: DirectionalLightShader,
@@ -242,7 +336,9 @@ pub struct DeferredLightRenderer {
     flat_shader: FlatShader,
     spot_shadow_map_renderer: SpotShadowMapRenderer,
     point_shadow_map_renderer: PointShadowMapRenderer,
+    csm_renderer: CsmRenderer,
     light_volume: LightVolumeRenderer,
+    ibl_renderer: IblRenderer,
 }
 
 pub(in crate) struct DeferredRendererContext<'a> {
@@ -251,11 +347,13 @@ pub(in crate) struct DeferredRendererContext<'a> {
     pub camera: &'a Camera,
     pub gbuffer: &'a mut GBuffer,
     pub white_dummy: Rc<RefCell<GpuTexture>>,
+    pub environment_dummy: Rc<RefCell<GpuTexture>>,
     pub ambient_color: Color,
     pub settings: &'a QualitySettings,
     pub textures: &'a mut TextureCache,
     pub geometry_cache: &'a mut GeometryCache,
     pub batch_storage: &'a BatchStorage,
+    pub occluded_meshes: &'a HashSet<Handle<Node>>,
 }
 
 impl DeferredLightRenderer {
@@ -270,6 +368,7 @@ impl DeferredLightRenderer {
                 frame_size.0 as usize,
                 frame_size.1 as usize,
             )?,
+            ssr_renderer: ScreenSpaceReflectionsRenderer::new()?,
             spot_light_shader: SpotLightShader::new()?,
             point_light_shader: PointLightShader::new()?,
             directional_light_shader: DirectionalLightShader::new()?,
@@ -336,7 +435,14 @@ impl DeferredLightRenderer {
                 settings.point_shadow_map_size,
                 QualitySettings::default().point_shadow_map_precision,
             )?,
+            csm_renderer: CsmRenderer::new(
+                state,
+                settings.directional_shadow_map_size,
+                settings.directional_shadow_cascade_count,
+                settings.directional_shadow_map_precision,
+            )?,
             light_volume: LightVolumeRenderer::new()?,
+            ibl_renderer: IblRenderer::new(state)?,
         })
     }
 
@@ -363,10 +469,32 @@ impl DeferredLightRenderer {
                 settings.point_shadow_map_precision,
             )?;
         }
+        if settings.directional_shadow_map_size != self.csm_renderer.size()
+            || settings.directional_shadow_map_precision != self.csm_renderer.precision()
+            || settings
+                .directional_shadow_cascade_count
+                .clamp(1, CSM_MAX_CASCADES)
+                != self.csm_renderer.cascade_count()
+        {
+            self.csm_renderer = CsmRenderer::new(
+                state,
+                settings.directional_shadow_map_size,
+                settings.directional_shadow_cascade_count,
+                settings.directional_shadow_map_precision,
+            )?;
+        }
         self.ssao_renderer.set_radius(settings.ssao_radius);
         Ok(())
     }
 
+    pub(in crate) fn update(&mut self, dt: f32) {
+        self.ibl_renderer.update(dt);
+    }
+
+    pub(in crate) fn flush(&mut self) {
+        self.ibl_renderer.clear();
+    }
+
     pub fn set_frame_size(
         &mut self,
         state: &mut PipelineState,
@@ -396,13 +524,23 @@ impl DeferredLightRenderer {
             camera,
             gbuffer,
             white_dummy,
+            environment_dummy,
             ambient_color,
             settings,
             textures,
             geometry_cache,
             batch_storage,
+            occluded_meshes,
         } = args;
 
+        // Meshes hidden from the camera still may cast visible shadows, so they're skipped
+        // in shadow passes only if it was explicitly allowed.
+        let shadow_occluded_meshes = if settings.occlusion_culling_in_shadows {
+            Some(occluded_meshes)
+        } else {
+            None
+        };
+
         let viewport = Rect::new(0, 0, gbuffer.width, gbuffer.height);
         let frustum = Frustum::from(camera.view_projection_matrix()).unwrap();
 
@@ -494,6 +632,36 @@ impl DeferredLightRenderer {
             }
         }
 
+        // Environment is optional, so if its maps can't be prepared fall back to ambient
+        // lighting only instead of failing whole frame.
+        let environment_maps = match self.ibl_renderer.prepare(
+            state,
+            geometry_cache,
+            textures,
+            camera.environment_ref(),
+        ) {
+            Ok((ibl_stats, environment_maps)) => {
+                pass_stats += ibl_stats;
+                environment_maps
+            }
+            Err(e) => {
+                Log::writeln(format!(
+                    "Failed to prepare environment maps of camera. Reason: {:?}",
+                    e
+                ));
+                None
+            }
+        };
+        let (irradiance_map, prefiltered_map) = match environment_maps.as_ref() {
+            Some(maps) => (maps.irradiance.clone(), maps.prefiltered.clone()),
+            None => (environment_dummy.clone(), environment_dummy),
+        };
+        let ao_map = if settings.use_ssao {
+            self.ssao_renderer.ao_map()
+        } else {
+            white_dummy.clone()
+        };
+
         state.set_blend(true);
         state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
@@ -532,11 +700,7 @@ impl DeferredLightRenderer {
                     self.ambient_light_shader.ao_sampler,
                     UniformValue::Sampler {
                         index: 1,
-                        texture: if settings.use_ssao {
-                            self.ssao_renderer.ao_map()
-                        } else {
-                            white_dummy.clone()
-                        },
+                        texture: ao_map.clone(),
                     },
                 ),
                 (
@@ -546,6 +710,75 @@ impl DeferredLightRenderer {
                         texture: gbuffer.ambient_texture(),
                     },
                 ),
+                (
+                    self.ambient_light_shader.depth_texture,
+                    UniformValue::Sampler {
+                        index: 3,
+                        texture: gbuffer.depth(),
+                    },
+                ),
+                (
+                    self.ambient_light_shader.normal_texture,
+                    UniformValue::Sampler {
+                        index: 4,
+                        texture: gbuffer.normal_texture(),
+                    },
+                ),
+                (
+                    self.ambient_light_shader.material_texture,
+                    UniformValue::Sampler {
+                        index: 5,
+                        texture: gbuffer.material_texture(),
+                    },
+                ),
+                (
+                    self.ambient_light_shader.emission_texture,
+                    UniformValue::Sampler {
+                        index: 6,
+                        texture: gbuffer.emission_texture(),
+                    },
+                ),
+                (
+                    self.ambient_light_shader.irradiance_map,
+                    UniformValue::Sampler {
+                        index: 7,
+                        texture: irradiance_map,
+                    },
+                ),
+                (
+                    self.ambient_light_shader.prefiltered_map,
+                    UniformValue::Sampler {
+                        index: 8,
+                        texture: prefiltered_map.clone(),
+                    },
+                ),
+                (
+                    self.ambient_light_shader.brdf_lut,
+                    UniformValue::Sampler {
+                        index: 9,
+                        texture: self.ibl_renderer.brdf_lut(),
+                    },
+                ),
+                (
+                    self.ambient_light_shader.inv_view_proj_matrix,
+                    UniformValue::Matrix4(inv_view_projection),
+                ),
+                (
+                    self.ambient_light_shader.camera_position,
+                    UniformValue::Vector3(camera.global_position()),
+                ),
+                (
+                    self.ambient_light_shader.environment_enabled,
+                    UniformValue::Bool(environment_maps.is_some()),
+                ),
+                (
+                    self.ambient_light_shader.prefiltered_max_lod,
+                    UniformValue::Float((PREFILTERED_MAP_MIP_COUNT - 1) as f32),
+                ),
+                (
+                    self.ambient_light_shader.ssr_enabled,
+                    UniformValue::Bool(settings.use_ssr),
+                ),
             ],
         );
 
@@ -636,6 +869,7 @@ impl DeferredLightRenderer {
                             batch_storage,
                             geometry_cache,
                             cascade_index,
+                            shadow_occluded_meshes,
                         );
 
                         light_stats.spot_shadow_maps_rendered += 1;
@@ -656,15 +890,29 @@ impl DeferredLightRenderer {
                                     geom_cache: geometry_cache,
                                     cascade: cascade_index,
                                     batch_storage,
+                                    occluded_meshes: shadow_occluded_meshes,
                                 });
 
                         light_stats.point_shadow_maps_rendered += 1;
 
                         true
                     }
-                    Light::Directional(_) => {
-                        // TODO: Add cascaded shadow map.
-                        false
+                    Light::Directional(_) if settings.directional_shadows_enabled => {
+                        pass_stats += self.csm_renderer.render(CsmRenderContext {
+                            state,
+                            graph: &scene.graph,
+                            camera,
+                            light_direction: -emit_direction,
+                            shadows_distance: settings.directional_shadows_distance,
+                            split_scheme: settings.directional_shadow_split_scheme,
+                            geom_cache: geometry_cache,
+                            batch_storage,
+                            occluded_meshes: shadow_occluded_meshes,
+                        });
+
+                        light_stats.csm_rendered += 1;
+
+                        true
                     }
                     _ => false,
                 };
@@ -760,6 +1008,8 @@ impl DeferredLightRenderer {
 
             let quad = geometry_cache.get(state, &self.quad);
 
+            let contact_shadows_enabled = settings.use_contact_shadows && light.is_cast_shadows();
+
             pass_stats += match light {
                 Light::Spot(spot_light) => {
                     let shader = &self.spot_light_shader;
@@ -834,9 +1084,16 @@ impl DeferredLightRenderer {
                             },
                         ),
                         (
-                            shader.spot_shadow_texture,
+                            shader.material_sampler,
                             UniformValue::Sampler {
                                 index: 3,
+                                texture: gbuffer.material_texture(),
+                            },
+                        ),
+                        (
+                            shader.spot_shadow_texture,
+                            UniformValue::Sampler {
+                                index: 4,
                                 texture: self
                                     .spot_shadow_map_renderer
                                     .cascade_texture(cascade_index),
@@ -846,7 +1103,7 @@ impl DeferredLightRenderer {
                         (
                             shader.cookie_texture,
                             UniformValue::Sampler {
-                                index: 4,
+                                index: 5,
                                 texture: cookie_texture,
                             },
                         ),
@@ -854,6 +1111,26 @@ impl DeferredLightRenderer {
                             shader.shadow_bias,
                             UniformValue::Float(spot_light.shadow_bias()),
                         ),
+                        (
+                            shader.view_proj_matrix,
+                            UniformValue::Matrix4(view_projection),
+                        ),
+                        (
+                            shader.contact_shadows_enabled,
+                            UniformValue::Bool(contact_shadows_enabled),
+                        ),
+                        (
+                            shader.contact_shadows_distance,
+                            UniformValue::Float(settings.contact_shadows_distance),
+                        ),
+                        (
+                            shader.contact_shadows_steps,
+                            UniformValue::Integer(settings.contact_shadows_steps as i32),
+                        ),
+                        (
+                            shader.contact_shadows_thickness,
+                            UniformValue::Float(settings.contact_shadows_thickness),
+                        ),
                     ];
 
                     light_stats.spot_lights_rendered += 1;
@@ -910,9 +1187,16 @@ impl DeferredLightRenderer {
                             },
                         ),
                         (
-                            shader.point_shadow_texture,
+                            shader.material_sampler,
                             UniformValue::Sampler {
                                 index: 3,
+                                texture: gbuffer.material_texture(),
+                            },
+                        ),
+                        (
+                            shader.point_shadow_texture,
+                            UniformValue::Sampler {
+                                index: 4,
                                 texture: self
                                     .point_shadow_map_renderer
                                     .cascade_texture(cascade_index),
@@ -922,6 +1206,26 @@ impl DeferredLightRenderer {
                             shader.shadow_bias,
                             UniformValue::Float(point_light.shadow_bias()),
                         ),
+                        (
+                            shader.view_proj_matrix,
+                            UniformValue::Matrix4(view_projection),
+                        ),
+                        (
+                            shader.contact_shadows_enabled,
+                            UniformValue::Bool(contact_shadows_enabled),
+                        ),
+                        (
+                            shader.contact_shadows_distance,
+                            UniformValue::Float(settings.contact_shadows_distance),
+                        ),
+                        (
+                            shader.contact_shadows_steps,
+                            UniformValue::Integer(settings.contact_shadows_steps as i32),
+                        ),
+                        (
+                            shader.contact_shadows_thickness,
+                            UniformValue::Float(settings.contact_shadows_thickness),
+                        ),
                     ];
 
                     light_stats.point_lights_rendered += 1;
@@ -935,10 +1239,77 @@ impl DeferredLightRenderer {
                         &uniforms,
                     )
                 }
-                Light::Directional(_) => {
+                Light::Directional(directional_light) => {
                     let shader = &self.directional_light_shader;
 
+                    let cascade_count = self.csm_renderer.cascade_count();
+                    let cascade_distances = &self.csm_renderer.split_distances()[1..];
+                    let cascade_texture = |cascade: usize| {
+                        if shadows_enabled && cascade < cascade_count {
+                            self.csm_renderer.cascade_texture(cascade)
+                        } else {
+                            white_dummy.clone()
+                        }
+                    };
+
                     let uniforms = [
+                        (shader.shadows_enabled, UniformValue::Bool(shadows_enabled)),
+                        (
+                            shader.soft_shadows,
+                            UniformValue::Bool(settings.directional_soft_shadows),
+                        ),
+                        (
+                            shader.shadow_map_inv_size,
+                            UniformValue::Float(1.0 / self.csm_renderer.size() as f32),
+                        ),
+                        (
+                            shader.shadow_bias,
+                            UniformValue::Float(directional_light.shadow_bias()),
+                        ),
+                        (
+                            shader.cascade_count,
+                            UniformValue::Integer(cascade_count as i32),
+                        ),
+                        (
+                            shader.cascade_distances,
+                            UniformValue::FloatArray(cascade_distances),
+                        ),
+                        (
+                            shader.light_view_proj_matrices,
+                            UniformValue::Mat4Array(self.csm_renderer.light_view_projections()),
+                        ),
+                        (
+                            shader.view_matrix,
+                            UniformValue::Matrix4(camera.view_matrix()),
+                        ),
+                        (
+                            shader.shadow_cascades[0],
+                            UniformValue::Sampler {
+                                index: 4,
+                                texture: cascade_texture(0),
+                            },
+                        ),
+                        (
+                            shader.shadow_cascades[1],
+                            UniformValue::Sampler {
+                                index: 5,
+                                texture: cascade_texture(1),
+                            },
+                        ),
+                        (
+                            shader.shadow_cascades[2],
+                            UniformValue::Sampler {
+                                index: 6,
+                                texture: cascade_texture(2),
+                            },
+                        ),
+                        (
+                            shader.shadow_cascades[3],
+                            UniformValue::Sampler {
+                                index: 7,
+                                texture: cascade_texture(3),
+                            },
+                        ),
                         (
                             shader.light_direction,
                             UniformValue::Vector3(emit_direction),
@@ -974,6 +1345,33 @@ impl DeferredLightRenderer {
                                 texture: gbuffer.normal_texture(),
                             },
                         ),
+                        (
+                            shader.material_sampler,
+                            UniformValue::Sampler {
+                                index: 3,
+                                texture: gbuffer.material_texture(),
+                            },
+                        ),
+                        (
+                            shader.view_proj_matrix,
+                            UniformValue::Matrix4(view_projection),
+                        ),
+                        (
+                            shader.contact_shadows_enabled,
+                            UniformValue::Bool(contact_shadows_enabled),
+                        ),
+                        (
+                            shader.contact_shadows_distance,
+                            UniformValue::Float(settings.contact_shadows_distance),
+                        ),
+                        (
+                            shader.contact_shadows_steps,
+                            UniformValue::Integer(settings.contact_shadows_steps as i32),
+                        ),
+                        (
+                            shader.contact_shadows_thickness,
+                            UniformValue::Float(settings.contact_shadows_thickness),
+                        ),
                     ];
 
                     light_stats.directional_lights_rendered += 1;
@@ -1012,6 +1410,22 @@ impl DeferredLightRenderer {
             }
         }
 
+        if settings.use_ssr {
+            pass_stats += self.ssr_renderer.render(SsrRenderContext {
+                state,
+                geometry_cache,
+                gbuffer,
+                camera,
+                settings,
+                ao_map,
+                prefiltered_map,
+                prefiltered_max_lod: (PREFILTERED_MAP_MIP_COUNT - 1) as f32,
+                environment_enabled: environment_maps.is_some(),
+                brdf_lut: self.ibl_renderer.brdf_lut(),
+                ambient_color,
+            });
+        }
+
         (pass_stats, light_stats)
     }
 }
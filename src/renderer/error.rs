@@ -54,6 +54,8 @@ pub enum RendererError {
     FailedToConstructFBO,
     /// Internal context error.
     Context(ContextError),
+    /// Render graph cannot be compiled, contains description of the problem.
+    InvalidRenderGraph(String),
 }
 
 impl From<NulError> for RendererError {
@@ -0,0 +1,430 @@
+//! Forward renderer draws transparent surfaces (see `BlendMode`) which can't be rendered
+//! into G-buffer. It runs after deferred lighting, surfaces are sorted back-to-front and
+//! lit by a limited set of lights closest to the camera. Forward-lit surfaces do not
+//! receive shadows.
+
+use crate::{
+    core::{
+        algebra::{Vector3, Vector4},
+        color::Color,
+        math::{frustum::Frustum, Rect},
+        pool::Handle,
+        scope_profile,
+    },
+    renderer::{
+        batch::BatchStorage,
+        error::RendererError,
+        framework::{
+            framebuffer::{CullFace, DrawParameters, FrameBuffer, FrameBufferTrait},
+            gl,
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            state::PipelineState,
+        },
+        surface::BlendMode,
+        GeometryCache, RenderPassStatistics,
+    },
+    scene::{camera::Camera, graph::Graph, light::Light, node::Node},
+};
+use std::cmp::Ordering;
+
+/// Maximum amount of lights that can affect transparent surfaces. Must be in sync with
+/// MAX_LIGHTS in forward_fs.glsl
+pub const MAX_FORWARD_LIGHTS: usize = 16;
+
+struct ForwardShader {
+    program: GpuProgram,
+    world_matrix: UniformLocation,
+    wvp_matrix: UniformLocation,
+    use_skeletal_animation: UniformLocation,
+    bone_matrices: UniformLocation,
+    bone_matrices_offset: UniformLocation,
+    diffuse_texture: UniformLocation,
+    normal_texture: UniformLocation,
+    specular_texture: UniformLocation,
+    lightmap_texture: UniformLocation,
+    roughness_texture: UniformLocation,
+    metallic_texture: UniformLocation,
+    ambient_occlusion_texture: UniformLocation,
+    emission_texture: UniformLocation,
+    diffuse_color: UniformLocation,
+    ambient_color: UniformLocation,
+    camera_position: UniformLocation,
+    light_count: UniformLocation,
+    light_kinds: UniformLocation,
+    light_positions: UniformLocation,
+    light_directions: UniformLocation,
+    light_colors: UniformLocation,
+    light_parameters: UniformLocation,
+}
+
+impl ForwardShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/forward_fs.glsl");
+        let vertex_source = include_str!("shaders/gbuffer_vs.glsl");
+        let program = GpuProgram::from_source("ForwardShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            world_matrix: program.uniform_location("worldMatrix")?,
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
+            bone_matrices: program.uniform_location("boneMatrices")?,
+            bone_matrices_offset: program.uniform_location("boneMatricesOffset")?,
+            diffuse_texture: program.uniform_location("diffuseTexture")?,
+            normal_texture: program.uniform_location("normalTexture")?,
+            specular_texture: program.uniform_location("specularTexture")?,
+            lightmap_texture: program.uniform_location("lightmapTexture")?,
+            roughness_texture: program.uniform_location("roughnessTexture")?,
+            metallic_texture: program.uniform_location("metallicTexture")?,
+            ambient_occlusion_texture: program.uniform_location("ambientOcclusionTexture")?,
+            emission_texture: program.uniform_location("emissionTexture")?,
+            diffuse_color: program.uniform_location("diffuseColor")?,
+            ambient_color: program.uniform_location("ambientColor")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            light_count: program.uniform_location("lightCount")?,
+            light_kinds: program.uniform_location("lightKinds")?,
+            light_positions: program.uniform_location("lightPositions")?,
+            light_directions: program.uniform_location("lightDirections")?,
+            light_colors: program.uniform_location("lightColors")?,
+            light_parameters: program.uniform_location("lightParameters")?,
+            program,
+        })
+    }
+}
+
+#[derive(Default)]
+struct LightSet {
+    kinds: Vec<i32>,
+    positions: Vec<Vector3<f32>>,
+    directions: Vec<Vector3<f32>>,
+    colors: Vec<Vector4<f32>>,
+    parameters: Vec<Vector3<f32>>,
+}
+
+impl LightSet {
+    fn clear(&mut self) {
+        self.kinds.clear();
+        self.positions.clear();
+        self.directions.clear();
+        self.colors.clear();
+        self.parameters.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.kinds.len()
+    }
+}
+
+struct TransparentInstance {
+    batch: usize,
+    instance: usize,
+    distance: f32,
+}
+
+pub struct ForwardRenderer {
+    shader: ForwardShader,
+    lights: LightSet,
+    // (distance to camera, handle of light) - used to select closest lights.
+    light_candidates: Vec<(f32, Handle<Node>)>,
+    instances: Vec<TransparentInstance>,
+}
+
+pub(in crate) struct ForwardRenderContext<'a, 'b, 'c> {
+    pub state: &'a mut PipelineState,
+    pub framebuffer: &'b mut FrameBuffer,
+    pub graph: &'c Graph,
+    pub camera: &'c Camera,
+    pub viewport: Rect<i32>,
+    pub ambient_color: Color,
+    pub batch_storage: &'a BatchStorage,
+    pub geom_cache: &'a mut GeometryCache,
+}
+
+impl ForwardRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: ForwardShader::new()?,
+            lights: Default::default(),
+            light_candidates: Default::default(),
+            instances: Default::default(),
+        })
+    }
+
+    fn collect_lights(&mut self, graph: &Graph, camera: &Camera, frustum: &Frustum) {
+        let camera_position = camera.global_position();
+
+        self.light_candidates.clear();
+        for (handle, node) in graph.pair_iter() {
+            if let Node::Light(light) = node {
+                if !light.global_visibility() {
+                    continue;
+                }
+
+                let position = light.global_position();
+                let distance = match light {
+                    // Directional lights affect everything, make sure they'll be selected first.
+                    Light::Directional(_) => -1.0,
+                    Light::Spot(spot) => {
+                        if !frustum.is_intersects_sphere(position, spot.distance()) {
+                            continue;
+                        }
+                        (position - camera_position).norm()
+                    }
+                    Light::Point(point) => {
+                        if !frustum.is_intersects_sphere(position, point.radius()) {
+                            continue;
+                        }
+                        (position - camera_position).norm()
+                    }
+                };
+                self.light_candidates.push((distance, handle));
+            }
+        }
+
+        // Distances are NaN if node has broken transform, such lights are kept in any order
+        // instead of panicking.
+        self.light_candidates
+            .sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        self.lights.clear();
+        for &(_, handle) in self.light_candidates.iter().take(MAX_FORWARD_LIGHTS) {
+            if let Node::Light(light) = &graph[handle] {
+                let scale = light.local_transform().scale();
+                let radius_scale = scale.x.max(scale.y).max(scale.z);
+                let direction = light
+                    .up_vector()
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_else(Vector3::z);
+
+                let (kind, parameters) = match light {
+                    Light::Point(point) => {
+                        (0, Vector3::new(point.radius() * radius_scale, 0.0, 0.0))
+                    }
+                    Light::Spot(spot) => (
+                        1,
+                        Vector3::new(
+                            spot.distance() * radius_scale,
+                            (spot.hotspot_cone_angle() * 0.5).cos(),
+                            (spot.full_cone_angle() * 0.5).cos(),
+                        ),
+                    ),
+                    Light::Directional(_) => (2, Vector3::default()),
+                };
+
+                self.lights.kinds.push(kind);
+                self.lights.positions.push(light.global_position());
+                self.lights.directions.push(direction);
+                self.lights.colors.push(light.color().as_frgba());
+                self.lights.parameters.push(parameters);
+            }
+        }
+    }
+
+    #[must_use]
+    pub(in crate) fn render(&mut self, args: ForwardRenderContext) -> RenderPassStatistics {
+        scope_profile!();
+
+        let mut statistics = RenderPassStatistics::default();
+
+        let ForwardRenderContext {
+            state,
+            framebuffer,
+            graph,
+            camera,
+            viewport,
+            ambient_color,
+            batch_storage,
+            geom_cache,
+        } = args;
+
+        // Sort transparent instances back-to-front, so they'll be blended correctly.
+        let camera_position = camera.global_position();
+        self.instances.clear();
+        for (batch_index, batch) in batch_storage.batches.iter().enumerate() {
+            if !batch.blend_mode.is_transparent() {
+                continue;
+            }
+            for (instance_index, instance) in batch.instances.iter().enumerate() {
+                if camera.visibility_cache.is_visible(instance.owner) {
+                    self.instances.push(TransparentInstance {
+                        batch: batch_index,
+                        instance: instance_index,
+                        distance: (graph[instance.owner].global_position() - camera_position)
+                            .norm_squared(),
+                    });
+                }
+            }
+        }
+
+        if self.instances.is_empty() {
+            return statistics;
+        }
+
+        self.instances.sort_unstable_by(|a, b| {
+            b.distance
+                .partial_cmp(&a.distance)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let frustum = Frustum::from(camera.view_projection_matrix()).unwrap_or_default();
+        self.collect_lights(graph, camera, &frustum);
+
+        let params = DrawParameters {
+            cull_face: CullFace::Back,
+            culling: true,
+            color_write: Default::default(),
+            depth_write: false,
+            stencil_test: false,
+            depth_test: true,
+            blend: true,
+        };
+
+        let initial_view_projection = camera.view_projection_matrix();
+
+        for transparent in self.instances.iter() {
+            let batch = &batch_storage.batches[transparent.batch];
+            let instance = &batch.instances[transparent.instance];
+
+            match batch.blend_mode {
+                BlendMode::Additive => state.set_blend_func(gl::SRC_ALPHA, gl::ONE),
+                _ => state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+            }
+
+            let view_projection = if instance.depth_offset != 0.0 {
+                let mut projection = camera.projection_matrix();
+                projection[14] -= instance.depth_offset;
+                projection * camera.view_matrix()
+            } else {
+                initial_view_projection
+            };
+
+            let data = batch.data.read().unwrap();
+            let geometry = geom_cache.get(state, &data);
+
+            statistics += framebuffer.draw(
+                geometry,
+                state,
+                viewport,
+                &self.shader.program,
+                &params,
+                &[
+                    (
+                        self.shader.diffuse_texture,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: batch.diffuse_texture.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.normal_texture,
+                        UniformValue::Sampler {
+                            index: 1,
+                            texture: batch.normal_texture.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.specular_texture,
+                        UniformValue::Sampler {
+                            index: 2,
+                            texture: batch.specular_texture.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.lightmap_texture,
+                        UniformValue::Sampler {
+                            index: 3,
+                            texture: batch.lightmap_texture.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.roughness_texture,
+                        UniformValue::Sampler {
+                            index: 4,
+                            texture: batch.roughness_texture.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.metallic_texture,
+                        UniformValue::Sampler {
+                            index: 5,
+                            texture: batch.metallic_texture.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.ambient_occlusion_texture,
+                        UniformValue::Sampler {
+                            index: 6,
+                            texture: batch.ambient_occlusion_texture.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.emission_texture,
+                        UniformValue::Sampler {
+                            index: 7,
+                            texture: batch.emission_texture.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.wvp_matrix,
+                        UniformValue::Matrix4(view_projection * instance.world_transform),
+                    ),
+                    (
+                        self.shader.world_matrix,
+                        UniformValue::Matrix4(instance.world_transform),
+                    ),
+                    (
+                        self.shader.use_skeletal_animation,
+                        UniformValue::Bool(batch.is_skinned),
+                    ),
+                    (
+                        self.shader.bone_matrices,
+                        UniformValue::Sampler {
+                            index: 8,
+                            texture: batch_storage.bone_matrices.matrices_storage.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.bone_matrices_offset,
+                        UniformValue::Integer(instance.bone_matrices_offset as i32),
+                    ),
+                    (
+                        self.shader.diffuse_color,
+                        UniformValue::Color(instance.color),
+                    ),
+                    (
+                        self.shader.ambient_color,
+                        UniformValue::Color(ambient_color),
+                    ),
+                    (
+                        self.shader.camera_position,
+                        UniformValue::Vector3(camera_position),
+                    ),
+                    (
+                        self.shader.light_count,
+                        UniformValue::Integer(self.lights.len() as i32),
+                    ),
+                    (
+                        self.shader.light_kinds,
+                        UniformValue::IntegerArray(&self.lights.kinds),
+                    ),
+                    (
+                        self.shader.light_positions,
+                        UniformValue::Vec3Array(&self.lights.positions),
+                    ),
+                    (
+                        self.shader.light_directions,
+                        UniformValue::Vec3Array(&self.lights.directions),
+                    ),
+                    (
+                        self.shader.light_colors,
+                        UniformValue::Vec4Array(&self.lights.colors),
+                    ),
+                    (
+                        self.shader.light_parameters,
+                        UniformValue::Vec3Array(&self.lights.parameters),
+                    ),
+                ],
+            );
+        }
+
+        statistics
+    }
+}
@@ -177,6 +177,18 @@ impl FrameBuffer {
         state: &mut PipelineState,
         attachment_index: usize,
         face: CubeMapFace,
+    ) -> &mut Self {
+        self.set_cubemap_face_level(state, attachment_index, face, 0)
+    }
+
+    /// Attaches given mip level of a face of cube map, it is used to render into mips
+    /// of cube maps.
+    pub fn set_cubemap_face_level(
+        &mut self,
+        state: &mut PipelineState,
+        attachment_index: usize,
+        face: CubeMapFace,
+        level: usize,
     ) -> &mut Self {
         unsafe {
             state.set_framebuffer(self.fbo);
@@ -187,7 +199,7 @@ impl FrameBuffer {
                 gl::COLOR_ATTACHMENT0 + attachment_index as u32,
                 face.into_gl_value(),
                 attachment.texture.borrow().id(),
-                0,
+                level as i32,
             );
         }
 
@@ -309,6 +321,29 @@ pub trait FrameBufferTrait {
             .bind(args.state)
             .draw_part(args.offset, args.count)
     }
+
+    /// Reads RGBA8 pixels of first color attachment in given rectangle, rows are stored
+    /// bottom-to-top.
+    fn read_pixels(&self, state: &mut PipelineState, rect: Rect<i32>) -> Vec<u8> {
+        scope_profile!();
+
+        state.set_framebuffer(self.id());
+
+        let mut pixels = vec![0u8; (rect.w().max(0) * rect.h().max(0) * 4) as usize];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                rect.x(),
+                rect.y(),
+                rect.w(),
+                rect.h(),
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+        pixels
+    }
 }
 
 impl FrameBufferTrait for FrameBuffer {
@@ -317,11 +352,20 @@ impl FrameBufferTrait for FrameBuffer {
     }
 }
 
-pub struct BackBuffer;
+/// Framebuffer that receives final image.
+pub enum BackBuffer {
+    /// Default framebuffer of a window.
+    Window,
+    /// Offscreen framebuffer, it is used when there is no window (headless rendering).
+    Offscreen(FrameBuffer),
+}
 
 impl FrameBufferTrait for BackBuffer {
     fn id(&self) -> u32 {
-        0
+        match self {
+            BackBuffer::Window => 0,
+            BackBuffer::Offscreen(framebuffer) => framebuffer.id(),
+        }
     }
 }
 
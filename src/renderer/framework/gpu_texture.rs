@@ -73,7 +73,7 @@ impl GpuTextureKind {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum PixelKind {
     F32,
     F16,
@@ -95,6 +95,7 @@ pub enum PixelKind {
     DXT3RGBA,
     DXT5RGBA,
     RGBA32F,
+    RGBA16F,
 }
 
 impl From<TexturePixelKind> for PixelKind {
@@ -121,7 +122,7 @@ impl From<TexturePixelKind> for PixelKind {
 impl PixelKind {
     fn unpack_alignment(self) -> i32 {
         match self {
-            Self::RGBA16 | Self::RGB16 | Self::RGBA32F => 8,
+            Self::RGBA16 | Self::RGB16 | Self::RGBA32F | Self::RGBA16F => 8,
             Self::RGBA8
             | Self::RGB8
             | Self::BGRA8
@@ -143,6 +144,7 @@ impl PixelKind {
             // Explicit match for rest of formats instead of _ will help to not forget
             // to add new entry here.
             Self::RGBA16
+            | Self::RGBA16F
             | Self::RGB16
             | Self::RGBA8
             | Self::RGB8
@@ -184,7 +186,7 @@ fn image_3d_size_bytes(pixel_kind: PixelKind, width: usize, height: usize, depth
     let pixel_count = width * height * depth;
     match pixel_kind {
         PixelKind::RGBA32F => 16 * pixel_count,
-        PixelKind::RGBA16 => 8 * pixel_count,
+        PixelKind::RGBA16 | PixelKind::RGBA16F => 8 * pixel_count,
         PixelKind::RGB16 => 6 * pixel_count,
         PixelKind::RGBA8
         | PixelKind::BGRA8
@@ -210,7 +212,7 @@ fn image_2d_size_bytes(pixel_kind: PixelKind, width: usize, height: usize) -> us
     let pixel_count = width * height;
     match pixel_kind {
         PixelKind::RGBA32F => 16 * pixel_count,
-        PixelKind::RGBA16 => 8 * pixel_count,
+        PixelKind::RGBA16 | PixelKind::RGBA16F => 8 * pixel_count,
         PixelKind::RGB16 => 6 * pixel_count,
         PixelKind::RGBA8
         | PixelKind::BGRA8
@@ -235,7 +237,7 @@ fn image_2d_size_bytes(pixel_kind: PixelKind, width: usize, height: usize) -> us
 fn image_1d_size_bytes(pixel_kind: PixelKind, length: usize) -> usize {
     match pixel_kind {
         PixelKind::RGBA32F => 16 * length,
-        PixelKind::RGBA16 => 8 * length,
+        PixelKind::RGBA16 | PixelKind::RGBA16F => 8 * length,
         PixelKind::RGB16 => 6 * length,
         PixelKind::RGBA8
         | PixelKind::BGRA8
@@ -567,6 +569,7 @@ impl<'a> TextureBinding<'a> {
                 PixelKind::DXT3RGBA => (0, 0, GL_COMPRESSED_RGBA_S3TC_DXT3_EXT),
                 PixelKind::DXT5RGBA => (0, 0, GL_COMPRESSED_RGBA_S3TC_DXT5_EXT),
                 PixelKind::RGBA32F => (gl::FLOAT, gl::RGBA, gl::RGBA32F),
+                PixelKind::RGBA16F => (gl::HALF_FLOAT, gl::RGBA, gl::RGBA16F),
             };
 
             let is_compressed = pixel_kind.is_compressed();
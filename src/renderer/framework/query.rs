@@ -1,47 +1,48 @@
 use crate::renderer::framework::gl;
-use crate::renderer::surface::SurfaceSharedData;
-use crate::scene::node::Node;
-use rg3d_core::pool::Handle;
 
-/// This struct represents an occlusion query
-/// useful to determine if a node is visible or
-/// not. The basic structure is:
-/// ```
-/// let query = Query::new(node_handle);
+/// This struct represents an occlusion query useful to determine if something
+/// is visible or not. Results of queries are fetched without stalling the
+/// pipeline, so usually they are available a frame after the query was issued.
+/// The basic structure is:
+/// ```ignore
+/// let mut query = Query::new();
 /// query.begin();
-/// // draw node without color and depth buffer
+/// // draw bounding volume of a node without color and depth writes
 /// query.end();
 ///
-/// if !query.is_occluded() {
-///     // draw node
+/// // Next frame.
+/// if let Some(samples_passed) = query.try_get_result() {
+///     // node is visible if any samples passed
 /// }
 /// ```
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug)]
 pub struct Query {
-    pub node: Handle<Node>,
     id: u32,
-    was_occluded: bool,
+    pending: bool,
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Query {
-    pub fn new(node: Handle<Node>) -> Self {
+    /// Creates new query object, it must be created on a thread with current OpenGL context.
+    pub fn new() -> Self {
         let mut id = 0;
         unsafe {
             gl::GenQueries(1, &mut id);
         }
 
-        Query {
-            node,
-            id,
-            was_occluded: false,
-        }
+        Query { id, pending: false }
     }
 
     /// Starts an occlusion query. Any draw calls after
     /// this method call manipulate the result of the
     /// occlusion query.
     #[inline]
-    pub fn begin(&self) {
+    pub fn begin(&mut self) {
         unsafe {
             gl::BeginQuery(gl::ANY_SAMPLES_PASSED_CONSERVATIVE, self.id);
         }
@@ -49,33 +50,47 @@ impl Query {
 
     /// Ends the current occlusion query.
     #[inline]
-    pub fn end(&self) {
+    pub fn end(&mut self) {
         unsafe {
             gl::EndQuery(gl::ANY_SAMPLES_PASSED_CONSERVATIVE);
         }
+        self.pending = true;
     }
 
-    /// If the node that has been drawn using this Query is
-    /// hidden by another object this method returns `true`.
-    /// Calling this method is only allowed after this query
-    /// has been finished by `self.end()`.
+    /// Returns `true` if query was issued, but its result was not fetched yet.
     #[inline]
-    pub fn update_occlusion_result(&mut self) -> bool {
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Returns `Some(true)` if any samples passed depth test during last query,
+    /// `Some(false)` if everything was occluded. `None` is returned if there is
+    /// no pending query or if GPU has not finished it yet, this method never waits
+    /// for the result.
+    pub fn try_get_result(&mut self) -> Option<bool> {
+        if !self.pending {
+            return None;
+        }
+
+        let mut available = 0;
+        unsafe { gl::GetQueryObjectuiv(self.id, gl::QUERY_RESULT_AVAILABLE, &mut available) }
+        if available == 0 {
+            return None;
+        }
+
         let mut any_samples_passed = 1;
         unsafe { gl::GetQueryObjectuiv(self.id, gl::QUERY_RESULT, &mut any_samples_passed) }
 
-        let is_occluded = match any_samples_passed {
-            0 => true,
-            _ => false,
-        };
-
-        self.was_occluded = is_occluded;
+        self.pending = false;
 
-        is_occluded
+        Some(any_samples_passed != 0)
     }
+}
 
-    /// Caches last result of a query.
-    pub fn was_occluded(&self) -> bool {
-        self.was_occluded
+impl Drop for Query {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(1, &self.id);
+        }
     }
 }
@@ -60,6 +60,8 @@ pub struct PipelineState {
     blend_src_factor: GLuint,
     blend_dst_factor: GLuint,
 
+    depth_func: GLenum,
+
     program: GLuint,
     texture_units: [TextureUnit; 32],
 
@@ -161,6 +163,12 @@ impl Default for StencilOp {
 
 impl PipelineState {
     pub fn new() -> Self {
+        // Filter across faces of cube maps, otherwise edges of faces are visible on blurry
+        // mips of prefiltered environment maps.
+        unsafe {
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+        }
+
         Self {
             blend: false,
             depth_test: false,
@@ -177,6 +185,7 @@ impl PipelineState {
             viewport: Rect::new(0, 0, 1, 1),
             blend_src_factor: gl::ONE,
             blend_dst_factor: gl::ZERO,
+            depth_func: gl::LESS,
             program: 0,
             texture_units: [Default::default(); 32],
             stencil_func: Default::default(),
@@ -355,6 +364,16 @@ impl PipelineState {
         }
     }
 
+    pub fn set_depth_func(&mut self, func: GLenum) {
+        if self.depth_func != func {
+            self.depth_func = func;
+
+            unsafe {
+                gl::DepthFunc(self.depth_func);
+            }
+        }
+    }
+
     pub fn set_program(&mut self, program: GLuint) {
         if self.program != program {
             self.program = program;
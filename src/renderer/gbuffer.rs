@@ -1,30 +1,36 @@
 use crate::renderer::TextureCache;
 use crate::{
     core::{
-        algebra::{Matrix4, Vector4},
+        algebra::{Matrix4, Vector2},
         color::Color,
-        math::Rect,
+        math::{frustum::Frustum, Rect},
+        pool::Handle,
         scope_profile,
     },
     renderer::{
-        batch::{BatchStorage, InstanceData, MatrixStorage, BONE_MATRICES_COUNT},
+        batch::{BatchStorage, InstanceData},
         error::RendererError,
         framework::{
             framebuffer::{
                 Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
             },
+            gl,
             gpu_program::{GpuProgram, UniformLocation, UniformValue},
             gpu_texture::{
                 Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter,
                 PixelKind, WrapMode,
             },
-            state::PipelineState,
+            state::{ColorMask, PipelineState},
         },
+        post_processing::PostProcessBuffers,
+        shader_cache::{SamplerFallbacks, ShaderCache},
+        surface::{BlendMode, SurfaceSharedData},
         GeometryCache, RenderPassStatistics,
     },
-    scene::camera::Camera,
+    resource::ResourceState,
+    scene::{camera::Camera, graph::Graph, node::Node},
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
 
 struct InstancedShader {
     program: GpuProgram,
@@ -33,13 +39,13 @@ struct InstancedShader {
     normal_texture: UniformLocation,
     specular_texture: UniformLocation,
     roughness_texture: UniformLocation,
+    metallic_texture: UniformLocation,
+    ambient_occlusion_texture: UniformLocation,
+    emission_texture: UniformLocation,
     lightmap_texture: UniformLocation,
-    matrix_buffer_stride: UniformLocation,
-    matrix_storage_size: UniformLocation,
-    matrix_storage: UniformLocation,
-    environment_map: UniformLocation,
-    camera_position: UniformLocation,
+    bone_matrices: UniformLocation,
     view_projection_matrix: UniformLocation,
+    alpha_test: UniformLocation,
 }
 
 impl InstancedShader {
@@ -54,13 +60,13 @@ impl InstancedShader {
             normal_texture: program.uniform_location("normalTexture")?,
             specular_texture: program.uniform_location("specularTexture")?,
             roughness_texture: program.uniform_location("roughnessTexture")?,
+            metallic_texture: program.uniform_location("metallicTexture")?,
+            ambient_occlusion_texture: program.uniform_location("ambientOcclusionTexture")?,
+            emission_texture: program.uniform_location("emissionTexture")?,
             lightmap_texture: program.uniform_location("lightmapTexture")?,
-            matrix_buffer_stride: program.uniform_location("matrixBufferStride")?,
-            matrix_storage_size: program.uniform_location("matrixStorageSize")?,
-            matrix_storage: program.uniform_location("matrixStorage")?,
-            environment_map: program.uniform_location("environmentMap")?,
-            camera_position: program.uniform_location("cameraPosition")?,
+            bone_matrices: program.uniform_location("boneMatrices")?,
             view_projection_matrix: program.uniform_location("viewProjectionMatrix")?,
+            alpha_test: program.uniform_location("alphaTest")?,
             program,
         })
     }
@@ -72,14 +78,18 @@ struct Shader {
     wvp_matrix: UniformLocation,
     use_skeletal_animation: UniformLocation,
     bone_matrices: UniformLocation,
+    bone_matrices_offset: UniformLocation,
     diffuse_texture: UniformLocation,
     normal_texture: UniformLocation,
     specular_texture: UniformLocation,
     roughness_texture: UniformLocation,
+    metallic_texture: UniformLocation,
+    ambient_occlusion_texture: UniformLocation,
+    emission_texture: UniformLocation,
     lightmap_texture: UniformLocation,
     diffuse_color: UniformLocation,
-    environment_map: UniformLocation,
-    camera_position: UniformLocation,
+    layer_index: UniformLocation,
+    alpha_test: UniformLocation,
 }
 
 impl Shader {
@@ -92,14 +102,86 @@ impl Shader {
             wvp_matrix: program.uniform_location("worldViewProjection")?,
             use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
             bone_matrices: program.uniform_location("boneMatrices")?,
+            bone_matrices_offset: program.uniform_location("boneMatricesOffset")?,
             diffuse_texture: program.uniform_location("diffuseTexture")?,
             normal_texture: program.uniform_location("normalTexture")?,
             specular_texture: program.uniform_location("specularTexture")?,
             roughness_texture: program.uniform_location("roughnessTexture")?,
+            metallic_texture: program.uniform_location("metallicTexture")?,
+            ambient_occlusion_texture: program.uniform_location("ambientOcclusionTexture")?,
+            emission_texture: program.uniform_location("emissionTexture")?,
             lightmap_texture: program.uniform_location("lightmapTexture")?,
             diffuse_color: program.uniform_location("diffuseColor")?,
-            environment_map: program.uniform_location("environmentMap")?,
-            camera_position: program.uniform_location("cameraPosition")?,
+            layer_index: program.uniform_location("layerIndex")?,
+            alpha_test: program.uniform_location("alphaTest")?,
+            program,
+        })
+    }
+}
+
+struct DecalShader {
+    program: GpuProgram,
+    world_view_projection: UniformLocation,
+    world_matrix: UniformLocation,
+    scene_depth: UniformLocation,
+    diffuse_texture: UniformLocation,
+    normal_texture: UniformLocation,
+    decal_mask: UniformLocation,
+    inv_view_proj: UniformLocation,
+    inv_world_decal: UniformLocation,
+    resolution: UniformLocation,
+    color: UniformLocation,
+    layer_index: UniformLocation,
+    has_normal_texture: UniformLocation,
+}
+
+impl DecalShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/decal_fs.glsl");
+        let vertex_source = include_str!("shaders/decal_vs.glsl");
+        let program = GpuProgram::from_source("DecalShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            world_view_projection: program.uniform_location("worldViewProjection")?,
+            world_matrix: program.uniform_location("worldMatrix")?,
+            scene_depth: program.uniform_location("sceneDepth")?,
+            diffuse_texture: program.uniform_location("diffuseTexture")?,
+            normal_texture: program.uniform_location("normalTexture")?,
+            decal_mask: program.uniform_location("decalMask")?,
+            inv_view_proj: program.uniform_location("invViewProj")?,
+            inv_world_decal: program.uniform_location("invWorldDecal")?,
+            resolution: program.uniform_location("resolution")?,
+            color: program.uniform_location("color")?,
+            layer_index: program.uniform_location("layerIndex")?,
+            has_normal_texture: program.uniform_location("hasNormalTexture")?,
+            program,
+        })
+    }
+}
+
+struct TerrainShader {
+    program: GpuProgram,
+    world_matrix: UniformLocation,
+    wvp_matrix: UniformLocation,
+    diffuse_texture: UniformLocation,
+    normal_texture: UniformLocation,
+    mask_texture: UniformLocation,
+    tile_factor: UniformLocation,
+    use_mask: UniformLocation,
+}
+
+impl TerrainShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/terrain_fs.glsl");
+        let vertex_source = include_str!("shaders/terrain_vs.glsl");
+        let program = GpuProgram::from_source("TerrainShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            world_matrix: program.uniform_location("worldMatrix")?,
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            diffuse_texture: program.uniform_location("diffuseTexture")?,
+            normal_texture: program.uniform_location("normalTexture")?,
+            mask_texture: program.uniform_location("maskTexture")?,
+            tile_factor: program.uniform_location("tileFactor")?,
+            use_mask: program.uniform_location("useMask")?,
             program,
         })
     }
@@ -107,14 +189,22 @@ impl Shader {
 
 pub struct GBuffer {
     framebuffer: FrameBuffer,
+    // Contains only diffuse and normal textures of the G-buffer, decals are drawn into it
+    // while depth and decal mask are sampled.
+    decal_framebuffer: FrameBuffer,
+    // Floating-point frame, lighting and everything else is accumulated in it.
     pub final_frame: FrameBuffer,
+    // Tone mapped frame with depth of the G-buffer.
+    pub ldr_frame: FrameBuffer,
+    pub post_buffers: PostProcessBuffers,
     instanced_shader: InstancedShader,
     shader: Shader,
+    decal_shader: DecalShader,
+    terrain_shader: TerrainShader,
+    cube: SurfaceSharedData,
     pub width: i32,
     pub height: i32,
-    matrix_storage: MatrixStorage,
     instance_data_set: Vec<InstanceData>,
-    bone_matrices: Vec<Matrix4<f32>>,
 }
 
 pub(in crate) struct GBufferRenderContext<'a, 'b> {
@@ -124,6 +214,14 @@ pub(in crate) struct GBufferRenderContext<'a, 'b> {
     pub batch_storage: &'a BatchStorage,
     pub texture_cache: &'a mut TextureCache,
     pub environment_dummy: Rc<RefCell<GpuTexture>>,
+    pub graph: &'b Graph,
+    pub white_dummy: Rc<RefCell<GpuTexture>>,
+    pub normal_dummy: Rc<RefCell<GpuTexture>>,
+    pub black_dummy: Rc<RefCell<GpuTexture>>,
+    pub specular_dummy: Rc<RefCell<GpuTexture>>,
+    pub shader_cache: &'a mut ShaderCache,
+    /// Meshes that are occluded for the camera, they won't be rendered.
+    pub occluded_meshes: &'b HashSet<Handle<Node>>,
 }
 
 impl GBuffer {
@@ -192,6 +290,52 @@ impl GBuffer {
             .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
             .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
 
+        let mut decal_mask_texture = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle { width, height },
+            PixelKind::R8,
+            MinificationFilter::Nearest,
+            MagnificationFilter::Nearest,
+            1,
+            None,
+        )?;
+        decal_mask_texture
+            .bind_mut(state, 0)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+        // R - metallic, G - roughness, B - ambient occlusion.
+        let mut material_texture = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle { width, height },
+            PixelKind::RGBA8,
+            MinificationFilter::Nearest,
+            MagnificationFilter::Nearest,
+            1,
+            None,
+        )?;
+        material_texture
+            .bind_mut(state, 0)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+        let mut emission_texture = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle { width, height },
+            PixelKind::RGBA8,
+            MinificationFilter::Nearest,
+            MagnificationFilter::Nearest,
+            1,
+            None,
+        )?;
+        emission_texture
+            .bind_mut(state, 0)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+        let diffuse_texture = Rc::new(RefCell::new(diffuse_texture));
+        let normal_texture = Rc::new(RefCell::new(normal_texture));
+
         let framebuffer = FrameBuffer::new(
             state,
             Some(Attachment {
@@ -201,23 +345,50 @@ impl GBuffer {
             vec![
                 Attachment {
                     kind: AttachmentKind::Color,
-                    texture: Rc::new(RefCell::new(diffuse_texture)),
+                    texture: diffuse_texture.clone(),
                 },
                 Attachment {
                     kind: AttachmentKind::Color,
-                    texture: Rc::new(RefCell::new(normal_texture)),
+                    texture: normal_texture.clone(),
                 },
                 Attachment {
                     kind: AttachmentKind::Color,
                     texture: Rc::new(RefCell::new(ambient_texture)),
                 },
+                Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: Rc::new(RefCell::new(decal_mask_texture)),
+                },
+                Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: Rc::new(RefCell::new(material_texture)),
+                },
+                Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: Rc::new(RefCell::new(emission_texture)),
+                },
             ],
         )?;
 
-        let frame_texture = GpuTexture::new(
+        let decal_framebuffer = FrameBuffer::new(
+            state,
+            None,
+            vec![
+                Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: diffuse_texture,
+                },
+                Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: normal_texture,
+                },
+            ],
+        )?;
+
+        let hdr_frame_texture = GpuTexture::new(
             state,
             GpuTextureKind::Rectangle { width, height },
-            PixelKind::RGBA8,
+            PixelKind::RGBA16F,
             MinificationFilter::Nearest,
             MagnificationFilter::Nearest,
             1,
@@ -225,6 +396,28 @@ impl GBuffer {
         )?;
 
         let opt_framebuffer = FrameBuffer::new(
+            state,
+            Some(Attachment {
+                kind: AttachmentKind::DepthStencil,
+                texture: depth_stencil.clone(),
+            }),
+            vec![Attachment {
+                kind: AttachmentKind::Color,
+                texture: Rc::new(RefCell::new(hdr_frame_texture)),
+            }],
+        )?;
+
+        let frame_texture = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle { width, height },
+            PixelKind::RGBA8,
+            MinificationFilter::Linear,
+            MagnificationFilter::Linear,
+            1,
+            None,
+        )?;
+
+        let ldr_framebuffer = FrameBuffer::new(
             state,
             Some(Attachment {
                 kind: AttachmentKind::DepthStencil,
@@ -238,18 +431,28 @@ impl GBuffer {
 
         Ok(Self {
             framebuffer,
+            decal_framebuffer,
             instanced_shader: InstancedShader::new()?,
             shader: Shader::new()?,
+            decal_shader: DecalShader::new()?,
+            terrain_shader: TerrainShader::new()?,
+            cube: SurfaceSharedData::make_cube(Matrix4::identity()),
             width: width as i32,
             height: height as i32,
             final_frame: opt_framebuffer,
-            matrix_storage: MatrixStorage::new(state)?,
+            ldr_frame: ldr_framebuffer,
+            post_buffers: PostProcessBuffers::new(state, width, height)?,
             instance_data_set: Default::default(),
-            bone_matrices: Default::default(),
         })
     }
 
+    /// Returns tone mapped frame, it is ready to be shown on screen.
     pub fn frame_texture(&self) -> Rc<RefCell<GpuTexture>> {
+        self.ldr_frame.color_attachments()[0].texture.clone()
+    }
+
+    /// Returns floating-point frame before tone mapping.
+    pub fn hdr_frame_texture(&self) -> Rc<RefCell<GpuTexture>> {
         self.final_frame.color_attachments()[0].texture.clone()
     }
 
@@ -269,6 +472,19 @@ impl GBuffer {
         self.framebuffer.color_attachments()[2].texture.clone()
     }
 
+    pub fn decal_mask_texture(&self) -> Rc<RefCell<GpuTexture>> {
+        self.framebuffer.color_attachments()[3].texture.clone()
+    }
+
+    /// Returns texture with metallic (R), roughness (G) and ambient occlusion (B) of surfaces.
+    pub fn material_texture(&self) -> Rc<RefCell<GpuTexture>> {
+        self.framebuffer.color_attachments()[4].texture.clone()
+    }
+
+    pub fn emission_texture(&self) -> Rc<RefCell<GpuTexture>> {
+        self.framebuffer.color_attachments()[5].texture.clone()
+    }
+
     #[must_use]
     pub(in crate) fn fill(&mut self, args: GBufferRenderContext) -> RenderPassStatistics {
         scope_profile!();
@@ -282,8 +498,26 @@ impl GBuffer {
             batch_storage,
             texture_cache,
             environment_dummy,
+            graph,
+            white_dummy,
+            normal_dummy,
+            black_dummy,
+            specular_dummy,
+            shader_cache,
+            occluded_meshes,
         } = args;
 
+        let is_visible = |owner: Handle<Node>| {
+            camera.visibility_cache.is_visible(owner) && !occluded_meshes.contains(&owner)
+        };
+
+        let fallbacks = SamplerFallbacks {
+            white: white_dummy.clone(),
+            normal: normal_dummy.clone(),
+            black: black_dummy,
+            specular: specular_dummy,
+        };
+
         let viewport = Rect::new(0, 0, self.width, self.height);
         self.framebuffer.clear(
             state,
@@ -305,21 +539,127 @@ impl GBuffer {
 
         let initial_view_projection = camera.view_projection_matrix();
 
-        for batch in batch_storage.batches.iter() {
+        // Transparent batches are rendered later on in forward pass.
+        for batch in batch_storage
+            .batches
+            .iter()
+            .filter(|batch| !batch.blend_mode.is_transparent())
+        {
+            let alpha_test = batch.blend_mode == BlendMode::AlphaTest;
+
             let data = batch.data.read().unwrap();
             let geometry = geom_cache.get(state, &data);
 
-            let environment = match camera.environment_ref() {
-                Some(texture) => texture_cache.get(state, texture.clone()).unwrap(),
-                None => environment_dummy.clone(),
-            };
+            // Surfaces with a material are drawn one-by-one using shader of the material. If
+            // the material is not loaded yet or its shader is invalid, surfaces will be drawn
+            // using standard shader.
+            if let Some(material) = batch.material.as_ref() {
+                let material = material.state();
+                if let ResourceState::Ok(material) = &*material {
+                    if let Some(program) = shader_cache.get(material.shader()) {
+                        let mut uniforms = Vec::new();
+                        let sampler_count = program.fill_property_uniforms(
+                            state,
+                            material,
+                            texture_cache,
+                            &fallbacks,
+                            &mut uniforms,
+                        );
+                        if let Some(location) = program.environment_map {
+                            let environment = camera
+                                .environment_ref()
+                                .and_then(|texture| texture_cache.get(state, texture.clone()))
+                                .unwrap_or_else(|| environment_dummy.clone());
+                            uniforms.push((
+                                location,
+                                UniformValue::Sampler {
+                                    index: sampler_count,
+                                    texture: environment,
+                                },
+                            ));
+                        }
+                        if let Some(location) = program.bone_matrices {
+                            uniforms.push((
+                                location,
+                                UniformValue::Sampler {
+                                    index: sampler_count + 1,
+                                    texture: batch_storage.bone_matrices.matrices_storage.clone(),
+                                },
+                            ));
+                        }
+                        let property_count = uniforms.len();
+
+                        for instance in batch.instances.iter() {
+                            if !is_visible(instance.owner) {
+                                continue;
+                            }
+
+                            let view_projection = if instance.depth_offset != 0.0 {
+                                let mut projection = camera.projection_matrix();
+                                projection[14] -= instance.depth_offset;
+                                projection * camera.view_matrix()
+                            } else {
+                                initial_view_projection
+                            };
+
+                            uniforms.truncate(property_count);
+                            {
+                                let mut push = |location: Option<UniformLocation>, value| {
+                                    if let Some(location) = location {
+                                        uniforms.push((location, value));
+                                    }
+                                };
+                                push(
+                                    program.wvp_matrix,
+                                    UniformValue::Matrix4(
+                                        view_projection * instance.world_transform,
+                                    ),
+                                );
+                                push(
+                                    program.world_matrix,
+                                    UniformValue::Matrix4(instance.world_transform),
+                                );
+                                push(
+                                    program.use_skeletal_animation,
+                                    UniformValue::Bool(batch.is_skinned),
+                                );
+                                push(
+                                    program.bone_matrices_offset,
+                                    UniformValue::Integer(instance.bone_matrices_offset as i32),
+                                );
+                                push(
+                                    program.camera_position,
+                                    UniformValue::Vector3(camera.global_position()),
+                                );
+                                push(program.diffuse_color, UniformValue::Color(instance.color));
+                                push(
+                                    program.layer_index,
+                                    UniformValue::Integer(instance.decal_layer_index as i32),
+                                );
+                                push(program.alpha_test, UniformValue::Bool(alpha_test));
+                            }
+
+                            statistics += self.framebuffer.draw(
+                                geometry,
+                                state,
+                                viewport,
+                                &program.program,
+                                &params,
+                                &uniforms,
+                            );
+                        }
+
+                        continue;
+                    }
+                }
+            }
 
             if batch.instances.len() == 1 {
                 // Draw single instances the usual way, there is no need to spend time to
                 // pass additional data via textures on GPU just to draw single instance.
 
                 let instance = batch.instances.first().unwrap();
-                if camera.visibility_cache.is_visible(instance.owner) {
+                if is_visible(instance.owner) {
                     let view_projection = if instance.depth_offset != 0.0 {
                         let mut projection = camera.projection_matrix();
                         projection[14] -= instance.depth_offset;
@@ -364,21 +704,31 @@ impl GBuffer {
                                 },
                             ),
                             (
-                                self.shader.camera_position,
-                                UniformValue::Vector3(camera.global_position()),
-                            ),
-                            (
-                                self.shader.environment_map,
+                                self.shader.roughness_texture,
                                 UniformValue::Sampler {
                                     index: 4,
-                                    texture: environment.clone(),
+                                    texture: batch.roughness_texture.clone(),
                                 },
                             ),
                             (
-                                self.shader.roughness_texture,
+                                self.shader.metallic_texture,
                                 UniformValue::Sampler {
                                     index: 5,
-                                    texture: batch.roughness_texture.clone(),
+                                    texture: batch.metallic_texture.clone(),
+                                },
+                            ),
+                            (
+                                self.shader.ambient_occlusion_texture,
+                                UniformValue::Sampler {
+                                    index: 6,
+                                    texture: batch.ambient_occlusion_texture.clone(),
+                                },
+                            ),
+                            (
+                                self.shader.emission_texture,
+                                UniformValue::Sampler {
+                                    index: 7,
+                                    texture: batch.emission_texture.clone(),
                                 },
                             ),
                             (
@@ -397,35 +747,40 @@ impl GBuffer {
                                 self.shader.diffuse_color,
                                 UniformValue::Color(instance.color),
                             ),
+                            (
+                                self.shader.layer_index,
+                                UniformValue::Integer(instance.decal_layer_index as i32),
+                            ),
+                            (self.shader.alpha_test, UniformValue::Bool(alpha_test)),
                             (
                                 self.shader.bone_matrices,
-                                UniformValue::Mat4Array({
-                                    self.bone_matrices.clear();
-                                    self.bone_matrices
-                                        .extend_from_slice(instance.bone_matrices.as_slice());
-                                    &self.bone_matrices
-                                }),
+                                UniformValue::Sampler {
+                                    index: 8,
+                                    texture: batch_storage.bone_matrices.matrices_storage.clone(),
+                                },
+                            ),
+                            (
+                                self.shader.bone_matrices_offset,
+                                UniformValue::Integer(instance.bone_matrices_offset as i32),
                             ),
                         ],
                     );
                 }
             } else {
-                self.matrix_storage.clear();
                 self.instance_data_set.clear();
                 for instance in batch.instances.iter() {
-                    if camera.visibility_cache.is_visible(instance.owner) {
+                    if is_visible(instance.owner) {
                         self.instance_data_set.push(InstanceData {
                             color: instance.color,
                             world: instance.world_transform,
                             depth_offset: instance.depth_offset,
+                            decal_layer_index: instance.decal_layer_index as f32,
+                            bone_matrices_offset: instance.bone_matrices_offset as f32,
                         });
-                        self.matrix_storage
-                            .push_slice(instance.bone_matrices.as_slice());
                     }
                 }
 
                 if !self.instance_data_set.is_empty() {
-                    self.matrix_storage.update(state);
                     geometry.set_buffer_data(state, 1, self.instance_data_set.as_slice());
 
                     statistics += self.framebuffer.draw_instances(
@@ -465,66 +820,298 @@ impl GBuffer {
                                 },
                             ),
                             (
-                                self.instanced_shader.camera_position,
-                                UniformValue::Vector3(camera.global_position()),
-                            ),
-                            (
-                                self.instanced_shader.environment_map,
+                                self.instanced_shader.roughness_texture,
                                 UniformValue::Sampler {
                                     index: 4,
-                                    texture: environment.clone(),
+                                    texture: batch.roughness_texture.clone(),
                                 },
                             ),
                             (
-                                self.instanced_shader.roughness_texture,
+                                self.instanced_shader.metallic_texture,
                                 UniformValue::Sampler {
                                     index: 5,
-                                    texture: batch.roughness_texture.clone(),
+                                    texture: batch.metallic_texture.clone(),
                                 },
                             ),
                             (
-                                self.instanced_shader.matrix_storage,
+                                self.instanced_shader.ambient_occlusion_texture,
                                 UniformValue::Sampler {
                                     index: 6,
-                                    texture: self.matrix_storage.matrices_storage.clone(),
+                                    texture: batch.ambient_occlusion_texture.clone(),
                                 },
                             ),
                             (
-                                self.instanced_shader.use_skeletal_animation,
-                                UniformValue::Bool(batch.is_skinned),
+                                self.instanced_shader.emission_texture,
+                                UniformValue::Sampler {
+                                    index: 7,
+                                    texture: batch.emission_texture.clone(),
+                                },
                             ),
                             (
-                                self.instanced_shader.matrix_buffer_stride,
-                                UniformValue::Integer(BONE_MATRICES_COUNT as i32),
+                                self.instanced_shader.bone_matrices,
+                                UniformValue::Sampler {
+                                    index: 8,
+                                    texture: batch_storage.bone_matrices.matrices_storage.clone(),
+                                },
                             ),
                             (
-                                self.instanced_shader.matrix_storage_size,
-                                UniformValue::Vector4({
-                                    let kind = self.matrix_storage.matrices_storage.borrow().kind();
-                                    let (w, h) =
-                                        if let GpuTextureKind::Rectangle { width, height } = kind {
-                                            (width, height)
-                                        } else {
-                                            unreachable!()
-                                        };
-                                    Vector4::new(
-                                        1.0 / (w as f32),
-                                        1.0 / (h as f32),
-                                        w as f32,
-                                        h as f32,
-                                    )
-                                }),
+                                self.instanced_shader.use_skeletal_animation,
+                                UniformValue::Bool(batch.is_skinned),
                             ),
                             (
                                 self.instanced_shader.view_projection_matrix,
                                 UniformValue::Matrix4(camera.view_projection_matrix()),
                             ),
+                            (
+                                self.instanced_shader.alpha_test,
+                                UniformValue::Bool(alpha_test),
+                            ),
                         ],
                     );
                 }
             }
         }
 
+        // Terrains are drawn layer by layer, first layer is opaque and fills depth buffer,
+        // every next layer is blended over it using its mask.
+        let frustum = Frustum::from(initial_view_projection).unwrap_or_default();
+        let camera_position = camera.global_position();
+
+        let layer_params = DrawParameters {
+            cull_face: CullFace::Back,
+            culling: true,
+            // Alpha channels of G-buffer contain data (specular, etc.) of first layer.
+            color_write: ColorMask {
+                alpha: false,
+                ..Default::default()
+            },
+            depth_write: false,
+            stencil_test: false,
+            depth_test: true,
+            blend: true,
+        };
+
+        state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        state.set_depth_func(gl::LEQUAL);
+
+        for terrain in graph.linear_iter().filter_map(|node| {
+            if let Node::Terrain(terrain) = node {
+                if terrain.global_visibility() {
+                    Some(terrain)
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        }) {
+            let world = terrain.global_transform();
+            let wvp = initial_view_projection * world;
+
+            // Terrain without layers is drawn using white texture.
+            let layer_count = terrain.layers().len().max(1);
+
+            for chunk in terrain.chunks() {
+                if chunk.lods().is_empty()
+                    || !frustum.is_intersects_aabb_transform(&chunk.local_bounds(), &world)
+                {
+                    continue;
+                }
+
+                let data = &chunk.lods()[terrain.select_lod(chunk, camera_position)];
+
+                for layer_index in 0..layer_count {
+                    let layer = terrain.layers().get(layer_index);
+
+                    let diffuse_texture = layer
+                        .and_then(|layer| layer.diffuse_texture())
+                        .and_then(|texture| texture_cache.get(state, texture))
+                        .unwrap_or_else(|| white_dummy.clone());
+                    let normal_texture = layer
+                        .and_then(|layer| layer.normal_texture())
+                        .and_then(|texture| texture_cache.get(state, texture))
+                        .unwrap_or_else(|| normal_dummy.clone());
+                    let mask_texture = layer
+                        .and_then(|layer| layer.mask_texture())
+                        .and_then(|texture| texture_cache.get(state, texture))
+                        .unwrap_or_else(|| white_dummy.clone());
+                    let tile_factor = layer
+                        .map(|layer| layer.tile_factor())
+                        .unwrap_or_else(|| Vector2::new(1.0, 1.0));
+
+                    statistics += self.framebuffer.draw(
+                        geom_cache.get(state, data),
+                        state,
+                        viewport,
+                        &self.terrain_shader.program,
+                        if layer_index == 0 {
+                            &params
+                        } else {
+                            &layer_params
+                        },
+                        &[
+                            (
+                                self.terrain_shader.diffuse_texture,
+                                UniformValue::Sampler {
+                                    index: 0,
+                                    texture: diffuse_texture,
+                                },
+                            ),
+                            (
+                                self.terrain_shader.normal_texture,
+                                UniformValue::Sampler {
+                                    index: 1,
+                                    texture: normal_texture,
+                                },
+                            ),
+                            (
+                                self.terrain_shader.mask_texture,
+                                UniformValue::Sampler {
+                                    index: 2,
+                                    texture: mask_texture,
+                                },
+                            ),
+                            (
+                                self.terrain_shader.tile_factor,
+                                UniformValue::Vector2(tile_factor),
+                            ),
+                            (
+                                self.terrain_shader.use_mask,
+                                UniformValue::Bool(layer_index != 0),
+                            ),
+                            (self.terrain_shader.wvp_matrix, UniformValue::Matrix4(wvp)),
+                            (
+                                self.terrain_shader.world_matrix,
+                                UniformValue::Matrix4(world),
+                            ),
+                        ],
+                    );
+                }
+            }
+        }
+
+        state.set_depth_func(gl::LESS);
+
+        // Decals are drawn over already filled G-buffer, each decal is a box which is used
+        // to find pixels of the scene that are inside of decal volume.
+        let inv_view_proj = initial_view_projection
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+        let depth = self.depth();
+        let decal_mask = self.decal_mask_texture();
+        let resolution = Vector2::new(self.width as f32, self.height as f32);
+
+        state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        let decal_params = DrawParameters {
+            cull_face: CullFace::Front,
+            culling: true,
+            // Keep alpha channels of G-buffer intact (specular is stored in alpha of normal map).
+            color_write: ColorMask {
+                alpha: false,
+                ..Default::default()
+            },
+            depth_write: false,
+            stencil_test: false,
+            depth_test: false,
+            blend: true,
+        };
+
+        for decal in graph.linear_iter().filter_map(|node| {
+            if let Node::Decal(decal) = node {
+                if decal.global_visibility() {
+                    Some(decal)
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        }) {
+            let world = decal.global_transform();
+            let inv_world = match world.try_inverse() {
+                Some(inv_world) => inv_world,
+                // Degenerated volume, nothing can be inside of it.
+                None => continue,
+            };
+
+            let diffuse_texture = decal
+                .diffuse_texture()
+                .and_then(|texture| texture_cache.get(state, texture))
+                .unwrap_or_else(|| white_dummy.clone());
+            let (normal_texture, has_normal_texture) = match decal
+                .normal_texture()
+                .and_then(|texture| texture_cache.get(state, texture))
+            {
+                Some(texture) => (texture, true),
+                None => (normal_dummy.clone(), false),
+            };
+
+            statistics += self.decal_framebuffer.draw(
+                geom_cache.get(state, &self.cube),
+                state,
+                viewport,
+                &self.decal_shader.program,
+                &decal_params,
+                &[
+                    (
+                        self.decal_shader.scene_depth,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: depth.clone(),
+                        },
+                    ),
+                    (
+                        self.decal_shader.diffuse_texture,
+                        UniformValue::Sampler {
+                            index: 1,
+                            texture: diffuse_texture,
+                        },
+                    ),
+                    (
+                        self.decal_shader.normal_texture,
+                        UniformValue::Sampler {
+                            index: 2,
+                            texture: normal_texture,
+                        },
+                    ),
+                    (
+                        self.decal_shader.decal_mask,
+                        UniformValue::Sampler {
+                            index: 3,
+                            texture: decal_mask.clone(),
+                        },
+                    ),
+                    (
+                        self.decal_shader.inv_view_proj,
+                        UniformValue::Matrix4(inv_view_proj),
+                    ),
+                    (
+                        self.decal_shader.inv_world_decal,
+                        UniformValue::Matrix4(inv_world),
+                    ),
+                    (
+                        self.decal_shader.world_view_projection,
+                        UniformValue::Matrix4(initial_view_projection * world),
+                    ),
+                    (self.decal_shader.world_matrix, UniformValue::Matrix4(world)),
+                    (
+                        self.decal_shader.resolution,
+                        UniformValue::Vector2(resolution),
+                    ),
+                    (self.decal_shader.color, UniformValue::Color(decal.color())),
+                    (
+                        self.decal_shader.layer_index,
+                        UniformValue::Integer(decal.layer() as i32),
+                    ),
+                    (
+                        self.decal_shader.has_normal_texture,
+                        UniformValue::Bool(has_normal_texture),
+                    ),
+                ],
+            );
+        }
+
         statistics
     }
 }
@@ -0,0 +1,541 @@
+//! High dynamic range (HDR) rendering. Lights are accumulated in floating-point frame
+//! texture, so bright areas won't be clipped. At the end of the frame HDR image is
+//! converted to LDR by tone mapping operator of a camera. Exposure may be computed
+//! automatically from average luminance of the frame - this mimics adaptation of the
+//! eye to brightness of the scene.
+//!
+//! Average luminance is computed by a chain of downsampling passes of logarithm of
+//! luminance of the frame (geometric mean is less sensitive to small very bright areas).
+//! Adapted luminance is stored per camera, so each camera adapts separately.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector2, Vector3, Vector4},
+        math::Rect,
+        pool::Handle,
+        scope_profile,
+    },
+    engine::resource_manager::TimedEntry,
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{
+                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+            },
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::{
+                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter,
+                PixelKind, WrapMode,
+            },
+            state::PipelineState,
+        },
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics,
+    },
+    scene::{
+        camera::{Camera, Exposure, ToneMapping},
+        node::Node,
+        Scene,
+    },
+};
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    rc::Rc,
+};
+
+/// Sizes of textures of downsampling chain, each next is 4 times smaller than previous.
+const LUMINANCE_CHAIN: [usize; 4] = [64, 16, 4, 1];
+
+struct LuminanceShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    frame_sampler: UniformLocation,
+    viewport: UniformLocation,
+    output_size: UniformLocation,
+}
+
+impl LuminanceShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/hdr_luminance_fs.glsl");
+        let vertex_source = include_str!("shaders/hdr_vs.glsl");
+        let program = GpuProgram::from_source("LuminanceShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_sampler: program.uniform_location("frameSampler")?,
+            viewport: program.uniform_location("viewport")?,
+            output_size: program.uniform_location("outputSize")?,
+            program,
+        })
+    }
+}
+
+struct DownsampleShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    lum_sampler: UniformLocation,
+}
+
+impl DownsampleShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/hdr_downsample_fs.glsl");
+        let vertex_source = include_str!("shaders/hdr_vs.glsl");
+        let program = GpuProgram::from_source("DownsampleShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            lum_sampler: program.uniform_location("lumSampler")?,
+            program,
+        })
+    }
+}
+
+struct AdaptationShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    old_lum_sampler: UniformLocation,
+    new_lum_sampler: UniformLocation,
+    adaptation_factor: UniformLocation,
+    min_luminance: UniformLocation,
+    max_luminance: UniformLocation,
+}
+
+impl AdaptationShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/hdr_adaptation_fs.glsl");
+        let vertex_source = include_str!("shaders/hdr_vs.glsl");
+        let program = GpuProgram::from_source("AdaptationShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            old_lum_sampler: program.uniform_location("oldLumSampler")?,
+            new_lum_sampler: program.uniform_location("newLumSampler")?,
+            adaptation_factor: program.uniform_location("adaptationFactor")?,
+            min_luminance: program.uniform_location("minLuminance")?,
+            max_luminance: program.uniform_location("maxLuminance")?,
+            program,
+        })
+    }
+}
+
+struct ToneMapShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    hdr_sampler: UniformLocation,
+    lum_sampler: UniformLocation,
+    auto_exposure: UniformLocation,
+    key_value: UniformLocation,
+    exposure: UniformLocation,
+    tone_map_operator: UniformLocation,
+    bloom_sampler: UniformLocation,
+    bloom_intensity: UniformLocation,
+    frame_size: UniformLocation,
+}
+
+impl ToneMapShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/hdr_tonemap_fs.glsl");
+        let vertex_source = include_str!("shaders/hdr_vs.glsl");
+        let program = GpuProgram::from_source("ToneMapShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            hdr_sampler: program.uniform_location("hdrSampler")?,
+            lum_sampler: program.uniform_location("lumSampler")?,
+            auto_exposure: program.uniform_location("autoExposure")?,
+            key_value: program.uniform_location("keyValue")?,
+            exposure: program.uniform_location("exposure")?,
+            tone_map_operator: program.uniform_location("toneMapOperator")?,
+            bloom_sampler: program.uniform_location("bloomSampler")?,
+            bloom_intensity: program.uniform_location("bloomIntensity")?,
+            frame_size: program.uniform_location("frameSize")?,
+            program,
+        })
+    }
+}
+
+/// Adapted luminance of a camera, stored in two 1x1 textures which are swapped each
+/// frame - previous value is needed to compute new one.
+struct AdaptedLuminance {
+    buffers: [FrameBuffer; 2],
+    current: usize,
+    initialized: bool,
+}
+
+impl AdaptedLuminance {
+    fn new(state: &mut PipelineState) -> Result<Self, RendererError> {
+        Ok(Self {
+            buffers: [
+                make_luminance_buffer(state, 1)?,
+                make_luminance_buffer(state, 1)?,
+            ],
+            current: 0,
+            initialized: false,
+        })
+    }
+
+    fn texture(&self) -> Rc<RefCell<GpuTexture>> {
+        self.buffers[self.current].color_attachments()[0]
+            .texture
+            .clone()
+    }
+}
+
+pub(in crate) struct HdrRenderContext<'a, 'b> {
+    pub state: &'a mut PipelineState,
+    pub geometry_cache: &'a mut GeometryCache,
+    /// Floating-point frame with accumulated lighting.
+    pub hdr_frame: Rc<RefCell<GpuTexture>>,
+    /// Blurred bright parts of the frame and intensity of bloom.
+    pub bloom: Option<(Rc<RefCell<GpuTexture>>, f32)>,
+    /// Framebuffer to write tone mapped frame to.
+    pub ldr_framebuffer: &'a mut FrameBuffer,
+    pub viewport: Rect<i32>,
+    pub camera: &'b Camera,
+    pub scene_handle: Handle<Scene>,
+    pub camera_handle: Handle<Node>,
+    pub dt: f32,
+}
+
+pub(in crate) struct HighDynamicRangeRenderer {
+    luminance_shader: LuminanceShader,
+    downsample_shader: DownsampleShader,
+    adaptation_shader: AdaptationShader,
+    tone_map_shader: ToneMapShader,
+    luminance_chain: Vec<FrameBuffer>,
+    adapted_luminance: HashMap<(Handle<Scene>, Handle<Node>), TimedEntry<AdaptedLuminance>>,
+    quad: SurfaceSharedData,
+}
+
+fn make_luminance_buffer(
+    state: &mut PipelineState,
+    size: usize,
+) -> Result<FrameBuffer, RendererError> {
+    let mut texture = GpuTexture::new(
+        state,
+        GpuTextureKind::Rectangle {
+            width: size,
+            height: size,
+        },
+        PixelKind::F32,
+        MinificationFilter::Nearest,
+        MagnificationFilter::Nearest,
+        1,
+        None,
+    )?;
+    texture
+        .bind_mut(state, 0)
+        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+    FrameBuffer::new(
+        state,
+        None,
+        vec![Attachment {
+            kind: AttachmentKind::Color,
+            texture: Rc::new(RefCell::new(texture)),
+        }],
+    )
+}
+
+fn frame_matrix(width: f32, height: f32) -> Matrix4<f32> {
+    Matrix4::new_orthographic(0.0, width, height, 0.0, -1.0, 1.0)
+        * Matrix4::new_nonuniform_scaling(&Vector3::new(width, height, 0.0))
+}
+
+fn draw_params() -> DrawParameters {
+    DrawParameters {
+        cull_face: CullFace::Back,
+        culling: false,
+        color_write: Default::default(),
+        depth_write: false,
+        stencil_test: false,
+        depth_test: false,
+        blend: false,
+    }
+}
+
+impl HighDynamicRangeRenderer {
+    pub fn new(state: &mut PipelineState) -> Result<Self, RendererError> {
+        Ok(Self {
+            luminance_shader: LuminanceShader::new()?,
+            downsample_shader: DownsampleShader::new()?,
+            adaptation_shader: AdaptationShader::new()?,
+            tone_map_shader: ToneMapShader::new()?,
+            luminance_chain: LUMINANCE_CHAIN
+                .iter()
+                .map(|&size| make_luminance_buffer(state, size))
+                .collect::<Result<Vec<_>, _>>()?,
+            adapted_luminance: Default::default(),
+            quad: SurfaceSharedData::make_unit_xy_quad(),
+        })
+    }
+
+    fn calculate_average_luminance(
+        &mut self,
+        state: &mut PipelineState,
+        geometry_cache: &mut GeometryCache,
+        hdr_frame: Rc<RefCell<GpuTexture>>,
+        viewport: Rect<i32>,
+    ) -> RenderPassStatistics {
+        let mut statistics = RenderPassStatistics::default();
+
+        let size = LUMINANCE_CHAIN[0] as f32;
+        statistics += self.luminance_chain[0].draw(
+            geometry_cache.get(state, &self.quad),
+            state,
+            Rect::new(0, 0, size as i32, size as i32),
+            &self.luminance_shader.program,
+            &draw_params(),
+            &[
+                (
+                    self.luminance_shader.wvp_matrix,
+                    UniformValue::Matrix4(frame_matrix(size, size)),
+                ),
+                (
+                    self.luminance_shader.frame_sampler,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: hdr_frame,
+                    },
+                ),
+                (
+                    self.luminance_shader.viewport,
+                    UniformValue::Vector4(Vector4::new(
+                        viewport.x() as f32,
+                        viewport.y() as f32,
+                        viewport.w() as f32,
+                        viewport.h() as f32,
+                    )),
+                ),
+                (self.luminance_shader.output_size, UniformValue::Float(size)),
+            ],
+        );
+
+        for (i, &size) in LUMINANCE_CHAIN.iter().enumerate().skip(1) {
+            let input = self.luminance_chain[i - 1].color_attachments()[0]
+                .texture
+                .clone();
+            let size = size as f32;
+            statistics += self.luminance_chain[i].draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                Rect::new(0, 0, size as i32, size as i32),
+                &self.downsample_shader.program,
+                &draw_params(),
+                &[
+                    (
+                        self.downsample_shader.wvp_matrix,
+                        UniformValue::Matrix4(frame_matrix(size, size)),
+                    ),
+                    (
+                        self.downsample_shader.lum_sampler,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: input,
+                        },
+                    ),
+                ],
+            );
+        }
+
+        statistics
+    }
+
+    pub(in crate) fn render(
+        &mut self,
+        args: HdrRenderContext,
+    ) -> Result<RenderPassStatistics, RendererError> {
+        scope_profile!();
+
+        let HdrRenderContext {
+            state,
+            geometry_cache,
+            hdr_frame,
+            bloom,
+            ldr_framebuffer,
+            viewport,
+            camera,
+            scene_handle,
+            camera_handle,
+            dt,
+        } = args;
+
+        let mut statistics = RenderPassStatistics::default();
+
+        let (auto_exposure, key_value, exposure) = match camera.exposure() {
+            Exposure::Auto {
+                key_value,
+                min_luminance,
+                max_luminance,
+                adaptation_speed,
+            } => {
+                statistics += self.calculate_average_luminance(
+                    state,
+                    geometry_cache,
+                    hdr_frame.clone(),
+                    viewport,
+                );
+
+                let adapted = match self.adapted_luminance.entry((scene_handle, camera_handle)) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(TimedEntry {
+                        value: AdaptedLuminance::new(state)?,
+                        time_to_live: 20.0,
+                    }),
+                };
+                adapted.time_to_live = 20.0;
+
+                // First frame must not adapt from some garbage, so take current luminance as is.
+                let adaptation_factor = if adapted.initialized {
+                    1.0 - (-dt * adaptation_speed.max(0.0)).exp()
+                } else {
+                    1.0
+                };
+
+                let old = adapted.texture();
+                let current = self.luminance_chain.last().unwrap().color_attachments()[0]
+                    .texture
+                    .clone();
+                adapted.current = 1 - adapted.current;
+                let next = adapted.current;
+                statistics += adapted.buffers[next].draw(
+                    geometry_cache.get(state, &self.quad),
+                    state,
+                    Rect::new(0, 0, 1, 1),
+                    &self.adaptation_shader.program,
+                    &draw_params(),
+                    &[
+                        (
+                            self.adaptation_shader.wvp_matrix,
+                            UniformValue::Matrix4(frame_matrix(1.0, 1.0)),
+                        ),
+                        (
+                            self.adaptation_shader.old_lum_sampler,
+                            UniformValue::Sampler {
+                                index: 0,
+                                texture: old,
+                            },
+                        ),
+                        (
+                            self.adaptation_shader.new_lum_sampler,
+                            UniformValue::Sampler {
+                                index: 1,
+                                texture: current,
+                            },
+                        ),
+                        (
+                            self.adaptation_shader.adaptation_factor,
+                            UniformValue::Float(adaptation_factor),
+                        ),
+                        (
+                            self.adaptation_shader.min_luminance,
+                            UniformValue::Float(min_luminance),
+                        ),
+                        (
+                            self.adaptation_shader.max_luminance,
+                            UniformValue::Float(max_luminance.max(min_luminance)),
+                        ),
+                    ],
+                );
+                adapted.initialized = true;
+
+                (true, key_value, 1.0)
+            }
+            Exposure::Manual(exposure) => (false, 0.0, exposure),
+        };
+
+        let luminance = match self.adapted_luminance.get(&(scene_handle, camera_handle)) {
+            Some(adapted) if auto_exposure => adapted.texture(),
+            // Not used by shader, but sampler must have some texture.
+            _ => self.luminance_chain.last().unwrap().color_attachments()[0]
+                .texture
+                .clone(),
+        };
+
+        let tone_map_operator = match camera.tone_mapping() {
+            ToneMapping::None => 0,
+            ToneMapping::Reinhard => 1,
+            ToneMapping::AcesFilmic => 2,
+        };
+
+        let frame_size = match hdr_frame.borrow().kind() {
+            GpuTextureKind::Rectangle { width, height } => Vector2::new(width as f32, height as f32),
+            _ => unreachable!(),
+        };
+
+        let (bloom_texture, bloom_intensity) = match bloom {
+            Some((texture, intensity)) => (texture, intensity),
+            // Not used by shader, but sampler must have some texture.
+            None => (luminance.clone(), 0.0),
+        };
+
+        statistics += ldr_framebuffer.draw(
+            geometry_cache.get(state, &self.quad),
+            state,
+            viewport,
+            &self.tone_map_shader.program,
+            &draw_params(),
+            &[
+                (
+                    self.tone_map_shader.wvp_matrix,
+                    UniformValue::Matrix4(frame_matrix(viewport.w() as f32, viewport.h() as f32)),
+                ),
+                (
+                    self.tone_map_shader.hdr_sampler,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: hdr_frame,
+                    },
+                ),
+                (
+                    self.tone_map_shader.lum_sampler,
+                    UniformValue::Sampler {
+                        index: 1,
+                        texture: luminance,
+                    },
+                ),
+                (
+                    self.tone_map_shader.auto_exposure,
+                    UniformValue::Bool(auto_exposure),
+                ),
+                (
+                    self.tone_map_shader.key_value,
+                    UniformValue::Float(key_value),
+                ),
+                (self.tone_map_shader.exposure, UniformValue::Float(exposure)),
+                (
+                    self.tone_map_shader.tone_map_operator,
+                    UniformValue::Integer(tone_map_operator),
+                ),
+                (
+                    self.tone_map_shader.bloom_sampler,
+                    UniformValue::Sampler {
+                        index: 2,
+                        texture: bloom_texture,
+                    },
+                ),
+                (
+                    self.tone_map_shader.bloom_intensity,
+                    UniformValue::Float(bloom_intensity),
+                ),
+                (
+                    self.tone_map_shader.frame_size,
+                    UniformValue::Vector2(frame_size),
+                ),
+            ],
+        );
+
+        Ok(statistics)
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        scope_profile!();
+
+        for entry in self.adapted_luminance.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.adapted_luminance.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.adapted_luminance.clear();
+    }
+}
@@ -0,0 +1,401 @@
+//! Image-based lighting (IBL) lights surfaces by environment map of a camera. Environment
+//! is convolved into two cube maps: irradiance map (diffuse part of lighting) and
+//! prefiltered map (specular part), each mip of prefiltered map corresponds to some
+//! roughness. Lookup table with integrated BRDF is computed only once. Maps are cached
+//! per environment, so convolution is done only when environment is used first time.
+//!
+//! See "Real Shading in Unreal Engine 4" by Brian Karis for details.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector3},
+        math::Rect,
+        scope_profile,
+    },
+    engine::resource_manager::TimedEntry,
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{
+                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+            },
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::{
+                Coordinate, CubeMapFace, GpuTexture, GpuTextureKind, MagnificationFilter,
+                MinificationFilter, PixelKind, WrapMode,
+            },
+            state::PipelineState,
+        },
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics, TextureCache,
+    },
+    resource::texture::Texture,
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// Size of a face of irradiance map. Irradiance changes slowly, so small size is enough.
+pub const IRRADIANCE_MAP_SIZE: usize = 32;
+
+/// Size of a face of the first mip of prefiltered map.
+pub const PREFILTERED_MAP_SIZE: usize = 128;
+
+/// Amount of mips in prefiltered map, the last one is 1x1.
+pub const PREFILTERED_MAP_MIP_COUNT: usize = 8;
+
+const BRDF_LUT_SIZE: usize = 256;
+
+// Must be in the same order as in S_CubeMapFaceDirection.
+const FACES: [CubeMapFace; 6] = [
+    CubeMapFace::PositiveX,
+    CubeMapFace::NegativeX,
+    CubeMapFace::PositiveY,
+    CubeMapFace::NegativeY,
+    CubeMapFace::PositiveZ,
+    CubeMapFace::NegativeZ,
+];
+
+struct IrradianceShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    environment_map: UniformLocation,
+    face_index: UniformLocation,
+    face_size: UniformLocation,
+}
+
+impl IrradianceShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/ibl_irradiance_fs.glsl");
+        let vertex_source = include_str!("shaders/ibl_vs.glsl");
+        let program = GpuProgram::from_source("IrradianceShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            environment_map: program.uniform_location("environmentMap")?,
+            face_index: program.uniform_location("faceIndex")?,
+            face_size: program.uniform_location("faceSize")?,
+            program,
+        })
+    }
+}
+
+struct PrefilterShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    environment_map: UniformLocation,
+    face_index: UniformLocation,
+    face_size: UniformLocation,
+    roughness: UniformLocation,
+}
+
+impl PrefilterShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/ibl_prefilter_fs.glsl");
+        let vertex_source = include_str!("shaders/ibl_vs.glsl");
+        let program = GpuProgram::from_source("PrefilterShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            environment_map: program.uniform_location("environmentMap")?,
+            face_index: program.uniform_location("faceIndex")?,
+            face_size: program.uniform_location("faceSize")?,
+            roughness: program.uniform_location("roughness")?,
+            program,
+        })
+    }
+}
+
+struct BrdfShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    lut_size: UniformLocation,
+}
+
+impl BrdfShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/ibl_brdf_fs.glsl");
+        let vertex_source = include_str!("shaders/ibl_vs.glsl");
+        let program = GpuProgram::from_source("BrdfShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            lut_size: program.uniform_location("lutSize")?,
+            program,
+        })
+    }
+}
+
+/// Convolved environment map.
+pub(in crate) struct EnvironmentMaps {
+    pub irradiance: Rc<RefCell<GpuTexture>>,
+    pub prefiltered: Rc<RefCell<GpuTexture>>,
+}
+
+pub(in crate) struct IblRenderer {
+    irradiance_shader: IrradianceShader,
+    prefilter_shader: PrefilterShader,
+    brdf_shader: BrdfShader,
+    brdf_lut: FrameBuffer,
+    brdf_lut_ready: bool,
+    quad: SurfaceSharedData,
+    cache: HashMap<usize, TimedEntry<Rc<EnvironmentMaps>>>,
+}
+
+fn make_cube_map(
+    state: &mut PipelineState,
+    size: usize,
+    min_filter: MinificationFilter,
+    mip_count: usize,
+) -> Result<FrameBuffer, RendererError> {
+    let mut texture = GpuTexture::new(
+        state,
+        GpuTextureKind::Cube {
+            width: size,
+            height: size,
+        },
+        PixelKind::RGBA16F,
+        min_filter,
+        MagnificationFilter::Linear,
+        mip_count,
+        None,
+    )?;
+    texture
+        .bind_mut(state, 0)
+        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::T, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::R, WrapMode::ClampToEdge);
+
+    FrameBuffer::new(
+        state,
+        None,
+        vec![Attachment {
+            kind: AttachmentKind::Color,
+            texture: Rc::new(RefCell::new(texture)),
+        }],
+    )
+}
+
+fn frame_matrix(size: usize) -> Matrix4<f32> {
+    let size = size as f32;
+    Matrix4::new_orthographic(0.0, size, size, 0.0, -1.0, 1.0)
+        * Matrix4::new_nonuniform_scaling(&Vector3::new(size, size, 0.0))
+}
+
+fn draw_params() -> DrawParameters {
+    DrawParameters {
+        cull_face: CullFace::Back,
+        culling: false,
+        color_write: Default::default(),
+        depth_write: false,
+        stencil_test: false,
+        depth_test: false,
+        blend: false,
+    }
+}
+
+impl IblRenderer {
+    pub fn new(state: &mut PipelineState) -> Result<Self, RendererError> {
+        let mut brdf_lut = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle {
+                width: BRDF_LUT_SIZE,
+                height: BRDF_LUT_SIZE,
+            },
+            PixelKind::RG16,
+            MinificationFilter::Linear,
+            MagnificationFilter::Linear,
+            1,
+            None,
+        )?;
+        brdf_lut
+            .bind_mut(state, 0)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+        Ok(Self {
+            irradiance_shader: IrradianceShader::new()?,
+            prefilter_shader: PrefilterShader::new()?,
+            brdf_shader: BrdfShader::new()?,
+            brdf_lut: FrameBuffer::new(
+                state,
+                None,
+                vec![Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: Rc::new(RefCell::new(brdf_lut)),
+                }],
+            )?,
+            brdf_lut_ready: false,
+            quad: SurfaceSharedData::make_unit_xy_quad(),
+            cache: Default::default(),
+        })
+    }
+
+    /// Returns lookup table with scale (R) and bias (G) of base reflectivity, it is indexed
+    /// by cosine between normal and view vector (U) and roughness (V).
+    pub fn brdf_lut(&self) -> Rc<RefCell<GpuTexture>> {
+        self.brdf_lut.color_attachments()[0].texture.clone()
+    }
+
+    /// Prepares lookup table and convolved maps of given environment, environment must be
+    /// a cube map, otherwise `None` is returned. Convolution is done only once per
+    /// environment.
+    pub fn prepare(
+        &mut self,
+        state: &mut PipelineState,
+        geometry_cache: &mut GeometryCache,
+        texture_cache: &mut TextureCache,
+        environment: Option<&Texture>,
+    ) -> Result<(RenderPassStatistics, Option<Rc<EnvironmentMaps>>), RendererError> {
+        scope_profile!();
+
+        let mut statistics = RenderPassStatistics::default();
+
+        if !self.brdf_lut_ready {
+            let viewport = Rect::new(0, 0, BRDF_LUT_SIZE as i32, BRDF_LUT_SIZE as i32);
+            statistics += self.brdf_lut.draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &self.brdf_shader.program,
+                &draw_params(),
+                &[
+                    (
+                        self.brdf_shader.wvp_matrix,
+                        UniformValue::Matrix4(frame_matrix(BRDF_LUT_SIZE)),
+                    ),
+                    (
+                        self.brdf_shader.lut_size,
+                        UniformValue::Float(BRDF_LUT_SIZE as f32),
+                    ),
+                ],
+            );
+            self.brdf_lut_ready = true;
+        }
+
+        let environment = match environment {
+            Some(environment) => environment,
+            None => return Ok((statistics, None)),
+        };
+
+        let key = environment.key();
+        if let Some(entry) = self.cache.get_mut(&key) {
+            entry.time_to_live = 20.0;
+            return Ok((statistics, Some(entry.value.clone())));
+        }
+
+        let environment = match texture_cache.get(state, environment.clone()) {
+            Some(texture) => texture,
+            // Not loaded yet.
+            None => return Ok((statistics, None)),
+        };
+        if !matches!(environment.borrow().kind(), GpuTextureKind::Cube { .. }) {
+            return Ok((statistics, None));
+        }
+
+        let mut irradiance =
+            make_cube_map(state, IRRADIANCE_MAP_SIZE, MinificationFilter::Linear, 1)?;
+        for (face_index, &face) in FACES.iter().enumerate() {
+            let viewport = Rect::new(0, 0, IRRADIANCE_MAP_SIZE as i32, IRRADIANCE_MAP_SIZE as i32);
+            statistics += irradiance.set_cubemap_face(state, 0, face).draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &self.irradiance_shader.program,
+                &draw_params(),
+                &[
+                    (
+                        self.irradiance_shader.wvp_matrix,
+                        UniformValue::Matrix4(frame_matrix(IRRADIANCE_MAP_SIZE)),
+                    ),
+                    (
+                        self.irradiance_shader.environment_map,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: environment.clone(),
+                        },
+                    ),
+                    (
+                        self.irradiance_shader.face_index,
+                        UniformValue::Integer(face_index as i32),
+                    ),
+                    (
+                        self.irradiance_shader.face_size,
+                        UniformValue::Float(IRRADIANCE_MAP_SIZE as f32),
+                    ),
+                ],
+            );
+        }
+
+        let mut prefiltered = make_cube_map(
+            state,
+            PREFILTERED_MAP_SIZE,
+            MinificationFilter::LinearMipMapLinear,
+            PREFILTERED_MAP_MIP_COUNT,
+        )?;
+        for mip in 0..PREFILTERED_MAP_MIP_COUNT {
+            let size = (PREFILTERED_MAP_SIZE >> mip).max(1);
+            let viewport = Rect::new(0, 0, size as i32, size as i32);
+            let roughness = mip as f32 / (PREFILTERED_MAP_MIP_COUNT - 1) as f32;
+            for (face_index, &face) in FACES.iter().enumerate() {
+                statistics += prefiltered
+                    .set_cubemap_face_level(state, 0, face, mip)
+                    .draw(
+                        geometry_cache.get(state, &self.quad),
+                        state,
+                        viewport,
+                        &self.prefilter_shader.program,
+                        &draw_params(),
+                        &[
+                            (
+                                self.prefilter_shader.wvp_matrix,
+                                UniformValue::Matrix4(frame_matrix(size)),
+                            ),
+                            (
+                                self.prefilter_shader.environment_map,
+                                UniformValue::Sampler {
+                                    index: 0,
+                                    texture: environment.clone(),
+                                },
+                            ),
+                            (
+                                self.prefilter_shader.face_index,
+                                UniformValue::Integer(face_index as i32),
+                            ),
+                            (
+                                self.prefilter_shader.face_size,
+                                UniformValue::Float(size as f32),
+                            ),
+                            (
+                                self.prefilter_shader.roughness,
+                                UniformValue::Float(roughness),
+                            ),
+                        ],
+                    );
+            }
+        }
+
+        let maps = Rc::new(EnvironmentMaps {
+            irradiance: irradiance.color_attachments()[0].texture.clone(),
+            prefiltered: prefiltered.color_attachments()[0].texture.clone(),
+        });
+
+        self.cache.insert(
+            key,
+            TimedEntry {
+                value: maps.clone(),
+                time_to_live: 20.0,
+            },
+        );
+
+        Ok((statistics, Some(maps)))
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        scope_profile!();
+
+        for entry in self.cache.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.cache.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
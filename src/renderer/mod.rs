@@ -10,6 +10,7 @@
 
 pub mod debug_renderer;
 pub mod error;
+pub mod render_graph;
 pub mod surface;
 
 // Framework wraps all OpenGL calls so it has to be unsafe. Rest of renderer
@@ -22,18 +23,22 @@ mod batch;
 mod blur;
 mod deferred_light_renderer;
 mod flat_shader;
+mod forward_renderer;
 mod gbuffer;
+mod hdr;
+mod ibl;
 mod light_volume;
+mod occlusion;
 mod particle_system_renderer;
+mod post_processing;
+mod shader_cache;
 mod shadow_map_renderer;
 mod sprite_renderer;
 mod ssao;
+mod ssr;
 mod ui_renderer;
 
 use crate::renderer::framework::framebuffer::FrameBuffer;
-use crate::renderer::framework::gpu_program::{GpuProgram, UniformLocation};
-use crate::renderer::framework::query::Query;
-use crate::renderer::framework::state::ColorMask;
 use crate::utils::log::Log;
 use crate::utils::raw_mesh::RawMeshBuilder;
 use crate::{
@@ -54,8 +59,11 @@ use crate::{
         },
         error::RendererError,
         flat_shader::FlatShader,
+        forward_renderer::{ForwardRenderContext, ForwardRenderer},
         framework::{
-            framebuffer::{BackBuffer, CullFace, DrawParameters, FrameBufferTrait},
+            framebuffer::{
+                Attachment, AttachmentKind, BackBuffer, CullFace, DrawParameters, FrameBufferTrait,
+            },
             geometry_buffer::{
                 AttributeDefinition, AttributeKind, BufferBuilder, DrawCallStatistics, ElementKind,
                 GeometryBuffer, GeometryBufferBuilder, GeometryBufferKind,
@@ -69,7 +77,12 @@ use crate::{
             state::{PipelineState, PipelineStatistics},
         },
         gbuffer::{GBuffer, GBufferRenderContext},
+        hdr::{HdrRenderContext, HighDynamicRangeRenderer},
+        occlusion::{OcclusionCuller, OcclusionQueryContext, OcclusionStatistics},
         particle_system_renderer::{ParticleSystemRenderContext, ParticleSystemRenderer},
+        post_processing::{HdrEffectsContext, LdrEffectsContext, PostProcessingRenderer},
+        render_graph::{BuiltinPass, CustomPassContext, PassKind, RenderGraph},
+        shader_cache::ShaderCache,
         sprite_renderer::{SpriteRenderContext, SpriteRenderer},
         surface::SurfaceSharedData,
         ui_renderer::{UiRenderContext, UiRenderer},
@@ -78,6 +91,7 @@ use crate::{
     scene::{node::Node, Scene, SceneContainer},
 };
 use glutin::PossiblyCurrent;
+use image::RgbaImage;
 use rapier3d::na::Matrix;
 use std::collections::hash_map::Entry;
 use std::{
@@ -99,6 +113,8 @@ pub struct Statistics {
     pub lighting: LightingStatistics,
     /// Shows how many draw calls was made and how many triangles were rendered.
     pub geometry: RenderPassStatistics,
+    /// Shows how many occlusion queries were issued and how many meshes were occluded.
+    pub occlusion: OcclusionStatistics,
     /// Real time consumed to render frame. Time given in **seconds**.
     pub pure_frame_time: f32,
     /// Total time renderer took to process single frame, usually includes
@@ -121,12 +137,14 @@ impl Display for Statistics {
             Capped Frame Time: {} ms\n\
             {}\n\
             {}\n\
+            {}\n\
             {}\n",
             self.frames_per_second,
             self.pure_frame_time * 1000.0,
             self.capped_frame_time * 1000.0,
             self.geometry,
             self.lighting,
+            self.occlusion,
             self.pipeline
         )
     }
@@ -192,6 +210,24 @@ pub enum ShadowMapPrecision {
     Full,
 }
 
+/// Maximum amount of cascades of directional light shadow map.
+pub const CSM_MAX_CASCADES: usize = 4;
+
+/// Defines how view frustum of a camera is split into cascades of directional
+/// light shadow map.
+#[derive(Copy, Clone, PartialEq)]
+pub enum CsmSplitScheme {
+    /// Blend between uniform and logarithmic splits (so called "practical split
+    /// scheme"). Parameter must be in [0; 1] range, where 0 - uniform splits and
+    /// 1 - logarithmic splits. Logarithmic splits give more resolution to cascades
+    /// close to camera.
+    Practical(f32),
+    /// Explicit far distances of cascades, given as fractions of shadows distance
+    /// in [0; 1] range. Values must be in ascending order, last used cascade will
+    /// always end at shadows distance.
+    Manual([f32; CSM_MAX_CASCADES]),
+}
+
 /// Quality settings allows you to find optimal balance between performance and
 /// graphics quality.
 #[derive(Copy, Clone, PartialEq)]
@@ -222,15 +258,84 @@ pub struct QualitySettings {
     /// quality and performance.
     pub spot_shadow_map_precision: ShadowMapPrecision,
 
+    /// Directional shadows
+    /// Size of square shadow map texture of each cascade in pixels.
+    pub directional_shadow_map_size: usize,
+    /// Use or not percentage close filtering (smoothing) for directional shadows.
+    pub directional_soft_shadows: bool,
+    /// Directional shadows enabled or not.
+    pub directional_shadows_enabled: bool,
+    /// Maximum distance from camera to draw shadows, cascades are distributed
+    /// within this distance.
+    pub directional_shadows_distance: f32,
+    /// Directional shadow map precision. Allows you to select compromise between
+    /// quality and performance.
+    pub directional_shadow_map_precision: ShadowMapPrecision,
+    /// Amount of cascades, will be clamped to [1; CSM_MAX_CASCADES] range.
+    pub directional_shadow_cascade_count: usize,
+    /// Defines how view frustum will be split into cascades.
+    pub directional_shadow_split_scheme: CsmSplitScheme,
+
     /// Whether to use screen space ambient occlusion or not.
     pub use_ssao: bool,
     /// Radius of sampling hemisphere used in SSAO, it defines much ambient
     /// occlusion will be in your scene.
     pub ssao_radius: f32,
 
+    /// Whether to use screen space reflections or not. Reflection rays are traced
+    /// through depth buffer, rays that leave the screen or miss fall back to
+    /// environment map of a camera.
+    pub use_ssr: bool,
+    /// Maximum distance (in world units) that a reflection ray can travel.
+    pub ssr_max_distance: f32,
+    /// Amount of steps along a reflection ray, more steps - less artifacts, but
+    /// lower performance.
+    pub ssr_steps: usize,
+    /// Assumed thickness (in world units) of objects in depth buffer.
+    pub ssr_thickness: f32,
+    /// Surfaces rougher than this value reflect environment map only, reflections
+    /// smoothly fade out when roughness approaches this value.
+    pub ssr_max_roughness: f32,
+
+    /// Whether to use screen space contact shadows or not. They add small-scale
+    /// shadows which are usually lost in shadow maps because of their resolution
+    /// and bias.
+    pub use_contact_shadows: bool,
+    /// Length (in world units) of rays traced from a surface towards a light.
+    pub contact_shadows_distance: f32,
+    /// Amount of steps along a contact shadow ray.
+    pub contact_shadows_steps: usize,
+    /// Assumed thickness (in world units) of objects in depth buffer.
+    pub contact_shadows_thickness: f32,
+
     /// Global switch to enable or disable light scattering. Each light can have
     /// its own scatter switch, but this one is able to globally disable scatter.
     pub light_scatter_enabled: bool,
+
+    /// Post effects
+    /// Each camera has its own settings of post effects, these switches are able to
+    /// globally disable them.
+    /// Whether to use bloom or not.
+    pub use_bloom: bool,
+    /// Whether to use fast approximate anti-aliasing or not.
+    pub use_fxaa: bool,
+    /// Whether to use depth of field or not. It is quite expensive effect.
+    pub use_depth_of_field: bool,
+    /// Whether to use color grading or not.
+    pub use_color_grading: bool,
+    /// Whether to use chromatic aberration or not.
+    pub use_chromatic_aberration: bool,
+    /// Whether to use vignette or not.
+    pub use_vignette: bool,
+
+    /// Whether to skip meshes occluded by other objects or not. Occlusion is
+    /// detected by hardware queries, results are used with a frame of latency.
+    pub use_occlusion_culling: bool,
+    /// Whether to skip meshes occluded from the camera in shadow passes too. This
+    /// saves even more draw calls, but occluded objects may still cast visible
+    /// shadows, so enable it only if your scene is fine with missing shadows of
+    /// hidden objects.
+    pub occlusion_culling_in_shadows: bool,
 }
 
 impl Default for QualitySettings {
@@ -253,13 +358,42 @@ impl QualitySettings {
             spot_shadows_enabled: true,
             spot_soft_shadows: true,
 
+            directional_shadow_map_size: 2048,
+            directional_shadows_distance: 100.0,
+            directional_shadows_enabled: true,
+            directional_soft_shadows: true,
+            directional_shadow_cascade_count: 4,
+            directional_shadow_split_scheme: CsmSplitScheme::Practical(0.75),
+
             use_ssao: true,
             ssao_radius: 0.5,
 
+            use_ssr: true,
+            ssr_max_distance: 20.0,
+            ssr_steps: 64,
+            ssr_thickness: 0.5,
+            ssr_max_roughness: 0.7,
+
+            use_contact_shadows: true,
+            contact_shadows_distance: 0.3,
+            contact_shadows_steps: 16,
+            contact_shadows_thickness: 0.05,
+
             light_scatter_enabled: true,
 
+            use_bloom: true,
+            use_fxaa: true,
+            use_depth_of_field: true,
+            use_color_grading: true,
+            use_chromatic_aberration: true,
+            use_vignette: true,
+
+            use_occlusion_culling: true,
+            occlusion_culling_in_shadows: false,
+
             point_shadow_map_precision: ShadowMapPrecision::Full,
             spot_shadow_map_precision: ShadowMapPrecision::Full,
+            directional_shadow_map_precision: ShadowMapPrecision::Full,
         }
     }
 
@@ -276,13 +410,42 @@ impl QualitySettings {
             spot_shadows_enabled: true,
             spot_soft_shadows: true,
 
+            directional_shadow_map_size: 1024,
+            directional_shadows_distance: 60.0,
+            directional_shadows_enabled: true,
+            directional_soft_shadows: true,
+            directional_shadow_cascade_count: 3,
+            directional_shadow_split_scheme: CsmSplitScheme::Practical(0.75),
+
             use_ssao: true,
             ssao_radius: 0.5,
 
+            use_ssr: true,
+            ssr_max_distance: 15.0,
+            ssr_steps: 32,
+            ssr_thickness: 0.5,
+            ssr_max_roughness: 0.6,
+
+            use_contact_shadows: true,
+            contact_shadows_distance: 0.25,
+            contact_shadows_steps: 12,
+            contact_shadows_thickness: 0.05,
+
             light_scatter_enabled: true,
 
+            use_bloom: true,
+            use_fxaa: true,
+            use_depth_of_field: true,
+            use_color_grading: true,
+            use_chromatic_aberration: true,
+            use_vignette: true,
+
+            use_occlusion_culling: true,
+            occlusion_culling_in_shadows: false,
+
             point_shadow_map_precision: ShadowMapPrecision::Half,
             spot_shadow_map_precision: ShadowMapPrecision::Half,
+            directional_shadow_map_precision: ShadowMapPrecision::Full,
         }
     }
 
@@ -299,13 +462,42 @@ impl QualitySettings {
             spot_shadows_enabled: true,
             spot_soft_shadows: false,
 
+            directional_shadow_map_size: 1024,
+            directional_shadows_distance: 40.0,
+            directional_shadows_enabled: true,
+            directional_soft_shadows: false,
+            directional_shadow_cascade_count: 2,
+            directional_shadow_split_scheme: CsmSplitScheme::Practical(0.6),
+
             use_ssao: true,
             ssao_radius: 0.5,
 
+            use_ssr: false,
+            ssr_max_distance: 10.0,
+            ssr_steps: 16,
+            ssr_thickness: 0.5,
+            ssr_max_roughness: 0.5,
+
+            use_contact_shadows: true,
+            contact_shadows_distance: 0.2,
+            contact_shadows_steps: 8,
+            contact_shadows_thickness: 0.05,
+
             light_scatter_enabled: false,
 
+            use_bloom: true,
+            use_fxaa: true,
+            use_depth_of_field: false,
+            use_color_grading: true,
+            use_chromatic_aberration: true,
+            use_vignette: true,
+
+            use_occlusion_culling: true,
+            occlusion_culling_in_shadows: false,
+
             point_shadow_map_precision: ShadowMapPrecision::Half,
             spot_shadow_map_precision: ShadowMapPrecision::Half,
+            directional_shadow_map_precision: ShadowMapPrecision::Half,
         }
     }
 
@@ -322,13 +514,42 @@ impl QualitySettings {
             spot_shadows_enabled: false,
             spot_soft_shadows: false,
 
+            directional_shadow_map_size: 1,
+            directional_shadows_distance: 0.0,
+            directional_shadows_enabled: false,
+            directional_soft_shadows: false,
+            directional_shadow_cascade_count: 1,
+            directional_shadow_split_scheme: CsmSplitScheme::Practical(0.5),
+
             use_ssao: false,
             ssao_radius: 0.5,
 
+            use_ssr: false,
+            ssr_max_distance: 10.0,
+            ssr_steps: 16,
+            ssr_thickness: 0.5,
+            ssr_max_roughness: 0.5,
+
+            use_contact_shadows: false,
+            contact_shadows_distance: 0.2,
+            contact_shadows_steps: 8,
+            contact_shadows_thickness: 0.05,
+
             light_scatter_enabled: false,
 
+            use_bloom: false,
+            use_fxaa: false,
+            use_depth_of_field: false,
+            use_color_grading: false,
+            use_chromatic_aberration: false,
+            use_vignette: false,
+
+            use_occlusion_culling: true,
+            occlusion_culling_in_shadows: false,
+
             point_shadow_map_precision: ShadowMapPrecision::Half,
             spot_shadow_map_precision: ShadowMapPrecision::Half,
+            directional_shadow_map_precision: ShadowMapPrecision::Half,
         }
     }
 }
@@ -339,6 +560,7 @@ impl Statistics {
         self.frame_start_time = time::Instant::now();
         self.geometry = Default::default();
         self.lighting = Default::default();
+        self.occlusion = Default::default();
     }
 
     /// Must be called before SwapBuffers but after all rendering is done.
@@ -375,6 +597,7 @@ impl Default for Statistics {
             pipeline: Default::default(),
             lighting: Default::default(),
             geometry: Default::default(),
+            occlusion: Default::default(),
             pure_frame_time: 0.0,
             capped_frame_time: 0.0,
             frames_per_second: 0,
@@ -385,31 +608,18 @@ impl Default for Statistics {
     }
 }
 
-pub(in crate) struct QueryShader {
-    program: GpuProgram,
-    wvp_matrix: UniformLocation,
-}
-
-impl QueryShader {
-    fn new() -> Result<Self, RendererError> {
-        let fragment_source = include_str!("shaders/query_fs.glsl");
-        let vertex_source = include_str!("shaders/query_vs.glsl");
-        let program = GpuProgram::from_source("QueryShader", &vertex_source, &fragment_source)?;
-        Ok(Self {
-            wvp_matrix: program.uniform_location("worldViewProjection")?,
-            program,
-        })
-    }
-}
-
 /// See module docs.
 pub struct Renderer {
     state: PipelineState,
     backbuffer: BackBuffer,
     deferred_light_renderer: DeferredLightRenderer,
     flat_shader: FlatShader,
+    forward_renderer: ForwardRenderer,
+    hdr_renderer: HighDynamicRangeRenderer,
     sprite_renderer: SpriteRenderer,
     particle_system_renderer: ParticleSystemRenderer,
+    post_processing_renderer: PostProcessingRenderer,
+    render_graph: RenderGraph,
     /// Dummy white one pixel texture which will be used as stub when rendering
     /// something without texture specified.
     white_dummy: Rc<RefCell<GpuTexture>>,
@@ -434,18 +644,24 @@ pub struct Renderer {
     backbuffer_clear_color: Color,
     texture_cache: TextureCache,
     geometry_cache: GeometryCache,
+    shader_cache: ShaderCache,
     batch_storage: BatchStorage,
-    query_shader: QueryShader,
-    bounding_box_surface: SurfaceSharedData
+    occlusion_culler: OcclusionCuller,
 }
 
+/// Cache of GPU geometry buffers of surfaces.
 #[derive(Default)]
-pub(in crate) struct GeometryCache {
+pub struct GeometryCache {
     map: HashMap<usize, TimedEntry<GeometryBuffer>>,
 }
 
 impl GeometryCache {
-    fn get(&mut self, state: &mut PipelineState, data: &SurfaceSharedData) -> &mut GeometryBuffer {
+    /// Returns geometry buffer for given surface data, creates new one if needed.
+    pub fn get(
+        &mut self,
+        state: &mut PipelineState,
+        data: &SurfaceSharedData,
+    ) -> &mut GeometryBuffer {
         scope_profile!();
 
         let key = (data as *const _) as usize;
@@ -540,6 +756,20 @@ impl GeometryCache {
                             kind: AttributeKind::Float,
                             normalized: false,
                             divisor: 1,
+                        })
+                        // Decal layer index.
+                        .with_attribute(AttributeDefinition {
+                            location: 13,
+                            kind: AttributeKind::Float,
+                            normalized: false,
+                            divisor: 1,
+                        })
+                        // Offset of bone matrices.
+                        .with_attribute(AttributeDefinition {
+                            location: 14,
+                            kind: AttributeKind::Float,
+                            normalized: false,
+                            divisor: 1,
                         }),
                 )
                 .build(state)
@@ -571,13 +801,15 @@ impl GeometryCache {
     }
 }
 
+/// Cache of GPU textures of texture resources.
 #[derive(Default)]
-pub(in crate) struct TextureCache {
+pub struct TextureCache {
     map: HashMap<usize, TimedEntry<Rc<RefCell<GpuTexture>>>>,
 }
 
 impl TextureCache {
-    fn get(
+    /// Returns GPU texture for given texture resource, `None` if the texture is not loaded.
+    pub fn get(
         &mut self,
         state: &mut PipelineState,
         texture: Texture,
@@ -679,21 +911,75 @@ impl TextureCache {
     }
 }
 
+fn make_offscreen_framebuffer(
+    state: &mut PipelineState,
+    frame_size: (u32, u32),
+) -> Result<FrameBuffer, RendererError> {
+    let width = frame_size.0.max(1) as usize;
+    let height = frame_size.1.max(1) as usize;
+
+    let depth_stencil = GpuTexture::new(
+        state,
+        GpuTextureKind::Rectangle { width, height },
+        PixelKind::D24S8,
+        MinificationFilter::Nearest,
+        MagnificationFilter::Nearest,
+        1,
+        None,
+    )?;
+
+    let color = GpuTexture::new(
+        state,
+        GpuTextureKind::Rectangle { width, height },
+        PixelKind::RGBA8,
+        MinificationFilter::Nearest,
+        MagnificationFilter::Nearest,
+        1,
+        None,
+    )?;
+
+    FrameBuffer::new(
+        state,
+        Some(Attachment {
+            kind: AttachmentKind::DepthStencil,
+            texture: Rc::new(RefCell::new(depth_stencil)),
+        }),
+        vec![Attachment {
+            kind: AttachmentKind::Color,
+            texture: Rc::new(RefCell::new(color)),
+        }],
+    )
+}
+
 impl Renderer {
     pub(in crate) fn new(
-        context: &mut glutin::WindowedContext<PossiblyCurrent>,
+        context: &glutin::Context<PossiblyCurrent>,
         frame_size: (u32, u32),
     ) -> Result<Self, RendererError> {
-        gl::load_with(|symbol| context.get_proc_address(symbol) as *const _);
+        Self::with_loader(
+            |symbol| context.get_proc_address(symbol) as *const _,
+            frame_size,
+        )
+    }
+
+    fn with_loader<F>(loader: F, frame_size: (u32, u32)) -> Result<Self, RendererError>
+    where
+        F: FnMut(&'static str) -> *const std::os::raw::c_void,
+    {
+        gl::load_with(loader);
 
         let settings = QualitySettings::default();
         let mut state = PipelineState::new();
 
         Ok(Self {
-            backbuffer: BackBuffer,
+            backbuffer: BackBuffer::Window,
             frame_size,
             deferred_light_renderer: DeferredLightRenderer::new(&mut state, frame_size, &settings)?,
             flat_shader: FlatShader::new()?,
+            forward_renderer: ForwardRenderer::new()?,
+            hdr_renderer: HighDynamicRangeRenderer::new(&mut state)?,
+            post_processing_renderer: PostProcessingRenderer::new(&mut state)?,
+            render_graph: Default::default(),
             statistics: Statistics::default(),
             sprite_renderer: SpriteRenderer::new()?,
             white_dummy: Rc::new(RefCell::new(GpuTexture::new(
@@ -761,7 +1047,7 @@ impl Renderer {
                 MinificationFilter::Linear,
                 MagnificationFilter::Linear,
                 1,
-                Some(&[32u8, 32u8, 32u8, 32u8]),
+                Some(&[128u8, 128u8, 128u8, 128u8]),
             )?)),
             quad: SurfaceSharedData::make_unit_xy_quad(),
             ui_renderer: UiRenderer::new(&mut state)?,
@@ -773,13 +1059,35 @@ impl Renderer {
             backbuffer_clear_color: Color::from_rgba(0, 0, 0, 0),
             texture_cache: Default::default(),
             geometry_cache: Default::default(),
+            shader_cache: Default::default(),
+            batch_storage: BatchStorage::new(&mut state)?,
             state,
-            batch_storage: Default::default(),
-            query_shader: QueryShader::new()?,
-            bounding_box_surface: SurfaceSharedData::make_cube(Matrix4::identity()),
+            occlusion_culler: OcclusionCuller::new()?,
         })
     }
 
+    /// Creates renderer that draws into an offscreen framebuffer instead of a window. OpenGL
+    /// functions are loaded using given loader of current context, the context could be created
+    /// without any display (surfaceless or OSMesa context), so the renderer will work even with
+    /// software OpenGL implementations.
+    pub(in crate) fn new_headless<F>(
+        loader: F,
+        frame_size: (u32, u32),
+    ) -> Result<Self, RendererError>
+    where
+        F: FnMut(&'static str) -> *const std::os::raw::c_void,
+    {
+        let mut renderer = Self::with_loader(loader, frame_size)?;
+        renderer.backbuffer =
+            BackBuffer::Offscreen(make_offscreen_framebuffer(&mut renderer.state, frame_size)?);
+        Ok(renderer)
+    }
+
+    /// Returns `true` if renderer draws into an offscreen framebuffer instead of a window.
+    pub fn is_headless(&self) -> bool {
+        matches!(self.backbuffer, BackBuffer::Offscreen(_))
+    }
+
     /// Sets new ambient color. Ambient color is used to imitate ambient lighting.
     pub fn set_ambient_color(&mut self, color: Color) {
         self.ambient_color = color;
@@ -812,6 +1120,11 @@ impl Renderer {
             .unwrap();
         self.frame_size.0 = new_size.0.max(1);
         self.frame_size.1 = new_size.1.max(1);
+        if let BackBuffer::Offscreen(_) = self.backbuffer {
+            self.backbuffer = BackBuffer::Offscreen(
+                make_offscreen_framebuffer(&mut self.state, self.frame_size).unwrap(),
+            );
+        }
         // Invalidate all g-buffers.
         self.gbuffers.clear();
     }
@@ -826,6 +1139,37 @@ impl Renderer {
         Vector2::new(self.frame_size.0 as f32, self.frame_size.1 as f32)
     }
 
+    /// Reads contents of back buffer into an image, it could be used to make screenshots
+    /// or to compare rendered frame with a reference image in tests.
+    ///
+    /// # Notes
+    ///
+    /// Contents of back buffer of a window is undefined after buffers were swapped, so
+    /// screenshots are reliable only for headless renderer. This method stalls the
+    /// pipeline until the frame is fully rendered, do not call it every frame.
+    pub fn read_pixels(&mut self) -> RgbaImage {
+        let (width, height) = self.frame_size;
+        let pixels = self.backbuffer.read_pixels(
+            &mut self.state,
+            Rect::new(0, 0, width as i32, height as i32),
+        );
+        let mut image = RgbaImage::from_raw(width, height, pixels)
+            .expect("back buffer pixels must match frame size");
+        // OpenGL stores rows bottom-to-top.
+        image::imageops::flip_vertical_in_place(&mut image);
+        image
+    }
+
+    /// Returns reference to render graph.
+    pub fn render_graph(&self) -> &RenderGraph {
+        &self.render_graph
+    }
+
+    /// Returns reference to render graph, it could be used to add custom render passes.
+    pub fn render_graph_mut(&mut self) -> &mut RenderGraph {
+        &mut self.render_graph
+    }
+
     /// Sets new quality settings for renderer. Never call this method in a loop, otherwise
     /// you may get **significant** lags. Always check if current quality setting differs
     /// from new!
@@ -849,6 +1193,12 @@ impl Renderer {
     pub fn flush(&mut self) {
         self.texture_cache.clear();
         self.geometry_cache.clear();
+        self.shader_cache.clear();
+        self.deferred_light_renderer.flush();
+        self.hdr_renderer.clear();
+        self.post_processing_renderer.clear();
+        self.occlusion_culler.clear();
+        self.render_graph.clear();
     }
 
     fn render_frame(
@@ -868,6 +1218,13 @@ impl Renderer {
         // Update caches - this will remove timed out resources.
         self.geometry_cache.update(dt);
         self.texture_cache.update(dt);
+        self.shader_cache.update(dt);
+        self.deferred_light_renderer.update(dt);
+        self.hdr_renderer.update(dt);
+        self.post_processing_renderer.update(dt);
+        self.occlusion_culler.update(dt);
+        self.render_graph.update(dt);
+        self.render_graph.prepare();
 
         self.statistics.begin_frame();
 
@@ -909,122 +1266,13 @@ impl Renderer {
                 })
                 .collect::<Vec<_>>();
 
-            let mut hidden_counter = 0;
-
-            for camera in graph.linear_iter().filter_map(|node| {
-                if let Node::Camera(camera) = node {
-                    if camera.is_enabled() {
-                        Some(camera)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            }) {
-                let mut occlusion_map = scene.occlusion_map.borrow_mut();
-
-                // TODO: update occlusion_map with results from last frame.
-                let occlusion_queries = occlusion_map.entry(camera.original).or_insert(vec![]);
-
-                hidden_counter += occlusion_queries
-                    .iter_mut()
-                    .map(|query| {
-                        query.update_occlusion_result();
-                        query
-                    })
-                    .filter(|query| query.was_occluded())
-                    .count();
-
-                println!("hidden: {}", hidden_counter);
-
-                for &(node, mesh) in &all_meshes {
-                    let aabb = mesh.bounding_box();
-                    let scale = aabb.max - aabb.min;
-
-                    // TODO: very bad runtime
-                    if let Some(query) = occlusion_queries.iter().find(|q| q.node == node) {
-                        query.begin();
-                        self.backbuffer.draw(
-                            self.geometry_cache.get(state, &self.bounding_box_surface),
-                            state,
-                            camera.viewport_pixels(frame_size),
-                            &self.query_shader.program,
-                            &DrawParameters {
-                                cull_face: CullFace::Back,
-                                culling: false,
-                                color_write: ColorMask::all(false),
-                                depth_write: true,
-                                stencil_test: false,
-                                depth_test: true,
-                                blend: false,
-                            },
-                            &[(
-                                self.query_shader.wvp_matrix,
-                                UniformValue::Matrix4(
-                                    camera.view_projection_matrix()
-                                        // * Matrix4::new_nonuniform_scaling(&scale)
-                                        * mesh.global_transform(),
-                                ),
-                            )],
-                        );
-                        query.end();
-                    } else {
-                        let query = Query::new(node);
-                        query.begin();
-                        self.backbuffer.draw(
-                            self.geometry_cache.get(state, &self.bounding_box_surface),
-                            state,
-                            camera.viewport_pixels(frame_size),
-                            &self.query_shader.program,
-                            &DrawParameters {
-                                cull_face: CullFace::Back,
-                                culling: false,
-                                color_write: ColorMask::all(false),
-                                depth_write: true,
-                                stencil_test: false,
-                                depth_test: true,
-                                blend: false,
-                            },
-                            &[(
-                                self.query_shader.wvp_matrix,
-                                UniformValue::Matrix4(
-                                    camera.view_projection_matrix()
-                                        // * Matrix4::new_nonuniform_scaling(&scale)
-                                        * mesh.global_transform(),
-                                ),
-                            )],
-                        );
-                        query.end();
-                        occlusion_queries.push(query);
-                    }
-                }
-            }
-
-            let meshes_to_render = all_meshes
-                .into_iter()
-                .filter(|(node, _)| {
-                    scene
-                        .occlusion_map
-                        .borrow_mut()
-                        .values()
-                        .flatten()
-                        .any(|q| q.node == *node && !q.was_occluded())
-                })
-                .collect::<Vec<_>>();
-
-            self.backbuffer.clear(
-                state,
-                window_viewport,
-                Some(self.backbuffer_clear_color),
-                Some(1.0),
-                Some(0),
-            );
+            // Occlusion results are valid only for cameras rendered in current frame.
+            scene.occlusion_map.borrow_mut().clear();
 
             self.batch_storage.generate_batches(
                 state,
                 graph,
-                &meshes_to_render,
+                &all_meshes,
                 self.black_dummy.clone(),
                 self.white_dummy.clone(),
                 self.normal_dummy.clone(),
@@ -1063,10 +1311,10 @@ impl Renderer {
                 );
             }
 
-            for camera in graph.linear_iter().filter_map(|node| {
+            for (camera_handle, camera) in graph.pair_iter().filter_map(|(handle, node)| {
                 if let Node::Camera(camera) = node {
                     if camera.is_enabled() {
-                        Some(camera)
+                        Some((handle, camera))
                     } else {
                         None
                     }
@@ -1076,68 +1324,195 @@ impl Renderer {
             }) {
                 let viewport = camera.viewport_pixels(frame_size);
 
-                self.statistics += gbuffer.fill(GBufferRenderContext {
-                    state,
-                    camera,
-                    geom_cache: &mut self.geometry_cache,
-                    batch_storage: &self.batch_storage,
-                    texture_cache: &mut self.texture_cache,
-                    environment_dummy: self.environment_dummy.clone(),
-                });
+                let occluded_meshes = if self.quality_settings.use_occlusion_culling {
+                    self.occlusion_culler
+                        .cull(scene_handle, camera_handle, graph, camera)
+                } else {
+                    Default::default()
+                };
+                self.statistics.occlusion.meshes_occluded += occluded_meshes.len();
+                scene
+                    .occlusion_map
+                    .borrow_mut()
+                    .insert(camera_handle, occluded_meshes.clone());
 
-                let (pass_stats, light_stats) =
-                    self.deferred_light_renderer
-                        .render(DeferredRendererContext {
-                            state,
-                            scene,
-                            camera,
-                            gbuffer,
-                            white_dummy: self.white_dummy.clone(),
-                            ambient_color: self.ambient_color,
-                            settings: &self.quality_settings,
-                            textures: &mut self.texture_cache,
-                            geometry_cache: &mut self.geometry_cache,
-                            batch_storage: &self.batch_storage,
-                        });
-
-                self.statistics.lighting += light_stats;
-                self.statistics.geometry += pass_stats;
-
-                let depth = gbuffer.depth();
-
-                self.statistics +=
-                    self.particle_system_renderer
-                        .render(ParticleSystemRenderContext {
-                            state,
-                            framebuffer: &mut gbuffer.final_frame,
-                            graph,
-                            camera,
-                            white_dummy: self.white_dummy.clone(),
-                            depth,
-                            frame_width: frame_size.x,
-                            frame_height: frame_size.y,
-                            viewport,
-                            texture_cache: &mut self.texture_cache,
-                        });
-
-                self.statistics += self.sprite_renderer.render(SpriteRenderContext {
-                    state,
-                    framebuffer: &mut gbuffer.final_frame,
-                    graph,
-                    camera,
-                    white_dummy: self.white_dummy.clone(),
-                    viewport,
-                    textures: &mut self.texture_cache,
-                    geom_map: &mut self.geometry_cache,
-                });
+                for index in 0..self.render_graph.schedule_len() {
+                    self.render_graph.begin_pass(
+                        index,
+                        state,
+                        gbuffer.width as usize,
+                        gbuffer.height as usize,
+                    )?;
+
+                    match self.render_graph.scheduled_pass(index) {
+                        PassKind::Builtin(BuiltinPass::GBuffer) => {
+                            self.statistics += gbuffer.fill(GBufferRenderContext {
+                                state,
+                                camera,
+                                geom_cache: &mut self.geometry_cache,
+                                batch_storage: &self.batch_storage,
+                                texture_cache: &mut self.texture_cache,
+                                environment_dummy: self.environment_dummy.clone(),
+                                graph,
+                                white_dummy: self.white_dummy.clone(),
+                                normal_dummy: self.normal_dummy.clone(),
+                                black_dummy: self.black_dummy.clone(),
+                                specular_dummy: self.specular_dummy.clone(),
+                                shader_cache: &mut self.shader_cache,
+                                occluded_meshes: &occluded_meshes,
+                            });
+
+                            // Queries are tested against depth of this frame, results will be
+                            // used on next frames.
+                            if self.quality_settings.use_occlusion_culling {
+                                self.statistics.occlusion.queries_issued +=
+                                    self.occlusion_culler.render_queries(OcclusionQueryContext {
+                                        state,
+                                        framebuffer: &mut gbuffer.final_frame,
+                                        geometry_cache: &mut self.geometry_cache,
+                                        scene_handle,
+                                        camera_handle,
+                                        camera,
+                                        viewport: Rect::new(0, 0, gbuffer.width, gbuffer.height),
+                                    });
+                            }
+                        }
+                        PassKind::Builtin(BuiltinPass::Lighting) => {
+                            let (pass_stats, light_stats) =
+                                self.deferred_light_renderer
+                                    .render(DeferredRendererContext {
+                                        state,
+                                        scene,
+                                        camera,
+                                        gbuffer,
+                                        white_dummy: self.white_dummy.clone(),
+                                        environment_dummy: self.environment_dummy.clone(),
+                                        ambient_color: self.ambient_color,
+                                        settings: &self.quality_settings,
+                                        textures: &mut self.texture_cache,
+                                        geometry_cache: &mut self.geometry_cache,
+                                        batch_storage: &self.batch_storage,
+                                        occluded_meshes: &occluded_meshes,
+                                    });
+
+                            self.statistics.lighting += light_stats;
+                            self.statistics.geometry += pass_stats;
+                        }
+                        PassKind::Builtin(BuiltinPass::Forward) => {
+                            self.statistics += self.forward_renderer.render(ForwardRenderContext {
+                                state,
+                                framebuffer: &mut gbuffer.final_frame,
+                                graph,
+                                camera,
+                                viewport,
+                                ambient_color: self.ambient_color,
+                                batch_storage: &self.batch_storage,
+                                geom_cache: &mut self.geometry_cache,
+                            });
+                        }
+                        PassKind::Builtin(BuiltinPass::Particles) => {
+                            let depth = gbuffer.depth();
+
+                            self.statistics +=
+                                self.particle_system_renderer
+                                    .render(ParticleSystemRenderContext {
+                                        state,
+                                        framebuffer: &mut gbuffer.final_frame,
+                                        graph,
+                                        camera,
+                                        white_dummy: self.white_dummy.clone(),
+                                        depth,
+                                        frame_width: frame_size.x,
+                                        frame_height: frame_size.y,
+                                        viewport,
+                                        texture_cache: &mut self.texture_cache,
+                                    });
+                        }
+                        PassKind::Builtin(BuiltinPass::Sprites) => {
+                            self.statistics += self.sprite_renderer.render(SpriteRenderContext {
+                                state,
+                                framebuffer: &mut gbuffer.final_frame,
+                                graph,
+                                camera,
+                                white_dummy: self.white_dummy.clone(),
+                                viewport,
+                                textures: &mut self.texture_cache,
+                                geom_map: &mut self.geometry_cache,
+                            });
+                        }
+                        PassKind::Builtin(BuiltinPass::ToneMapping) => {
+                            let (hdr_effects_stats, hdr_frame, bloom) = self
+                                .post_processing_renderer
+                                .render_hdr_effects(HdrEffectsContext {
+                                    state,
+                                    geometry_cache: &mut self.geometry_cache,
+                                    gbuffer,
+                                    camera,
+                                    viewport,
+                                    settings: &self.quality_settings,
+                                });
+                            self.statistics += hdr_effects_stats;
+
+                            self.statistics += self.hdr_renderer.render(HdrRenderContext {
+                                state,
+                                geometry_cache: &mut self.geometry_cache,
+                                hdr_frame,
+                                ldr_framebuffer: &mut gbuffer.ldr_frame,
+                                viewport,
+                                camera,
+                                scene_handle,
+                                camera_handle,
+                                dt,
+                                bloom,
+                            })?;
+                        }
+                        PassKind::Builtin(BuiltinPass::LdrEffects) => {
+                            self.statistics += self.post_processing_renderer.render_ldr_effects(
+                                LdrEffectsContext {
+                                    state,
+                                    geometry_cache: &mut self.geometry_cache,
+                                    texture_cache: &mut self.texture_cache,
+                                    shader_cache: &mut self.shader_cache,
+                                    gbuffer,
+                                    camera,
+                                    viewport,
+                                    settings: &self.quality_settings,
+                                    white_dummy: self.white_dummy.clone(),
+                                    normal_dummy: self.normal_dummy.clone(),
+                                    black_dummy: self.black_dummy.clone(),
+                                    specular_dummy: self.specular_dummy.clone(),
+                                },
+                            );
+                        }
+                        PassKind::Builtin(BuiltinPass::Debug) => {
+                            // Debug geometry is drawn after tone mapping, so its colors stay as is.
+                            self.statistics += self.debug_renderer.render(
+                                state,
+                                viewport,
+                                &mut gbuffer.ldr_frame,
+                                &scene.drawing_context,
+                                camera,
+                            );
+                        }
+                        PassKind::Custom(handle) => {
+                            self.statistics += self.render_graph.execute_custom_pass(
+                                index,
+                                handle,
+                                CustomPassContext {
+                                    state,
+                                    geometry_cache: &mut self.geometry_cache,
+                                    texture_cache: &mut self.texture_cache,
+                                    gbuffer,
+                                    scene,
+                                    camera,
+                                    viewport,
+                                },
+                            )?;
+                        }
+                    }
 
-                self.statistics += self.debug_renderer.render(
-                    state,
-                    viewport,
-                    &mut gbuffer.final_frame,
-                    &scene.drawing_context,
-                    camera,
-                );
+                    self.render_graph.end_pass(index);
+                }
 
                 // Finally render everything into back buffer.
                 if scene.render_target.is_none() {
@@ -1201,6 +1576,20 @@ impl Renderer {
         Ok(())
     }
 
+    pub(in crate) fn render_offscreen(
+        &mut self,
+        scenes: &SceneContainer,
+        drawing_context: &DrawingContext,
+        dt: f32,
+    ) -> Result<(), RendererError> {
+        self.render_frame(scenes, drawing_context, dt)?;
+        self.statistics.end_frame();
+        check_gl_error!();
+        self.statistics.finalize();
+        self.statistics.pipeline = self.state.pipeline_statistics();
+        Ok(())
+    }
+
     pub(in crate) fn render_and_swap_buffers(
         &mut self,
         scenes: &SceneContainer,
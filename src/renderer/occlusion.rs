@@ -0,0 +1,608 @@
+//! Hierarchical GPU occlusion culling.
+//!
+//! Scene graph is used as a bounding volume hierarchy: every node has a box that encloses
+//! meshes of its whole subtree. Each frame, after G-buffer is filled, bounding boxes are drawn
+//! against its depth with occlusion queries. Results are read back on next frames without
+//! waiting for GPU, so there is one or two frames of latency but no pipeline stalls.
+//!
+//! Visible nodes are traversed further and each mesh is tested with its own box. When a mesh
+//! and all children of a node are occluded, occlusion is "pulled up" to the node and the whole
+//! subtree is tested with a single query until it becomes visible again. Subtrees that contain
+//! skinned meshes are never culled, because their bounds are not known in advance.
+//!
+//! Results are stored per camera in `Scene::occlusion_map`.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Point3, Vector3},
+        math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, Rect},
+        pool::Handle,
+        scope_profile,
+    },
+    engine::resource_manager::TimedEntry,
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{CullFace, DrawParameters, FrameBuffer, FrameBufferTrait},
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            query::Query,
+            state::{ColorMask, PipelineState},
+        },
+        surface::SurfaceSharedData,
+        GeometryCache,
+    },
+    scene::{camera::Camera, graph::Graph, node::Node, Scene, VisibilityCache},
+};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt::{Display, Formatter},
+    ops::AddAssign,
+};
+
+/// Occlusion culling statistics for one frame.
+#[derive(Copy, Clone, Default)]
+pub struct OcclusionStatistics {
+    /// Amount of occlusion queries issued.
+    pub queries_issued: usize,
+    /// Amount of meshes which passed frustum culling, but were skipped because they
+    /// are occluded by other objects.
+    pub meshes_occluded: usize,
+}
+
+impl AddAssign for OcclusionStatistics {
+    fn add_assign(&mut self, rhs: Self) {
+        self.queries_issued += rhs.queries_issued;
+        self.meshes_occluded += rhs.meshes_occluded;
+    }
+}
+
+impl Display for OcclusionStatistics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Occlusion Statistics:\n\
+            \tQueries Issued: {}\n\
+            \tMeshes Occluded: {}",
+            self.queries_issued, self.meshes_occluded
+        )
+    }
+}
+
+struct QueryShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+}
+
+impl QueryShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/query_fs.glsl");
+        let vertex_source = include_str!("shaders/query_vs.glsl");
+        let program = GpuProgram::from_source("QueryShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            program,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+enum QueryTarget {
+    /// Box of the whole subtree of a node.
+    Subtree,
+    /// Box of the mesh of a node itself.
+    Mesh,
+}
+
+struct QueryRequest {
+    node: Handle<Node>,
+    target: QueryTarget,
+    bounds: AxisAlignedBoundingBox,
+}
+
+#[derive(Default)]
+struct NodeOcclusion {
+    subtree_occluded: bool,
+    mesh_occluded: bool,
+}
+
+#[derive(Copy, Clone)]
+struct SubtreeBounds {
+    bounds: AxisAlignedBoundingBox,
+    has_meshes: bool,
+    /// `false` if subtree contains meshes with unknown bounds (skinned meshes).
+    cullable: bool,
+}
+
+/// Returns world-space box of a mesh or `None` if its bounds cannot be known in advance.
+fn mesh_bounds(node: &Node) -> Option<AxisAlignedBoundingBox> {
+    if let Node::Mesh(mesh) = node {
+        if mesh
+            .surfaces()
+            .iter()
+            .any(|surface| !surface.bones().is_empty())
+        {
+            return None;
+        }
+        Some(transform_aabb(
+            &mesh.bounding_box(),
+            &mesh.global_transform(),
+        ))
+    } else {
+        None
+    }
+}
+
+fn transform_aabb(aabb: &AxisAlignedBoundingBox, m: &Matrix4<f32>) -> AxisAlignedBoundingBox {
+    let corners = aabb
+        .corners()
+        .iter()
+        .map(|corner| m.transform_point(&Point3::from(*corner)).coords)
+        .collect::<Vec<_>>();
+    AxisAlignedBoundingBox::from_points(&corners)
+}
+
+/// Slightly enlarges box so it won't be occluded by depth of the object inside it.
+fn inflate(aabb: &AxisAlignedBoundingBox, margin: f32) -> AxisAlignedBoundingBox {
+    let margin = Vector3::repeat(margin) + (aabb.max - aabb.min).scale(0.01);
+    AxisAlignedBoundingBox::from_min_max(aabb.min - margin, aabb.max + margin)
+}
+
+/// Occlusion state of a single camera.
+#[derive(Default)]
+pub(in crate) struct CameraOcclusion {
+    nodes: HashMap<Handle<Node>, NodeOcclusion>,
+    queries: HashMap<(Handle<Node>, QueryTarget), Query>,
+    requests: Vec<QueryRequest>,
+    bounds: HashMap<Handle<Node>, SubtreeBounds>,
+}
+
+struct TraverseContext<'a> {
+    graph: &'a Graph,
+    visibility_cache: &'a VisibilityCache,
+    frustum: &'a Frustum,
+    observer_position: Vector3<f32>,
+    observer_margin: f32,
+    previous: HashMap<Handle<Node>, NodeOcclusion>,
+    occluded: HashSet<Handle<Node>>,
+}
+
+impl CameraOcclusion {
+    /// Fetches results of queries issued on previous frames, if they're ready.
+    fn fetch_results(&mut self) {
+        let mut results = Vec::new();
+        for (&key, query) in self.queries.iter_mut() {
+            if let Some(samples_passed) = query.try_get_result() {
+                results.push((key, samples_passed));
+            }
+        }
+        for ((node, target), samples_passed) in results {
+            self.apply_result(node, target, samples_passed);
+        }
+    }
+
+    fn apply_result(&mut self, node: Handle<Node>, target: QueryTarget, samples_passed: bool) {
+        if let Some(state) = self.nodes.get_mut(&node) {
+            match target {
+                QueryTarget::Subtree => {
+                    state.subtree_occluded = !samples_passed;
+                    if samples_passed {
+                        // Descendants were not tracked while subtree was occluded, so they
+                        // will be tested individually starting from next frame.
+                        state.mesh_occluded = false;
+                    }
+                }
+                QueryTarget::Mesh => state.mesh_occluded = !samples_passed,
+            }
+        }
+    }
+
+    fn is_query_pending(&self, node: Handle<Node>, target: QueryTarget) -> bool {
+        match self.queries.get(&(node, target)) {
+            Some(query) => query.is_pending(),
+            None => false,
+        }
+    }
+
+    fn request(&mut self, node: Handle<Node>, target: QueryTarget, bounds: AxisAlignedBoundingBox) {
+        if !self.is_query_pending(node, target) {
+            self.requests.push(QueryRequest {
+                node,
+                target,
+                bounds,
+            });
+        }
+    }
+
+    fn calculate_bounds(&mut self, graph: &Graph, handle: Handle<Node>) -> SubtreeBounds {
+        let node = &graph[handle];
+
+        let mut result = SubtreeBounds {
+            bounds: Default::default(),
+            has_meshes: false,
+            cullable: true,
+        };
+
+        if let Node::Mesh(_) = node {
+            result.has_meshes = true;
+            match mesh_bounds(node) {
+                Some(bounds) => result.bounds.add_box(bounds),
+                None => result.cullable = false,
+            }
+        }
+
+        for &child in node.children() {
+            let child_bounds = self.calculate_bounds(graph, child);
+            if child_bounds.has_meshes {
+                result.has_meshes = true;
+                result.cullable &= child_bounds.cullable;
+                if child_bounds.cullable {
+                    result.bounds.add_box(child_bounds.bounds);
+                }
+            }
+        }
+
+        self.bounds.insert(handle, result);
+
+        result
+    }
+
+    fn collect_meshes(&self, ctx: &mut TraverseContext, handle: Handle<Node>) {
+        let node = &ctx.graph[handle];
+        if let Node::Mesh(_) = node {
+            if ctx.visibility_cache.is_visible(handle) {
+                ctx.occluded.insert(handle);
+            }
+        }
+        for &child in node.children() {
+            self.collect_meshes(ctx, child);
+        }
+    }
+
+    /// Returns `true` if everything in subtree of the node is either occluded or not
+    /// rendered at all.
+    fn traverse(&mut self, ctx: &mut TraverseContext, handle: Handle<Node>) -> bool {
+        let subtree = self.bounds[&handle];
+        if !subtree.has_meshes
+            || (subtree.cullable && !ctx.frustum.is_intersects_aabb(&subtree.bounds))
+        {
+            return true;
+        }
+
+        let mut state = ctx.previous.remove(&handle).unwrap_or_default();
+
+        let subtree_bounds = inflate(&subtree.bounds, 0.0);
+        let observer_inside =
+            inflate(&subtree.bounds, ctx.observer_margin).is_contains_point(ctx.observer_position);
+
+        if !subtree.cullable || observer_inside {
+            state.subtree_occluded = false;
+        }
+
+        if state.subtree_occluded {
+            self.collect_meshes(ctx, handle);
+            self.request(handle, QueryTarget::Subtree, subtree_bounds);
+            self.nodes.insert(handle, state);
+            return true;
+        }
+
+        let node = &ctx.graph[handle];
+
+        let mut everything_occluded = true;
+        for &child in node.children() {
+            everything_occluded &= self.traverse(ctx, child);
+        }
+
+        if let Node::Mesh(_) = node {
+            if ctx.visibility_cache.is_visible(handle) {
+                match mesh_bounds(node) {
+                    Some(bounds)
+                        if !inflate(&bounds, ctx.observer_margin)
+                            .is_contains_point(ctx.observer_position) =>
+                    {
+                        if state.mesh_occluded {
+                            ctx.occluded.insert(handle);
+                        } else {
+                            everything_occluded = false;
+                        }
+                        self.request(handle, QueryTarget::Mesh, inflate(&bounds, 0.0));
+                    }
+                    _ => {
+                        state.mesh_occluded = false;
+                        everything_occluded = false;
+                    }
+                }
+            }
+        }
+
+        if everything_occluded && subtree.cullable && !observer_inside {
+            state.subtree_occluded = true;
+        }
+
+        self.nodes.insert(handle, state);
+
+        everything_occluded
+    }
+
+    /// Decides which meshes are occluded using known results of queries and prepares
+    /// new queries to issue. Returns set of occluded meshes.
+    fn cull(
+        &mut self,
+        graph: &Graph,
+        visibility_cache: &VisibilityCache,
+        frustum: &Frustum,
+        observer_position: Vector3<f32>,
+        observer_margin: f32,
+    ) -> HashSet<Handle<Node>> {
+        self.requests.clear();
+        self.bounds.clear();
+
+        let root = graph.get_root();
+        self.calculate_bounds(graph, root);
+
+        let mut ctx = TraverseContext {
+            graph,
+            visibility_cache,
+            frustum,
+            observer_position,
+            observer_margin,
+            previous: std::mem::take(&mut self.nodes),
+            occluded: Default::default(),
+        };
+
+        self.traverse(&mut ctx, root);
+
+        // Forget queries of nodes that are not tracked anymore: they're either outside of
+        // frustum, inside of occluded subtree, or removed from graph.
+        let nodes = &self.nodes;
+        self.queries.retain(|(node, _), _| nodes.contains_key(node));
+
+        ctx.occluded
+    }
+}
+
+pub(in crate) struct OcclusionQueryContext<'a, 'b> {
+    pub state: &'a mut PipelineState,
+    pub framebuffer: &'a mut FrameBuffer,
+    pub geometry_cache: &'a mut GeometryCache,
+    pub scene_handle: Handle<Scene>,
+    pub camera_handle: Handle<Node>,
+    pub camera: &'b Camera,
+    pub viewport: Rect<i32>,
+}
+
+pub(in crate) struct OcclusionCuller {
+    shader: QueryShader,
+    bounding_box: SurfaceSharedData,
+    cameras: HashMap<(Handle<Scene>, Handle<Node>), TimedEntry<CameraOcclusion>>,
+}
+
+impl OcclusionCuller {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: QueryShader::new()?,
+            bounding_box: SurfaceSharedData::make_cube(Matrix4::identity()),
+            cameras: Default::default(),
+        })
+    }
+
+    /// Returns set of meshes occluded for given camera. Must be called after
+    /// visibility cache of the camera is updated.
+    pub(in crate) fn cull(
+        &mut self,
+        scene_handle: Handle<Scene>,
+        camera_handle: Handle<Node>,
+        graph: &Graph,
+        camera: &Camera,
+    ) -> HashSet<Handle<Node>> {
+        scope_profile!();
+
+        let occlusion = match self.cameras.entry((scene_handle, camera_handle)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Default::default()),
+        };
+        occlusion.time_to_live = 20.0;
+
+        occlusion.fetch_results();
+
+        let frustum = Frustum::from(camera.view_projection_matrix()).unwrap_or_default();
+
+        // Near plane of the camera may clip faces of a box which is right in front of it,
+        // such box must be treated as if camera is inside of it.
+        occlusion.cull(
+            graph,
+            &camera.visibility_cache,
+            &frustum,
+            camera.global_position(),
+            camera.z_near() * 2.0,
+        )
+    }
+
+    /// Draws boxes of requested queries against depth buffer of given framebuffer.
+    /// Returns amount of issued queries.
+    pub(in crate) fn render_queries(&mut self, args: OcclusionQueryContext) -> usize {
+        scope_profile!();
+
+        let OcclusionQueryContext {
+            state,
+            framebuffer,
+            geometry_cache,
+            scene_handle,
+            camera_handle,
+            camera,
+            viewport,
+        } = args;
+
+        let occlusion = match self.cameras.get_mut(&(scene_handle, camera_handle)) {
+            Some(occlusion) => occlusion,
+            None => return 0,
+        };
+
+        let geometry = geometry_cache.get(state, &self.bounding_box);
+        let view_projection = camera.view_projection_matrix();
+
+        let params = DrawParameters {
+            cull_face: CullFace::Back,
+            culling: false,
+            color_write: ColorMask::all(false),
+            depth_write: false,
+            stencil_test: false,
+            depth_test: true,
+            blend: false,
+        };
+
+        let requests = std::mem::take(&mut occlusion.requests);
+        for request in requests.iter() {
+            let query = occlusion
+                .queries
+                .entry((request.node, request.target))
+                .or_insert_with(Query::new);
+
+            let world = Matrix4::new_translation(&request.bounds.center())
+                * Matrix4::new_nonuniform_scaling(&(request.bounds.max - request.bounds.min));
+
+            query.begin();
+            framebuffer.draw(
+                geometry,
+                state,
+                viewport,
+                &self.shader.program,
+                &params,
+                &[(
+                    self.shader.wvp_matrix,
+                    UniformValue::Matrix4(view_projection * world),
+                )],
+            );
+            query.end();
+        }
+
+        requests.len()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for entry in self.cameras.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.cameras.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.cameras.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{Matrix4, Vector2, Vector3},
+            math::frustum::Frustum,
+            pool::Handle,
+        },
+        renderer::{
+            occlusion::{CameraOcclusion, QueryTarget},
+            surface::{SurfaceBuilder, SurfaceSharedData},
+        },
+        scene::{
+            base::BaseBuilder, camera::CameraBuilder, mesh::MeshBuilder, node::Node,
+            transform::TransformBuilder, Scene,
+        },
+    };
+    use std::sync::{Arc, RwLock};
+
+    fn cube(scene: &mut Scene, position: Vector3<f32>, parent: Handle<Node>) -> Handle<Node> {
+        let cube = MeshBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(position)
+                    .build(),
+            ),
+        )
+        .with_surfaces(vec![SurfaceBuilder::new(Arc::new(RwLock::new(
+            SurfaceSharedData::make_cube(Matrix4::identity()),
+        )))
+        .build()])
+        .build_node();
+        let cube = scene.graph.add_node(cube);
+        scene.graph.link_nodes(cube, parent);
+        cube
+    }
+
+    fn requests(occlusion: &CameraOcclusion) -> Vec<(Handle<Node>, QueryTarget)> {
+        occlusion
+            .requests
+            .iter()
+            .map(|r| (r.node, r.target))
+            .collect()
+    }
+
+    #[test]
+    fn hierarchical_occlusion() {
+        let mut scene = Scene::new();
+        let camera = scene
+            .graph
+            .add_node(CameraBuilder::new(BaseBuilder::new()).build_node());
+        let group = scene.graph.add_node(BaseBuilder::new().build_node());
+        let near = cube(&mut scene, Vector3::new(0.0, 0.0, 5.0), group);
+        let far = cube(&mut scene, Vector3::new(0.0, 0.0, 10.0), group);
+        // Always visible cube keeps root of the graph from being occluded.
+        let root = scene.graph.get_root();
+        let other = cube(&mut scene, Vector3::new(2.0, 0.0, 5.0), root);
+        scene.update(Vector2::new(100.0, 100.0), 0.0);
+
+        let camera = scene.graph[camera].as_camera();
+        let frustum = Frustum::from(camera.view_projection_matrix()).unwrap();
+        let position = camera.global_position();
+        let mut occlusion = CameraOcclusion::default();
+        let cull = |occlusion: &mut CameraOcclusion| {
+            occlusion.cull(
+                &scene.graph,
+                &camera.visibility_cache,
+                &frustum,
+                position,
+                0.1,
+            )
+        };
+
+        // Nothing is known yet, so everything is visible and tested individually.
+        assert!(cull(&mut occlusion).is_empty());
+        let issued = requests(&occlusion);
+        assert!(issued.contains(&(near, QueryTarget::Mesh)));
+        assert!(issued.contains(&(far, QueryTarget::Mesh)));
+
+        // Far cube is hidden behind near one.
+        occlusion.apply_result(near, QueryTarget::Mesh, true);
+        occlusion.apply_result(far, QueryTarget::Mesh, false);
+        let occluded = cull(&mut occlusion);
+        assert!(occluded.contains(&far) && !occluded.contains(&near));
+
+        // Occlusion is pulled up to the far cube, so now its subtree is tested.
+        assert!(cull(&mut occlusion).contains(&far));
+        assert!(requests(&occlusion).contains(&(far, QueryTarget::Subtree)));
+
+        // Both cubes are occluded - whole group is tested with single query.
+        occlusion.apply_result(near, QueryTarget::Mesh, false);
+        cull(&mut occlusion);
+        let occluded = cull(&mut occlusion);
+        assert!(occluded.contains(&far) && occluded.contains(&near));
+        assert_eq!(
+            requests(&occlusion),
+            vec![(group, QueryTarget::Subtree), (other, QueryTarget::Mesh)]
+        );
+
+        // Group became visible again.
+        occlusion.apply_result(group, QueryTarget::Subtree, true);
+        assert!(cull(&mut occlusion).is_empty());
+
+        // Observer inside of a box never treats it as occluded.
+        occlusion.apply_result(near, QueryTarget::Mesh, false);
+        occlusion.apply_result(far, QueryTarget::Mesh, false);
+        let occluded = occlusion.cull(
+            &scene.graph,
+            &camera.visibility_cache,
+            &frustum,
+            Vector3::new(0.0, 0.0, 5.0),
+            0.1,
+        );
+        assert!(!occluded.contains(&near));
+    }
+}
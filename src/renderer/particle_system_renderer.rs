@@ -1,5 +1,10 @@
 use crate::{
-    core::{algebra::Vector2, math::Matrix4Ext, math::Rect, scope_profile},
+    core::{
+        algebra::{Vector2, Vector3},
+        math::Matrix4Ext,
+        math::Rect,
+        scope_profile,
+    },
     renderer::{
         error::RendererError,
         framework::{
@@ -15,7 +20,12 @@ use crate::{
         },
         RenderPassStatistics, TextureCache,
     },
-    scene::{camera::Camera, graph::Graph, node::Node, particle_system},
+    scene::{
+        camera::{Camera, Projection},
+        graph::Graph,
+        node::Node,
+        particle_system,
+    },
 };
 use std::{cell::RefCell, rc::Rc};
 
@@ -108,6 +118,12 @@ impl ParticleSystemRenderer {
                     kind: AttributeKind::UnsignedByte4,
                     normalized: true,
                     divisor: 0,
+                })
+                .with_attribute(AttributeDefinition {
+                    location: 5,
+                    kind: AttributeKind::Float4,
+                    normalized: false,
+                    divisor: 0,
                 }),
             )
             .build(state)?;
@@ -202,7 +218,7 @@ impl ParticleSystemRenderer {
                 ),
                 (
                     self.shader.world_matrix,
-                    UniformValue::Matrix4(node.global_transform()),
+                    UniformValue::Matrix4(particle_system.simulation_transform()),
                 ),
                 (
                     self.shader.inv_screen_size,
@@ -210,7 +226,15 @@ impl ParticleSystemRenderer {
                 ),
                 (
                     self.shader.proj_params,
-                    UniformValue::Vector2(Vector2::new(camera.z_far(), camera.z_near())),
+                    UniformValue::Vector3(Vector3::new(
+                        camera.z_far(),
+                        camera.z_near(),
+                        if camera.projection() == Projection::Orthographic {
+                            1.0
+                        } else {
+                            0.0
+                        },
+                    )),
                 ),
             ];
 
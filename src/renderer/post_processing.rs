@@ -0,0 +1,935 @@
+//! Post processing applies fullscreen effects to the frame of a camera. Effects are split
+//! into two groups: HDR effects (depth of field and bloom) are applied before tone mapping,
+//! LDR effects (FXAA, color grading, chromatic aberration, vignette and custom passes) are
+//! applied to tone mapped frame. LDR effects are "ping-ponged" between two frames, so each
+//! effect reads result of previous one.
+//!
+//! Settings of effects are stored in each camera (see `PostEffects`), quality settings of
+//! the renderer are able to globally disable each effect.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector2, Vector3, Vector4},
+        math::Rect,
+        scope_profile,
+    },
+    engine::resource_manager::TimedEntry,
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{
+                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+            },
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::{
+                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter,
+                PixelKind, WrapMode,
+            },
+            state::PipelineState,
+        },
+        gbuffer::GBuffer,
+        shader_cache::{SamplerFallbacks, ShaderCache},
+        surface::SurfaceSharedData,
+        GeometryCache, QualitySettings, RenderPassStatistics, TextureCache,
+    },
+    resource::{
+        texture::{Texture, TextureKind, TexturePixelKind, TextureState},
+        ResourceState,
+    },
+    scene::camera::Camera,
+    utils::log::Log,
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+struct CopyShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    frame_sampler: UniformLocation,
+}
+
+impl CopyShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/post_copy_fs.glsl");
+        let vertex_source = include_str!("shaders/post_effect_vs.glsl");
+        let program = GpuProgram::from_source("CopyShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_sampler: program.uniform_location("frameSampler")?,
+            program,
+        })
+    }
+}
+
+struct DepthOfFieldShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    frame_sampler: UniformLocation,
+    depth_sampler: UniformLocation,
+    inv_projection: UniformLocation,
+    frame_size: UniformLocation,
+    viewport: UniformLocation,
+    focal_distance: UniformLocation,
+    focal_range: UniformLocation,
+    max_blur: UniformLocation,
+}
+
+impl DepthOfFieldShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/post_dof_fs.glsl");
+        let vertex_source = include_str!("shaders/post_effect_vs.glsl");
+        let program =
+            GpuProgram::from_source("DepthOfFieldShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_sampler: program.uniform_location("frameSampler")?,
+            depth_sampler: program.uniform_location("depthSampler")?,
+            inv_projection: program.uniform_location("invProjection")?,
+            frame_size: program.uniform_location("frameSize")?,
+            viewport: program.uniform_location("viewport")?,
+            focal_distance: program.uniform_location("focalDistance")?,
+            focal_range: program.uniform_location("focalRange")?,
+            max_blur: program.uniform_location("maxBlur")?,
+            program,
+        })
+    }
+}
+
+struct BloomBrightShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    frame_sampler: UniformLocation,
+    viewport: UniformLocation,
+    threshold: UniformLocation,
+}
+
+impl BloomBrightShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/post_bloom_bright_fs.glsl");
+        let vertex_source = include_str!("shaders/post_effect_vs.glsl");
+        let program = GpuProgram::from_source("BloomBrightShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_sampler: program.uniform_location("frameSampler")?,
+            viewport: program.uniform_location("viewport")?,
+            threshold: program.uniform_location("threshold")?,
+            program,
+        })
+    }
+}
+
+struct BloomBlurShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    input_sampler: UniformLocation,
+    viewport: UniformLocation,
+    horizontal: UniformLocation,
+}
+
+impl BloomBlurShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/post_bloom_blur_fs.glsl");
+        let vertex_source = include_str!("shaders/post_effect_vs.glsl");
+        let program = GpuProgram::from_source("BloomBlurShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            input_sampler: program.uniform_location("inputSampler")?,
+            viewport: program.uniform_location("viewport")?,
+            horizontal: program.uniform_location("horizontal")?,
+            program,
+        })
+    }
+}
+
+struct FxaaShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    frame_sampler: UniformLocation,
+    frame_size: UniformLocation,
+}
+
+impl FxaaShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/post_fxaa_fs.glsl");
+        let vertex_source = include_str!("shaders/post_effect_vs.glsl");
+        let program = GpuProgram::from_source("FxaaShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_sampler: program.uniform_location("frameSampler")?,
+            frame_size: program.uniform_location("frameSize")?,
+            program,
+        })
+    }
+}
+
+struct CompositeShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    frame_sampler: UniformLocation,
+    lut_sampler: UniformLocation,
+    frame_size: UniformLocation,
+    viewport: UniformLocation,
+    use_color_grading: UniformLocation,
+    lut_size: UniformLocation,
+    chromatic_aberration: UniformLocation,
+    vignette_intensity: UniformLocation,
+    vignette_radius: UniformLocation,
+}
+
+impl CompositeShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/post_composite_fs.glsl");
+        let vertex_source = include_str!("shaders/post_effect_vs.glsl");
+        let program = GpuProgram::from_source("CompositeShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_sampler: program.uniform_location("frameSampler")?,
+            lut_sampler: program.uniform_location("lutSampler")?,
+            frame_size: program.uniform_location("frameSize")?,
+            viewport: program.uniform_location("viewport")?,
+            use_color_grading: program.uniform_location("useColorGrading")?,
+            lut_size: program.uniform_location("lutSize")?,
+            chromatic_aberration: program.uniform_location("chromaticAberration")?,
+            vignette_intensity: program.uniform_location("vignetteIntensity")?,
+            vignette_radius: program.uniform_location("vignetteRadius")?,
+            program,
+        })
+    }
+}
+
+/// Intermediate frames used by post effects, they have the same size as G-buffer.
+pub struct PostProcessBuffers {
+    /// Full-size HDR frame, also holds a copy of lit frame for screen space reflections.
+    pub hdr_temp: FrameBuffer,
+    // Half-size frames, bright parts of the frame are blurred between them.
+    bloom: [FrameBuffer; 2],
+    pub ldr_temp: FrameBuffer,
+}
+
+fn make_frame(
+    state: &mut PipelineState,
+    width: usize,
+    height: usize,
+    pixel_kind: PixelKind,
+    filter: bool,
+) -> Result<FrameBuffer, RendererError> {
+    let (min_filter, mag_filter) = if filter {
+        (MinificationFilter::Linear, MagnificationFilter::Linear)
+    } else {
+        (MinificationFilter::Nearest, MagnificationFilter::Nearest)
+    };
+    let mut texture = GpuTexture::new(
+        state,
+        GpuTextureKind::Rectangle { width, height },
+        pixel_kind,
+        min_filter,
+        mag_filter,
+        1,
+        None,
+    )?;
+    texture
+        .bind_mut(state, 0)
+        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+    FrameBuffer::new(
+        state,
+        None,
+        vec![Attachment {
+            kind: AttachmentKind::Color,
+            texture: Rc::new(RefCell::new(texture)),
+        }],
+    )
+}
+
+impl PostProcessBuffers {
+    pub fn new(
+        state: &mut PipelineState,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, RendererError> {
+        let half_width = (width / 2).max(1);
+        let half_height = (height / 2).max(1);
+
+        Ok(Self {
+            hdr_temp: make_frame(state, width, height, PixelKind::RGBA16F, false)?,
+            bloom: [
+                make_frame(state, half_width, half_height, PixelKind::RGBA16F, true)?,
+                make_frame(state, half_width, half_height, PixelKind::RGBA16F, true)?,
+            ],
+            ldr_temp: make_frame(state, width, height, PixelKind::RGBA8, true)?,
+        })
+    }
+}
+
+/// Converts color grading lookup table stored as horizontal strip of slices (N*N x N) into
+/// data of N x N x N RGBA8 volume texture. Returns `None` if the strip has invalid size or
+/// unsupported pixel format.
+fn lut_strip_to_volume(
+    pixel_kind: TexturePixelKind,
+    width: usize,
+    height: usize,
+    bytes: &[u8],
+) -> Option<(usize, Vec<u8>)> {
+    let (bytes_per_pixel, bgr) = match pixel_kind {
+        TexturePixelKind::RGB8 => (3, false),
+        TexturePixelKind::RGBA8 => (4, false),
+        TexturePixelKind::BGR8 => (3, true),
+        TexturePixelKind::BGRA8 => (4, true),
+        _ => return None,
+    };
+
+    let size = height;
+    if size == 0 || width != size * size || bytes.len() < width * height * bytes_per_pixel {
+        return None;
+    }
+
+    let mut volume = Vec::with_capacity(size * size * size * 4);
+    for slice in 0..size {
+        for y in 0..size {
+            for x in 0..size {
+                let offset = (y * width + slice * size + x) * bytes_per_pixel;
+                let pixel = &bytes[offset..(offset + 3)];
+                if bgr {
+                    volume.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+                } else {
+                    volume.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+                }
+            }
+        }
+    }
+
+    Some((size, volume))
+}
+
+pub(in crate) struct HdrEffectsContext<'a, 'b> {
+    pub state: &'a mut PipelineState,
+    pub geometry_cache: &'a mut GeometryCache,
+    pub gbuffer: &'a mut GBuffer,
+    pub camera: &'b Camera,
+    pub viewport: Rect<i32>,
+    pub settings: &'b QualitySettings,
+}
+
+pub(in crate) struct LdrEffectsContext<'a, 'b> {
+    pub state: &'a mut PipelineState,
+    pub geometry_cache: &'a mut GeometryCache,
+    pub texture_cache: &'a mut TextureCache,
+    pub shader_cache: &'a mut ShaderCache,
+    pub gbuffer: &'a mut GBuffer,
+    pub camera: &'b Camera,
+    pub viewport: Rect<i32>,
+    pub settings: &'b QualitySettings,
+    pub white_dummy: Rc<RefCell<GpuTexture>>,
+    pub normal_dummy: Rc<RefCell<GpuTexture>>,
+    pub black_dummy: Rc<RefCell<GpuTexture>>,
+    pub specular_dummy: Rc<RefCell<GpuTexture>>,
+}
+
+// Size and volume texture of a color grading lookup table.
+type LookupTable = (usize, Rc<RefCell<GpuTexture>>);
+
+pub(in crate) struct PostProcessingRenderer {
+    copy_shader: CopyShader,
+    dof_shader: DepthOfFieldShader,
+    bloom_bright_shader: BloomBrightShader,
+    bloom_blur_shader: BloomBlurShader,
+    fxaa_shader: FxaaShader,
+    composite_shader: CompositeShader,
+    // Failed lookup tables are stored too, so they won't be converted every frame.
+    lut_cache: HashMap<usize, TimedEntry<Option<LookupTable>>>,
+    // Bound to 3D sampler when there is no lookup table.
+    lut_dummy: Rc<RefCell<GpuTexture>>,
+    quad: SurfaceSharedData,
+}
+
+fn frame_matrix(width: f32, height: f32) -> Matrix4<f32> {
+    Matrix4::new_orthographic(0.0, width, height, 0.0, -1.0, 1.0)
+        * Matrix4::new_nonuniform_scaling(&Vector3::new(width, height, 0.0))
+}
+
+fn viewport_matrix(viewport: Rect<i32>) -> Matrix4<f32> {
+    frame_matrix(viewport.w() as f32, viewport.h() as f32)
+}
+
+fn viewport_vector(viewport: Rect<i32>) -> Vector4<f32> {
+    Vector4::new(
+        viewport.x() as f32,
+        viewport.y() as f32,
+        viewport.w() as f32,
+        viewport.h() as f32,
+    )
+}
+
+fn draw_params() -> DrawParameters {
+    DrawParameters {
+        cull_face: CullFace::Back,
+        culling: false,
+        color_write: Default::default(),
+        depth_write: false,
+        stencil_test: false,
+        depth_test: false,
+        blend: false,
+    }
+}
+
+fn color_texture(framebuffer: &FrameBuffer) -> Rc<RefCell<GpuTexture>> {
+    framebuffer.color_attachments()[0].texture.clone()
+}
+
+impl PostProcessingRenderer {
+    pub fn new(state: &mut PipelineState) -> Result<Self, RendererError> {
+        let mut lut_dummy = GpuTexture::new(
+            state,
+            GpuTextureKind::Volume {
+                width: 1,
+                height: 1,
+                depth: 1,
+            },
+            PixelKind::RGBA8,
+            MinificationFilter::Nearest,
+            MagnificationFilter::Nearest,
+            1,
+            Some(&[255u8, 255u8, 255u8, 255u8]),
+        )?;
+        lut_dummy
+            .bind_mut(state, 0)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::R, WrapMode::ClampToEdge);
+
+        Ok(Self {
+            copy_shader: CopyShader::new()?,
+            dof_shader: DepthOfFieldShader::new()?,
+            bloom_bright_shader: BloomBrightShader::new()?,
+            bloom_blur_shader: BloomBlurShader::new()?,
+            fxaa_shader: FxaaShader::new()?,
+            composite_shader: CompositeShader::new()?,
+            lut_cache: Default::default(),
+            lut_dummy: Rc::new(RefCell::new(lut_dummy)),
+            quad: SurfaceSharedData::make_unit_xy_quad(),
+        })
+    }
+
+    /// Returns size and volume texture of given lookup table, `None` if the table is not
+    /// loaded yet or invalid.
+    fn color_grading_lut(
+        &mut self,
+        state: &mut PipelineState,
+        lut: &Texture,
+    ) -> Option<LookupTable> {
+        let key = lut.key();
+        if let Some(entry) = self.lut_cache.get_mut(&key) {
+            entry.time_to_live = 20.0;
+            return entry.value.clone();
+        }
+
+        let lut = lut.state();
+        let data = match &*lut {
+            TextureState::Ok(data) => data,
+            // Not loaded yet.
+            _ => return None,
+        };
+
+        let volume = if let TextureKind::Rectangle { width, height } = data.kind() {
+            lut_strip_to_volume(
+                data.pixel_kind,
+                width as usize,
+                height as usize,
+                &data.bytes,
+            )
+        } else {
+            None
+        };
+
+        let texture = volume.and_then(|(size, bytes)| {
+            match GpuTexture::new(
+                state,
+                GpuTextureKind::Volume {
+                    width: size,
+                    height: size,
+                    depth: size,
+                },
+                PixelKind::RGBA8,
+                MinificationFilter::Linear,
+                MagnificationFilter::Linear,
+                1,
+                Some(&bytes),
+            ) {
+                Ok(mut texture) => {
+                    texture
+                        .bind_mut(state, 0)
+                        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+                        .set_wrap(Coordinate::T, WrapMode::ClampToEdge)
+                        .set_wrap(Coordinate::R, WrapMode::ClampToEdge);
+                    Some((size, Rc::new(RefCell::new(texture))))
+                }
+                Err(e) => {
+                    Log::writeln(format!(
+                        "Failed to create color grading lookup table. Reason: {:?}",
+                        e
+                    ));
+                    None
+                }
+            }
+        });
+
+        if texture.is_none() {
+            Log::writeln(format!(
+                "Invalid color grading lookup table {:?}, it must be N*N x N RGB(A)8 texture!",
+                lut.path()
+            ));
+        }
+
+        self.lut_cache.insert(
+            key,
+            TimedEntry {
+                value: texture.clone(),
+                time_to_live: 20.0,
+            },
+        );
+
+        texture
+    }
+
+    /// Applies depth of field and extracts bloom from HDR frame of given camera. Returns
+    /// HDR frame which must be used for tone mapping and optional bloom texture with its
+    /// intensity.
+    #[allow(clippy::type_complexity)]
+    pub(in crate) fn render_hdr_effects(
+        &mut self,
+        args: HdrEffectsContext,
+    ) -> (
+        RenderPassStatistics,
+        Rc<RefCell<GpuTexture>>,
+        Option<(Rc<RefCell<GpuTexture>>, f32)>,
+    ) {
+        scope_profile!();
+
+        let HdrEffectsContext {
+            state,
+            geometry_cache,
+            gbuffer,
+            camera,
+            viewport,
+            settings,
+        } = args;
+
+        let mut statistics = RenderPassStatistics::default();
+        let post_effects = camera.post_effects();
+        let frame_size = Vector2::new(gbuffer.width as f32, gbuffer.height as f32);
+
+        let mut frame = gbuffer.hdr_frame_texture();
+
+        if let Some(dof) = post_effects
+            .depth_of_field
+            .filter(|_| settings.use_depth_of_field)
+        {
+            let inv_projection = camera.projection_matrix().try_inverse().unwrap_or_default();
+
+            let depth = gbuffer.depth();
+            let target = &mut gbuffer.post_buffers.hdr_temp;
+            statistics += target.draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &self.dof_shader.program,
+                &draw_params(),
+                &[
+                    (
+                        self.dof_shader.wvp_matrix,
+                        UniformValue::Matrix4(viewport_matrix(viewport)),
+                    ),
+                    (
+                        self.dof_shader.frame_sampler,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: frame,
+                        },
+                    ),
+                    (
+                        self.dof_shader.depth_sampler,
+                        UniformValue::Sampler {
+                            index: 1,
+                            texture: depth,
+                        },
+                    ),
+                    (
+                        self.dof_shader.inv_projection,
+                        UniformValue::Matrix4(inv_projection),
+                    ),
+                    (
+                        self.dof_shader.frame_size,
+                        UniformValue::Vector2(frame_size),
+                    ),
+                    (
+                        self.dof_shader.viewport,
+                        UniformValue::Vector4(viewport_vector(viewport)),
+                    ),
+                    (
+                        self.dof_shader.focal_distance,
+                        UniformValue::Float(dof.focal_distance),
+                    ),
+                    (
+                        self.dof_shader.focal_range,
+                        UniformValue::Float(dof.focal_range),
+                    ),
+                    (
+                        self.dof_shader.max_blur,
+                        UniformValue::Float(dof.max_blur.max(0.0)),
+                    ),
+                ],
+            );
+            frame = color_texture(target);
+        }
+
+        let bloom = if let Some(bloom) = post_effects.bloom.filter(|_| settings.use_bloom) {
+            let half_viewport = Rect::new(
+                viewport.x() / 2,
+                viewport.y() / 2,
+                (viewport.w() / 2).max(1),
+                (viewport.h() / 2).max(1),
+            );
+
+            let bloom_buffers = &mut gbuffer.post_buffers.bloom;
+
+            statistics += bloom_buffers[0].draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                half_viewport,
+                &self.bloom_bright_shader.program,
+                &draw_params(),
+                &[
+                    (
+                        self.bloom_bright_shader.wvp_matrix,
+                        UniformValue::Matrix4(viewport_matrix(half_viewport)),
+                    ),
+                    (
+                        self.bloom_bright_shader.frame_sampler,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: frame.clone(),
+                        },
+                    ),
+                    (
+                        self.bloom_bright_shader.viewport,
+                        UniformValue::Vector4(viewport_vector(viewport)),
+                    ),
+                    (
+                        self.bloom_bright_shader.threshold,
+                        UniformValue::Float(bloom.threshold),
+                    ),
+                ],
+            );
+
+            // Two iterations of separable blur gives wider glow.
+            for _ in 0..2 {
+                for &(source, target, horizontal) in &[(0, 1, true), (1, 0, false)] {
+                    let input = color_texture(&bloom_buffers[source]);
+                    statistics += bloom_buffers[target].draw(
+                        geometry_cache.get(state, &self.quad),
+                        state,
+                        half_viewport,
+                        &self.bloom_blur_shader.program,
+                        &draw_params(),
+                        &[
+                            (
+                                self.bloom_blur_shader.wvp_matrix,
+                                UniformValue::Matrix4(viewport_matrix(half_viewport)),
+                            ),
+                            (
+                                self.bloom_blur_shader.input_sampler,
+                                UniformValue::Sampler {
+                                    index: 0,
+                                    texture: input,
+                                },
+                            ),
+                            (
+                                self.bloom_blur_shader.viewport,
+                                UniformValue::Vector4(viewport_vector(half_viewport)),
+                            ),
+                            (
+                                self.bloom_blur_shader.horizontal,
+                                UniformValue::Bool(horizontal),
+                            ),
+                        ],
+                    );
+                }
+            }
+
+            Some((color_texture(&bloom_buffers[0]), bloom.intensity))
+        } else {
+            None
+        };
+
+        (statistics, frame, bloom)
+    }
+
+    /// Applies LDR effects to tone mapped frame of given camera, result is always written
+    /// to LDR frame of G-buffer.
+    pub(in crate) fn render_ldr_effects(&mut self, args: LdrEffectsContext) -> RenderPassStatistics {
+        scope_profile!();
+
+        let LdrEffectsContext {
+            state,
+            geometry_cache,
+            texture_cache,
+            shader_cache,
+            gbuffer,
+            camera,
+            viewport,
+            settings,
+            white_dummy,
+            normal_dummy,
+            black_dummy,
+            specular_dummy,
+        } = args;
+
+        let mut statistics = RenderPassStatistics::default();
+        let post_effects = camera.post_effects();
+        let frame_size = Vector2::new(gbuffer.width as f32, gbuffer.height as f32);
+        let depth = gbuffer.depth();
+
+        // Index of a frame with result of last pass: 0 - LDR frame of G-buffer, 1 - temporary.
+        let mut current = 0;
+        let frames = [&mut gbuffer.ldr_frame, &mut gbuffer.post_buffers.ldr_temp];
+
+        if post_effects.fxaa && settings.use_fxaa {
+            let source = color_texture(frames[current]);
+            statistics += frames[1 - current].draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &self.fxaa_shader.program,
+                &draw_params(),
+                &[
+                    (
+                        self.fxaa_shader.wvp_matrix,
+                        UniformValue::Matrix4(viewport_matrix(viewport)),
+                    ),
+                    (
+                        self.fxaa_shader.frame_sampler,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: source,
+                        },
+                    ),
+                    (
+                        self.fxaa_shader.frame_size,
+                        UniformValue::Vector2(frame_size),
+                    ),
+                ],
+            );
+            current = 1 - current;
+        }
+
+        let lut = if settings.use_color_grading {
+            post_effects
+                .color_grading_lut
+                .as_ref()
+                .and_then(|lut| self.color_grading_lut(state, lut))
+        } else {
+            None
+        };
+        let chromatic_aberration = post_effects
+            .chromatic_aberration
+            .filter(|_| settings.use_chromatic_aberration);
+        let vignette = post_effects.vignette.filter(|_| settings.use_vignette);
+
+        if lut.is_some() || chromatic_aberration.is_some() || vignette.is_some() {
+            let source = color_texture(frames[current]);
+            let (lut_size, lut_texture) =
+                lut.clone().unwrap_or_else(|| (1, self.lut_dummy.clone()));
+            statistics += frames[1 - current].draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &self.composite_shader.program,
+                &draw_params(),
+                &[
+                    (
+                        self.composite_shader.wvp_matrix,
+                        UniformValue::Matrix4(viewport_matrix(viewport)),
+                    ),
+                    (
+                        self.composite_shader.frame_sampler,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: source,
+                        },
+                    ),
+                    (
+                        self.composite_shader.lut_sampler,
+                        UniformValue::Sampler {
+                            index: 1,
+                            texture: lut_texture,
+                        },
+                    ),
+                    (
+                        self.composite_shader.frame_size,
+                        UniformValue::Vector2(frame_size),
+                    ),
+                    (
+                        self.composite_shader.viewport,
+                        UniformValue::Vector4(viewport_vector(viewport)),
+                    ),
+                    (
+                        self.composite_shader.use_color_grading,
+                        UniformValue::Bool(lut.is_some()),
+                    ),
+                    (
+                        self.composite_shader.lut_size,
+                        UniformValue::Float(lut_size as f32),
+                    ),
+                    (
+                        self.composite_shader.chromatic_aberration,
+                        UniformValue::Float(chromatic_aberration.map_or(0.0, |ca| ca.strength)),
+                    ),
+                    (
+                        self.composite_shader.vignette_intensity,
+                        UniformValue::Float(vignette.map_or(0.0, |v| v.intensity)),
+                    ),
+                    (
+                        self.composite_shader.vignette_radius,
+                        UniformValue::Float(vignette.map_or(0.0, |v| v.radius)),
+                    ),
+                ],
+            );
+            current = 1 - current;
+        }
+
+        let fallbacks = SamplerFallbacks {
+            white: white_dummy,
+            normal: normal_dummy,
+            black: black_dummy,
+            specular: specular_dummy,
+        };
+
+        for material in post_effects.custom_passes.iter() {
+            let material = material.state();
+            let material = match &*material {
+                ResourceState::Ok(material) => material,
+                // Not loaded yet or failed to load, skip.
+                _ => continue,
+            };
+
+            let program = match shader_cache.get(material.shader()) {
+                Some(program) => program,
+                // Compilation error is already written to the log.
+                None => continue,
+            };
+
+            let source = color_texture(frames[current]);
+
+            let mut uniforms = Vec::new();
+            let sampler_count = program.fill_property_uniforms(
+                state,
+                material,
+                texture_cache,
+                &fallbacks,
+                &mut uniforms,
+            );
+            if let Some(location) = program.wvp_matrix {
+                uniforms.push((location, UniformValue::Matrix4(viewport_matrix(viewport))));
+            }
+            if let Some(location) = program.frame_texture {
+                uniforms.push((
+                    location,
+                    UniformValue::Sampler {
+                        index: sampler_count,
+                        texture: source,
+                    },
+                ));
+            }
+            if let Some(location) = program.depth_texture {
+                uniforms.push((
+                    location,
+                    UniformValue::Sampler {
+                        index: sampler_count + 1,
+                        texture: depth.clone(),
+                    },
+                ));
+            }
+            if let Some(location) = program.frame_size {
+                uniforms.push((location, UniformValue::Vector2(frame_size)));
+            }
+
+            statistics += frames[1 - current].draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &program.program,
+                &draw_params(),
+                &uniforms,
+            );
+            current = 1 - current;
+        }
+
+        // Result must be in LDR frame of G-buffer.
+        if current != 0 {
+            let source = color_texture(frames[current]);
+            statistics += frames[0].draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &self.copy_shader.program,
+                &draw_params(),
+                &[
+                    (
+                        self.copy_shader.wvp_matrix,
+                        UniformValue::Matrix4(viewport_matrix(viewport)),
+                    ),
+                    (
+                        self.copy_shader.frame_sampler,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: source,
+                        },
+                    ),
+                ],
+            );
+        }
+
+        statistics
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        scope_profile!();
+
+        for entry in self.lut_cache.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.lut_cache.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.lut_cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        renderer::post_processing::lut_strip_to_volume, resource::texture::TexturePixelKind,
+    };
+
+    #[test]
+    fn lut_strip_conversion() {
+        // 2x2x2 table as 4x2 RGB strip, red of a pixel is its index in the strip.
+        let mut strip = Vec::new();
+        for i in 0..8u8 {
+            strip.extend_from_slice(&[i, 0, 0]);
+        }
+
+        let (size, volume) = lut_strip_to_volume(TexturePixelKind::RGB8, 4, 2, &strip).unwrap();
+        assert_eq!(size, 2);
+        assert_eq!(volume.len(), 2 * 2 * 2 * 4);
+
+        // Volume texel (x, y, z) must be taken from strip pixel (z * size + x, y).
+        let red = volume.chunks(4).map(|texel| texel[0]).collect::<Vec<_>>();
+        assert_eq!(red, vec![0, 1, 4, 5, 2, 3, 6, 7]);
+        assert!(volume.chunks(4).all(|texel| texel[3] == 255));
+
+        assert!(lut_strip_to_volume(TexturePixelKind::RGB8, 3, 2, &strip).is_none());
+        assert!(lut_strip_to_volume(TexturePixelKind::R8, 4, 2, &strip).is_none());
+    }
+}
@@ -0,0 +1,933 @@
+//! Render graph allows to extend renderer with custom passes without modifying its code.
+//!
+//! # Overview
+//!
+//! Each camera is rendered by a sequence of passes. Renderer has a fixed set of built-in
+//! passes (G-buffer fill, lighting, forward rendering, tone mapping, etc.), custom passes
+//! can be added using [RenderGraph::add_pass]. Every pass declares resources it reads and
+//! writes, renderer uses these declarations to find order of passes:
+//!
+//! - Passes that write a resource are executed in order of registration, built-in passes
+//!   go first.
+//! - A pass that only reads a resource is executed after every pass that writes it.
+//!
+//! For example a pass that writes [RenderResource::HdrFrame] is executed after lighting
+//! and transparent objects, but before tone mapping. A pass that writes
+//! [RenderResource::LdrFrame] is executed after all post effects and is good for overlays.
+//!
+//! # Transient resources
+//!
+//! A pass could create a transient texture (see [PassBuilder::create_transient]) which can be
+//! read by other passes. Transient textures live only while they're used, renderer reuses
+//! them between passes and cameras. Passes whose results are never used are culled - a pass
+//! is executed only if it writes a built-in resource or a transient texture used by other
+//! executed pass.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::renderer::{
+//!     error::RendererError,
+//!     render_graph::{PassBuilder, PassContext, RenderPass, RenderResource},
+//!     RenderPassStatistics,
+//! };
+//!
+//! struct Overlay;
+//!
+//! impl RenderPass for Overlay {
+//!     fn name(&self) -> &str {
+//!         "Overlay"
+//!     }
+//!
+//!     fn declare(&self, builder: &mut PassBuilder) {
+//!         builder.write(RenderResource::LdrFrame);
+//!     }
+//!
+//!     fn execute(&mut self, mut context: PassContext) -> Result<RenderPassStatistics, RendererError> {
+//!         let _framebuffer = context.resources.framebuffer(&RenderResource::LdrFrame).unwrap();
+//!         // Draw something using framework.
+//!         Ok(Default::default())
+//!     }
+//! }
+//! ```
+
+use crate::{
+    core::{math::Rect, pool::Handle, pool::Pool},
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{Attachment, AttachmentKind, FrameBuffer},
+            gpu_texture::{
+                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter,
+                PixelKind, WrapMode,
+            },
+            state::PipelineState,
+        },
+        gbuffer::GBuffer,
+        GeometryCache, RenderPassStatistics, TextureCache,
+    },
+    scene::{camera::Camera, Scene},
+    utils::log::Log,
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// Resource that could be read or written by a pass.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum RenderResource {
+    /// Depth-stencil texture of G-buffer. Read-only for custom passes.
+    Depth,
+    /// Diffuse texture of G-buffer. Read-only for custom passes.
+    GBufferDiffuse,
+    /// Normal texture of G-buffer. Read-only for custom passes.
+    GBufferNormal,
+    /// Floating-point frame before tone mapping, depth of G-buffer is attached to it.
+    HdrFrame,
+    /// Tone mapped frame, depth of G-buffer is attached to it.
+    LdrFrame,
+    /// Texture created by a pass, see [PassBuilder::create_transient].
+    Transient(String),
+}
+
+/// Size of a transient texture.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TransientSize {
+    /// Size is given relative to frame size, `1.0` means full size, `0.5` - half size.
+    Relative(f32),
+    /// Fixed size in pixels.
+    Absolute {
+        /// Width in pixels.
+        width: usize,
+        /// Height in pixels.
+        height: usize,
+    },
+}
+
+/// Description of a transient texture.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TransientTextureDescriptor {
+    /// Size of the texture.
+    pub size: TransientSize,
+    /// Pixel format of the texture.
+    pub pixel_kind: PixelKind,
+}
+
+impl TransientTextureDescriptor {
+    fn resolve(&self, frame_width: usize, frame_height: usize) -> (usize, usize) {
+        match self.size {
+            TransientSize::Relative(scale) => (
+                ((frame_width as f32 * scale) as usize).max(1),
+                ((frame_height as f32 * scale) as usize).max(1),
+            ),
+            TransientSize::Absolute { width, height } => (width.max(1), height.max(1)),
+        }
+    }
+}
+
+/// Collects resources used by a pass.
+#[derive(Default, Clone, Debug)]
+pub struct PassBuilder {
+    reads: Vec<RenderResource>,
+    writes: Vec<RenderResource>,
+    transients: Vec<(String, TransientTextureDescriptor)>,
+}
+
+impl PassBuilder {
+    /// Declares that pass reads given resource.
+    pub fn read(&mut self, resource: RenderResource) -> &mut Self {
+        if !self.reads.contains(&resource) {
+            self.reads.push(resource);
+        }
+        self
+    }
+
+    /// Declares that pass writes given resource.
+    pub fn write(&mut self, resource: RenderResource) -> &mut Self {
+        if !self.writes.contains(&resource) {
+            self.writes.push(resource);
+        }
+        self
+    }
+
+    /// Declares that pass creates and writes transient texture with given name.
+    pub fn create_transient<S: AsRef<str>>(
+        &mut self,
+        name: S,
+        descriptor: TransientTextureDescriptor,
+    ) -> &mut Self {
+        self.transients.push((name.as_ref().to_owned(), descriptor));
+        self.write(RenderResource::Transient(name.as_ref().to_owned()))
+    }
+
+    fn uses(&self, resource: &RenderResource) -> bool {
+        self.reads.contains(resource) || self.writes.contains(resource)
+    }
+}
+
+/// Custom render pass.
+pub trait RenderPass {
+    /// Returns name of the pass, it is used in error messages.
+    fn name(&self) -> &str;
+
+    /// Declares resources used by the pass. It is called when the graph is compiled, call
+    /// [RenderGraph::invalidate] if declarations of a pass have changed.
+    fn declare(&self, builder: &mut PassBuilder);
+
+    /// Executes the pass for a camera.
+    fn execute(&mut self, context: PassContext) -> Result<RenderPassStatistics, RendererError>;
+}
+
+/// Provides access to resources declared by a pass.
+pub struct PassResources<'a> {
+    gbuffer: &'a mut GBuffer,
+    transients: &'a mut TransientPool,
+    bindings: &'a HashMap<String, usize>,
+    declaration: &'a PassBuilder,
+}
+
+impl<'a> PassResources<'a> {
+    /// Returns texture of given resource. Returns `None` if the pass did not declare it
+    /// as read or written resource.
+    pub fn texture(&self, resource: &RenderResource) -> Option<Rc<RefCell<GpuTexture>>> {
+        if !self.declaration.uses(resource) {
+            return None;
+        }
+        match resource {
+            RenderResource::Depth => Some(self.gbuffer.depth()),
+            RenderResource::GBufferDiffuse => Some(self.gbuffer.diffuse_texture()),
+            RenderResource::GBufferNormal => Some(self.gbuffer.normal_texture()),
+            RenderResource::HdrFrame => Some(self.gbuffer.hdr_frame_texture()),
+            RenderResource::LdrFrame => Some(self.gbuffer.frame_texture()),
+            RenderResource::Transient(name) => self.bindings.get(name).map(|&index| {
+                self.transients.targets[index]
+                    .framebuffer
+                    .color_attachments()[0]
+                    .texture
+                    .clone()
+            }),
+        }
+    }
+
+    /// Returns framebuffer of given resource. Returns `None` if the pass did not declare it
+    /// as written resource or if the resource is read-only.
+    pub fn framebuffer(&mut self, resource: &RenderResource) -> Option<&mut FrameBuffer> {
+        if !self.declaration.writes.contains(resource) {
+            return None;
+        }
+        match resource {
+            RenderResource::HdrFrame => Some(&mut self.gbuffer.final_frame),
+            RenderResource::LdrFrame => Some(&mut self.gbuffer.ldr_frame),
+            RenderResource::Transient(name) => match self.bindings.get(name) {
+                Some(&index) => Some(&mut self.transients.targets[index].framebuffer),
+                None => None,
+            },
+            RenderResource::Depth
+            | RenderResource::GBufferDiffuse
+            | RenderResource::GBufferNormal => None,
+        }
+    }
+}
+
+/// Everything a custom pass needs to render.
+pub struct PassContext<'a, 'b> {
+    /// Pipeline state.
+    pub state: &'a mut PipelineState,
+    /// Cache of geometry buffers.
+    pub geometry_cache: &'a mut GeometryCache,
+    /// Cache of textures.
+    pub texture_cache: &'a mut TextureCache,
+    /// Scene being rendered.
+    pub scene: &'b Scene,
+    /// Camera being rendered.
+    pub camera: &'b Camera,
+    /// Viewport of the camera in pixels.
+    pub viewport: Rect<i32>,
+    /// Resources declared by the pass.
+    pub resources: PassResources<'a>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(in crate) enum BuiltinPass {
+    GBuffer,
+    Lighting,
+    Forward,
+    Particles,
+    Sprites,
+    ToneMapping,
+    LdrEffects,
+    Debug,
+}
+
+impl BuiltinPass {
+    const ALL: [BuiltinPass; 8] = [
+        BuiltinPass::GBuffer,
+        BuiltinPass::Lighting,
+        BuiltinPass::Forward,
+        BuiltinPass::Particles,
+        BuiltinPass::Sprites,
+        BuiltinPass::ToneMapping,
+        BuiltinPass::LdrEffects,
+        BuiltinPass::Debug,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            BuiltinPass::GBuffer => "GBuffer",
+            BuiltinPass::Lighting => "Lighting",
+            BuiltinPass::Forward => "Forward",
+            BuiltinPass::Particles => "Particles",
+            BuiltinPass::Sprites => "Sprites",
+            BuiltinPass::ToneMapping => "ToneMapping",
+            BuiltinPass::LdrEffects => "LdrEffects",
+            BuiltinPass::Debug => "Debug",
+        }
+    }
+
+    fn declare(self, builder: &mut PassBuilder) {
+        match self {
+            BuiltinPass::GBuffer => {
+                builder
+                    .write(RenderResource::Depth)
+                    .write(RenderResource::GBufferDiffuse)
+                    .write(RenderResource::GBufferNormal);
+            }
+            BuiltinPass::Lighting => {
+                builder
+                    .read(RenderResource::Depth)
+                    .read(RenderResource::GBufferDiffuse)
+                    .read(RenderResource::GBufferNormal)
+                    .write(RenderResource::HdrFrame);
+            }
+            BuiltinPass::Forward | BuiltinPass::Particles => {
+                builder
+                    .read(RenderResource::Depth)
+                    .write(RenderResource::HdrFrame);
+            }
+            BuiltinPass::Sprites => {
+                builder.write(RenderResource::HdrFrame);
+            }
+            BuiltinPass::ToneMapping => {
+                builder
+                    .read(RenderResource::Depth)
+                    .read(RenderResource::HdrFrame)
+                    .write(RenderResource::LdrFrame);
+            }
+            BuiltinPass::LdrEffects => {
+                builder
+                    .read(RenderResource::Depth)
+                    .write(RenderResource::LdrFrame);
+            }
+            BuiltinPass::Debug => {
+                builder.write(RenderResource::LdrFrame);
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(in crate) enum PassKind {
+    Builtin(BuiltinPass),
+    Custom(Handle<Box<dyn RenderPass>>),
+}
+
+pub(in crate) struct ScheduledPass {
+    pub kind: PassKind,
+    pub declaration: PassBuilder,
+    // Transient textures that must be acquired before the pass.
+    pub acquire: Vec<(String, TransientTextureDescriptor)>,
+    // Transient textures that are not used after the pass.
+    pub release: Vec<String>,
+}
+
+struct TransientTarget {
+    width: usize,
+    height: usize,
+    pixel_kind: PixelKind,
+    framebuffer: FrameBuffer,
+    in_use: bool,
+    time_to_live: f32,
+}
+
+/// Pool of textures for transient resources, free textures are reused by other passes.
+#[derive(Default)]
+pub(in crate) struct TransientPool {
+    targets: Vec<TransientTarget>,
+}
+
+impl TransientPool {
+    fn acquire(
+        &mut self,
+        state: &mut PipelineState,
+        width: usize,
+        height: usize,
+        pixel_kind: PixelKind,
+    ) -> Result<usize, RendererError> {
+        if let Some(index) = self.targets.iter().position(|t| {
+            !t.in_use && t.width == width && t.height == height && t.pixel_kind == pixel_kind
+        }) {
+            let target = &mut self.targets[index];
+            target.in_use = true;
+            target.time_to_live = 20.0;
+            return Ok(index);
+        }
+
+        let mut texture = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle { width, height },
+            pixel_kind,
+            MinificationFilter::Linear,
+            MagnificationFilter::Linear,
+            1,
+            None,
+        )?;
+        texture
+            .bind_mut(state, 0)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+        let framebuffer = FrameBuffer::new(
+            state,
+            None,
+            vec![Attachment {
+                kind: AttachmentKind::Color,
+                texture: Rc::new(RefCell::new(texture)),
+            }],
+        )?;
+
+        self.targets.push(TransientTarget {
+            width,
+            height,
+            pixel_kind,
+            framebuffer,
+            in_use: true,
+            time_to_live: 20.0,
+        });
+
+        Ok(self.targets.len() - 1)
+    }
+
+    fn release(&mut self, index: usize) {
+        self.targets[index].in_use = false;
+    }
+
+    fn update(&mut self, dt: f32) {
+        for target in self.targets.iter_mut() {
+            target.time_to_live -= dt;
+        }
+        self.targets.retain(|t| t.in_use || t.time_to_live > 0.0);
+    }
+}
+
+/// See module docs.
+pub struct RenderGraph {
+    passes: Pool<Box<dyn RenderPass>>,
+    schedule: Vec<ScheduledPass>,
+    need_compile: bool,
+    transients: TransientPool,
+    // Transient name to pool index mapping for camera being rendered.
+    bindings: HashMap<String, usize>,
+}
+
+impl Default for RenderGraph {
+    fn default() -> Self {
+        Self {
+            passes: Pool::new(),
+            schedule: Default::default(),
+            need_compile: true,
+            transients: Default::default(),
+            bindings: Default::default(),
+        }
+    }
+}
+
+pub(in crate) struct CustomPassContext<'a, 'b> {
+    pub state: &'a mut PipelineState,
+    pub geometry_cache: &'a mut GeometryCache,
+    pub texture_cache: &'a mut TextureCache,
+    pub gbuffer: &'a mut GBuffer,
+    pub scene: &'b Scene,
+    pub camera: &'b Camera,
+    pub viewport: Rect<i32>,
+}
+
+struct Node {
+    kind: PassKind,
+    name: String,
+    declaration: PassBuilder,
+}
+
+impl RenderGraph {
+    /// Adds new custom pass, graph will be recompiled on next frame.
+    pub fn add_pass(&mut self, pass: Box<dyn RenderPass>) -> Handle<Box<dyn RenderPass>> {
+        self.need_compile = true;
+        self.passes.spawn(pass)
+    }
+
+    /// Removes custom pass, graph will be recompiled on next frame.
+    pub fn remove_pass(&mut self, handle: Handle<Box<dyn RenderPass>>) -> Box<dyn RenderPass> {
+        self.need_compile = true;
+        self.passes.free(handle)
+    }
+
+    /// Returns reference to a custom pass.
+    pub fn pass(&self, handle: Handle<Box<dyn RenderPass>>) -> &dyn RenderPass {
+        &*self.passes[handle]
+    }
+
+    /// Returns reference to a custom pass.
+    pub fn pass_mut(&mut self, handle: Handle<Box<dyn RenderPass>>) -> &mut dyn RenderPass {
+        &mut *self.passes[handle]
+    }
+
+    /// Forces graph to be recompiled on next frame. Must be called when resources declared
+    /// by a pass have changed.
+    pub fn invalidate(&mut self) {
+        self.need_compile = true;
+    }
+
+    /// Returns names of passes in order of execution, culled passes are not included.
+    pub fn execution_order(&mut self) -> Result<Vec<String>, RendererError> {
+        self.compile()?;
+        Ok(self
+            .schedule
+            .iter()
+            .map(|scheduled| match scheduled.kind {
+                PassKind::Builtin(builtin) => builtin.name().to_owned(),
+                PassKind::Custom(handle) => self.passes[handle].name().to_owned(),
+            })
+            .collect())
+    }
+
+    /// Finds order of passes, culls unused passes and calculates lifetimes of transient
+    /// resources. Graph is compiled automatically when it is needed, but this method could
+    /// be used to validate custom passes.
+    pub fn compile(&mut self) -> Result<(), RendererError> {
+        self.schedule = self.build_schedule()?;
+        self.need_compile = false;
+        Ok(())
+    }
+
+    fn build_schedule(&self) -> Result<Vec<ScheduledPass>, RendererError> {
+        let mut nodes = BuiltinPass::ALL
+            .iter()
+            .map(|&builtin| {
+                let mut declaration = PassBuilder::default();
+                builtin.declare(&mut declaration);
+                Node {
+                    kind: PassKind::Builtin(builtin),
+                    name: builtin.name().to_owned(),
+                    declaration,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for (handle, pass) in self.passes.pair_iter() {
+            let mut declaration = PassBuilder::default();
+            pass.declare(&mut declaration);
+            nodes.push(Node {
+                kind: PassKind::Custom(handle),
+                name: pass.name().to_owned(),
+                declaration,
+            });
+        }
+
+        // Validate transient resources - each must be created by exactly one pass.
+        let mut creators = HashMap::new();
+        for (index, node) in nodes.iter().enumerate() {
+            for (name, descriptor) in node.declaration.transients.iter() {
+                if creators
+                    .insert(name.clone(), (index, *descriptor))
+                    .is_some()
+                {
+                    return Err(RendererError::InvalidRenderGraph(format!(
+                        "transient resource {} is created by more than one pass",
+                        name
+                    )));
+                }
+            }
+        }
+        for node in nodes.iter() {
+            for resource in node
+                .declaration
+                .reads
+                .iter()
+                .chain(node.declaration.writes.iter())
+            {
+                if let RenderResource::Transient(name) = resource {
+                    if !creators.contains_key(name) {
+                        return Err(RendererError::InvalidRenderGraph(format!(
+                            "pass {} uses transient resource {} which is never created",
+                            node.name, name
+                        )));
+                    }
+                }
+            }
+        }
+
+        // Cull passes which results are never used. Built-in passes are always alive.
+        let mut alive = nodes
+            .iter()
+            .map(|node| match node.kind {
+                PassKind::Builtin(_) => true,
+                PassKind::Custom(_) => node
+                    .declaration
+                    .writes
+                    .iter()
+                    .any(|resource| !matches!(resource, RenderResource::Transient(_))),
+            })
+            .collect::<Vec<_>>();
+        loop {
+            let mut changed = false;
+            for index in 0..nodes.len() {
+                if alive[index] {
+                    continue;
+                }
+                let used = nodes[index].declaration.writes.iter().any(|resource| {
+                    nodes.iter().enumerate().any(|(other, node)| {
+                        other != index && alive[other] && node.declaration.reads.contains(resource)
+                    })
+                });
+                if used {
+                    alive[index] = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        // Build dependencies: writers of a resource are ordered by registration, readers
+        // go after all writers.
+        let mut dependencies = vec![Vec::new(); nodes.len()];
+        let mut resources = Vec::new();
+        for node in nodes.iter() {
+            for resource in node
+                .declaration
+                .reads
+                .iter()
+                .chain(node.declaration.writes.iter())
+            {
+                if !resources.contains(resource) {
+                    resources.push(resource.clone());
+                }
+            }
+        }
+        for resource in resources.iter() {
+            let writers = (0..nodes.len())
+                .filter(|&i| alive[i] && nodes[i].declaration.writes.contains(resource))
+                .collect::<Vec<_>>();
+            for pair in writers.windows(2) {
+                dependencies[pair[1]].push(pair[0]);
+            }
+            for reader in (0..nodes.len()).filter(|&i| {
+                alive[i]
+                    && nodes[i].declaration.reads.contains(resource)
+                    && !nodes[i].declaration.writes.contains(resource)
+            }) {
+                dependencies[reader].extend_from_slice(&writers);
+            }
+        }
+
+        // Topological sort, if there are several candidates - pass with lowest index is taken
+        // so the order is stable.
+        let mut order = Vec::new();
+        let mut scheduled = vec![false; nodes.len()];
+        loop {
+            let next = (0..nodes.len()).find(|&i| {
+                alive[i] && !scheduled[i] && dependencies[i].iter().all(|&d| scheduled[d])
+            });
+            match next {
+                Some(index) => {
+                    scheduled[index] = true;
+                    order.push(index);
+                }
+                None => break,
+            }
+        }
+        if let Some(index) = (0..nodes.len()).find(|&i| alive[i] && !scheduled[i]) {
+            return Err(RendererError::InvalidRenderGraph(format!(
+                "pass {} has cyclic dependency",
+                nodes[index].name
+            )));
+        }
+
+        // Calculate lifetimes of transient resources.
+        let mut schedule = order
+            .iter()
+            .map(|&index| ScheduledPass {
+                kind: nodes[index].kind,
+                declaration: nodes[index].declaration.clone(),
+                acquire: Default::default(),
+                release: Default::default(),
+            })
+            .collect::<Vec<_>>();
+        for (name, (_, descriptor)) in creators.iter() {
+            let resource = RenderResource::Transient(name.clone());
+            let first = schedule
+                .iter()
+                .position(|scheduled| scheduled.declaration.uses(&resource));
+            let last = schedule
+                .iter()
+                .rposition(|scheduled| scheduled.declaration.uses(&resource));
+            if let (Some(first), Some(last)) = (first, last) {
+                schedule[first].acquire.push((name.clone(), *descriptor));
+                schedule[last].release.push(name.clone());
+            }
+        }
+
+        Ok(schedule)
+    }
+
+    fn builtin_schedule() -> Vec<ScheduledPass> {
+        BuiltinPass::ALL
+            .iter()
+            .map(|&builtin| {
+                let mut declaration = PassBuilder::default();
+                builtin.declare(&mut declaration);
+                ScheduledPass {
+                    kind: PassKind::Builtin(builtin),
+                    declaration,
+                    acquire: Default::default(),
+                    release: Default::default(),
+                }
+            })
+            .collect()
+    }
+
+    /// Compiles the graph if needed, if the graph is invalid only built-in passes will be
+    /// executed.
+    pub(in crate) fn prepare(&mut self) {
+        if self.need_compile {
+            if let Err(e) = self.compile() {
+                Log::writeln(format!(
+                    "Invalid render graph, custom passes are disabled. Reason: {:?}",
+                    e
+                ));
+                self.schedule = Self::builtin_schedule();
+                self.need_compile = false;
+            }
+        }
+    }
+
+    pub(in crate) fn schedule_len(&self) -> usize {
+        self.schedule.len()
+    }
+
+    pub(in crate) fn scheduled_pass(&self, index: usize) -> PassKind {
+        self.schedule[index].kind
+    }
+
+    /// Acquires transient textures needed by a scheduled pass.
+    pub(in crate) fn begin_pass(
+        &mut self,
+        index: usize,
+        state: &mut PipelineState,
+        frame_width: usize,
+        frame_height: usize,
+    ) -> Result<(), RendererError> {
+        for (name, descriptor) in self.schedule[index].acquire.iter() {
+            let (width, height) = descriptor.resolve(frame_width, frame_height);
+            let target = self
+                .transients
+                .acquire(state, width, height, descriptor.pixel_kind)?;
+            self.bindings.insert(name.clone(), target);
+        }
+        Ok(())
+    }
+
+    /// Releases transient textures that are not needed anymore.
+    pub(in crate) fn end_pass(&mut self, index: usize) {
+        for name in self.schedule[index].release.iter() {
+            if let Some(target) = self.bindings.remove(name) {
+                self.transients.release(target);
+            }
+        }
+    }
+
+    pub(in crate) fn execute_custom_pass(
+        &mut self,
+        index: usize,
+        handle: Handle<Box<dyn RenderPass>>,
+        args: CustomPassContext,
+    ) -> Result<RenderPassStatistics, RendererError> {
+        let CustomPassContext {
+            state,
+            geometry_cache,
+            texture_cache,
+            gbuffer,
+            scene,
+            camera,
+            viewport,
+        } = args;
+
+        let RenderGraph {
+            passes,
+            schedule,
+            transients,
+            bindings,
+            ..
+        } = self;
+
+        passes[handle].execute(PassContext {
+            state,
+            geometry_cache,
+            texture_cache,
+            scene,
+            camera,
+            viewport,
+            resources: PassResources {
+                gbuffer,
+                transients,
+                bindings,
+                declaration: &schedule[index].declaration,
+            },
+        })
+    }
+
+    pub(in crate) fn update(&mut self, dt: f32) {
+        self.transients.update(dt);
+    }
+
+    pub(in crate) fn clear(&mut self) {
+        self.bindings.clear();
+        self.transients.targets.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::renderer::{
+        error::RendererError,
+        framework::gpu_texture::PixelKind,
+        render_graph::{
+            PassBuilder, PassContext, RenderGraph, RenderPass, RenderResource, TransientSize,
+            TransientTextureDescriptor,
+        },
+        RenderPassStatistics,
+    };
+
+    struct TestPass {
+        name: &'static str,
+        reads: Vec<RenderResource>,
+        writes: Vec<RenderResource>,
+        creates: Option<&'static str>,
+    }
+
+    impl RenderPass for TestPass {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn declare(&self, builder: &mut PassBuilder) {
+            for resource in self.reads.iter() {
+                builder.read(resource.clone());
+            }
+            for resource in self.writes.iter() {
+                builder.write(resource.clone());
+            }
+            if let Some(name) = self.creates {
+                builder.create_transient(
+                    name,
+                    TransientTextureDescriptor {
+                        size: TransientSize::Relative(0.5),
+                        pixel_kind: PixelKind::RGBA8,
+                    },
+                );
+            }
+        }
+
+        fn execute(&mut self, _: PassContext) -> Result<RenderPassStatistics, RendererError> {
+            unreachable!()
+        }
+    }
+
+    fn transient(name: &str) -> RenderResource {
+        RenderResource::Transient(name.to_owned())
+    }
+
+    #[test]
+    fn render_graph_schedule() {
+        let mut graph = RenderGraph::default();
+
+        // Overlay is registered before the pass that produces its input.
+        graph.add_pass(Box::new(TestPass {
+            name: "Minimap",
+            reads: vec![transient("MinimapImage")],
+            writes: vec![RenderResource::LdrFrame],
+            creates: None,
+        }));
+        graph.add_pass(Box::new(TestPass {
+            name: "Outline",
+            reads: vec![RenderResource::Depth],
+            writes: vec![RenderResource::HdrFrame],
+            creates: None,
+        }));
+        graph.add_pass(Box::new(TestPass {
+            name: "MinimapImage",
+            reads: vec![RenderResource::Depth],
+            writes: vec![],
+            creates: Some("MinimapImage"),
+        }));
+        // Nobody uses results of this pass.
+        graph.add_pass(Box::new(TestPass {
+            name: "Unused",
+            reads: vec![RenderResource::HdrFrame],
+            writes: vec![],
+            creates: Some("UnusedImage"),
+        }));
+
+        assert_eq!(
+            graph.execution_order().unwrap(),
+            vec![
+                "GBuffer",
+                "Lighting",
+                "Forward",
+                "Particles",
+                "Sprites",
+                "Outline",
+                "ToneMapping",
+                "LdrEffects",
+                "Debug",
+                "MinimapImage",
+                "Minimap",
+            ]
+        );
+
+        let minimap_image = graph
+            .schedule
+            .iter()
+            .position(|s| s.acquire.iter().any(|(name, _)| name == "MinimapImage"))
+            .unwrap();
+        let minimap = graph
+            .schedule
+            .iter()
+            .position(|s| s.release.contains(&"MinimapImage".to_owned()))
+            .unwrap();
+        assert_eq!(minimap_image, 9);
+        assert_eq!(minimap, 10);
+    }
+
+    #[test]
+    fn render_graph_errors() {
+        let mut graph = RenderGraph::default();
+        let a = graph.add_pass(Box::new(TestPass {
+            name: "A",
+            reads: vec![transient("B")],
+            writes: vec![RenderResource::LdrFrame],
+            creates: Some("A"),
+        }));
+        let b = graph.add_pass(Box::new(TestPass {
+            name: "B",
+            reads: vec![transient("A")],
+            writes: vec![],
+            creates: Some("B"),
+        }));
+        assert!(graph.compile().is_err());
+
+        graph.remove_pass(b);
+        assert!(graph.compile().is_err());
+
+        graph.remove_pass(a);
+        assert!(graph.compile().is_ok());
+    }
+}
@@ -0,0 +1,177 @@
+//! Shader cache compiles shaders of materials on demand and keeps compiled programs while
+//! they're used. Programs are shared between materials with the same shader source.
+
+use crate::{
+    core::scope_profile,
+    engine::resource_manager::TimedEntry,
+    renderer::{
+        framework::{
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::GpuTexture,
+            state::PipelineState,
+        },
+        TextureCache,
+    },
+    resource::material::{MaterialData, PropertyValue, SamplerFallback, ShaderDefinition},
+    utils::log::Log,
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// Compiled shader of a material with locations of its uniforms. Built-in uniforms are
+/// optional, a shader may not use some of them.
+pub(in crate) struct MaterialProgram {
+    pub program: GpuProgram,
+    pub world_matrix: Option<UniformLocation>,
+    pub wvp_matrix: Option<UniformLocation>,
+    pub use_skeletal_animation: Option<UniformLocation>,
+    pub bone_matrices: Option<UniformLocation>,
+    pub bone_matrices_offset: Option<UniformLocation>,
+    pub camera_position: Option<UniformLocation>,
+    pub diffuse_color: Option<UniformLocation>,
+    pub layer_index: Option<UniformLocation>,
+    pub alpha_test: Option<UniformLocation>,
+    pub environment_map: Option<UniformLocation>,
+    pub frame_texture: Option<UniformLocation>,
+    pub depth_texture: Option<UniformLocation>,
+    pub frame_size: Option<UniformLocation>,
+    properties: Vec<(String, UniformLocation)>,
+}
+
+impl MaterialProgram {
+    fn new(shader: &ShaderDefinition) -> Option<Self> {
+        let program = match GpuProgram::from_source(
+            shader.name(),
+            shader.vertex_source(),
+            shader.fragment_source(),
+        ) {
+            Ok(program) => program,
+            Err(e) => {
+                Log::writeln(format!(
+                    "Unable to compile {} shader of material! Reason: {:?}",
+                    shader.name(),
+                    e
+                ));
+                return None;
+            }
+        };
+
+        Some(Self {
+            world_matrix: program.uniform_location("worldMatrix").ok(),
+            wvp_matrix: program.uniform_location("worldViewProjection").ok(),
+            use_skeletal_animation: program.uniform_location("useSkeletalAnimation").ok(),
+            bone_matrices: program.uniform_location("boneMatrices").ok(),
+            bone_matrices_offset: program.uniform_location("boneMatricesOffset").ok(),
+            camera_position: program.uniform_location("cameraPosition").ok(),
+            diffuse_color: program.uniform_location("diffuseColor").ok(),
+            layer_index: program.uniform_location("layerIndex").ok(),
+            alpha_test: program.uniform_location("alphaTest").ok(),
+            environment_map: program.uniform_location("environmentMap").ok(),
+            frame_texture: program.uniform_location("frameTexture").ok(),
+            depth_texture: program.uniform_location("depthTexture").ok(),
+            frame_size: program.uniform_location("frameSize").ok(),
+            // Unused properties are optimized out by shader compiler, just ignore them.
+            properties: shader
+                .properties()
+                .iter()
+                .filter_map(|p| {
+                    program
+                        .uniform_location(&p.name)
+                        .ok()
+                        .map(|location| (p.name.clone(), location))
+                })
+                .collect(),
+            program,
+        })
+    }
+
+    /// Fills uniforms with values of properties of given material. Returns amount of
+    /// texture units occupied by samplers, built-in samplers must use units after them.
+    pub fn fill_property_uniforms(
+        &self,
+        state: &mut PipelineState,
+        material: &MaterialData,
+        texture_cache: &mut TextureCache,
+        fallbacks: &SamplerFallbacks,
+        uniforms: &mut Vec<(UniformLocation, UniformValue<'_>)>,
+    ) -> usize {
+        let mut sampler_count = 0;
+        for (name, location) in self.properties.iter() {
+            let value = match material.property(name) {
+                Some(value) => value,
+                None => continue,
+            };
+            let uniform = match value {
+                PropertyValue::Float(v) => UniformValue::Float(*v),
+                PropertyValue::Vector2(v) => UniformValue::Vector2(*v),
+                PropertyValue::Vector3(v) => UniformValue::Vector3(*v),
+                PropertyValue::Vector4(v) => UniformValue::Vector4(*v),
+                PropertyValue::Color(v) => UniformValue::Color(*v),
+                PropertyValue::Sampler { value, fallback } => {
+                    let texture = value
+                        .clone()
+                        .and_then(|texture| texture_cache.get(state, texture))
+                        .unwrap_or_else(|| fallbacks.get(*fallback));
+                    sampler_count += 1;
+                    UniformValue::Sampler {
+                        index: sampler_count - 1,
+                        texture,
+                    }
+                }
+            };
+            uniforms.push((*location, uniform));
+        }
+        sampler_count
+    }
+}
+
+/// Textures that are used when a sampler property has no texture.
+pub(in crate) struct SamplerFallbacks {
+    pub white: Rc<RefCell<GpuTexture>>,
+    pub normal: Rc<RefCell<GpuTexture>>,
+    pub black: Rc<RefCell<GpuTexture>>,
+    pub specular: Rc<RefCell<GpuTexture>>,
+}
+
+impl SamplerFallbacks {
+    fn get(&self, fallback: SamplerFallback) -> Rc<RefCell<GpuTexture>> {
+        match fallback {
+            SamplerFallback::White => self.white.clone(),
+            SamplerFallback::Normal => self.normal.clone(),
+            SamplerFallback::Black => self.black.clone(),
+            SamplerFallback::Specular => self.specular.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(in crate) struct ShaderCache {
+    // Failed shaders are stored too, so they won't be recompiled every frame.
+    map: HashMap<u64, TimedEntry<Option<Rc<MaterialProgram>>>>,
+}
+
+impl ShaderCache {
+    pub fn get(&mut self, shader: &ShaderDefinition) -> Option<Rc<MaterialProgram>> {
+        scope_profile!();
+
+        let entry = self.map.entry(shader.key()).or_insert_with(|| TimedEntry {
+            value: MaterialProgram::new(shader).map(Rc::new),
+            time_to_live: 20.0,
+        });
+
+        entry.time_to_live = 20.0;
+        entry.value.clone()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        scope_profile!();
+
+        for entry in self.map.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.map.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+}
@@ -3,14 +3,61 @@
 uniform sampler2D diffuseTexture;
 uniform sampler2D aoSampler;
 uniform sampler2D ambientTexture;
+uniform sampler2D depthTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D materialTexture;
+uniform sampler2D emissionTexture;
+uniform samplerCube irradianceMap;
+uniform samplerCube prefilteredMap;
+uniform sampler2D brdfLut;
 uniform vec4 ambientColor;
+uniform mat4 invViewProj;
+uniform vec3 cameraPosition;
+// When there is no environment, surfaces are lit by ambient color only.
+uniform bool environmentEnabled;
+uniform float prefilteredMaxLod;
+// Specular part of ambient lighting is rendered by screen space reflections pass when it is enabled.
+uniform bool ssrEnabled;
 
 out vec4 FragColor;
 in vec2 texCoord;
 
 void main()
 {
-    float ambientOcclusion = texture(aoSampler, texCoord).r;
-    FragColor = (ambientColor + texture(ambientTexture, texCoord)) * texture(diffuseTexture, texCoord);
-    FragColor.rgb *= ambientOcclusion;
-}
\ No newline at end of file
+    vec4 diffuse = texture(diffuseTexture, texCoord);
+    vec4 normal = texture(normalTexture, texCoord);
+    vec4 material = texture(materialTexture, texCoord);
+    float metallic = material.r;
+    float roughness = material.g;
+
+    vec3 fragmentPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
+    vec3 n = normalize(normal.xyz * 2.0 - 1.0);
+    vec3 v = normalize(cameraPosition - fragmentPosition);
+    float nDotV = max(dot(n, v), 0.0);
+
+    vec3 f0 = S_BaseReflectivity(diffuse.rgb, metallic, normal.w);
+    vec3 F = S_FresnelSchlickRoughness(nDotV, f0, roughness);
+    vec3 kD = (vec3(1.0) - F) * (1.0 - metallic);
+
+    vec3 irradiance = vec3(1.0);
+    vec3 prefiltered = vec3(1.0);
+    if (environmentEnabled)
+    {
+        irradiance = texture(irradianceMap, n).rgb;
+        prefiltered = textureLod(prefilteredMap, reflect(-v, n), roughness * prefilteredMaxLod).rgb;
+    }
+
+    vec2 brdf = texture(brdfLut, vec2(nDotV, roughness)).rg;
+
+    vec3 diffuseLighting = kD * diffuse.rgb * (ambientColor.rgb * irradiance + texture(ambientTexture, texCoord).rgb);
+    vec3 specularLighting = vec3(0.0);
+    if (!ssrEnabled)
+    {
+        specularLighting = ambientColor.rgb * prefiltered * (F * brdf.x + brdf.y);
+    }
+
+    float ambientOcclusion = texture(aoSampler, texCoord).r * material.b;
+
+    FragColor.rgb = (diffuseLighting + specularLighting) * ambientOcclusion + texture(emissionTexture, texCoord).rgb;
+    FragColor.a = diffuse.a;
+}
@@ -0,0 +1,43 @@
+#version 330 core
+
+layout(location = 0) out vec4 outDiffuseMap;
+layout(location = 1) out vec4 outNormalMap;
+
+uniform sampler2D sceneDepth;
+uniform sampler2D diffuseTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D decalMask;
+uniform mat4 invViewProj;
+uniform mat4 invWorldDecal;
+uniform vec2 resolution;
+uniform vec4 color;
+uniform int layerIndex;
+uniform bool hasNormalTexture;
+
+in mat3 tangentSpace;
+
+void main()
+{
+    vec2 screenPos = gl_FragCoord.xy / resolution;
+
+    if (int(texture(decalMask, screenPos).r * 255.0 + 0.5) != layerIndex) {
+        discard;
+    }
+
+    vec3 sceneWorldPosition = S_UnProject(vec3(screenPos, texture(sceneDepth, screenPos).r), invViewProj);
+    vec3 decalSpacePosition = (invWorldDecal * vec4(sceneWorldPosition, 1.0)).xyz;
+
+    // Discard pixels of the scene that are outside of decal volume.
+    vec3 distance = vec3(0.5) - abs(decalSpacePosition);
+    if (distance.x < 0.0 || distance.y < 0.0 || distance.z < 0.0) {
+        discard;
+    }
+
+    vec2 decalTexCoord = decalSpacePosition.xz + 0.5;
+
+    outDiffuseMap = color * texture(diffuseTexture, decalTexCoord);
+
+    // Zero alpha leaves normals of the scene untouched when there is no normal texture.
+    vec3 n = normalize(texture(normalTexture, decalTexCoord).xyz * 2.0 - 1.0);
+    outNormalMap = vec4(normalize(tangentSpace * n) * 0.5 + 0.5, hasNormalTexture ? outDiffuseMap.a : 0.0);
+}
@@ -0,0 +1,16 @@
+#version 330 core
+
+layout(location = 0) in vec3 vertexPosition;
+
+uniform mat4 worldViewProjection;
+uniform mat4 worldMatrix;
+
+out mat3 tangentSpace;
+
+void main()
+{
+    // Decal projects along its local -Y axis, so local Y axis is the normal and local X and Z
+    // axes are tangent and binormal respectively.
+    tangentSpace = mat3(normalize(worldMatrix[0].xyz), normalize(worldMatrix[2].xyz), normalize(worldMatrix[1].xyz));
+    gl_Position = worldViewProjection * vec4(vertexPosition, 1.0);
+}
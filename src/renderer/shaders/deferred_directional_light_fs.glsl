@@ -1,30 +1,121 @@
 #version 330 core
 
+#define CSM_MAX_CASCADES 4
+
 uniform sampler2D depthTexture;
 uniform sampler2D colorTexture;
 uniform sampler2D normalTexture;
-uniform samplerCube pointShadowTexture;
+uniform sampler2D materialTexture;
+uniform sampler2D shadowCascade0;
+uniform sampler2D shadowCascade1;
+uniform sampler2D shadowCascade2;
+uniform sampler2D shadowCascade3;
 
 uniform vec3 lightDirection;
 uniform vec4 lightColor;
 uniform mat4 invViewProj;
+uniform mat4 viewMatrix;
 uniform vec3 cameraPosition;
+uniform bool shadowsEnabled;
+uniform bool softShadows;
+uniform float shadowMapInvSize;
+uniform float shadowBias;
+uniform mat4 viewProj;
+uniform bool contactShadowsEnabled;
+uniform float contactShadowsDistance;
+uniform int contactShadowsSteps;
+uniform float contactShadowsThickness;
+uniform int cascadeCount;
+uniform float cascadeDistances[CSM_MAX_CASCADES];
+uniform mat4 lightViewProjMatrices[CSM_MAX_CASCADES];
 
 in vec2 texCoord;
 out vec4 FragColor;
 
+float CsmShadowFactor(sampler2D shadowMap, vec3 lightSpacePosition, float bias)
+{
+    if (softShadows)
+    {
+        float lit = 0.0;
+        for (float y = -1.0; y <= 1.0; y += 1.0)
+        {
+            for (float x = -1.0; x <= 1.0; x += 1.0)
+            {
+                vec2 fetchTexCoord = lightSpacePosition.xy + vec2(x, y) * shadowMapInvSize;
+                if (lightSpacePosition.z - bias <= texture(shadowMap, fetchTexCoord).r)
+                {
+                    lit += 1.0;
+                }
+            }
+        }
+        return lit / 9.0;
+    }
+    else
+    {
+        return lightSpacePosition.z - bias > texture(shadowMap, lightSpacePosition.xy).r ? 0.0 : 1.0;
+    }
+}
+
 void main()
 {
-    vec3 fragmentNormal = normalize(texture(normalTexture, texCoord).xyz * 2.0 - 1.0);
+    vec4 normal = texture(normalTexture, texCoord);
+    vec4 material = texture(materialTexture, texCoord);
+    vec3 fragmentNormal = normalize(normal.xyz * 2.0 - 1.0);
     vec3 fragmentPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
-    float specularPower = 255.0 * texture(normalTexture, texCoord).w;
 
-    vec3 h = normalize(lightDirection + (cameraPosition - fragmentPosition));
-    float specular = pow(clamp(dot(fragmentNormal, h), 0.0, 1.0), specularPower);
+    TPBRContext ctx;
+    ctx.albedo = texture(colorTexture, texCoord).rgb;
+    ctx.metallic = material.r;
+    ctx.roughness = material.g;
+    ctx.specular = normal.w;
+    ctx.fragmentNormal = fragmentNormal;
+    ctx.fragmentToLight = lightDirection;
+    ctx.fragmentToCamera = normalize(cameraPosition - fragmentPosition);
+    vec3 lighting = S_PBR_CalculateLight(ctx);
 
     float lambertian = max(dot(fragmentNormal, lightDirection), 0);
 
-    FragColor = texture(colorTexture, texCoord);
-    FragColor.xyz += 0.4 * specular;
-    FragColor *= lambertian * lightColor;
+    float shadow = 1.0;
+    if (shadowsEnabled)
+    {
+        float viewDepth = -(viewMatrix * vec4(fragmentPosition, 1.0)).z;
+
+        // Surfaces at grazing angles need more bias to get rid of "shadow acne".
+        float bias = shadowBias * clamp(tan(acos(clamp(lambertian, 0.0, 1.0))), 1.0, 10.0);
+
+        for (int i = 0; i < cascadeCount; ++i)
+        {
+            if (viewDepth <= cascadeDistances[i])
+            {
+                vec3 lightSpacePosition = S_Project(fragmentPosition, lightViewProjMatrices[i]);
+                if (i == 0)
+                {
+                    shadow = CsmShadowFactor(shadowCascade0, lightSpacePosition, bias);
+                }
+                else if (i == 1)
+                {
+                    shadow = CsmShadowFactor(shadowCascade1, lightSpacePosition, bias);
+                }
+                else if (i == 2)
+                {
+                    shadow = CsmShadowFactor(shadowCascade2, lightSpacePosition, bias);
+                }
+                else
+                {
+                    shadow = CsmShadowFactor(shadowCascade3, lightSpacePosition, bias);
+                }
+                break;
+            }
+        }
+    }
+
+    if (contactShadowsEnabled && shadow > 0.0)
+    {
+        float jitter = S_InterleavedGradientNoise(gl_FragCoord.xy);
+        shadow *= S_ContactShadow(depthTexture, fragmentPosition, fragmentNormal, lightDirection, contactShadowsDistance,
+            contactShadowsSteps, contactShadowsThickness, jitter, viewProj, invViewProj, cameraPosition);
+    }
+
+    // Alpha is left untouched, it holds coverage of the frame.
+    FragColor = vec4(lighting * shadow * lightColor.rgb, 0.0);
 }
@@ -3,6 +3,7 @@
 uniform sampler2D depthTexture;
 uniform sampler2D colorTexture;
 uniform sampler2D normalTexture;
+uniform sampler2D materialTexture;
 uniform samplerCube pointShadowTexture;
 
 uniform vec3 lightPos;
@@ -13,20 +14,34 @@ uniform vec3 cameraPosition;
 uniform bool softShadows;
 uniform bool shadowsEnabled;
 uniform float shadowBias;
+uniform mat4 viewProj;
+uniform bool contactShadowsEnabled;
+uniform float contactShadowsDistance;
+uniform int contactShadowsSteps;
+uniform float contactShadowsThickness;
 
 in vec2 texCoord;
 out vec4 FragColor;
 
 void main()
 {
-    TBlinnPhongContext ctx;
-    ctx.lightPosition = lightPos;
-    ctx.lightRadius = lightRadius;
-    ctx.fragmentNormal = normalize(texture(normalTexture, texCoord).xyz * 2.0 - 1.0);
-    ctx.fragmentPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
-    ctx.cameraPosition = cameraPosition;
-    ctx.specularPower = 255.0 * texture(normalTexture, texCoord).w;
-    TBlinnPhong lighting = S_BlinnPhong(ctx);
+    vec3 fragmentPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
+    vec3 lightVector = lightPos - fragmentPosition;
+    float distance = length(lightVector);
+    vec3 fragmentToLight = lightVector / distance;
+
+    vec4 normal = texture(normalTexture, texCoord);
+    vec4 material = texture(materialTexture, texCoord);
+
+    TPBRContext ctx;
+    ctx.albedo = texture(colorTexture, texCoord).rgb;
+    ctx.metallic = material.r;
+    ctx.roughness = material.g;
+    ctx.specular = normal.w;
+    ctx.fragmentNormal = normalize(normal.xyz * 2.0 - 1.0);
+    ctx.fragmentToLight = fragmentToLight;
+    ctx.fragmentToCamera = normalize(cameraPosition - fragmentPosition);
+    vec3 lighting = S_PBR_CalculateLight(ctx) * S_LightDistanceAttenuation(distance, lightRadius);
 
     float shadow = 1.0;
 
@@ -48,9 +63,9 @@ void main()
 
             for (int i = 0; i < samples; ++i)
             {
-                vec3 fetchDirection = -lighting.direction + directions[i] * diskRadius;
+                vec3 fetchDirection = -fragmentToLight + directions[i] * diskRadius;
                 float shadowDistanceToLight = texture(pointShadowTexture, fetchDirection).r;
-                if (lighting.distance - shadowBias > shadowDistanceToLight)
+                if (distance - shadowBias > shadowDistanceToLight)
                 {
                     shadow += 1.0;
                 }
@@ -60,15 +75,21 @@ void main()
         }
         else
         {
-            float shadowDistanceToLight = texture(pointShadowTexture, -lighting.direction).r;
-            if (lighting.distance - shadowBias > shadowDistanceToLight)
+            float shadowDistanceToLight = texture(pointShadowTexture, -fragmentToLight).r;
+            if (distance - shadowBias > shadowDistanceToLight)
             {
                 shadow = 0.0;
             }
         }
     }
 
-    FragColor = texture(colorTexture, texCoord);
-    FragColor.xyz += 0.4 * lighting.specular;
-    FragColor *= lighting.attenuation * shadow * lightColor;
+    if (contactShadowsEnabled && shadow > 0.0)
+    {
+        float jitter = S_InterleavedGradientNoise(gl_FragCoord.xy);
+        shadow *= S_ContactShadow(depthTexture, fragmentPosition, ctx.fragmentNormal, fragmentToLight, min(contactShadowsDistance, distance),
+            contactShadowsSteps, contactShadowsThickness, jitter, viewProj, invViewProj, cameraPosition);
+    }
+
+    // Alpha is left untouched, it holds coverage of the frame.
+    FragColor = vec4(lighting * shadow * lightColor.rgb, 0.0);
 }
@@ -3,6 +3,7 @@
 uniform sampler2D depthTexture;
 uniform sampler2D colorTexture;
 uniform sampler2D normalTexture;
+uniform sampler2D materialTexture;
 uniform sampler2D spotShadowTexture;
 uniform sampler2D cookieTexture;
 
@@ -19,6 +20,11 @@ uniform bool shadowsEnabled;
 uniform bool softShadows;
 uniform float shadowMapInvSize;
 uniform float shadowBias;
+uniform mat4 viewProj;
+uniform bool contactShadowsEnabled;
+uniform float contactShadowsDistance;
+uniform int contactShadowsSteps;
+uniform float contactShadowsThickness;
 uniform bool cookieEnabled;
 
 in vec2 texCoord;
@@ -26,22 +32,31 @@ out vec4 FragColor;
 
 void main()
 {
-    TBlinnPhongContext ctx;
-    ctx.lightPosition = lightPos;
-    ctx.lightRadius = lightRadius;
-    ctx.fragmentNormal = normalize(texture(normalTexture, texCoord).xyz * 2.0 - 1.0);
-    ctx.fragmentPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
-    ctx.cameraPosition = cameraPosition;
-    ctx.specularPower = 255.0 * texture(normalTexture, texCoord).w;
-    TBlinnPhong lighting = S_BlinnPhong(ctx);
+    vec3 fragmentPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
+    vec3 lightVector = lightPos - fragmentPosition;
+    float distance = length(lightVector);
+    vec3 fragmentToLight = lightVector / distance;
 
-    float spotAngleCos = dot(lightDirection, lighting.direction);
+    vec4 normal = texture(normalTexture, texCoord);
+    vec4 material = texture(materialTexture, texCoord);
+
+    TPBRContext ctx;
+    ctx.albedo = texture(colorTexture, texCoord).rgb;
+    ctx.metallic = material.r;
+    ctx.roughness = material.g;
+    ctx.specular = normal.w;
+    ctx.fragmentNormal = normalize(normal.xyz * 2.0 - 1.0);
+    ctx.fragmentToLight = fragmentToLight;
+    ctx.fragmentToCamera = normalize(cameraPosition - fragmentPosition);
+    vec3 lighting = S_PBR_CalculateLight(ctx) * S_LightDistanceAttenuation(distance, lightRadius);
+
+    float spotAngleCos = dot(lightDirection, fragmentToLight);
     float coneFactor = smoothstep(halfConeAngleCos, halfHotspotConeAngleCos, spotAngleCos);
 
     float shadow = 1.0;
     if (shadowsEnabled)
     {
-        vec3 lightSpacePosition = S_Project(ctx.fragmentPosition, lightViewProjMatrix);
+        vec3 lightSpacePosition = S_Project(fragmentPosition, lightViewProjMatrix);
         if (softShadows)
         {
             for (float y = -1.5; y <= 1.5; y += 0.5)
@@ -69,11 +84,17 @@ void main()
 
     vec4 cookieAttenuation = vec4(1.0);
     if (cookieEnabled) {
-        vec2 texCoords = S_Project(ctx.fragmentPosition, lightViewProjMatrix).xy;
+        vec2 texCoords = S_Project(fragmentPosition, lightViewProjMatrix).xy;
         cookieAttenuation = texture(cookieTexture, texCoords);
     }
 
-    FragColor = texture(colorTexture, texCoord);
-    FragColor.rgb += 0.4 * lighting.specular;
-    FragColor *= cookieAttenuation * coneFactor * shadow * lighting.attenuation * lightColor;
+    if (contactShadowsEnabled && shadow > 0.0)
+    {
+        float jitter = S_InterleavedGradientNoise(gl_FragCoord.xy);
+        shadow *= S_ContactShadow(depthTexture, fragmentPosition, ctx.fragmentNormal, fragmentToLight, min(contactShadowsDistance, distance),
+            contactShadowsSteps, contactShadowsThickness, jitter, viewProj, invViewProj, cameraPosition);
+    }
+
+    // Alpha is left untouched, it holds coverage of the frame.
+    FragColor = vec4(lighting * cookieAttenuation.rgb * coneFactor * shadow * lightColor.rgb, 0.0);
 }
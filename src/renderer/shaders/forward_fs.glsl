@@ -0,0 +1,87 @@
+#version 330 core
+
+// Must be in sync with MAX_FORWARD_LIGHTS in forward_renderer.rs
+#define MAX_LIGHTS 16
+
+#define POINT_LIGHT 0
+#define SPOT_LIGHT 1
+#define DIRECTIONAL_LIGHT 2
+
+uniform sampler2D diffuseTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D specularTexture;
+uniform sampler2D lightmapTexture;
+uniform sampler2D roughnessTexture;
+uniform sampler2D metallicTexture;
+uniform sampler2D ambientOcclusionTexture;
+uniform sampler2D emissionTexture;
+uniform vec4 diffuseColor;
+uniform vec4 ambientColor;
+uniform vec3 cameraPosition;
+
+uniform int lightCount;
+uniform int lightKinds[MAX_LIGHTS];
+uniform vec3 lightPositions[MAX_LIGHTS];
+uniform vec3 lightDirections[MAX_LIGHTS];
+uniform vec4 lightColors[MAX_LIGHTS];
+// x - radius, y - cosine of half hotspot angle, z - cosine of half cone angle.
+uniform vec3 lightParameters[MAX_LIGHTS];
+
+in vec3 position;
+in vec3 normal;
+in vec2 texCoord;
+in vec3 tangent;
+in vec3 binormal;
+in vec2 secondTexCoord;
+
+out vec4 FragColor;
+
+void main()
+{
+    vec4 diffuse = diffuseColor * texture(diffuseTexture, texCoord);
+
+    vec3 n = normalize(texture(normalTexture, texCoord).xyz * 2.0 - 1.0);
+    mat3 tangentSpace = mat3(tangent, binormal, normal);
+
+    TPBRContext ctx;
+    ctx.albedo = diffuse.rgb;
+    ctx.metallic = texture(metallicTexture, texCoord).r;
+    ctx.roughness = texture(roughnessTexture, texCoord).r;
+    ctx.specular = texture(specularTexture, texCoord).r;
+    ctx.fragmentNormal = normalize(tangentSpace * n);
+    ctx.fragmentToCamera = normalize(cameraPosition - position);
+
+    // Transparent surfaces are not lit by environment, only by ambient color.
+    vec3 ambient = ambientColor.rgb + texture(lightmapTexture, secondTexCoord).rgb;
+    vec3 f0 = S_BaseReflectivity(ctx.albedo, ctx.metallic, ctx.specular);
+    vec3 lighting = ambient * (ctx.albedo * (1.0 - ctx.metallic) + f0) * texture(ambientOcclusionTexture, texCoord).r;
+    lighting += texture(emissionTexture, texCoord).rgb;
+
+    for (int i = 0; i < lightCount; ++i)
+    {
+        float attenuation = 1.0;
+
+        if (lightKinds[i] == DIRECTIONAL_LIGHT)
+        {
+            ctx.fragmentToLight = lightDirections[i];
+        }
+        else
+        {
+            vec3 lightVector = lightPositions[i] - position;
+            float distance = length(lightVector);
+            ctx.fragmentToLight = lightVector / distance;
+
+            attenuation = S_LightDistanceAttenuation(distance, lightParameters[i].x);
+
+            if (lightKinds[i] == SPOT_LIGHT)
+            {
+                float spotAngleCos = dot(lightDirections[i], ctx.fragmentToLight);
+                attenuation *= smoothstep(lightParameters[i].z, lightParameters[i].y, spotAngleCos);
+            }
+        }
+
+        lighting += S_PBR_CalculateLight(ctx) * attenuation * lightColors[i].rgb;
+    }
+
+    FragColor = vec4(lighting, diffuse.a);
+}
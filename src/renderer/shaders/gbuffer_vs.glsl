@@ -11,7 +11,9 @@ layout(location = 6) in vec4 boneIndices;
 uniform mat4 worldMatrix;
 uniform mat4 worldViewProjection;
 uniform bool useSkeletalAnimation;
-uniform mat4 boneMatrices[60];
+// Storage of bone matrices, see S_FetchMatrix.
+uniform sampler2D boneMatrices;
+uniform int boneMatricesOffset;
 
 out vec3 position;
 out vec3 normal;
@@ -27,27 +29,10 @@ void main()
     vec3 localTangent = vec3(0);
     if (useSkeletalAnimation)
     {
-        vec4 vertex = vec4(vertexPosition, 1.0);
-
-        int i0 = int(boneIndices.x);
-        int i1 = int(boneIndices.y);
-        int i2 = int(boneIndices.z);
-        int i3 = int(boneIndices.w);
-
-        localPosition += boneMatrices[i0] * vertex * boneWeights.x;
-        localPosition += boneMatrices[i1] * vertex * boneWeights.y;
-        localPosition += boneMatrices[i2] * vertex * boneWeights.z;
-        localPosition += boneMatrices[i3] * vertex * boneWeights.w;
-
-        localNormal += mat3(boneMatrices[i0]) * vertexNormal * boneWeights.x;
-        localNormal += mat3(boneMatrices[i1]) * vertexNormal * boneWeights.y;
-        localNormal += mat3(boneMatrices[i2]) * vertexNormal * boneWeights.z;
-        localNormal += mat3(boneMatrices[i3]) * vertexNormal * boneWeights.w;
-
-        localTangent += mat3(boneMatrices[i0]) * vertexTangent.xyz * boneWeights.x;
-        localTangent += mat3(boneMatrices[i1]) * vertexTangent.xyz * boneWeights.y;
-        localTangent += mat3(boneMatrices[i2]) * vertexTangent.xyz * boneWeights.z;
-        localTangent += mat3(boneMatrices[i3]) * vertexTangent.xyz * boneWeights.w;
+        mat4 skinningMatrix = S_SkinningMatrix(boneMatrices, boneMatricesOffset, boneIndices, boneWeights);
+        localPosition = skinningMatrix * vec4(vertexPosition, 1.0);
+        localNormal = mat3(skinningMatrix) * vertexNormal;
+        localTangent = mat3(skinningMatrix) * vertexTangent.xyz;
     }
     else
     {
@@ -0,0 +1,18 @@
+#version 330 core
+
+uniform sampler2D oldLumSampler;
+uniform sampler2D newLumSampler;
+uniform float adaptationFactor;
+uniform float minLuminance;
+uniform float maxLuminance;
+
+out float outLuminance;
+
+// Moves adapted luminance of previous frame towards average luminance of current frame.
+void main()
+{
+    float oldLuminance = texelFetch(oldLumSampler, ivec2(0, 0), 0).r;
+    float newLuminance = exp(texelFetch(newLumSampler, ivec2(0, 0), 0).r);
+    newLuminance = clamp(newLuminance, minLuminance, maxLuminance);
+    outLuminance = mix(oldLuminance, newLuminance, adaptationFactor);
+}
@@ -0,0 +1,22 @@
+#version 330 core
+
+uniform sampler2D lumSampler;
+
+out float outLogLuminance;
+
+// Averages 4x4 block of texels of the input texture, input must be 4 times larger than output.
+void main()
+{
+    ivec2 origin = ivec2(gl_FragCoord.xy) * 4;
+
+    float sum = 0.0;
+    for (int y = 0; y < 4; ++y)
+    {
+        for (int x = 0; x < 4; ++x)
+        {
+            sum += texelFetch(lumSampler, origin + ivec2(x, y), 0).r;
+        }
+    }
+
+    outLogLuminance = sum / 16.0;
+}
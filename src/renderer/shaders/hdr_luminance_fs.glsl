@@ -0,0 +1,29 @@
+#version 330 core
+
+uniform sampler2D frameSampler;
+// Origin (xy) and size (zw) of the viewport in the frame in pixels.
+uniform vec4 viewport;
+uniform float outputSize;
+
+out float outLogLuminance;
+
+// Computes average logarithm of luminance of a region of the frame that corresponds to
+// the current texel of the output texture. Region is sampled by a 4x4 grid.
+void main()
+{
+    vec2 regionSize = viewport.zw / outputSize;
+    vec2 regionOrigin = viewport.xy + floor(gl_FragCoord.xy) * regionSize;
+
+    float sum = 0.0;
+    for (int y = 0; y < 4; ++y)
+    {
+        for (int x = 0; x < 4; ++x)
+        {
+            vec2 pixel = regionOrigin + (vec2(x, y) + 0.5) * regionSize * 0.25;
+            vec3 color = texelFetch(frameSampler, ivec2(pixel), 0).rgb;
+            sum += log(max(S_Luminance(color), 0.0001));
+        }
+    }
+
+    outLogLuminance = sum / 16.0;
+}
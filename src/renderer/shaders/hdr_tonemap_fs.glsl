@@ -0,0 +1,52 @@
+#version 330 core
+
+uniform sampler2D hdrSampler;
+uniform sampler2D lumSampler;
+uniform sampler2D bloomSampler;
+uniform float bloomIntensity;
+uniform vec2 frameSize;
+uniform bool autoExposure;
+uniform float keyValue;
+uniform float exposure;
+// 0 - None, 1 - Reinhard, 2 - ACES filmic
+uniform int toneMapOperator;
+
+out vec4 FragColor;
+
+// https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+vec3 AcesFilmic(vec3 x)
+{
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+void main()
+{
+    vec4 hdrColor = texelFetch(hdrSampler, ivec2(gl_FragCoord.xy), 0);
+
+    float finalExposure = exposure;
+    if (autoExposure)
+    {
+        float luminance = texelFetch(lumSampler, ivec2(0, 0), 0).r;
+        finalExposure = keyValue / max(luminance, 0.0001);
+    }
+
+    vec3 bloom = texture(bloomSampler, gl_FragCoord.xy / frameSize).rgb * bloomIntensity;
+
+    vec3 color = (hdrColor.rgb + bloom) * finalExposure;
+
+    if (toneMapOperator == 1)
+    {
+        color = color / (vec3(1.0) + color);
+    }
+    else if (toneMapOperator == 2)
+    {
+        color = AcesFilmic(color);
+    }
+
+    FragColor = vec4(clamp(color, 0.0, 1.0), clamp(hdrColor.a, 0.0, 1.0));
+}
@@ -0,0 +1,44 @@
+#version 330 core
+
+uniform float lutSize;
+
+out vec4 FragColor;
+
+// Integrates specular BRDF for given cosine between normal and view vector (x) and roughness (y),
+// output is a scale (r) and a bias (g) to base reflectivity of a surface (split sum approximation).
+void main()
+{
+    vec2 uv = gl_FragCoord.xy / lutSize;
+    float nDotV = max(uv.x, 0.001);
+    float roughness = uv.y;
+
+    vec3 v = vec3(sqrt(1.0 - nDotV * nDotV), 0.0, nDotV);
+    vec3 n = vec3(0.0, 0.0, 1.0);
+
+    // Geometry term for image-based lighting uses different remapping of roughness.
+    float k = roughness * roughness / 2.0;
+
+    const uint sampleCount = 512u;
+    float a = 0.0;
+    float b = 0.0;
+    for (uint i = 0u; i < sampleCount; ++i)
+    {
+        vec3 h = S_ImportanceSampleGGX(S_Hammersley(i, sampleCount), n, roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+
+        float nDotL = max(l.z, 0.0);
+        float nDotH = max(h.z, 0.0);
+        float vDotH = max(dot(v, h), 0.0);
+
+        if (nDotL > 0.0)
+        {
+            float g = S_GeometrySchlickGGX(nDotV, k) * S_GeometrySchlickGGX(nDotL, k);
+            float gVis = g * vDotH / (nDotH * nDotV);
+            float fc = pow(1.0 - vDotH, 5.0);
+            a += (1.0 - fc) * gVis;
+            b += fc * gVis;
+        }
+    }
+
+    FragColor = vec4(a / float(sampleCount), b / float(sampleCount), 0.0, 1.0);
+}
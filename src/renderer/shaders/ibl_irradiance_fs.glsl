@@ -0,0 +1,34 @@
+#version 330 core
+
+uniform samplerCube environmentMap;
+uniform int faceIndex;
+uniform float faceSize;
+
+out vec4 FragColor;
+
+// Convolves environment map with cosine lobe, the result is irradiance divided by PI, so it
+// can be multiplied by albedo directly.
+void main()
+{
+    vec3 n = S_CubeMapFaceDirection(faceIndex, gl_FragCoord.xy / faceSize * 2.0 - 1.0);
+
+    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, n));
+    up = cross(n, right);
+
+    const float sampleDelta = 0.05;
+    vec3 irradiance = vec3(0.0);
+    float sampleCount = 0.0;
+    for (float phi = 0.0; phi < 2.0 * S_PI; phi += sampleDelta)
+    {
+        for (float theta = 0.0; theta < 0.5 * S_PI; theta += sampleDelta)
+        {
+            vec3 tangentSample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 sampleVec = tangentSample.x * right + tangentSample.y * up + tangentSample.z * n;
+            irradiance += texture(environmentMap, sampleVec).rgb * cos(theta) * sin(theta);
+            sampleCount += 1.0;
+        }
+    }
+
+    FragColor = vec4(S_PI * irradiance / sampleCount, 1.0);
+}
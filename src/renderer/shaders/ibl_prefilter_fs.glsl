@@ -0,0 +1,32 @@
+#version 330 core
+
+uniform samplerCube environmentMap;
+uniform int faceIndex;
+uniform float faceSize;
+uniform float roughness;
+
+out vec4 FragColor;
+
+// Convolves environment map with GGX lobe of given roughness (split sum approximation),
+// view direction is assumed to be equal to normal.
+void main()
+{
+    vec3 n = S_CubeMapFaceDirection(faceIndex, gl_FragCoord.xy / faceSize * 2.0 - 1.0);
+
+    const uint sampleCount = 512u;
+    vec3 color = vec3(0.0);
+    float totalWeight = 0.0;
+    for (uint i = 0u; i < sampleCount; ++i)
+    {
+        vec3 h = S_ImportanceSampleGGX(S_Hammersley(i, sampleCount), n, roughness);
+        vec3 l = normalize(2.0 * dot(n, h) * h - n);
+        float nDotL = dot(n, l);
+        if (nDotL > 0.0)
+        {
+            color += texture(environmentMap, l).rgb * nDotL;
+            totalWeight += nDotL;
+        }
+    }
+
+    FragColor = vec4(color / max(totalWeight, 0.0001), 1.0);
+}
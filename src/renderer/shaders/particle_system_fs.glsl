@@ -3,7 +3,8 @@
 uniform sampler2D diffuseTexture;
 uniform sampler2D depthBufferTexture;
 uniform vec2 invScreenSize;
-uniform vec2 projParams;
+// x - far, y - near, z - 1.0 for orthographic projection, 0.0 - for perspective.
+uniform vec3 projParams;
 
 out vec4 FragColor;
 in vec2 texCoord;
@@ -13,13 +14,18 @@ float toProjSpace(float z)
 {
     float far = projParams.x;
     float near = projParams.y;
+    if (projParams.z > 0.5) {
+        // Depth is linear in orthographic projection.
+        return near + z * (far - near);
+    }
     return (far * near) / (far - z * (far + near));
 }
 
 void main()
 {
     float sceneDepth = toProjSpace(texture(depthBufferTexture, gl_FragCoord.xy * invScreenSize).r);
-    float depthOpacity = clamp((sceneDepth - gl_FragCoord.z / gl_FragCoord.w) * 2.0f, 0.0, 1.0);
+    float fragmentDepth = projParams.z > 0.5 ? toProjSpace(gl_FragCoord.z) : gl_FragCoord.z / gl_FragCoord.w;
+    float depthOpacity = clamp((sceneDepth - fragmentDepth) * 2.0f, 0.0, 1.0);
     FragColor = color * texture(diffuseTexture, texCoord).r;
     FragColor.a *= depthOpacity;
 }
\ No newline at end of file
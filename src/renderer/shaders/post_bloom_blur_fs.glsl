@@ -0,0 +1,28 @@
+#version 330 core
+
+uniform sampler2D inputSampler;
+// Origin (xy) and size (zw) of the viewport in the half-size texture in pixels.
+uniform vec4 viewport;
+uniform bool horizontal;
+
+out vec4 FragColor;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+// One direction of separable gaussian blur.
+void main()
+{
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    ivec2 minPixel = ivec2(viewport.xy);
+    ivec2 maxPixel = ivec2(viewport.xy + viewport.zw) - ivec2(1);
+    ivec2 direction = horizontal ? ivec2(1, 0) : ivec2(0, 1);
+
+    vec3 color = texelFetch(inputSampler, pixel, 0).rgb * weights[0];
+    for (int i = 1; i < 5; ++i)
+    {
+        color += texelFetch(inputSampler, clamp(pixel + direction * i, minPixel, maxPixel), 0).rgb * weights[i];
+        color += texelFetch(inputSampler, clamp(pixel - direction * i, minPixel, maxPixel), 0).rgb * weights[i];
+    }
+
+    FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,32 @@
+#version 330 core
+
+uniform sampler2D frameSampler;
+// Origin (xy) and size (zw) of the viewport in the full-size frame in pixels.
+uniform vec4 viewport;
+uniform float threshold;
+
+out vec4 FragColor;
+
+// Extracts bright parts of the frame into half-size texture, each output pixel is an average
+// of 2x2 block of the frame.
+void main()
+{
+    ivec2 origin = ivec2(gl_FragCoord.xy) * 2;
+    ivec2 minPixel = ivec2(viewport.xy);
+    ivec2 maxPixel = ivec2(viewport.xy + viewport.zw) - ivec2(1);
+
+    vec3 color = vec3(0.0);
+    for (int y = 0; y < 2; ++y)
+    {
+        for (int x = 0; x < 2; ++x)
+        {
+            color += texelFetch(frameSampler, clamp(origin + ivec2(x, y), minPixel, maxPixel), 0).rgb;
+        }
+    }
+    color *= 0.25;
+
+    // Very bright pixels are clamped, otherwise they'll produce flickering blocky glow.
+    color = min(max(color - vec3(threshold), vec3(0.0)), vec3(64.0));
+
+    FragColor = vec4(color, 1.0);
+}
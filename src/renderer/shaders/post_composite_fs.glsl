@@ -0,0 +1,53 @@
+#version 330 core
+
+uniform sampler2D frameSampler;
+uniform sampler3D lutSampler;
+uniform vec2 frameSize;
+// Origin (xy) and size (zw) of the viewport in pixels.
+uniform vec4 viewport;
+uniform bool useColorGrading;
+uniform float lutSize;
+uniform float chromaticAberration;
+uniform float vignetteIntensity;
+uniform float vignetteRadius;
+
+out vec4 FragColor;
+
+// Combines cheap per-pixel effects in a single pass: chromatic aberration, color grading
+// and vignette.
+void main()
+{
+    vec2 uv = gl_FragCoord.xy / frameSize;
+
+    vec2 viewportMin = (viewport.xy + 0.5) / frameSize;
+    vec2 viewportMax = (viewport.xy + viewport.zw - 0.5) / frameSize;
+    vec2 viewportCenter = (viewport.xy + 0.5 * viewport.zw) / frameSize;
+    // In [-1; 1] range within the viewport.
+    vec2 fromCenter = (gl_FragCoord.xy - viewport.xy - 0.5 * viewport.zw) / (0.5 * viewport.zw);
+
+    vec4 color = texture(frameSampler, uv);
+
+    if (chromaticAberration > 0.0)
+    {
+        // Offset grows from the center to the edges.
+        vec2 direction = uv - viewportCenter;
+        vec2 offset = direction / max(length(direction), 0.0001) * length(fromCenter) * chromaticAberration;
+        color.r = texture(frameSampler, clamp(uv + offset, viewportMin, viewportMax)).r;
+        color.b = texture(frameSampler, clamp(uv - offset, viewportMin, viewportMax)).b;
+    }
+
+    if (useColorGrading)
+    {
+        // Sample at centers of texels of the lookup table.
+        vec3 lutCoord = clamp(color.rgb, 0.0, 1.0) * ((lutSize - 1.0) / lutSize) + 0.5 / lutSize;
+        color.rgb = texture(lutSampler, lutCoord).rgb;
+    }
+
+    if (vignetteIntensity > 0.0)
+    {
+        float distance = length(fromCenter) / sqrt(2.0);
+        color.rgb *= 1.0 - vignetteIntensity * smoothstep(vignetteRadius, 1.0, distance);
+    }
+
+    FragColor = color;
+}
@@ -0,0 +1,10 @@
+#version 330 core
+
+uniform sampler2D frameSampler;
+
+out vec4 FragColor;
+
+void main()
+{
+    FragColor = texelFetch(frameSampler, ivec2(gl_FragCoord.xy), 0);
+}
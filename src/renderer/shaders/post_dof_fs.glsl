@@ -0,0 +1,58 @@
+#version 330 core
+
+uniform sampler2D frameSampler;
+uniform sampler2D depthSampler;
+uniform mat4 invProjection;
+uniform vec2 frameSize;
+// Origin (xy) and size (zw) of the viewport in pixels.
+uniform vec4 viewport;
+uniform float focalDistance;
+uniform float focalRange;
+uniform float maxBlur;
+
+out vec4 FragColor;
+
+const int sampleCount = 24;
+const float goldenAngle = 2.39996323;
+
+// Returns radius of circle of confusion in pixels (x) and distance to camera (y) for given pixel.
+vec2 CircleOfConfusion(ivec2 pixel)
+{
+    float depth = texelFetch(depthSampler, pixel, 0).r;
+    vec3 viewPosition = S_UnProject(vec3((vec2(pixel) + 0.5) / frameSize, depth), invProjection);
+    float distance = -viewPosition.z;
+    float blur = (abs(distance - focalDistance) - focalRange) / max(focalRange, 0.0001);
+    return vec2(clamp(blur, 0.0, 1.0) * maxBlur, distance);
+}
+
+// Gathers samples in a disk around the pixel (Vogel disk), each sample contributes only if
+// its own circle of confusion covers the pixel, so sharp objects won't bleed on blurry ones.
+void main()
+{
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    ivec2 minPixel = ivec2(viewport.xy);
+    ivec2 maxPixel = ivec2(viewport.xy + viewport.zw) - ivec2(1);
+
+    vec2 centerCoc = CircleOfConfusion(pixel);
+    vec4 center = texelFetch(frameSampler, pixel, 0);
+
+    vec3 sum = center.rgb;
+    float weightSum = 1.0;
+
+    for (int i = 0; i < sampleCount; ++i)
+    {
+        float radius = sqrt((float(i) + 0.5) / float(sampleCount)) * maxBlur;
+        float angle = float(i) * goldenAngle;
+        ivec2 samplePixel = clamp(pixel + ivec2(round(radius * vec2(cos(angle), sin(angle)))), minPixel, maxPixel);
+
+        vec2 sampleCoc = CircleOfConfusion(samplePixel);
+        // Blurry background must not bleed onto sharp foreground.
+        float coc = sampleCoc.y > centerCoc.y ? min(sampleCoc.x, centerCoc.x) : sampleCoc.x;
+        float weight = clamp(coc - radius + 1.0, 0.0, 1.0);
+
+        sum += texelFetch(frameSampler, samplePixel, 0).rgb * weight;
+        weightSum += weight;
+    }
+
+    FragColor = vec4(sum / weightSum, center.a);
+}
@@ -0,0 +1,152 @@
+#version 330 core
+
+// Fast approximate anti-aliasing, based on FXAA 3.11 by Timothy Lottes (quality preset
+// with reduced amount of search steps).
+
+uniform sampler2D frameSampler;
+uniform vec2 frameSize;
+
+out vec4 FragColor;
+
+const float edgeThresholdMin = 0.0312;
+const float edgeThreshold = 0.125;
+const float subpixelQuality = 0.75;
+const int searchSteps = 8;
+const float searchStepSizes[8] = float[](1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 4.0, 8.0);
+
+float Luma(vec3 color)
+{
+    return sqrt(S_Luminance(color));
+}
+
+float LumaAt(vec2 uv)
+{
+    return Luma(texture(frameSampler, uv).rgb);
+}
+
+void main()
+{
+    vec2 inverseSize = 1.0 / frameSize;
+    vec2 uv = gl_FragCoord.xy * inverseSize;
+
+    vec4 center = texture(frameSampler, uv);
+    float lumaCenter = Luma(center.rgb);
+    float lumaDown = LumaAt(uv + vec2(0.0, -1.0) * inverseSize);
+    float lumaUp = LumaAt(uv + vec2(0.0, 1.0) * inverseSize);
+    float lumaLeft = LumaAt(uv + vec2(-1.0, 0.0) * inverseSize);
+    float lumaRight = LumaAt(uv + vec2(1.0, 0.0) * inverseSize);
+
+    float lumaMin = min(lumaCenter, min(min(lumaDown, lumaUp), min(lumaLeft, lumaRight)));
+    float lumaMax = max(lumaCenter, max(max(lumaDown, lumaUp), max(lumaLeft, lumaRight)));
+    float lumaRange = lumaMax - lumaMin;
+
+    // Not an edge - skip.
+    if (lumaRange < max(edgeThresholdMin, lumaMax * edgeThreshold))
+    {
+        FragColor = center;
+        return;
+    }
+
+    float lumaDownLeft = LumaAt(uv + vec2(-1.0, -1.0) * inverseSize);
+    float lumaUpRight = LumaAt(uv + vec2(1.0, 1.0) * inverseSize);
+    float lumaUpLeft = LumaAt(uv + vec2(-1.0, 1.0) * inverseSize);
+    float lumaDownRight = LumaAt(uv + vec2(1.0, -1.0) * inverseSize);
+
+    float lumaDownUp = lumaDown + lumaUp;
+    float lumaLeftRight = lumaLeft + lumaRight;
+    float lumaLeftCorners = lumaDownLeft + lumaUpLeft;
+    float lumaDownCorners = lumaDownLeft + lumaDownRight;
+    float lumaRightCorners = lumaDownRight + lumaUpRight;
+    float lumaUpCorners = lumaUpRight + lumaUpLeft;
+
+    float edgeHorizontal = abs(-2.0 * lumaLeft + lumaLeftCorners) + abs(-2.0 * lumaCenter + lumaDownUp) * 2.0 + abs(-2.0 * lumaRight + lumaRightCorners);
+    float edgeVertical = abs(-2.0 * lumaUp + lumaUpCorners) + abs(-2.0 * lumaCenter + lumaLeftRight) * 2.0 + abs(-2.0 * lumaDown + lumaDownCorners);
+    bool isHorizontal = edgeHorizontal >= edgeVertical;
+
+    float luma1 = isHorizontal ? lumaDown : lumaLeft;
+    float luma2 = isHorizontal ? lumaUp : lumaRight;
+    float gradient1 = luma1 - lumaCenter;
+    float gradient2 = luma2 - lumaCenter;
+    bool is1Steepest = abs(gradient1) >= abs(gradient2);
+    float gradientScaled = 0.25 * max(abs(gradient1), abs(gradient2));
+
+    float stepLength = isHorizontal ? inverseSize.y : inverseSize.x;
+    float lumaLocalAverage;
+    if (is1Steepest)
+    {
+        stepLength = -stepLength;
+        lumaLocalAverage = 0.5 * (luma1 + lumaCenter);
+    }
+    else
+    {
+        lumaLocalAverage = 0.5 * (luma2 + lumaCenter);
+    }
+
+    // Move to the edge between pixels.
+    vec2 currentUv = uv;
+    if (isHorizontal)
+    {
+        currentUv.y += stepLength * 0.5;
+    }
+    else
+    {
+        currentUv.x += stepLength * 0.5;
+    }
+
+    // Search for the ends of the edge in both directions.
+    vec2 offset = isHorizontal ? vec2(inverseSize.x, 0.0) : vec2(0.0, inverseSize.y);
+    vec2 uv1 = currentUv - offset;
+    vec2 uv2 = currentUv + offset;
+    float lumaEnd1 = LumaAt(uv1) - lumaLocalAverage;
+    float lumaEnd2 = LumaAt(uv2) - lumaLocalAverage;
+    bool reached1 = abs(lumaEnd1) >= gradientScaled;
+    bool reached2 = abs(lumaEnd2) >= gradientScaled;
+
+    for (int i = 1; i < searchSteps && !(reached1 && reached2); ++i)
+    {
+        if (!reached1)
+        {
+            uv1 -= offset * searchStepSizes[i];
+            lumaEnd1 = LumaAt(uv1) - lumaLocalAverage;
+            reached1 = abs(lumaEnd1) >= gradientScaled;
+        }
+        if (!reached2)
+        {
+            uv2 += offset * searchStepSizes[i];
+            lumaEnd2 = LumaAt(uv2) - lumaLocalAverage;
+            reached2 = abs(lumaEnd2) >= gradientScaled;
+        }
+    }
+
+    float distance1 = isHorizontal ? (uv.x - uv1.x) : (uv.y - uv1.y);
+    float distance2 = isHorizontal ? (uv2.x - uv.x) : (uv2.y - uv.y);
+    bool isDirection1 = distance1 < distance2;
+    float distanceFinal = min(distance1, distance2);
+    float edgeLength = distance1 + distance2;
+    float pixelOffset = -distanceFinal / edgeLength + 0.5;
+
+    // Offset is applied only if luma variation at the end of the edge is coherent with
+    // luma of the current pixel.
+    bool isLumaCenterSmaller = lumaCenter < lumaLocalAverage;
+    bool correctVariation = ((isDirection1 ? lumaEnd1 : lumaEnd2) < 0.0) != isLumaCenterSmaller;
+    float finalOffset = correctVariation ? pixelOffset : 0.0;
+
+    // Sub-pixel anti-aliasing.
+    float lumaAverage = (1.0 / 12.0) * (2.0 * (lumaDownUp + lumaLeftRight) + lumaLeftCorners + lumaRightCorners);
+    float subPixelOffset1 = clamp(abs(lumaAverage - lumaCenter) / lumaRange, 0.0, 1.0);
+    float subPixelOffset2 = (-2.0 * subPixelOffset1 + 3.0) * subPixelOffset1 * subPixelOffset1;
+    float subPixelOffsetFinal = subPixelOffset2 * subPixelOffset2 * subpixelQuality;
+    finalOffset = max(finalOffset, subPixelOffsetFinal);
+
+    vec2 finalUv = uv;
+    if (isHorizontal)
+    {
+        finalUv.y += finalOffset * stepLength;
+    }
+    else
+    {
+        finalUv.x += finalOffset * stepLength;
+    }
+
+    FragColor = vec4(texture(frameSampler, finalUv).rgb, center.a);
+}
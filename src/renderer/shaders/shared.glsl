@@ -111,6 +111,138 @@ TBlinnPhong S_BlinnPhong(TBlinnPhongContext ctx)
     return TBlinnPhong(attenuation, specular, distance, lightVector);
 }
 
+const float S_PI = 3.14159265359;
+
+// Normal distribution function of GGX (Trowbridge-Reitz) microfacet model.
+float S_DistributionGGX(vec3 n, vec3 h, float roughness)
+{
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float nDotH = max(dot(n, h), 0.0);
+    float d = nDotH * nDotH * (a2 - 1.0) + 1.0;
+    return a2 / max(S_PI * d * d, 0.0000001);
+}
+
+// Schlick-GGX approximation of geometry term for one direction. `k` is remapped roughness,
+// it differs for direct and image-based lighting.
+float S_GeometrySchlickGGX(float nDotV, float k)
+{
+    return nDotV / (nDotV * (1.0 - k) + k);
+}
+
+// Smith's method of combining geometry obstruction and shadowing terms for direct lighting.
+float S_GeometrySmith(float nDotV, float nDotL, float roughness)
+{
+    float r = roughness + 1.0;
+    float k = r * r / 8.0;
+    return S_GeometrySchlickGGX(nDotV, k) * S_GeometrySchlickGGX(nDotL, k);
+}
+
+// Schlick's approximation of Fresnel equations.
+vec3 S_FresnelSchlick(float cosTheta, vec3 f0)
+{
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// Fresnel term for image-based lighting, rough surfaces reflect less at grazing angles.
+vec3 S_FresnelSchlickRoughness(float cosTheta, vec3 f0, float roughness)
+{
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// Returns reflectance of a surface at normal incidence. Dielectrics use specular intensity
+// (0.5 gives 4% which is good for most of the materials), metals use their albedo.
+vec3 S_BaseReflectivity(vec3 albedo, float metallic, float specular)
+{
+    return mix(vec3(0.08 * specular), albedo, metallic);
+}
+
+// Metallic-roughness lighting model input parameters.
+struct TPBRContext {
+    vec3 albedo;
+    float metallic;
+    float roughness;
+    // Specular intensity of dielectrics, see S_BaseReflectivity.
+    float specular;
+    vec3 fragmentNormal;
+    // Normalized vector from fragment position to light.
+    vec3 fragmentToLight;
+    // Normalized vector from fragment position to camera.
+    vec3 fragmentToCamera;
+};
+
+// Calculates color of light of unit intensity reflected towards camera using Cook-Torrance
+// BRDF with GGX distribution for specular and Lambert for diffuse. Result is multiplied by PI,
+// so white diffuse surface facing the light reflects exactly the color of the light.
+vec3 S_PBR_CalculateLight(TPBRContext ctx)
+{
+    // Perfectly smooth surfaces produce infinitely small highlights from punctual lights.
+    float roughness = max(ctx.roughness, 0.045);
+
+    vec3 h = normalize(ctx.fragmentToLight + ctx.fragmentToCamera);
+    float nDotL = max(dot(ctx.fragmentNormal, ctx.fragmentToLight), 0.0);
+    float nDotV = max(dot(ctx.fragmentNormal, ctx.fragmentToCamera), 0.0001);
+
+    vec3 f0 = S_BaseReflectivity(ctx.albedo, ctx.metallic, ctx.specular);
+    vec3 F = S_FresnelSchlick(max(dot(h, ctx.fragmentToCamera), 0.0), f0);
+    float D = S_DistributionGGX(ctx.fragmentNormal, h, roughness);
+    float G = S_GeometrySmith(nDotV, nDotL, roughness);
+
+    vec3 specular = D * G * F / max(4.0 * nDotV * nDotL, 0.0001);
+    vec3 kD = (vec3(1.0) - F) * (1.0 - ctx.metallic);
+
+    return (kD * ctx.albedo + S_PI * specular) * nDotL;
+}
+
+// Returns i-th point of Hammersley low-discrepancy sequence of n points.
+vec2 S_Hammersley(uint i, uint n)
+{
+    uint bits = i;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    float radicalInverse = float(bits) * 2.3283064365386963e-10;
+    return vec2(float(i) / float(n), radicalInverse);
+}
+
+// Generates halfway vector around given normal, distribution of vectors follows GGX lobe
+// of given roughness.
+vec3 S_ImportanceSampleGGX(vec2 xi, vec3 n, float roughness)
+{
+    float a = roughness * roughness;
+
+    float phi = 2.0 * S_PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    vec3 h = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, n));
+    vec3 bitangent = cross(n, tangent);
+
+    return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+}
+
+// Returns direction to a texel of a face of a cube map, `uv` are in [-1; 1] range, faces
+// are ordered like so: +X, -X, +Y, -Y, +Z, -Z.
+vec3 S_CubeMapFaceDirection(int face, vec2 uv)
+{
+    if (face == 0) return normalize(vec3(1.0, -uv.y, -uv.x));
+    if (face == 1) return normalize(vec3(-1.0, -uv.y, uv.x));
+    if (face == 2) return normalize(vec3(uv.x, 1.0, uv.y));
+    if (face == 3) return normalize(vec3(uv.x, -1.0, -uv.y));
+    if (face == 4) return normalize(vec3(uv.x, -uv.y, 1.0));
+    return normalize(vec3(-uv.x, -uv.y, -1.0));
+}
+
+// Returns relative luminance of a linear color (Rec. 709 weights).
+float S_Luminance(vec3 color)
+{
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
 // Returns scatter amount for given parameters.
 // https://cseweb.ucsd.edu/~ravir/papers/singlescat/scattering.pdf
 // https://blog.mmacklin.com/2010/05/29/in-scattering-demo/
@@ -149,4 +281,134 @@ bool S_RaySphereIntersection(vec3 origin, vec3 dir, vec3 center, float radius, o
     float b = 2.0 * dot(dir, d);
     float c = dot(d, d) - radius * radius;
     return S_SolveQuadraticEq(a, b, c, minT, maxT);
-}
\ No newline at end of file
+}
+// Cheap per-pixel noise in [0; 1) range, useful to hide banding of ray marching.
+// http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+float S_InterleavedGradientNoise(vec2 fragCoord)
+{
+    return fract(52.9829189 * fract(dot(fragCoord, vec2(0.06711056, 0.00583715))));
+}
+
+struct TScreenSpaceRay {
+    vec3 origin;
+    vec3 direction;
+    float maxDistance;
+    int steps;
+    // Thickness of objects in depth buffer in world units. Samples behind depth buffer by more than
+    // this value are not considered as intersections, so rays can pass behind objects.
+    float thickness;
+    // Offset of first step in [0; 1] range, in fractions of step length.
+    float jitter;
+};
+
+// Marches given ray in world space and checks each step against depth buffer. If the ray hits
+// something on screen, returns true. In out parameters hitTexCoord will be screen-space coordinates
+// of the hit and hitFraction will be traveled distance in fractions of max distance of the ray.
+bool S_TraceScreenSpaceRay(sampler2D depthTexture, TScreenSpaceRay ray, mat4 viewProj, mat4 invViewProj, vec3 cameraPosition, out vec2 hitTexCoord, out float hitFraction)
+{
+    hitTexCoord = vec2(0.0);
+    hitFraction = 1.0;
+
+    float stepLength = ray.maxDistance / float(max(ray.steps, 1));
+    float prevT = 0.0;
+
+    for (int i = 0; i < ray.steps; ++i)
+    {
+        float t = (float(i) + ray.jitter) * stepLength;
+        vec3 position = ray.origin + ray.direction * t;
+
+        vec4 clipPosition = viewProj * vec4(position, 1.0);
+        if (clipPosition.w <= 0.0)
+        {
+            // Ray went behind the camera.
+            return false;
+        }
+        vec3 screenPosition = clipPosition.xyz / clipPosition.w * 0.5 + 0.5;
+        if (any(lessThan(screenPosition, vec3(0.0))) || any(greaterThan(screenPosition, vec3(1.0))))
+        {
+            // Ray left the screen, there is nothing to intersect with.
+            return false;
+        }
+
+        vec3 scenePosition = S_UnProject(vec3(screenPosition.xy, textureLod(depthTexture, screenPosition.xy, 0.0).r), invViewProj);
+        float difference = distance(cameraPosition, position) - distance(cameraPosition, scenePosition);
+        if (difference > 0.0 && difference < ray.thickness)
+        {
+            // Refine intersection point by binary search between last two steps.
+            float minT = prevT;
+            float maxT = t;
+            for (int j = 0; j < 4; ++j)
+            {
+                float midT = (minT + maxT) * 0.5;
+                vec3 midPosition = ray.origin + ray.direction * midT;
+                vec2 midTexCoord = S_Project(midPosition, viewProj).xy;
+                vec3 midScenePosition = S_UnProject(vec3(midTexCoord, textureLod(depthTexture, midTexCoord, 0.0).r), invViewProj);
+                if (distance(cameraPosition, midPosition) > distance(cameraPosition, midScenePosition))
+                {
+                    maxT = midT;
+                }
+                else
+                {
+                    minT = midT;
+                }
+            }
+
+            hitTexCoord = S_Project(ray.origin + ray.direction * maxT, viewProj).xy;
+            hitFraction = maxT / ray.maxDistance;
+            return true;
+        }
+
+        prevT = t;
+    }
+
+    return false;
+}
+
+// Returns 0.0 if light is blocked by something in depth buffer on its way to fragment within given
+// distance, 1.0 otherwise. Catches small-scale details which are missing in shadow maps.
+float S_ContactShadow(sampler2D depthTexture, vec3 fragmentPosition, vec3 fragmentNormal, vec3 fragmentToLight, float maxDistance, int steps, float thickness, float jitter, mat4 viewProj, mat4 invViewProj, vec3 cameraPosition)
+{
+    TScreenSpaceRay ray;
+    // Start slightly above the surface to not intersect it.
+    ray.origin = fragmentPosition + fragmentNormal * maxDistance / float(max(steps, 1));
+    ray.direction = fragmentToLight;
+    ray.maxDistance = maxDistance;
+    ray.steps = steps;
+    ray.thickness = thickness;
+    ray.jitter = jitter;
+
+    vec2 hitTexCoord;
+    float hitFraction;
+    if (S_TraceScreenSpaceRay(depthTexture, ray, viewProj, invViewProj, cameraPosition, hitTexCoord, hitFraction))
+    {
+        // Fade out occluders at the end of the ray to hide hard cut-off.
+        return smoothstep(0.5, 1.0, hitFraction);
+    }
+    return 1.0;
+}
+
+// Fetches matrix with given index from matrix storage. Each matrix occupies 4 consecutive
+// RGBA32F pixels, width of the storage must be multiple of 4.
+mat4 S_FetchMatrix(sampler2D storage, int index)
+{
+    int width = textureSize(storage, 0).x;
+    int pixel = 4 * index;
+    ivec2 coords = ivec2(pixel % width, pixel / width);
+
+    return mat4(
+        texelFetch(storage, coords, 0),
+        texelFetch(storage, coords + ivec2(1, 0), 0),
+        texelFetch(storage, coords + ivec2(2, 0), 0),
+        texelFetch(storage, coords + ivec2(3, 0), 0)
+    );
+}
+
+// Blends bone matrices of a vertex using its weights. Bone indices are relative to the offset
+// of bone palette of an instance in matrix storage.
+mat4 S_SkinningMatrix(sampler2D boneMatrices, int offset, vec4 boneIndices, vec4 boneWeights)
+{
+    return S_FetchMatrix(boneMatrices, offset + int(boneIndices.x)) * boneWeights.x
+        + S_FetchMatrix(boneMatrices, offset + int(boneIndices.y)) * boneWeights.y
+        + S_FetchMatrix(boneMatrices, offset + int(boneIndices.z)) * boneWeights.z
+        + S_FetchMatrix(boneMatrices, offset + int(boneIndices.w)) * boneWeights.w;
+}
@@ -7,7 +7,8 @@ layout(location = 6) in vec4 boneIndices;
 
 uniform mat4 worldViewProjection;
 uniform bool useSkeletalAnimation;
-uniform mat4 boneMatrices[60];
+uniform sampler2D boneMatrices;
+uniform int boneMatricesOffset;
 
 out vec2 texCoord;
 
@@ -17,12 +18,8 @@ void main()
 
     if (useSkeletalAnimation)
     {
-        vec4 vertex = vec4(vertexPosition, 1.0);
-
-        localPosition += boneMatrices[int(boneIndices.x)] * vertex * boneWeights.x;
-        localPosition += boneMatrices[int(boneIndices.y)] * vertex * boneWeights.y;
-        localPosition += boneMatrices[int(boneIndices.z)] * vertex * boneWeights.z;
-        localPosition += boneMatrices[int(boneIndices.w)] * vertex * boneWeights.w;
+        mat4 skinningMatrix = S_SkinningMatrix(boneMatrices, boneMatricesOffset, boneIndices, boneWeights);
+        localPosition = skinningMatrix * vec4(vertexPosition, 1.0);
     }
     else
     {
@@ -7,8 +7,14 @@ uniform mat4 viewProjectionMatrix;
 uniform mat4 worldMatrix;
 uniform vec3 cameraUpVector;
 uniform vec3 cameraSideVector;
-uniform float size;
+uniform vec3 cameraPosition;
+// Half-width and half-height.
+uniform vec2 size;
 uniform float rotation;
+// Rectangle in texture coordinates: xy - position, zw - size.
+uniform vec4 uvRect;
+// xyz - axis in world space, w - 1.0 if sprite is locked to the axis (cylindrical billboarding).
+uniform vec4 billboardAxis;
 
 out vec2 texCoord;
 
@@ -22,9 +28,18 @@ vec2 rotateVec2(vec2 v, float angle)
 
 void main()
 {
-    texCoord = vertexTexCoord;
-    vec2 vertexOffset = rotateVec2(vertexTexCoord * 2.0 - 1.0, rotation);
+    texCoord = uvRect.xy + vertexTexCoord * uvRect.zw;
+    vec2 vertexOffset = rotateVec2((vertexTexCoord * 2.0 - 1.0) * size, rotation);
     vec4 worldPosition = worldMatrix * vec4(vertexPosition, 1.0);
-    vec3 offset = (vertexOffset.x * cameraSideVector + vertexOffset.y * cameraUpVector) * size;
+    vec3 sideVector = cameraSideVector;
+    vec3 upVector = cameraUpVector;
+    if (billboardAxis.w > 0.5) {
+        vec3 side = cross(billboardAxis.xyz, cameraPosition - worldPosition.xyz);
+        if (dot(side, side) > 0.000001) {
+            sideVector = normalize(side);
+            upVector = billboardAxis.xyz;
+        }
+    }
+    vec3 offset = vertexOffset.x * sideVector + vertexOffset.y * upVector;
     gl_Position = viewProjectionMatrix * (worldPosition + vec4(offset.x, offset.y, offset.z, 0.0));
 }
\ No newline at end of file
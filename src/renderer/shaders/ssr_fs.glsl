@@ -0,0 +1,84 @@
+#version 330 core
+
+// Copy of lit frame, reflections are sampled from it.
+uniform sampler2D frameTexture;
+uniform sampler2D diffuseTexture;
+uniform sampler2D aoSampler;
+uniform sampler2D depthTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D materialTexture;
+uniform samplerCube prefilteredMap;
+uniform sampler2D brdfLut;
+uniform vec4 ambientColor;
+uniform mat4 viewProj;
+uniform mat4 invViewProj;
+uniform vec3 cameraPosition;
+uniform bool environmentEnabled;
+uniform float prefilteredMaxLod;
+uniform float maxDistance;
+uniform int steps;
+uniform float thickness;
+uniform float maxRoughness;
+
+out vec4 FragColor;
+in vec2 texCoord;
+
+void main()
+{
+    vec4 diffuse = texture(diffuseTexture, texCoord);
+    vec4 normal = texture(normalTexture, texCoord);
+    vec4 material = texture(materialTexture, texCoord);
+    float metallic = material.r;
+    float roughness = material.g;
+
+    vec3 fragmentPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
+    vec3 n = normalize(normal.xyz * 2.0 - 1.0);
+    vec3 v = normalize(cameraPosition - fragmentPosition);
+    vec3 r = reflect(-v, n);
+    float nDotV = max(dot(n, v), 0.0);
+
+    vec3 f0 = S_BaseReflectivity(diffuse.rgb, metallic, normal.w);
+    vec3 F = S_FresnelSchlickRoughness(nDotV, f0, roughness);
+
+    // Environment is a fallback for rays that miss or leave the screen.
+    vec3 environment = ambientColor.rgb;
+    if (environmentEnabled)
+    {
+        environment *= textureLod(prefilteredMap, r, roughness * prefilteredMaxLod).rgb;
+    }
+
+    vec3 reflection = environment;
+
+    if (roughness < maxRoughness && diffuse.a > 0.0)
+    {
+        TScreenSpaceRay ray;
+        ray.origin = fragmentPosition;
+        ray.direction = r;
+        ray.maxDistance = maxDistance;
+        ray.steps = steps;
+        ray.thickness = thickness;
+        ray.jitter = S_InterleavedGradientNoise(gl_FragCoord.xy);
+
+        vec2 hitTexCoord;
+        float hitFraction;
+        if (S_TraceScreenSpaceRay(depthTexture, ray, viewProj, invViewProj, cameraPosition, hitTexCoord, hitFraction))
+        {
+            // Smoothly blend to environment near screen edges, at the end of the ray and on rough
+            // surfaces to hide the points where screen-space information ends.
+            vec2 edgeFade = smoothstep(0.0, 0.1, hitTexCoord) * (1.0 - smoothstep(0.9, 1.0, hitTexCoord));
+            float distanceFade = 1.0 - hitFraction * hitFraction;
+            float roughnessFade = 1.0 - smoothstep(0.5 * maxRoughness, maxRoughness, roughness);
+            float confidence = clamp(edgeFade.x * edgeFade.y * distanceFade * roughnessFade, 0.0, 1.0);
+
+            reflection = mix(environment, texture(frameTexture, hitTexCoord).rgb, confidence);
+        }
+    }
+
+    vec2 brdf = texture(brdfLut, vec2(nDotV, roughness)).rg;
+
+    float ambientOcclusion = texture(aoSampler, texCoord).r * material.b;
+
+    // Result is added to lit frame, it replaces specular part of ambient lighting.
+    FragColor.rgb = reflection * (F * brdf.x + brdf.y) * ambientOcclusion * diffuse.a;
+    FragColor.a = 0.0;
+}
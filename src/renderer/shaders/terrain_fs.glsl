@@ -0,0 +1,40 @@
+#version 330 core
+
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec4 outNormal;
+layout(location = 2) out vec4 outAmbient;
+layout(location = 3) out vec4 outDecalMask;
+layout(location = 4) out vec4 outMaterial;
+layout(location = 5) out vec4 outEmission;
+
+uniform sampler2D diffuseTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D maskTexture;
+uniform vec2 tileFactor;
+// First layer is opaque, every next layer is blended over previous ones using its mask.
+uniform bool useMask;
+
+in vec3 normal;
+in vec2 texCoord;
+in vec3 tangent;
+in vec3 binormal;
+
+void main()
+{
+    // Mask covers whole terrain, while layer textures are tiled.
+    vec2 tiledTexCoord = texCoord * tileFactor;
+    float alpha = useMask ? texture(maskTexture, texCoord).r : 1.0;
+
+    // Blending is done separately for each attachment using its own alpha, alpha channels
+    // themselves are not written when blending is enabled.
+    outColor = vec4(texture(diffuseTexture, tiledTexCoord).rgb, alpha);
+    vec4 n = normalize(texture(normalTexture, tiledTexCoord) * 2.0 - 1.0);
+    mat3 tangentSpace = mat3(tangent, binormal, normal);
+    outNormal.xyz = normalize(tangentSpace * n.xyz) * 0.5 + 0.5;
+    outNormal.w = useMask ? alpha : 0.5;
+    outAmbient = vec4(0.0, 0.0, 0.0, alpha);
+    outDecalMask = vec4(0.0, 0.0, 0.0, alpha);
+    // Terrain is a rough dielectric.
+    outMaterial = vec4(0.0, 1.0, 1.0, alpha);
+    outEmission = vec4(0.0, 0.0, 0.0, alpha);
+}
@@ -1,11 +1,11 @@
 #![warn(clippy::too_many_arguments)]
 
-use crate::renderer::ShadowMapPrecision;
 use crate::{
     core::{
-        algebra::{Matrix4, Point3, Vector3},
+        algebra::{Matrix4, Point3, Vector3, Vector4},
         color::Color,
         math::{frustum::Frustum, Rect},
+        pool::Handle,
         scope_profile,
     },
     renderer::{
@@ -22,15 +22,16 @@ use crate::{
             },
             state::{ColorMask, PipelineState},
         },
-        GeometryCache, RenderPassStatistics,
+        CsmSplitScheme, GeometryCache, RenderPassStatistics, ShadowMapPrecision, CSM_MAX_CASCADES,
     },
-    scene::{graph::Graph, node::Node},
+    scene::{camera::Camera, graph::Graph, node::Node},
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
 
 struct SpotShadowMapShader {
     program: GpuProgram,
     bone_matrices: UniformLocation,
+    bone_matrices_offset: UniformLocation,
     world_view_projection_matrix: UniformLocation,
     use_skeletal_animation: UniformLocation,
     diffuse_texture: UniformLocation,
@@ -44,6 +45,7 @@ impl SpotShadowMapShader {
             GpuProgram::from_source("SpotShadowMapShader", vertex_source, fragment_source)?;
         Ok(Self {
             bone_matrices: program.uniform_location("boneMatrices")?,
+            bone_matrices_offset: program.uniform_location("boneMatricesOffset")?,
             world_view_projection_matrix: program.uniform_location("worldViewProjection")?,
             use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
             diffuse_texture: program.uniform_location("diffuseTexture")?,
@@ -61,7 +63,6 @@ pub struct SpotShadowMapRenderer {
     //  1 - medium, for lights with medium distance to camera.
     //  2 - small, for farthest lights.
     cascades: [FrameBuffer; 3],
-    bone_matrices: Vec<Matrix4<f32>>,
     size: usize,
 }
 
@@ -74,64 +75,63 @@ fn cascade_size(base_size: usize, cascade: usize) -> usize {
     }
 }
 
+fn make_depth_framebuffer(
+    state: &mut PipelineState,
+    size: usize,
+    precision: ShadowMapPrecision,
+) -> Result<FrameBuffer, RendererError> {
+    let depth = {
+        let kind = GpuTextureKind::Rectangle {
+            width: size,
+            height: size,
+        };
+        let mut texture = GpuTexture::new(
+            state,
+            kind,
+            match precision {
+                ShadowMapPrecision::Full => PixelKind::D32,
+                ShadowMapPrecision::Half => PixelKind::D16,
+            },
+            MinificationFilter::Nearest,
+            MagnificationFilter::Nearest,
+            1,
+            None,
+        )?;
+        texture
+            .bind_mut(state, 0)
+            .set_magnification_filter(MagnificationFilter::Linear)
+            .set_minification_filter(MinificationFilter::Linear)
+            .set_wrap(Coordinate::T, WrapMode::ClampToBorder)
+            .set_wrap(Coordinate::S, WrapMode::ClampToBorder)
+            .set_border_color(Color::WHITE);
+        texture
+    };
+
+    FrameBuffer::new(
+        state,
+        Some(Attachment {
+            kind: AttachmentKind::Depth,
+            texture: Rc::new(RefCell::new(depth)),
+        }),
+        vec![],
+    )
+}
+
 impl SpotShadowMapRenderer {
     pub fn new(
         state: &mut PipelineState,
         size: usize,
         precision: ShadowMapPrecision,
     ) -> Result<Self, RendererError> {
-        fn make_cascade(
-            state: &mut PipelineState,
-            size: usize,
-            precision: ShadowMapPrecision,
-        ) -> Result<FrameBuffer, RendererError> {
-            let depth = {
-                let kind = GpuTextureKind::Rectangle {
-                    width: size,
-                    height: size,
-                };
-                let mut texture = GpuTexture::new(
-                    state,
-                    kind,
-                    match precision {
-                        ShadowMapPrecision::Full => PixelKind::D32,
-                        ShadowMapPrecision::Half => PixelKind::D16,
-                    },
-                    MinificationFilter::Nearest,
-                    MagnificationFilter::Nearest,
-                    1,
-                    None,
-                )?;
-                texture
-                    .bind_mut(state, 0)
-                    .set_magnification_filter(MagnificationFilter::Linear)
-                    .set_minification_filter(MinificationFilter::Linear)
-                    .set_wrap(Coordinate::T, WrapMode::ClampToBorder)
-                    .set_wrap(Coordinate::S, WrapMode::ClampToBorder)
-                    .set_border_color(Color::WHITE);
-                texture
-            };
-
-            FrameBuffer::new(
-                state,
-                Some(Attachment {
-                    kind: AttachmentKind::Depth,
-                    texture: Rc::new(RefCell::new(depth)),
-                }),
-                vec![],
-            )
-        }
-
         Ok(Self {
             precision,
             size,
             cascades: [
-                make_cascade(state, cascade_size(size, 0), precision)?,
-                make_cascade(state, cascade_size(size, 1), precision)?,
-                make_cascade(state, cascade_size(size, 2), precision)?,
+                make_depth_framebuffer(state, cascade_size(size, 0), precision)?,
+                make_depth_framebuffer(state, cascade_size(size, 1), precision)?,
+                make_depth_framebuffer(state, cascade_size(size, 2), precision)?,
             ],
             shader: SpotShadowMapShader::new()?,
-            bone_matrices: Vec::new(),
         })
     }
 
@@ -164,26 +164,81 @@ impl SpotShadowMapRenderer {
         batches: &BatchStorage,
         geom_cache: &mut GeometryCache,
         cascade: usize,
+        occluded_meshes: Option<&HashSet<Handle<Node>>>,
     ) -> RenderPassStatistics {
         scope_profile!();
 
-        let mut statistics = RenderPassStatistics::default();
-
         let framebuffer = &mut self.cascades[cascade];
         let cascade_size = cascade_size(self.size, cascade);
 
         let viewport = Rect::new(0, 0, cascade_size as i32, cascade_size as i32);
 
         framebuffer.clear(state, viewport, None, Some(1.0), None);
-        let frustum = Frustum::from(*light_view_projection).unwrap();
+        render_shadow_casters(ShadowCastersRenderContext {
+            state,
+            graph,
+            framebuffer,
+            viewport,
+            shader: &self.shader,
+            light_view_projection,
+            batches,
+            geom_cache,
+            occluded_meshes,
+        })
+    }
+}
 
-        for batch in batches.batches.iter() {
-            let geometry = geom_cache.get(state, &batch.data.read().unwrap());
+struct ShadowCastersRenderContext<'a, 'b> {
+    state: &'a mut PipelineState,
+    graph: &'b Graph,
+    framebuffer: &'a mut FrameBuffer,
+    viewport: Rect<i32>,
+    shader: &'a SpotShadowMapShader,
+    light_view_projection: &'b Matrix4<f32>,
+    batches: &'b BatchStorage,
+    geom_cache: &'a mut GeometryCache,
+    occluded_meshes: Option<&'b HashSet<Handle<Node>>>,
+}
 
-            for instance in batch.instances.iter() {
-                let node = &graph[instance.owner];
+fn is_occluded(occluded_meshes: Option<&HashSet<Handle<Node>>>, node: Handle<Node>) -> bool {
+    match occluded_meshes {
+        Some(occluded_meshes) => occluded_meshes.contains(&node),
+        None => false,
+    }
+}
 
-                let visible = node.global_visibility() && {
+/// Renders every visible mesh that intersects frustum of given light view-projection
+/// matrix into depth-only framebuffer.
+fn render_shadow_casters(args: ShadowCastersRenderContext) -> RenderPassStatistics {
+    let ShadowCastersRenderContext {
+        state,
+        graph,
+        framebuffer,
+        viewport,
+        shader,
+        light_view_projection,
+        batches,
+        geom_cache,
+        occluded_meshes,
+    } = args;
+
+    let mut statistics = RenderPassStatistics::default();
+
+    let frustum = Frustum::from(*light_view_projection).unwrap();
+
+    // Transparent surfaces do not cast shadows.
+    for batch in batches
+        .batches
+        .iter()
+        .filter(|batch| !batch.blend_mode.is_transparent())
+    {
+        let geometry = geom_cache.get(state, &batch.data.read().unwrap());
+
+        for instance in batch.instances.iter() {
+            let node = &graph[instance.owner];
+
+            let visible =
+                node.global_visibility() && !is_occluded(occluded_meshes, instance.owner) && {
                     if let Node::Mesh(mesh) = node {
                         mesh.is_intersect_frustum(graph, &frustum)
                     } else {
@@ -191,53 +246,307 @@ impl SpotShadowMapRenderer {
                     }
                 };
 
-                if visible {
-                    statistics += framebuffer.draw(
-                        geometry,
-                        state,
-                        viewport,
-                        &self.shader.program,
-                        &DrawParameters {
-                            cull_face: CullFace::Back,
-                            culling: true,
-                            color_write: ColorMask::all(false),
-                            depth_write: true,
-                            stencil_test: false,
-                            depth_test: true,
-                            blend: false,
-                        },
-                        &[
-                            (
-                                self.shader.world_view_projection_matrix,
-                                UniformValue::Matrix4(
-                                    light_view_projection * instance.world_transform,
-                                ),
-                            ),
-                            (
-                                self.shader.use_skeletal_animation,
-                                UniformValue::Bool(batch.is_skinned),
-                            ),
-                            (
-                                self.shader.bone_matrices,
-                                UniformValue::Mat4Array({
-                                    self.bone_matrices.clear();
-                                    self.bone_matrices
-                                        .extend_from_slice(instance.bone_matrices.as_slice());
-                                    &self.bone_matrices
-                                }),
-                            ),
-                            (
-                                self.shader.diffuse_texture,
-                                UniformValue::Sampler {
-                                    index: 0,
-                                    texture: batch.diffuse_texture.clone(),
-                                },
-                            ),
-                        ],
-                    );
-                }
+            if visible {
+                statistics += framebuffer.draw(
+                    geometry,
+                    state,
+                    viewport,
+                    &shader.program,
+                    &DrawParameters {
+                        cull_face: CullFace::Back,
+                        culling: true,
+                        color_write: ColorMask::all(false),
+                        depth_write: true,
+                        stencil_test: false,
+                        depth_test: true,
+                        blend: false,
+                    },
+                    &[
+                        (
+                            shader.world_view_projection_matrix,
+                            UniformValue::Matrix4(light_view_projection * instance.world_transform),
+                        ),
+                        (
+                            shader.use_skeletal_animation,
+                            UniformValue::Bool(batch.is_skinned),
+                        ),
+                        (
+                            shader.bone_matrices,
+                            UniformValue::Sampler {
+                                index: 1,
+                                texture: batches.bone_matrices.matrices_storage.clone(),
+                            },
+                        ),
+                        (
+                            shader.bone_matrices_offset,
+                            UniformValue::Integer(instance.bone_matrices_offset as i32),
+                        ),
+                        (
+                            shader.diffuse_texture,
+                            UniformValue::Sampler {
+                                index: 0,
+                                texture: batch.diffuse_texture.clone(),
+                            },
+                        ),
+                    ],
+                );
             }
         }
+    }
+
+    statistics
+}
+
+/// Calculates distances from camera to near plane of first cascade and to far planes
+/// of each of `count` cascades. Cascades are distributed between `z_near` and
+/// `max_distance` using given split scheme.
+pub(in crate) fn csm_split_distances(
+    z_near: f32,
+    max_distance: f32,
+    count: usize,
+    scheme: CsmSplitScheme,
+) -> [f32; CSM_MAX_CASCADES + 1] {
+    let count = count.clamp(1, CSM_MAX_CASCADES);
+    let max_distance = max_distance.max(z_near);
+
+    let mut distances = [max_distance; CSM_MAX_CASCADES + 1];
+    distances[0] = z_near;
+    for (i, distance) in distances.iter_mut().enumerate().take(count).skip(1) {
+        *distance = match scheme {
+            CsmSplitScheme::Practical(lambda) => {
+                let fraction = i as f32 / count as f32;
+                let log = z_near * (max_distance / z_near).powf(fraction);
+                let uniform = z_near + (max_distance - z_near) * fraction;
+                let lambda = lambda.clamp(0.0, 1.0);
+                log * lambda + uniform * (1.0 - lambda)
+            }
+            CsmSplitScheme::Manual(fractions) => {
+                z_near + (max_distance - z_near) * fractions[i - 1].clamp(0.0, 1.0)
+            }
+        };
+    }
+    // Keep distances monotonic even if user-defined fractions are not.
+    for i in 1..=count {
+        distances[i] = distances[i].max(distances[i - 1]);
+    }
+
+    distances
+}
+
+/// Returns world-space corners of a slice of camera frustum between `slice_near` and
+/// `slice_far` distances. First four corners lie on near plane of the slice.
+pub(in crate) fn frustum_slice_corners(
+    inv_view_projection: &Matrix4<f32>,
+    z_near: f32,
+    z_far: f32,
+    slice_near: f32,
+    slice_far: f32,
+) -> [Vector3<f32>; 8] {
+    let unproject = |x: f32, y: f32, z: f32| {
+        let p = inv_view_projection * Vector4::new(x, y, z, 1.0);
+        p.xyz().scale(1.0 / p.w)
+    };
+
+    let depth_range = (z_far - z_near).max(f32::EPSILON);
+    let t_near = (slice_near - z_near) / depth_range;
+    let t_far = (slice_far - z_near) / depth_range;
+
+    let mut corners = [Vector3::default(); 8];
+    for (i, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .iter()
+        .enumerate()
+    {
+        let near = unproject(*x, *y, -1.0);
+        let far = unproject(*x, *y, 1.0);
+        corners[i] = near.lerp(&far, t_near);
+        corners[i + 4] = near.lerp(&far, t_far);
+    }
+    corners
+}
+
+/// Builds orthographic view-projection matrix of a light with given direction of rays
+/// that covers bounding sphere of given frustum slice. Projection is snapped to texels
+/// of shadow map so shadows won't flicker when camera moves or rotates. `caster_distance`
+/// defines how far towards the light near plane will be moved to catch shadow casters
+/// which are outside of the slice.
+pub(in crate) fn csm_light_view_projection(
+    corners: &[Vector3<f32>; 8],
+    light_direction: Vector3<f32>,
+    shadow_map_size: usize,
+    caster_distance: f32,
+) -> Matrix4<f32> {
+    let center = corners
+        .iter()
+        .fold(Vector3::default(), |acc, corner| acc + corner)
+        .scale(1.0 / corners.len() as f32);
+    let radius = corners
+        .iter()
+        .fold(0.0f32, |acc, corner| acc.max((corner - center).norm()));
+    // Bounding sphere does not depend on orientation of camera, quantize its radius to
+    // keep size of texels constant regardless of precision issues.
+    let radius = ((radius * 16.0).ceil() / 16.0).max(f32::EPSILON);
+
+    let light_direction = light_direction
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(|| -Vector3::y());
+    let up = if light_direction.y.abs() < 0.99 {
+        Vector3::y()
+    } else {
+        Vector3::z()
+    };
+    let light_view = Matrix4::look_at_rh(&Point3::origin(), &Point3::from(light_direction), &up);
+
+    let center = light_view.transform_point(&Point3::from(center));
+    let texel_size = 2.0 * radius / shadow_map_size.max(1) as f32;
+    let x = (center.x / texel_size).floor() * texel_size;
+    let y = (center.y / texel_size).floor() * texel_size;
+
+    let light_projection = Matrix4::new_orthographic(
+        x - radius,
+        x + radius,
+        y - radius,
+        y + radius,
+        -center.z - radius - caster_distance,
+        -center.z + radius,
+    );
+
+    light_projection * light_view
+}
+
+/// Cascaded shadow map renderer for directional lights. View frustum of camera is split
+/// into few slices (cascades) and each cascade gets its own shadow map, so objects close
+/// to camera get high-resolution shadows while distant ones still have shadows.
+pub struct CsmRenderer {
+    precision: ShadowMapPrecision,
+    shader: SpotShadowMapShader,
+    cascades: Vec<FrameBuffer>,
+    size: usize,
+    light_view_projections: [Matrix4<f32>; CSM_MAX_CASCADES],
+    split_distances: [f32; CSM_MAX_CASCADES + 1],
+}
+
+pub(in crate) struct CsmRenderContext<'a, 'c> {
+    pub state: &'a mut PipelineState,
+    pub graph: &'c Graph,
+    pub camera: &'c Camera,
+    pub light_direction: Vector3<f32>,
+    pub shadows_distance: f32,
+    pub split_scheme: CsmSplitScheme,
+    pub geom_cache: &'a mut GeometryCache,
+    pub batch_storage: &'a BatchStorage,
+    pub occluded_meshes: Option<&'c HashSet<Handle<Node>>>,
+}
+
+impl CsmRenderer {
+    pub fn new(
+        state: &mut PipelineState,
+        size: usize,
+        cascade_count: usize,
+        precision: ShadowMapPrecision,
+    ) -> Result<Self, RendererError> {
+        let cascades = (0..cascade_count.clamp(1, CSM_MAX_CASCADES))
+            .map(|_| make_depth_framebuffer(state, size, precision))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            precision,
+            size,
+            cascades,
+            shader: SpotShadowMapShader::new()?,
+            light_view_projections: [Matrix4::identity(); CSM_MAX_CASCADES],
+            split_distances: [0.0; CSM_MAX_CASCADES + 1],
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn precision(&self) -> ShadowMapPrecision {
+        self.precision
+    }
+
+    pub fn cascade_count(&self) -> usize {
+        self.cascades.len()
+    }
+
+    pub fn cascade_texture(&self, cascade: usize) -> Rc<RefCell<GpuTexture>> {
+        self.cascades[cascade]
+            .depth_attachment()
+            .unwrap()
+            .texture
+            .clone()
+    }
+
+    /// Returns light view-projection matrices of each cascade calculated in last `render` call.
+    pub fn light_view_projections(&self) -> &[Matrix4<f32>; CSM_MAX_CASCADES] {
+        &self.light_view_projections
+    }
+
+    /// Returns split distances calculated in last `render` call, see `csm_split_distances`.
+    pub fn split_distances(&self) -> &[f32; CSM_MAX_CASCADES + 1] {
+        &self.split_distances
+    }
+
+    pub(in crate) fn render(&mut self, args: CsmRenderContext) -> RenderPassStatistics {
+        scope_profile!();
+
+        let CsmRenderContext {
+            state,
+            graph,
+            camera,
+            light_direction,
+            shadows_distance,
+            split_scheme,
+            geom_cache,
+            batch_storage,
+            occluded_meshes,
+        } = args;
+
+        let mut statistics = RenderPassStatistics::default();
+
+        let z_near = camera.z_near();
+        let z_far = camera.z_far();
+        let inv_view_projection = camera
+            .view_projection_matrix()
+            .try_inverse()
+            .unwrap_or_default();
+
+        self.split_distances = csm_split_distances(
+            z_near,
+            shadows_distance.min(z_far),
+            self.cascades.len(),
+            split_scheme,
+        );
+
+        let viewport = Rect::new(0, 0, self.size as i32, self.size as i32);
+
+        for (cascade, framebuffer) in self.cascades.iter_mut().enumerate() {
+            let corners = frustum_slice_corners(
+                &inv_view_projection,
+                z_near,
+                z_far,
+                self.split_distances[cascade],
+                self.split_distances[cascade + 1],
+            );
+
+            let light_view_projection =
+                csm_light_view_projection(&corners, light_direction, self.size, shadows_distance);
+            self.light_view_projections[cascade] = light_view_projection;
+
+            framebuffer.clear(state, viewport, None, Some(1.0), None);
+            statistics += render_shadow_casters(ShadowCastersRenderContext {
+                state,
+                graph,
+                framebuffer,
+                viewport,
+                shader: &self.shader,
+                light_view_projection: &light_view_projection,
+                batches: batch_storage,
+                geom_cache,
+                occluded_meshes,
+            });
+        }
 
         statistics
     }
@@ -247,6 +556,7 @@ struct PointShadowMapShader {
     program: GpuProgram,
     world_matrix: UniformLocation,
     bone_matrices: UniformLocation,
+    bone_matrices_offset: UniformLocation,
     world_view_projection_matrix: UniformLocation,
     use_skeletal_animation: UniformLocation,
     diffuse_texture: UniformLocation,
@@ -262,6 +572,7 @@ impl PointShadowMapShader {
         Ok(Self {
             world_matrix: program.uniform_location("worldMatrix")?,
             bone_matrices: program.uniform_location("boneMatrices")?,
+            bone_matrices_offset: program.uniform_location("boneMatricesOffset")?,
             world_view_projection_matrix: program.uniform_location("worldViewProjection")?,
             use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
             diffuse_texture: program.uniform_location("diffuseTexture")?,
@@ -273,7 +584,6 @@ impl PointShadowMapShader {
 
 pub struct PointShadowMapRenderer {
     precision: ShadowMapPrecision,
-    bone_matrices: Vec<Matrix4<f32>>,
     shader: PointShadowMapShader,
     cascades: [FrameBuffer; 3],
     size: usize,
@@ -294,6 +604,7 @@ pub(in crate) struct PointShadowMapRenderContext<'a, 'c> {
     pub geom_cache: &'a mut GeometryCache,
     pub cascade: usize,
     pub batch_storage: &'a BatchStorage,
+    pub occluded_meshes: Option<&'c HashSet<Handle<Node>>>,
 }
 
 impl PointShadowMapRenderer {
@@ -372,7 +683,6 @@ impl PointShadowMapRenderer {
 
         Ok(Self {
             precision,
-            bone_matrices: Default::default(),
             cascades: [
                 make_cascade(state, cascade_size(size, 0), precision)?,
                 make_cascade(state, cascade_size(size, 1), precision)?,
@@ -442,6 +752,7 @@ impl PointShadowMapRenderer {
             geom_cache,
             cascade,
             batch_storage,
+            occluded_meshes,
         } = args;
 
         let framebuffer = &mut self.cascades[cascade];
@@ -471,19 +782,25 @@ impl PointShadowMapRenderer {
 
             let frustum = Frustum::from(light_view_projection_matrix).unwrap();
 
-            for batch in batch_storage.batches.iter() {
+            for batch in batch_storage
+                .batches
+                .iter()
+                .filter(|batch| !batch.blend_mode.is_transparent())
+            {
                 let geometry = geom_cache.get(state, &batch.data.read().unwrap());
 
                 for instance in batch.instances.iter() {
                     let node = &graph[instance.owner];
 
-                    let visible = node.global_visibility() && {
-                        if let Node::Mesh(mesh) = node {
-                            mesh.is_intersect_frustum(graph, &frustum)
-                        } else {
-                            false
-                        }
-                    };
+                    let visible = node.global_visibility()
+                        && !is_occluded(occluded_meshes, instance.owner)
+                        && {
+                            if let Node::Mesh(mesh) = node {
+                                mesh.is_intersect_frustum(graph, &frustum)
+                            } else {
+                                false
+                            }
+                        };
 
                     if visible {
                         statistics += framebuffer.draw(
@@ -518,12 +835,17 @@ impl PointShadowMapRenderer {
                                 ),
                                 (
                                     self.shader.bone_matrices,
-                                    UniformValue::Mat4Array({
-                                        self.bone_matrices.clear();
-                                        self.bone_matrices
-                                            .extend_from_slice(instance.bone_matrices.as_slice());
-                                        &self.bone_matrices
-                                    }),
+                                    UniformValue::Sampler {
+                                        index: 1,
+                                        texture: batch_storage
+                                            .bone_matrices
+                                            .matrices_storage
+                                            .clone(),
+                                    },
+                                ),
+                                (
+                                    self.shader.bone_matrices_offset,
+                                    UniformValue::Integer(instance.bone_matrices_offset as i32),
                                 ),
                                 (
                                     self.shader.diffuse_texture,
@@ -542,3 +864,69 @@ impl PointShadowMapRenderer {
         statistics
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::{Matrix4, Point3, Vector3},
+        renderer::{
+            shadow_map_renderer::{
+                csm_light_view_projection, csm_split_distances, frustum_slice_corners,
+            },
+            CsmSplitScheme,
+        },
+    };
+
+    #[test]
+    fn csm_splits() {
+        let splits = csm_split_distances(0.1, 100.0, 4, CsmSplitScheme::Practical(0.5));
+        assert_eq!(splits[0], 0.1);
+        assert_eq!(splits[4], 100.0);
+        assert!(splits.windows(2).all(|w| w[0] < w[1]));
+
+        // Unused cascades are collapsed at shadows distance.
+        let splits = csm_split_distances(0.1, 100.0, 2, CsmSplitScheme::Practical(0.5));
+        assert_eq!(&splits[2..], &[100.0, 100.0, 100.0]);
+
+        let splits =
+            csm_split_distances(0.0, 100.0, 3, CsmSplitScheme::Manual([0.1, 0.3, 0.5, 1.0]));
+        for (split, expected) in splits.iter().zip([0.0, 10.0, 30.0, 100.0].iter()) {
+            assert!((split - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn csm_cascade_covers_slice_and_is_snapped() {
+        let projection = Matrix4::new_perspective(1.0, 1.2, 0.1, 100.0);
+        let light_direction = Vector3::new(0.3, -1.0, 0.2);
+        let size = 1024;
+
+        let make = |x: f32| {
+            let view = Matrix4::look_at_rh(
+                &Point3::new(x, 2.0, 0.0),
+                &Point3::new(x, 2.0, 1.0),
+                &Vector3::y(),
+            );
+            let inv_view_projection = (projection * view).try_inverse().unwrap();
+            let corners = frustum_slice_corners(&inv_view_projection, 0.1, 100.0, 5.0, 20.0);
+            (
+                corners,
+                csm_light_view_projection(&corners, light_direction, size, 50.0),
+            )
+        };
+
+        let (corners, light_view_projection) = make(0.0);
+        for corner in corners.iter() {
+            let p = light_view_projection.transform_point(&Point3::from(*corner));
+            assert!(p.coords.iter().all(|c| c.abs() <= 1.0 + 1e-4));
+        }
+
+        // Moving camera must shift projection only by whole texels.
+        let (_, moved) = make(0.37);
+        let origin = Point3::origin();
+        let a = light_view_projection.transform_point(&origin);
+        let b = moved.transform_point(&origin);
+        let texels = (a.x - b.x) * size as f32 * 0.5;
+        assert!((texels - texels.round()).abs() < 1e-2);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    core::{math::Matrix4Ext, math::Rect, scope_profile},
+    core::{algebra::Vector4, math::Matrix4Ext, math::Rect, scope_profile},
     renderer::{
         error::RendererError,
         framework::{
@@ -24,8 +24,11 @@ struct SpriteShader {
     camera_up_vector: UniformLocation,
     color: UniformLocation,
     diffuse_texture: UniformLocation,
+    camera_position: UniformLocation,
     size: UniformLocation,
     rotation: UniformLocation,
+    uv_rect: UniformLocation,
+    billboard_axis: UniformLocation,
 }
 
 impl SpriteShader {
@@ -42,6 +45,9 @@ impl SpriteShader {
             diffuse_texture: program.uniform_location("diffuseTexture")?,
             color: program.uniform_location("color")?,
             rotation: program.uniform_location("rotation")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            uv_rect: program.uniform_location("uvRect")?,
+            billboard_axis: program.uniform_location("billboardAxis")?,
             program,
         })
     }
@@ -96,6 +102,7 @@ impl SpriteRenderer {
 
         let camera_up = inv_view.up();
         let camera_side = inv_view.side();
+        let camera_position = camera.global_position();
 
         for node in graph.linear_iter() {
             let sprite = if let Node::Sprite(sprite) = node {
@@ -114,6 +121,12 @@ impl SpriteRenderer {
                 white_dummy.clone()
             };
 
+            let uv_rect = sprite.actual_uv_rect();
+            let billboard_axis = match sprite.world_billboard_axis() {
+                Some(axis) => Vector4::new(axis.x, axis.y, axis.z, 1.0),
+                None => Vector4::default(),
+            };
+
             statistics += framebuffer.draw(
                 geom_map.get(state, &self.surface),
                 state,
@@ -152,9 +165,26 @@ impl SpriteRenderer {
                         self.shader.camera_side_vector,
                         UniformValue::Vector3(camera_side),
                     ),
-                    (self.shader.size, UniformValue::Float(sprite.size())),
+                    (
+                        self.shader.camera_position,
+                        UniformValue::Vector3(camera_position),
+                    ),
+                    (self.shader.size, UniformValue::Vector2(sprite.dimensions())),
                     (self.shader.color, UniformValue::Color(sprite.color())),
                     (self.shader.rotation, UniformValue::Float(sprite.rotation())),
+                    (
+                        self.shader.uv_rect,
+                        UniformValue::Vector4(Vector4::new(
+                            uv_rect.x(),
+                            uv_rect.y(),
+                            uv_rect.w(),
+                            uv_rect.h(),
+                        )),
+                    ),
+                    (
+                        self.shader.billboard_axis,
+                        UniformValue::Vector4(billboard_axis),
+                    ),
                 ],
             );
         }
@@ -0,0 +1,305 @@
+//! Screen space reflections. Reflection ray of each pixel is traced through depth buffer of the
+//! G-buffer and, if it hits something on screen, lit frame is sampled at the hit point. Rays that
+//! miss, leave the screen or start on rough surfaces fall back to environment map of the camera,
+//! so this pass replaces specular part of ambient lighting and must be done after all lights.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector3},
+        color::Color,
+        math::Rect,
+        scope_profile,
+    },
+    renderer::{
+        error::RendererError,
+        flat_shader::FlatShader,
+        framework::{
+            framebuffer::{CullFace, DrawParameters, FrameBufferTrait},
+            gl,
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::GpuTexture,
+            state::PipelineState,
+        },
+        gbuffer::GBuffer,
+        surface::SurfaceSharedData,
+        GeometryCache, QualitySettings, RenderPassStatistics,
+    },
+    scene::camera::Camera,
+};
+use std::{cell::RefCell, rc::Rc};
+
+struct Shader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    frame_texture: UniformLocation,
+    diffuse_texture: UniformLocation,
+    ao_sampler: UniformLocation,
+    depth_texture: UniformLocation,
+    normal_texture: UniformLocation,
+    material_texture: UniformLocation,
+    prefiltered_map: UniformLocation,
+    brdf_lut: UniformLocation,
+    ambient_color: UniformLocation,
+    view_proj_matrix: UniformLocation,
+    inv_view_proj_matrix: UniformLocation,
+    camera_position: UniformLocation,
+    environment_enabled: UniformLocation,
+    prefiltered_max_lod: UniformLocation,
+    max_distance: UniformLocation,
+    steps: UniformLocation,
+    thickness: UniformLocation,
+    max_roughness: UniformLocation,
+}
+
+impl Shader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/ssr_fs.glsl");
+        let vertex_source = include_str!("shaders/ambient_light_vs.glsl");
+        let program = GpuProgram::from_source("SsrShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_texture: program.uniform_location("frameTexture")?,
+            diffuse_texture: program.uniform_location("diffuseTexture")?,
+            ao_sampler: program.uniform_location("aoSampler")?,
+            depth_texture: program.uniform_location("depthTexture")?,
+            normal_texture: program.uniform_location("normalTexture")?,
+            material_texture: program.uniform_location("materialTexture")?,
+            prefiltered_map: program.uniform_location("prefilteredMap")?,
+            brdf_lut: program.uniform_location("brdfLut")?,
+            ambient_color: program.uniform_location("ambientColor")?,
+            view_proj_matrix: program.uniform_location("viewProj")?,
+            inv_view_proj_matrix: program.uniform_location("invViewProj")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            environment_enabled: program.uniform_location("environmentEnabled")?,
+            prefiltered_max_lod: program.uniform_location("prefilteredMaxLod")?,
+            max_distance: program.uniform_location("maxDistance")?,
+            steps: program.uniform_location("steps")?,
+            thickness: program.uniform_location("thickness")?,
+            max_roughness: program.uniform_location("maxRoughness")?,
+            program,
+        })
+    }
+}
+
+pub(in crate) struct SsrRenderContext<'a> {
+    pub state: &'a mut PipelineState,
+    pub geometry_cache: &'a mut GeometryCache,
+    pub gbuffer: &'a mut GBuffer,
+    pub camera: &'a Camera,
+    pub settings: &'a QualitySettings,
+    pub ao_map: Rc<RefCell<GpuTexture>>,
+    /// Prefiltered environment map of the camera, used for rays that missed.
+    pub prefiltered_map: Rc<RefCell<GpuTexture>>,
+    pub prefiltered_max_lod: f32,
+    pub environment_enabled: bool,
+    pub brdf_lut: Rc<RefCell<GpuTexture>>,
+    pub ambient_color: Color,
+}
+
+pub struct ScreenSpaceReflectionsRenderer {
+    shader: Shader,
+    copy_shader: FlatShader,
+    quad: SurfaceSharedData,
+}
+
+impl ScreenSpaceReflectionsRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: Shader::new()?,
+            copy_shader: FlatShader::new()?,
+            quad: SurfaceSharedData::make_unit_xy_quad(),
+        })
+    }
+
+    /// Adds reflections to the final frame of the G-buffer.
+    #[must_use]
+    pub(in crate) fn render(&mut self, args: SsrRenderContext) -> RenderPassStatistics {
+        scope_profile!();
+
+        let SsrRenderContext {
+            state,
+            geometry_cache,
+            gbuffer,
+            camera,
+            settings,
+            ao_map,
+            prefiltered_map,
+            prefiltered_max_lod,
+            environment_enabled,
+            brdf_lut,
+            ambient_color,
+        } = args;
+
+        let mut statistics = RenderPassStatistics::default();
+
+        let viewport = Rect::new(0, 0, gbuffer.width, gbuffer.height);
+        let frame_matrix = Matrix4::new_orthographic(
+            0.0,
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+            -1.0,
+            1.0,
+        ) * Matrix4::new_nonuniform_scaling(&Vector3::new(
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+        ));
+
+        let view_projection = camera.view_projection_matrix();
+        let inv_view_projection = view_projection.try_inverse().unwrap_or_default();
+
+        // Reflections are sampled from lit frame, but it cannot be read while rendering to it,
+        // so make a copy first.
+        let hdr_frame = gbuffer.hdr_frame_texture();
+        let frame_copy = &mut gbuffer.post_buffers.hdr_temp;
+        statistics += frame_copy.draw(
+            geometry_cache.get(state, &self.quad),
+            state,
+            viewport,
+            &self.copy_shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: false,
+            },
+            &[
+                (
+                    self.copy_shader.wvp_matrix,
+                    UniformValue::Matrix4(frame_matrix),
+                ),
+                (
+                    self.copy_shader.diffuse_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: hdr_frame,
+                    },
+                ),
+            ],
+        );
+        let frame_texture = frame_copy.color_attachments()[0].texture.clone();
+
+        state.set_blend_func(gl::ONE, gl::ONE);
+
+        statistics += gbuffer.final_frame.draw(
+            geometry_cache.get(state, &self.quad),
+            state,
+            viewport,
+            &self.shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: true,
+            },
+            &[
+                (self.shader.wvp_matrix, UniformValue::Matrix4(frame_matrix)),
+                (
+                    self.shader.frame_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: frame_texture,
+                    },
+                ),
+                (
+                    self.shader.diffuse_texture,
+                    UniformValue::Sampler {
+                        index: 1,
+                        texture: gbuffer.diffuse_texture(),
+                    },
+                ),
+                (
+                    self.shader.ao_sampler,
+                    UniformValue::Sampler {
+                        index: 2,
+                        texture: ao_map,
+                    },
+                ),
+                (
+                    self.shader.depth_texture,
+                    UniformValue::Sampler {
+                        index: 3,
+                        texture: gbuffer.depth(),
+                    },
+                ),
+                (
+                    self.shader.normal_texture,
+                    UniformValue::Sampler {
+                        index: 4,
+                        texture: gbuffer.normal_texture(),
+                    },
+                ),
+                (
+                    self.shader.material_texture,
+                    UniformValue::Sampler {
+                        index: 5,
+                        texture: gbuffer.material_texture(),
+                    },
+                ),
+                (
+                    self.shader.prefiltered_map,
+                    UniformValue::Sampler {
+                        index: 6,
+                        texture: prefiltered_map,
+                    },
+                ),
+                (
+                    self.shader.brdf_lut,
+                    UniformValue::Sampler {
+                        index: 7,
+                        texture: brdf_lut,
+                    },
+                ),
+                (
+                    self.shader.ambient_color,
+                    UniformValue::Color(ambient_color),
+                ),
+                (
+                    self.shader.view_proj_matrix,
+                    UniformValue::Matrix4(view_projection),
+                ),
+                (
+                    self.shader.inv_view_proj_matrix,
+                    UniformValue::Matrix4(inv_view_projection),
+                ),
+                (
+                    self.shader.camera_position,
+                    UniformValue::Vector3(camera.global_position()),
+                ),
+                (
+                    self.shader.environment_enabled,
+                    UniformValue::Bool(environment_enabled),
+                ),
+                (
+                    self.shader.prefiltered_max_lod,
+                    UniformValue::Float(prefiltered_max_lod),
+                ),
+                (
+                    self.shader.max_distance,
+                    UniformValue::Float(settings.ssr_max_distance),
+                ),
+                (
+                    self.shader.steps,
+                    UniformValue::Integer(settings.ssr_steps as i32),
+                ),
+                (
+                    self.shader.thickness,
+                    UniformValue::Float(settings.ssr_thickness),
+                ),
+                (
+                    self.shader.max_roughness,
+                    UniformValue::Float(settings.ssr_max_roughness),
+                ),
+            ],
+        );
+
+        statistics
+    }
+}
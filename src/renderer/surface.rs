@@ -12,8 +12,11 @@ use crate::{
         pool::{ErasedHandle, Handle},
         visitor::{Visit, VisitResult, Visitor},
     },
-    resource::texture::Texture,
-    scene::node::Node,
+    resource::{
+        material::{Material, MaterialData, PropertyValue},
+        texture::Texture,
+    },
+    scene::{graph::Graph, node::Node},
     utils::raw_mesh::{RawMesh, RawMeshBuilder},
 };
 use std::collections::hash_map::DefaultHasher;
@@ -1005,6 +1008,66 @@ impl VertexWeightSet {
     }
 }
 
+/// Defines the way how surface will be blended with other objects on screen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Surface is fully opaque, alpha of its color and diffuse texture is ignored.
+    Opaque,
+    /// Fragments with alpha less than 0.5 are discarded, the rest are opaque. Good for
+    /// foliage, fences, etc. This is default mode.
+    AlphaTest,
+    /// Surface is rendered in forward transparency pass after lighting and blended with
+    /// everything behind it using its alpha. Glass, water, fading objects, etc.
+    AlphaBlend,
+    /// Same as `AlphaBlend`, but color of the surface is added to everything behind it.
+    /// Good for glowing things like force fields, holograms, etc.
+    Additive,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        Self::AlphaTest
+    }
+}
+
+impl BlendMode {
+    /// Returns true if surface with such blend mode must be rendered in forward
+    /// transparency pass instead of G-buffer.
+    pub fn is_transparent(self) -> bool {
+        matches!(self, Self::AlphaBlend | Self::Additive)
+    }
+
+    fn id(self) -> u32 {
+        match self {
+            Self::Opaque => 0,
+            Self::AlphaTest => 1,
+            Self::AlphaBlend => 2,
+            Self::Additive => 3,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::Opaque),
+            1 => Ok(Self::AlphaTest),
+            2 => Ok(Self::AlphaBlend),
+            3 => Ok(Self::Additive),
+            _ => Err(format!("Invalid blend mode id {}", id)),
+        }
+    }
+}
+
+impl Visit for BlendMode {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id = self.id();
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        Ok(())
+    }
+}
+
 /// See module docs.
 #[derive(Debug, Default)]
 pub struct Surface {
@@ -1016,6 +1079,9 @@ pub struct Surface {
     lightmap_texture: Option<Texture>,
     specular_texture: Option<Texture>,
     roughness_texture: Option<Texture>,
+    metallic_texture: Option<Texture>,
+    ambient_occlusion_texture: Option<Texture>,
+    emission_texture: Option<Texture>,
     /// Temporal array for FBX conversion needs, it holds skinning data (weight + bone handle)
     /// and will be used to fill actual bone indices and weight in vertices that will be
     /// sent to GPU. The idea is very simple: GPU needs to know only indices of matrices of
@@ -1026,6 +1092,8 @@ pub struct Surface {
     /// Array of handle to scene nodes which are used as bones.
     pub bones: Vec<Handle<Node>>,
     color: Color,
+    blend_mode: BlendMode,
+    material: Option<Material>,
 }
 
 /// Shallow copy of surface.
@@ -1042,10 +1110,15 @@ impl Clone for Surface {
             normal_texture: self.normal_texture.clone(),
             specular_texture: self.specular_texture.clone(),
             roughness_texture: self.roughness_texture.clone(),
+            metallic_texture: self.metallic_texture.clone(),
+            ambient_occlusion_texture: self.ambient_occlusion_texture.clone(),
+            emission_texture: self.emission_texture.clone(),
             bones: self.bones.clone(),
             vertex_weights: Vec::new(), // Intentionally not copied.
             color: self.color,
             lightmap_texture: self.lightmap_texture.clone(),
+            blend_mode: self.blend_mode,
+            material: self.material.clone(),
         }
     }
 }
@@ -1060,10 +1133,15 @@ impl Surface {
             normal_texture: None,
             specular_texture: None,
             roughness_texture: None,
+            metallic_texture: None,
+            ambient_occlusion_texture: None,
+            emission_texture: None,
             bones: Vec::new(),
             vertex_weights: Vec::new(),
             color: Color::WHITE,
             lightmap_texture: None,
+            blend_mode: Default::default(),
+            material: None,
         }
     }
 
@@ -1109,7 +1187,8 @@ impl Surface {
         self.specular_texture.clone()
     }
 
-    /// Sets new roughness texture.
+    /// Sets new roughness texture. Roughness is taken from red channel, surfaces without
+    /// roughness texture are fully rough.
     #[inline]
     pub fn set_roughness_texture(&mut self, tex: Option<Texture>) {
         self.roughness_texture = tex;
@@ -1121,6 +1200,45 @@ impl Surface {
         self.roughness_texture.clone()
     }
 
+    /// Sets new metallic texture. Metalness is taken from red channel, surfaces without
+    /// metallic texture are dielectrics.
+    #[inline]
+    pub fn set_metallic_texture(&mut self, tex: Option<Texture>) {
+        self.metallic_texture = tex;
+    }
+
+    /// Returns current metallic texture.
+    #[inline]
+    pub fn metallic_texture(&self) -> Option<Texture> {
+        self.metallic_texture.clone()
+    }
+
+    /// Sets new ambient occlusion texture. Occlusion is taken from red channel, it affects
+    /// only ambient lighting.
+    #[inline]
+    pub fn set_ambient_occlusion_texture(&mut self, tex: Option<Texture>) {
+        self.ambient_occlusion_texture = tex;
+    }
+
+    /// Returns current ambient occlusion texture.
+    #[inline]
+    pub fn ambient_occlusion_texture(&self) -> Option<Texture> {
+        self.ambient_occlusion_texture.clone()
+    }
+
+    /// Sets new emission texture. Emitted light is added to lighting of surface, but it does
+    /// not light other surfaces.
+    #[inline]
+    pub fn set_emission_texture(&mut self, tex: Option<Texture>) {
+        self.emission_texture = tex;
+    }
+
+    /// Returns current emission texture.
+    #[inline]
+    pub fn emission_texture(&self) -> Option<Texture> {
+        self.emission_texture.clone()
+    }
+
     /// Sets new lightmap texture.
     #[inline]
     pub fn set_lightmap_texture(&mut self, tex: Option<Texture>) {
@@ -1145,11 +1263,109 @@ impl Surface {
         self.color
     }
 
+    /// Sets new blend mode of surface. Surfaces with `AlphaBlend` or `Additive` modes are
+    /// rendered in forward transparency pass, sorted back-to-front.
+    #[inline]
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    /// Returns current blend mode of surface.
+    #[inline]
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// Sets new material of surface. Material replaces texture slots of the surface, surfaces
+    /// without a material are drawn using the standard material made of texture slots (see
+    /// [`make_standard_material`](#method.make_standard_material)). Color and blend mode of
+    /// the surface are still used with a material.
+    #[inline]
+    pub fn set_material(&mut self, material: Option<Material>) {
+        self.material = material;
+    }
+
+    /// Returns current material of surface.
+    #[inline]
+    pub fn material(&self) -> Option<Material> {
+        self.material.clone()
+    }
+
+    /// Creates the standard material filled with texture slots of the surface. Such
+    /// material looks exactly the same as the surface without a material.
+    pub fn make_standard_material(&self) -> MaterialData {
+        let mut material = MaterialData::standard();
+        for (name, texture) in [
+            ("diffuseTexture", &self.diffuse_texture),
+            ("normalTexture", &self.normal_texture),
+            ("specularTexture", &self.specular_texture),
+            ("roughnessTexture", &self.roughness_texture),
+            ("metallicTexture", &self.metallic_texture),
+            ("ambientOcclusionTexture", &self.ambient_occlusion_texture),
+            ("emissionTexture", &self.emission_texture),
+            ("lightmapTexture", &self.lightmap_texture),
+        ]
+        .iter()
+        {
+            if let Some(&PropertyValue::Sampler { fallback, .. }) = material.property(name) {
+                let value = PropertyValue::Sampler {
+                    value: (*texture).clone(),
+                    fallback,
+                };
+                // Standard shader has all of these properties.
+                material.set_property(name, value).unwrap();
+            }
+        }
+        material
+    }
+
     /// Returns list of bones that affects the surface.
     #[inline]
     pub fn bones(&self) -> &[Handle<Node>] {
         &self.bones
     }
+
+    /// Calculates world-space positions of vertices of the surface in current pose of its bones
+    /// in given graph, so animated geometry can be used on CPU side (picking, particle emission
+    /// and so on). `mesh_transform` is global transform of the mesh that owns the surface, it is
+    /// used instead of bones that are no longer in the graph. Previous content of `positions`
+    /// is discarded.
+    pub fn skinned_positions(
+        &self,
+        graph: &Graph,
+        mesh_transform: Matrix4<f32>,
+        positions: &mut Vec<Vector3<f32>>,
+    ) {
+        let bone_matrices = self
+            .bones
+            .iter()
+            .map(|&bone| {
+                if graph.is_valid_handle(bone) {
+                    let bone = &graph[bone];
+                    bone.global_transform() * bone.inv_bind_pose_transform()
+                } else {
+                    mesh_transform
+                }
+            })
+            .collect::<Vec<Matrix4<f32>>>();
+
+        let data = self.data();
+        let data = data.read().unwrap();
+        positions.clear();
+        positions.extend(data.get_vertices().iter().map(|vertex| {
+            let mut position = Vector3::default();
+            for (&bone_index, &weight) in vertex.bone_indices.iter().zip(vertex.bone_weights.iter())
+            {
+                if let Some(bone_matrix) = bone_matrices.get(bone_index as usize) {
+                    position += bone_matrix
+                        .transform_point(&Point3::from(vertex.position))
+                        .coords
+                        .scale(weight);
+                }
+            }
+            position
+        }));
+    }
 }
 
 impl Visit for Surface {
@@ -1161,6 +1377,11 @@ impl Visit for Surface {
         self.diffuse_texture.visit("DiffuseTexture", visitor)?;
         let _ = self.specular_texture.visit("SpecularTexture", visitor);
         let _ = self.roughness_texture.visit("RoughnessTexture", visitor);
+        let _ = self.metallic_texture.visit("MetallicTexture", visitor);
+        let _ = self
+            .ambient_occlusion_texture
+            .visit("AmbientOcclusionTexture", visitor);
+        let _ = self.emission_texture.visit("EmissionTexture", visitor);
         self.color.visit("Color", visitor)?;
         self.bones.visit("Bones", visitor)?;
         // self.vertex_weights intentionally not serialized!
@@ -1168,6 +1389,8 @@ impl Visit for Surface {
         // Try to get lightmap texture but don't care if it is missing, it can
         // be missing on previous versions.
         let _ = self.lightmap_texture.visit("LightmapTexture", visitor);
+        let _ = self.blend_mode.visit("BlendMode", visitor);
+        let _ = self.material.visit("Material", visitor);
 
         visitor.leave_region()
     }
@@ -1181,8 +1404,13 @@ pub struct SurfaceBuilder {
     lightmap_texture: Option<Texture>,
     specular_texture: Option<Texture>,
     roughness_texture: Option<Texture>,
+    metallic_texture: Option<Texture>,
+    ambient_occlusion_texture: Option<Texture>,
+    emission_texture: Option<Texture>,
     bones: Vec<Handle<Node>>,
     color: Color,
+    blend_mode: BlendMode,
+    material: Option<Material>,
 }
 
 impl SurfaceBuilder {
@@ -1195,8 +1423,13 @@ impl SurfaceBuilder {
             lightmap_texture: None,
             specular_texture: None,
             roughness_texture: None,
+            metallic_texture: None,
+            ambient_occlusion_texture: None,
+            emission_texture: None,
             bones: Default::default(),
             color: Color::WHITE,
+            blend_mode: Default::default(),
+            material: None,
         }
     }
 
@@ -1230,12 +1463,42 @@ impl SurfaceBuilder {
         self
     }
 
+    /// Sets desired metallic texture.
+    pub fn with_metallic_texture(mut self, tex: Texture) -> Self {
+        self.metallic_texture = Some(tex);
+        self
+    }
+
+    /// Sets desired ambient occlusion texture.
+    pub fn with_ambient_occlusion_texture(mut self, tex: Texture) -> Self {
+        self.ambient_occlusion_texture = Some(tex);
+        self
+    }
+
+    /// Sets desired emission texture.
+    pub fn with_emission_texture(mut self, tex: Texture) -> Self {
+        self.emission_texture = Some(tex);
+        self
+    }
+
     /// Sets desired color of surface.
     pub fn with_color(mut self, color: Color) -> Self {
         self.color = color;
         self
     }
 
+    /// Sets desired blend mode.
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// Sets desired material.
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+
     /// Sets desired bones array. Make sure your vertices has valid indices of bones!
     pub fn with_bones(mut self, bones: Vec<Handle<Node>>) -> Self {
         self.bones = bones;
@@ -1251,9 +1514,71 @@ impl SurfaceBuilder {
             lightmap_texture: self.lightmap_texture,
             specular_texture: self.specular_texture,
             roughness_texture: self.roughness_texture,
+            metallic_texture: self.metallic_texture,
+            ambient_occlusion_texture: self.ambient_occlusion_texture,
+            emission_texture: self.emission_texture,
             vertex_weights: Default::default(),
             bones: self.bones,
             color: self.color,
+            blend_mode: self.blend_mode,
+            material: self.material,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::Matrix4,
+        renderer::surface::{BlendMode, Surface, SurfaceBuilder, SurfaceSharedData},
+        resource::{
+            material::{Material, MaterialData, PropertyValue, SamplerFallback},
+            ResourceState,
+        },
+        utils::testing::visit_round_trip,
+    };
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn surface_blend_mode_visit() {
+        let mut source = SurfaceBuilder::new(Arc::new(RwLock::new(SurfaceSharedData::make_cube(
+            Matrix4::identity(),
+        ))))
+        .with_blend_mode(BlendMode::Additive)
+        .build();
+        let mut surface = Surface::default();
+        visit_round_trip("Surface", &mut source, &mut surface);
+
+        assert_eq!(surface.blend_mode(), BlendMode::Additive);
+        assert!(surface.blend_mode().is_transparent());
+        assert!(!BlendMode::default().is_transparent());
+    }
+
+    #[test]
+    fn surface_material_visit() {
+        let mut source = SurfaceBuilder::new(Arc::new(RwLock::new(SurfaceSharedData::make_cube(
+            Matrix4::identity(),
+        ))))
+        .with_material(Material::from(MaterialData::standard()))
+        .build();
+        let mut surface = Surface::default();
+        visit_round_trip("Surface", &mut source, &mut surface);
+
+        let material = surface.material().unwrap();
+        let state = material.state();
+        if let ResourceState::Ok(material) = &*state {
+            assert_eq!(material.shader().name(), "StandardShader");
+            assert_eq!(material.properties().count(), 8);
+        } else {
+            panic!("material must be loaded");
         }
+
+        assert!(matches!(
+            surface.make_standard_material().property("normalTexture"),
+            Some(PropertyValue::Sampler {
+                value: None,
+                fallback: SamplerFallback::Normal
+            })
+        ));
     }
 }
@@ -234,10 +234,20 @@ fn create_surfaces(
                     let texture_path = resource_manager.state().textures_path().join(&filename);
                     let texture = resource_manager.request_texture(texture_path.as_path());
                     match name.as_str() {
-                        "AmbientColor" => (), // TODO: Add ambient occlusion (AO) map support.
+                        "AmbientColor" | "Maya|TEX_ao_map" => {
+                            surface.set_ambient_occlusion_texture(Some(texture))
+                        }
                         "DiffuseColor" => surface.set_diffuse_texture(Some(texture)),
                         "SpecularFactor" => surface.set_specular_texture(Some(texture)),
-                        "ShininessExponent" => surface.set_roughness_texture(Some(texture)),
+                        "ShininessExponent" | "Maya|TEX_roughness_map" => {
+                            surface.set_roughness_texture(Some(texture))
+                        }
+                        "ReflectionFactor" | "Maya|TEX_metallic_map" => {
+                            surface.set_metallic_texture(Some(texture))
+                        }
+                        "EmissiveColor" | "Maya|TEX_emissive_map" => {
+                            surface.set_emission_texture(Some(texture))
+                        }
                         // No idea why it can be different for normal maps.
                         "Bump" | "NormalMap" => surface.set_normal_texture(Some(texture)),
                         _ => (),
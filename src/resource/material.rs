@@ -0,0 +1,698 @@
+#![warn(missing_docs)]
+
+//! Contains all data structures and method to work with material resources.
+//!
+//! Material is a combination of a shader and a set of values for properties declared by
+//! the shader. Every surface can have its own material (see `Surface::set_material`),
+//! surfaces without a material are drawn using the standard material, which is built
+//! from the texture slots of the surface.
+//!
+//! # Shaders
+//!
+//! Shader is described by [`ShaderDefinition`](struct.ShaderDefinition.html) - it holds
+//! GLSL source code of vertex and fragment programs and a list of properties (uniforms)
+//! that can be set by a material. Shaders of materials are used in G-buffer pass, so
+//! fragment program must write to the same outputs as the standard one:
+//!
+//! ```text
+//! layout(location = 0) out vec4 outColor;     // Diffuse color (albedo), alpha is ignored.
+//! layout(location = 1) out vec4 outNormal;    // xyz - normal packed in [0; 1], w - specular.
+//! layout(location = 2) out vec4 outAmbient;   // Ambient (lightmap) color.
+//! layout(location = 3) out vec4 outDecalMask; // r - decal layer index divided by 255.
+//! layout(location = 4) out vec4 outMaterial;  // r - metallic, g - roughness, b - ambient occlusion.
+//! layout(location = 5) out vec4 outEmission;  // rgb - emitted light.
+//! ```
+//!
+//! Surfaces are lit using metallic-roughness model, specular is reflectance of dielectrics
+//! where 0.5 corresponds to 4% (reflectance of most of non-metals).
+//!
+//! Besides properties, the engine provides following built-in uniforms, each of them is
+//! optional and will be set only if it is used by the shader: `worldMatrix`,
+//! `worldViewProjection`, `useSkeletalAnimation`, `boneMatrices` (texture with bone
+//! matrices of all skinned surfaces), `boneMatricesOffset` (index of first bone matrix
+//! of the surface in `boneMatrices`), `cameraPosition`, `diffuseColor` (color of the
+//! surface), `layerIndex` (decal layer index), `alphaTest` and `environmentMap`. Skinning
+//! matrix of a vertex can be computed using `S_SkinningMatrix` function which is available
+//! in every shader. Standard vertex program ([`STANDARD_VERTEX_SHADER`](constant.STANDARD_VERTEX_SHADER.html))
+//! can be re-used by custom shaders, it outputs `position`, `normal`, `texCoord`, `tangent`,
+//! `binormal` and `secondTexCoord`.
+//!
+//! # Post effects
+//!
+//! A material can also be used as a custom fullscreen pass of a camera (see
+//! `PostEffects::custom_passes`). Such shaders should use
+//! [`POST_EFFECT_VERTEX_SHADER`](constant.POST_EFFECT_VERTEX_SHADER.html) as vertex program
+//! and write result to a single `vec4` output. Besides properties, following built-in
+//! uniforms are available: `frameTexture` (tone mapped frame), `depthTexture` and
+//! `frameSize` (size of the frame in pixels). Texture coordinates of current pixel can be
+//! computed as `gl_FragCoord.xy / frameSize`.
+//!
+//! # Persistence
+//!
+//! Materials are saved in native binary format (see [`MaterialData::save`](struct.MaterialData.html#method.save))
+//! and can be loaded using `ResourceManager::request_material`.
+
+use crate::{
+    core::{
+        algebra::{Vector2, Vector3, Vector4},
+        color::Color,
+        visitor::{Visit, VisitError, VisitResult, Visitor},
+    },
+    engine::resource_manager::ResourceManager,
+    resource::{texture::Texture, Resource, ResourceData, ResourceState},
+};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+/// Source code of vertex program of the standard shader.
+pub const STANDARD_VERTEX_SHADER: &str = include_str!("../renderer/shaders/gbuffer_vs.glsl");
+
+/// Source code of fragment program of the standard shader.
+pub const STANDARD_FRAGMENT_SHADER: &str = include_str!("../renderer/shaders/gbuffer_fs.glsl");
+
+/// Source code of vertex program for custom fullscreen passes.
+pub const POST_EFFECT_VERTEX_SHADER: &str = include_str!("../renderer/shaders/post_effect_vs.glsl");
+
+/// Defines which texture will be used if a sampler property has no texture or the texture
+/// is not loaded yet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SamplerFallback {
+    /// 1x1 white texture.
+    White,
+    /// 1x1 flat normal map texture (0.5, 0.5, 1.0).
+    Normal,
+    /// 1x1 black texture.
+    Black,
+    /// 1x1 texture with default specular intensity (0.5).
+    Specular,
+}
+
+impl Default for SamplerFallback {
+    fn default() -> Self {
+        Self::White
+    }
+}
+
+impl SamplerFallback {
+    fn id(self) -> u32 {
+        match self {
+            SamplerFallback::White => 0,
+            SamplerFallback::Normal => 1,
+            SamplerFallback::Black => 2,
+            SamplerFallback::Specular => 3,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(SamplerFallback::White),
+            1 => Ok(SamplerFallback::Normal),
+            2 => Ok(SamplerFallback::Black),
+            3 => Ok(SamplerFallback::Specular),
+            _ => Err(format!("Invalid sampler fallback id {}!", id)),
+        }
+    }
+}
+
+impl Visit for SamplerFallback {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id = self.id();
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Value of a material property.
+#[derive(Clone, Debug)]
+pub enum PropertyValue {
+    /// `float` uniform.
+    Float(f32),
+    /// `vec2` uniform.
+    Vector2(Vector2<f32>),
+    /// `vec3` uniform.
+    Vector3(Vector3<f32>),
+    /// `vec4` uniform.
+    Vector4(Vector4<f32>),
+    /// `vec4` uniform, color is passed in normalized form.
+    Color(Color),
+    /// `sampler2D` uniform.
+    Sampler {
+        /// Actual texture, can be None - fallback texture will be used in this case.
+        value: Option<Texture>,
+        /// Texture that will be used if there is no texture or it is not loaded yet.
+        fallback: SamplerFallback,
+    },
+}
+
+impl Default for PropertyValue {
+    fn default() -> Self {
+        Self::Float(0.0)
+    }
+}
+
+impl PropertyValue {
+    fn id(&self) -> u32 {
+        match self {
+            PropertyValue::Float(_) => 0,
+            PropertyValue::Vector2(_) => 1,
+            PropertyValue::Vector3(_) => 2,
+            PropertyValue::Vector4(_) => 3,
+            PropertyValue::Color(_) => 4,
+            PropertyValue::Sampler { .. } => 5,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(PropertyValue::Float(Default::default())),
+            1 => Ok(PropertyValue::Vector2(Default::default())),
+            2 => Ok(PropertyValue::Vector3(Default::default())),
+            3 => Ok(PropertyValue::Vector4(Default::default())),
+            4 => Ok(PropertyValue::Color(Default::default())),
+            5 => Ok(PropertyValue::Sampler {
+                value: None,
+                fallback: Default::default(),
+            }),
+            _ => Err(format!("Invalid property value id {}!", id)),
+        }
+    }
+
+    /// Returns true if both values have the same type.
+    pub fn is_same_kind(&self, other: &PropertyValue) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Visit for PropertyValue {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+
+        match self {
+            PropertyValue::Float(v) => v.visit("Value", visitor)?,
+            PropertyValue::Vector2(v) => v.visit("Value", visitor)?,
+            PropertyValue::Vector3(v) => v.visit("Value", visitor)?,
+            PropertyValue::Vector4(v) => v.visit("Value", visitor)?,
+            PropertyValue::Color(v) => v.visit("Value", visitor)?,
+            PropertyValue::Sampler { value, fallback } => {
+                value.visit("Value", visitor)?;
+                fallback.visit("Fallback", visitor)?;
+            }
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Declaration of a shader property: name of the uniform and its default value.
+#[derive(Clone, Debug, Default)]
+pub struct PropertyDefinition {
+    /// Name of the uniform in shader source.
+    pub name: String,
+    /// Default value of the property, it also defines its type.
+    pub default_value: PropertyValue,
+}
+
+impl PropertyDefinition {
+    /// Creates new property definition.
+    pub fn new<N: AsRef<str>>(name: N, default_value: PropertyValue) -> Self {
+        Self {
+            name: name.as_ref().to_owned(),
+            default_value,
+        }
+    }
+}
+
+impl Visit for PropertyDefinition {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.name.visit("Name", visitor)?;
+        self.default_value.visit("DefaultValue", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Shader is GLSL source code of vertex and fragment programs with a list of properties
+/// that can be set by a material. See module docs for more info.
+#[derive(Clone, Debug)]
+pub struct ShaderDefinition {
+    name: String,
+    vertex_source: String,
+    fragment_source: String,
+    properties: Vec<PropertyDefinition>,
+    // Hash of source code, it is used by renderer to find compiled program.
+    key: u64,
+}
+
+impl Default for ShaderDefinition {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl ShaderDefinition {
+    /// Creates new shader definition.
+    pub fn new<N: AsRef<str>>(
+        name: N,
+        vertex_source: String,
+        fragment_source: String,
+        properties: Vec<PropertyDefinition>,
+    ) -> Self {
+        let mut shader = Self {
+            name: name.as_ref().to_owned(),
+            vertex_source,
+            fragment_source,
+            properties,
+            key: 0,
+        };
+        shader.update_key();
+        shader
+    }
+
+    /// Creates definition of the standard shader - the one which is used to draw surfaces
+    /// without a material. It has following properties: `diffuseTexture`, `normalTexture`,
+    /// `specularTexture`, `roughnessTexture`, `metallicTexture`, `ambientOcclusionTexture`,
+    /// `emissionTexture` and `lightmapTexture`.
+    pub fn standard() -> Self {
+        Self::new(
+            "StandardShader",
+            STANDARD_VERTEX_SHADER.to_owned(),
+            STANDARD_FRAGMENT_SHADER.to_owned(),
+            vec![
+                PropertyDefinition::new(
+                    "diffuseTexture",
+                    PropertyValue::Sampler {
+                        value: None,
+                        fallback: SamplerFallback::White,
+                    },
+                ),
+                PropertyDefinition::new(
+                    "normalTexture",
+                    PropertyValue::Sampler {
+                        value: None,
+                        fallback: SamplerFallback::Normal,
+                    },
+                ),
+                PropertyDefinition::new(
+                    "specularTexture",
+                    PropertyValue::Sampler {
+                        value: None,
+                        fallback: SamplerFallback::Specular,
+                    },
+                ),
+                PropertyDefinition::new(
+                    "roughnessTexture",
+                    PropertyValue::Sampler {
+                        value: None,
+                        fallback: SamplerFallback::White,
+                    },
+                ),
+                PropertyDefinition::new(
+                    "metallicTexture",
+                    PropertyValue::Sampler {
+                        value: None,
+                        fallback: SamplerFallback::Black,
+                    },
+                ),
+                PropertyDefinition::new(
+                    "ambientOcclusionTexture",
+                    PropertyValue::Sampler {
+                        value: None,
+                        fallback: SamplerFallback::White,
+                    },
+                ),
+                PropertyDefinition::new(
+                    "emissionTexture",
+                    PropertyValue::Sampler {
+                        value: None,
+                        fallback: SamplerFallback::Black,
+                    },
+                ),
+                PropertyDefinition::new(
+                    "lightmapTexture",
+                    PropertyValue::Sampler {
+                        value: None,
+                        fallback: SamplerFallback::Black,
+                    },
+                ),
+            ],
+        )
+    }
+
+    fn update_key(&mut self) {
+        let mut hasher = DefaultHasher::new();
+        self.name.hash(&mut hasher);
+        self.vertex_source.hash(&mut hasher);
+        self.fragment_source.hash(&mut hasher);
+        self.key = hasher.finish();
+    }
+
+    /// Returns name of the shader.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns source code of vertex program.
+    pub fn vertex_source(&self) -> &str {
+        &self.vertex_source
+    }
+
+    /// Returns source code of fragment program.
+    pub fn fragment_source(&self) -> &str {
+        &self.fragment_source
+    }
+
+    /// Returns list of declared properties.
+    pub fn properties(&self) -> &[PropertyDefinition] {
+        &self.properties
+    }
+
+    /// Tries to find property definition by its name.
+    pub fn find_property(&self, name: &str) -> Option<&PropertyDefinition> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    /// Returns a hash of the source code of the shader. Shaders with the same key share
+    /// the same GPU program.
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+}
+
+impl Visit for ShaderDefinition {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.name.visit("Name", visitor)?;
+        self.vertex_source.visit("VertexSource", visitor)?;
+        self.fragment_source.visit("FragmentSource", visitor)?;
+        if visitor.is_reading() {
+            // Default shader has properties, collections are appended on read.
+            self.properties.clear();
+        }
+        self.properties.visit("Properties", visitor)?;
+
+        if visitor.is_reading() {
+            self.update_key();
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// See module docs.
+#[derive(Debug, Clone)]
+pub struct MaterialData {
+    path: PathBuf,
+    shader: ShaderDefinition,
+    properties: HashMap<String, PropertyValue>,
+}
+
+/// See module docs.
+pub type Material = Resource<MaterialData, MaterialError>;
+
+impl From<MaterialData> for Material {
+    fn from(data: MaterialData) -> Self {
+        Material::new(ResourceState::Ok(data))
+    }
+}
+
+/// All possible errors that may occur while working with materials.
+#[derive(Debug)]
+pub enum MaterialError {
+    /// An error occurred while reading or writing a material file.
+    Visit(VisitError),
+    /// Shader of material has no property with given name.
+    NoSuchProperty(String),
+    /// Type of given value does not match type of the property.
+    TypeMismatch {
+        /// Name of the property.
+        property_name: String,
+        /// Value with expected type.
+        expected: PropertyValue,
+    },
+}
+
+impl From<VisitError> for MaterialError {
+    fn from(e: VisitError) -> Self {
+        MaterialError::Visit(e)
+    }
+}
+
+impl ResourceData for MaterialData {
+    fn path(&self) -> Cow<Path> {
+        Cow::Borrowed(&self.path)
+    }
+}
+
+impl Default for MaterialData {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl Visit for MaterialData {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.path.visit("Path", visitor)?;
+        self.shader.visit("Shader", visitor)?;
+        if visitor.is_reading() {
+            self.properties.clear();
+        }
+        self.properties.visit("Properties", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+impl MaterialData {
+    /// Creates new material with given shader, every property will have default value
+    /// declared by the shader.
+    pub fn new(shader: ShaderDefinition) -> Self {
+        let properties = shader
+            .properties()
+            .iter()
+            .map(|p| (p.name.clone(), p.default_value.clone()))
+            .collect();
+
+        Self {
+            path: Default::default(),
+            shader,
+            properties,
+        }
+    }
+
+    /// Creates new material with the standard shader. Such material looks exactly the same
+    /// as surfaces without a material.
+    pub fn standard() -> Self {
+        Self::new(ShaderDefinition::standard())
+    }
+
+    /// Returns shader of the material.
+    pub fn shader(&self) -> &ShaderDefinition {
+        &self.shader
+    }
+
+    /// Sets new shader. Values of properties that have the same name and type in both
+    /// shaders are preserved, the rest of properties will have default values.
+    pub fn set_shader(&mut self, shader: ShaderDefinition) {
+        let mut properties = HashMap::new();
+        for definition in shader.properties() {
+            let value = match self.properties.remove(&definition.name) {
+                Some(value) if value.is_same_kind(&definition.default_value) => value,
+                _ => definition.default_value.clone(),
+            };
+            properties.insert(definition.name.clone(), value);
+        }
+        self.properties = properties;
+        self.shader = shader;
+    }
+
+    /// Sets new value of a property. Fails if there is no such property in the shader or
+    /// the type of the value does not match the type of the property.
+    pub fn set_property<N: AsRef<str>>(
+        &mut self,
+        name: N,
+        value: PropertyValue,
+    ) -> Result<(), MaterialError> {
+        let name = name.as_ref();
+        match self.shader.find_property(name) {
+            Some(definition) => {
+                if definition.default_value.is_same_kind(&value) {
+                    self.properties.insert(name.to_owned(), value);
+                    Ok(())
+                } else {
+                    Err(MaterialError::TypeMismatch {
+                        property_name: name.to_owned(),
+                        expected: definition.default_value.clone(),
+                    })
+                }
+            }
+            None => Err(MaterialError::NoSuchProperty(name.to_owned())),
+        }
+    }
+
+    /// Returns value of a property.
+    pub fn property(&self, name: &str) -> Option<&PropertyValue> {
+        self.properties.get(name)
+    }
+
+    /// Returns an iterator over all properties of the material.
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &PropertyValue)> {
+        self.properties
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Sets new path of the material.
+    pub fn set_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.path = path.as_ref().to_owned();
+    }
+
+    /// Saves the material to given file in native binary format and changes its path.
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<(), MaterialError> {
+        self.path = path.as_ref().to_owned();
+        let mut visitor = Visitor::new();
+        self.visit("Material", &mut visitor)?;
+        visitor.save_binary(path)?;
+        Ok(())
+    }
+
+    /// Requests every texture used by the material from given resource manager. Material
+    /// stores only paths to textures, this method restores real resources.
+    pub(in crate) fn resolve_textures(&mut self, resource_manager: &ResourceManager) {
+        for value in self.properties.values_mut() {
+            if let PropertyValue::Sampler {
+                value: Some(texture),
+                ..
+            } = value
+            {
+                let path = texture.state().path().to_path_buf();
+                *texture = resource_manager.request_texture(path);
+            }
+        }
+    }
+
+    pub(in crate) async fn load<P: AsRef<Path>>(
+        path: P,
+        resource_manager: ResourceManager,
+    ) -> Result<Self, MaterialError> {
+        let mut material = MaterialData::default();
+        {
+            let mut visitor = Visitor::load_binary(path.as_ref())?;
+            material.visit("Material", &mut visitor)?;
+        }
+        material.path = path.as_ref().to_owned();
+        material.resolve_textures(&resource_manager);
+        Ok(material)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            color::Color,
+            visitor::{Visit, Visitor},
+        },
+        resource::material::{
+            MaterialData, MaterialError, PropertyDefinition, PropertyValue, ShaderDefinition,
+            STANDARD_VERTEX_SHADER,
+        },
+        utils::testing::TempFile,
+    };
+
+    fn custom_shader() -> ShaderDefinition {
+        ShaderDefinition::new(
+            "Custom",
+            STANDARD_VERTEX_SHADER.to_owned(),
+            "void main() {}".to_owned(),
+            vec![
+                PropertyDefinition::new("tint", PropertyValue::Color(Color::WHITE)),
+                PropertyDefinition::new("strength", PropertyValue::Float(1.0)),
+            ],
+        )
+    }
+
+    #[test]
+    fn material_properties() {
+        let mut material = MaterialData::new(custom_shader());
+
+        assert!(matches!(
+            material.property("strength"),
+            Some(PropertyValue::Float(v)) if *v == 1.0
+        ));
+        assert!(material
+            .set_property("strength", PropertyValue::Float(2.0))
+            .is_ok());
+        assert!(matches!(
+            material.set_property("strength", PropertyValue::Color(Color::RED)),
+            Err(MaterialError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            material.set_property("foo", PropertyValue::Float(2.0)),
+            Err(MaterialError::NoSuchProperty(_))
+        ));
+
+        // Compatible values must survive shader change.
+        let mut shader = custom_shader();
+        shader.properties.truncate(1);
+        shader.properties.push(PropertyDefinition::new(
+            "strength",
+            PropertyValue::Color(Color::BLACK),
+        ));
+        material
+            .set_property("tint", PropertyValue::Color(Color::RED))
+            .unwrap();
+        material.set_shader(shader);
+        assert!(matches!(
+            material.property("tint"),
+            Some(PropertyValue::Color(c)) if *c == Color::RED
+        ));
+        assert!(matches!(
+            material.property("strength"),
+            Some(PropertyValue::Color(c)) if *c == Color::BLACK
+        ));
+    }
+
+    #[test]
+    fn material_visit() {
+        let file = TempFile::new();
+
+        let key = {
+            let mut material = MaterialData::new(custom_shader());
+            material
+                .set_property("tint", PropertyValue::Color(Color::opaque(10, 20, 30)))
+                .unwrap();
+            material.save(file.path()).unwrap();
+            material.shader().key()
+        };
+
+        let mut visitor = Visitor::load_binary(file.path()).unwrap();
+        let mut material = MaterialData::default();
+        material.visit("Material", &mut visitor).unwrap();
+
+        assert_eq!(material.shader().name(), "Custom");
+        assert_eq!(material.shader().key(), key);
+        assert_eq!(material.shader().properties().len(), 2);
+        assert!(matches!(
+            material.property("tint"),
+            Some(PropertyValue::Color(c)) if *c == Color::opaque(10, 20, 30)
+        ));
+    }
+}
@@ -15,6 +15,7 @@ use std::{
 };
 
 pub mod fbx;
+pub mod material;
 pub mod model;
 pub mod texture;
 
@@ -29,6 +29,7 @@ use crate::{
     utils::log::Log,
 };
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 /// See module docs.
@@ -45,9 +46,18 @@ impl Model {
     /// Tries to instantiate model from given resource. Does not retarget available
     /// animations from model to its instance. Can be helpful if you only need geometry.
     pub fn instantiate_geometry(&self, dest_scene: &mut Scene) -> Handle<Node> {
+        self.instantiate_geometry_with_mapping(dest_scene).0
+    }
+
+    /// Does the same as [`instantiate_geometry`](#method.instantiate_geometry) but also returns
+    /// a map which maps nodes of the resource to their copies in destination scene.
+    pub fn instantiate_geometry_with_mapping(
+        &self,
+        dest_scene: &mut Scene,
+    ) -> (Handle<Node>, HashMap<Handle<Node>, Handle<Node>>) {
         let data = self.data_ref();
 
-        let (root, _) = data.scene.graph.copy_node(
+        let (root, old_new_mapping) = data.scene.graph.copy_node(
             data.scene.graph.get_root(),
             &mut dest_scene.graph,
             &mut |_, _| true,
@@ -66,7 +76,7 @@ impl Model {
             stack.extend_from_slice(node.children());
         }
 
-        root
+        (root, old_new_mapping)
     }
 
     /// Tries to instantiate model from given resource.
@@ -14,7 +14,7 @@ use crate::{
         visitor::{Visit, VisitResult, Visitor},
     },
     resource::model::Model,
-    scene::{node::Node, transform::Transform},
+    scene::{constraint::Constraint, node::Node, transform::Transform},
 };
 use std::cell::Cell;
 
@@ -142,6 +142,7 @@ pub struct Base {
     pub(in crate) lifetime: Option<f32>,
     depth_offset: f32,
     lod_group: Option<LodGroup>,
+    constraints: Vec<Constraint>,
 }
 
 impl Base {
@@ -183,6 +184,9 @@ impl Base {
     /// system node and it will be removed from scene when time will end. This is
     /// efficient algorithm because scene holds every object in pool and allocation
     /// or deallocation of node takes very little amount of time.
+    ///
+    /// Node with expired lifetime is destroyed by the scene at the end of the frame,
+    /// and its handle is reported in `Scene::destroyed_nodes`.
     pub fn set_lifetime(&mut self, time_seconds: f32) -> &mut Self {
         self.lifetime = Some(time_seconds);
         self
@@ -313,6 +317,17 @@ impl Base {
         self.lod_group.as_mut()
     }
 
+    /// Returns shared reference to list of transform constraints of the node.
+    pub fn constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
+
+    /// Returns mutable reference to list of transform constraints of the node. Constraints
+    /// are evaluated in order, see `constraint` module docs for more info.
+    pub fn constraints_mut(&mut self) -> &mut Vec<Constraint> {
+        &mut self.constraints
+    }
+
     /// Shallow copy of node data. You should never use this directly, shallow copy
     /// will produce invalid node in most cases!
     pub fn raw_copy(&self) -> Self {
@@ -326,6 +341,7 @@ impl Base {
             resource: self.resource.clone(),
             is_resource_instance: self.is_resource_instance,
             lifetime: self.lifetime,
+            constraints: self.constraints.clone(),
             // Rest of data is *not* copied!
             ..Default::default()
         }
@@ -353,6 +369,7 @@ impl Visit for Base {
         self.lifetime.visit("Lifetime", visitor)?;
         self.depth_offset.visit("DepthOffset", visitor)?;
         let _ = self.lod_group.visit("LodGroup", visitor);
+        let _ = self.constraints.visit("Constraints", visitor);
 
         visitor.leave_region()
     }
@@ -367,6 +384,7 @@ pub struct BaseBuilder {
     lifetime: Option<f32>,
     depth_offset: f32,
     lod_group: Option<LodGroup>,
+    constraints: Vec<Constraint>,
 }
 
 impl Default for BaseBuilder {
@@ -386,6 +404,7 @@ impl BaseBuilder {
             lifetime: None,
             depth_offset: 0.0,
             lod_group: None,
+            constraints: Default::default(),
         }
     }
 
@@ -431,6 +450,12 @@ impl BaseBuilder {
         self
     }
 
+    /// Sets desired list of transform constraints.
+    pub fn with_constraints(mut self, constraints: Vec<Constraint>) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
     /// Creates new instance of base scene node. Do not forget to add
     /// node to scene or pass to other nodes as base.
     pub fn build(self) -> Base {
@@ -449,6 +474,7 @@ impl BaseBuilder {
             is_resource_instance: false,
             depth_offset: self.depth_offset,
             lod_group: self.lod_group,
+            constraints: self.constraints,
         }
     }
 
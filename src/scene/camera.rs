@@ -1,7 +1,7 @@
 //! Contains all methods and structures to create and manage cameras.
 //!
-//! Camera allows you to see world from specific point in world. Currently only
-//! perspective projection is supported.
+//! Camera allows you to see world from specific point in world. Camera supports
+//! perspective and orthographic projections, see [`Projection`](enum.Projection.html).
 //!
 //! # Multiple cameras
 //!
@@ -20,7 +20,7 @@ use crate::{
         math::{ray::Ray, Rect},
         visitor::{Visit, VisitResult, Visitor},
     },
-    resource::texture::Texture,
+    resource::{material::Material, texture::Texture},
     scene::{
         base::{Base, BaseBuilder},
         node::Node,
@@ -30,11 +30,360 @@ use crate::{
 use rapier3d::na::Point3;
 use std::ops::{Deref, DerefMut};
 
+/// Defines the way how camera will project 3D world on screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Objects farther from camera look smaller, field of view defines how much of
+    /// the world is visible. Typical projection for first and third person games.
+    Perspective,
+    /// Objects have same size regardless of distance to camera, vertical size defines
+    /// height of visible area in world units. Useful for top-down strategy views, 2D
+    /// in 3D world, etc. Skybox is meaningless in this mode.
+    Orthographic,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self::Perspective
+    }
+}
+
+impl Projection {
+    fn id(self) -> u32 {
+        match self {
+            Self::Perspective => 0,
+            Self::Orthographic => 1,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::Perspective),
+            1 => Ok(Self::Orthographic),
+            _ => Err(format!("Invalid projection id {}", id)),
+        }
+    }
+}
+
+impl Visit for Projection {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id = self.id();
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Defines how bright lit areas of the HDR frame will be before tone mapping.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Exposure {
+    /// Exposure is computed from average luminance of the frame, so eye adapts to
+    /// brightness of the scene as time goes.
+    Auto {
+        /// Defines brightness of "middle gray" of the frame, larger values gives
+        /// brighter image.
+        key_value: f32,
+        /// Lower bound of average luminance, prevents overexposure of dark scenes.
+        min_luminance: f32,
+        /// Upper bound of average luminance, prevents underexposure of bright scenes.
+        max_luminance: f32,
+        /// Defines how fast eye adapts to changes of brightness, larger values gives
+        /// faster adaptation.
+        adaptation_speed: f32,
+    },
+    /// Fixed exposure, HDR color of the frame is just multiplied by given value.
+    Manual(f32),
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Self::Auto {
+            key_value: 0.18,
+            min_luminance: 0.05,
+            max_luminance: 16.0,
+            adaptation_speed: 1.5,
+        }
+    }
+}
+
+impl Exposure {
+    fn id(self) -> u32 {
+        match self {
+            Self::Auto { .. } => 0,
+            Self::Manual(_) => 1,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::default()),
+            1 => Ok(Self::Manual(1.0)),
+            _ => Err(format!("Invalid exposure id {}", id)),
+        }
+    }
+}
+
+impl Visit for Exposure {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        match self {
+            Self::Auto {
+                key_value,
+                min_luminance,
+                max_luminance,
+                adaptation_speed,
+            } => {
+                key_value.visit("KeyValue", visitor)?;
+                min_luminance.visit("MinLuminance", visitor)?;
+                max_luminance.visit("MaxLuminance", visitor)?;
+                adaptation_speed.visit("AdaptationSpeed", visitor)?;
+            }
+            Self::Manual(exposure) => exposure.visit("Exposure", visitor)?,
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Operator that maps HDR colors of the frame to displayable [0; 1] range.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMapping {
+    /// Colors are just clamped, everything brighter than 1.0 will be white.
+    None,
+    /// Simple `c / (1 + c)` operator, gives soft but a bit washed out image.
+    Reinhard,
+    /// Approximation of ACES filmic curve by Krzysztof Narkowicz, gives contrast
+    /// "cinematic" image.
+    AcesFilmic,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self::AcesFilmic
+    }
+}
+
+impl ToneMapping {
+    fn id(self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Reinhard => 1,
+            Self::AcesFilmic => 2,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Reinhard),
+            2 => Ok(Self::AcesFilmic),
+            _ => Err(format!("Invalid tone mapping id {}", id)),
+        }
+    }
+}
+
+impl Visit for ToneMapping {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id = self.id();
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Bloom makes bright areas of the frame "glow" - light bleeds into surrounding pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bloom {
+    /// Only parts of HDR frame brighter than the threshold will glow.
+    pub threshold: f32,
+    /// Defines how much of the glow will be added to the frame.
+    pub intensity: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.25,
+        }
+    }
+}
+
+impl Visit for Bloom {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.threshold.visit("Threshold", visitor)?;
+        self.intensity.visit("Intensity", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Depth of field blurs objects which are out of focus of the camera.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DepthOfField {
+    /// Distance from camera to the plane of focus.
+    pub focal_distance: f32,
+    /// Objects closer than this distance to the plane of focus are sharp, blur reaches its
+    /// maximum at distance of twice of the range.
+    pub focal_range: f32,
+    /// Maximum radius of blur in pixels.
+    pub max_blur: f32,
+}
+
+impl Default for DepthOfField {
+    fn default() -> Self {
+        Self {
+            focal_distance: 10.0,
+            focal_range: 5.0,
+            max_blur: 8.0,
+        }
+    }
+}
+
+impl Visit for DepthOfField {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.focal_distance.visit("FocalDistance", visitor)?;
+        self.focal_range.visit("FocalRange", visitor)?;
+        self.max_blur.visit("MaxBlur", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Chromatic aberration splits color channels near the edges of the frame, as a cheap lens
+/// would do.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChromaticAberration {
+    /// Offset of red and blue channels at the edges of the frame in fractions of the frame
+    /// size.
+    pub strength: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self { strength: 0.004 }
+    }
+}
+
+impl Visit for ChromaticAberration {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.strength.visit("Strength", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Vignette darkens corners of the frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vignette {
+    /// Darkness of the corners in [0; 1] range.
+    pub intensity: f32,
+    /// Distance from center of the frame (1.0 - corner) where darkening starts.
+    pub radius: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.4,
+            radius: 0.6,
+        }
+    }
+}
+
+impl Visit for Vignette {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.intensity.visit("Intensity", visitor)?;
+        self.radius.visit("Radius", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Post effects of a camera, they're applied to the frame in following order: depth of
+/// field and bloom (in HDR), tone mapping, FXAA, color grading, chromatic aberration and
+/// vignette, and finally custom passes. Each effect can also be disabled globally by
+/// quality settings of the renderer.
+#[derive(Clone, Debug)]
+pub struct PostEffects {
+    /// Bloom settings, `None` - disabled.
+    pub bloom: Option<Bloom>,
+    /// Depth of field settings, `None` - disabled.
+    pub depth_of_field: Option<DepthOfField>,
+    /// Color grading lookup table. It is a 2D texture with all slices of 3D table placed
+    /// horizontally, so a table of size N must have N*N x N size. Red grows along X axis
+    /// of a slice, green along Y axis, blue grows from one slice to another.
+    pub color_grading_lut: Option<Texture>,
+    /// Chromatic aberration settings, `None` - disabled.
+    pub chromatic_aberration: Option<ChromaticAberration>,
+    /// Vignette settings, `None` - disabled.
+    pub vignette: Option<Vignette>,
+    /// Use fast approximate anti-aliasing or not.
+    pub fxaa: bool,
+    /// Custom fullscreen passes, see module docs of materials for details.
+    pub custom_passes: Vec<Material>,
+}
+
+impl Default for PostEffects {
+    fn default() -> Self {
+        Self {
+            bloom: None,
+            depth_of_field: None,
+            color_grading_lut: None,
+            chromatic_aberration: None,
+            vignette: None,
+            fxaa: true,
+            custom_passes: Default::default(),
+        }
+    }
+}
+
+impl Visit for PostEffects {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        if visitor.is_reading() {
+            self.custom_passes.clear();
+        }
+
+        self.bloom.visit("Bloom", visitor)?;
+        self.depth_of_field.visit("DepthOfField", visitor)?;
+        self.color_grading_lut.visit("ColorGradingLut", visitor)?;
+        self.chromatic_aberration
+            .visit("ChromaticAberration", visitor)?;
+        self.vignette.visit("Vignette", visitor)?;
+        self.fxaa.visit("Fxaa", visitor)?;
+        self.custom_passes.visit("CustomPasses", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
 /// See module docs.
 #[derive(Debug)]
 pub struct Camera {
     base: Base,
+    projection: Projection,
     fov: f32,
+    vertical_size: f32,
     z_near: f32,
     z_far: f32,
     viewport: Rect<f32>,
@@ -43,6 +392,9 @@ pub struct Camera {
     enabled: bool,
     skybox: Option<SkyBox>,
     environment: Option<Texture>,
+    exposure: Exposure,
+    tone_mapping: ToneMapping,
+    post_effects: PostEffects,
     /// Visibility cache allows you to quickly check if object is visible from the camera or not.
     pub visibility_cache: VisibilityCache,
 }
@@ -78,6 +430,11 @@ impl Visit for Camera {
         self.enabled.visit("Enabled", visitor)?;
         let _ = self.skybox.visit("SkyBox", visitor);
         let _ = self.environment.visit("Environment", visitor);
+        let _ = self.projection.visit("Projection", visitor);
+        let _ = self.vertical_size.visit("VerticalSize", visitor);
+        let _ = self.exposure.visit("Exposure", visitor);
+        let _ = self.tone_mapping.visit("ToneMapping", visitor);
+        let _ = self.post_effects.visit("PostEffects", visitor);
         // self.visibility_cache intentionally not serialized. It is valid only for one frame.
         visitor.leave_region()
     }
@@ -96,8 +453,23 @@ impl Camera {
 
         let viewport = self.viewport_pixels(frame_size);
         let aspect = viewport.w() as f32 / viewport.h() as f32;
-        self.projection_matrix =
-            Matrix4::new_perspective(aspect, self.fov, self.z_near, self.z_far);
+        self.projection_matrix = match self.projection {
+            Projection::Perspective => {
+                Matrix4::new_perspective(aspect, self.fov, self.z_near, self.z_far)
+            }
+            Projection::Orthographic => {
+                let half_height = self.vertical_size * 0.5;
+                let half_width = half_height * aspect;
+                Matrix4::new_orthographic(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.z_near,
+                    self.z_far,
+                )
+            }
+        };
     }
 
     /// Sets new viewport in resolution-independent format. In other words
@@ -187,6 +559,33 @@ impl Camera {
         self.fov
     }
 
+    /// Sets new projection mode.
+    #[inline]
+    pub fn set_projection(&mut self, projection: Projection) -> &mut Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Returns current projection mode.
+    #[inline]
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Sets height of visible area in world units. Used only in orthographic mode, width
+    /// of visible area is defined by aspect ratio of viewport.
+    #[inline]
+    pub fn set_vertical_size(&mut self, vertical_size: f32) -> &mut Self {
+        self.vertical_size = vertical_size;
+        self
+    }
+
+    /// Returns height of visible area in world units in orthographic mode.
+    #[inline]
+    pub fn vertical_size(&self) -> f32 {
+        self.vertical_size
+    }
+
     /// Returns state of camera: enabled or not.
     #[inline]
     pub fn is_enabled(&self) -> bool {
@@ -239,7 +638,47 @@ impl Camera {
         self.environment.clone()
     }
 
-    /// Creates picking ray from given screen coordinates.
+    /// Sets new exposure, see [`Exposure`](enum.Exposure.html) for details.
+    pub fn set_exposure(&mut self, exposure: Exposure) -> &mut Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Returns current exposure.
+    pub fn exposure(&self) -> Exposure {
+        self.exposure
+    }
+
+    /// Sets new tone mapping operator which will be used to convert HDR frame to LDR.
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) -> &mut Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Returns current tone mapping operator.
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    /// Sets new post effects of the camera.
+    pub fn set_post_effects(&mut self, post_effects: PostEffects) -> &mut Self {
+        self.post_effects = post_effects;
+        self
+    }
+
+    /// Returns shared reference to post effects of the camera.
+    pub fn post_effects(&self) -> &PostEffects {
+        &self.post_effects
+    }
+
+    /// Returns mutable reference to post effects of the camera.
+    pub fn post_effects_mut(&mut self) -> &mut PostEffects {
+        &mut self.post_effects
+    }
+
+    /// Creates picking ray from given screen coordinates. Rays of perspective camera starts
+    /// from near plane and go through a single point, rays of orthographic camera are
+    /// parallel to look vector of the camera.
     pub fn make_ray(&self, screen_coord: Vector2<f32>, screen_size: Vector2<f32>) -> Ray {
         let viewport = self.viewport_pixels(screen_size);
         let nx = screen_coord.x / (viewport.w() as f32) * 2.0 - 1.0;
@@ -279,7 +718,9 @@ impl Camera {
     pub fn raw_copy(&self) -> Self {
         Self {
             base: self.base.raw_copy(),
+            projection: self.projection,
             fov: self.fov,
+            vertical_size: self.vertical_size,
             z_near: self.z_near,
             z_far: self.z_far,
             viewport: self.viewport,
@@ -288,6 +729,9 @@ impl Camera {
             enabled: self.enabled,
             skybox: self.skybox.clone(),
             environment: self.environment.clone(),
+            exposure: self.exposure,
+            tone_mapping: self.tone_mapping,
+            post_effects: self.post_effects.clone(),
             // No need to copy cache. It is valid only for one frame.
             visibility_cache: Default::default(),
         }
@@ -298,13 +742,18 @@ impl Camera {
 /// This is typical implementation of Builder pattern.
 pub struct CameraBuilder {
     base_builder: BaseBuilder,
+    projection: Projection,
     fov: f32,
+    vertical_size: f32,
     z_near: f32,
     z_far: f32,
     viewport: Rect<f32>,
     enabled: bool,
     skybox: Option<SkyBox>,
     environment: Option<Texture>,
+    exposure: Exposure,
+    tone_mapping: ToneMapping,
+    post_effects: PostEffects,
 }
 
 impl CameraBuilder {
@@ -313,12 +762,17 @@ impl CameraBuilder {
         Self {
             enabled: true,
             base_builder,
+            projection: Projection::Perspective,
             fov: 75.0f32.to_radians(),
+            vertical_size: 10.0,
             z_near: 0.025,
             z_far: 2048.0,
             viewport: Rect::new(0.0, 0.0, 1.0, 1.0),
             skybox: None,
             environment: None,
+            exposure: Default::default(),
+            tone_mapping: Default::default(),
+            post_effects: Default::default(),
         }
     }
 
@@ -328,6 +782,18 @@ impl CameraBuilder {
         self
     }
 
+    /// Sets desired projection mode.
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Sets desired height of visible area in world units for orthographic projection.
+    pub fn with_vertical_size(mut self, vertical_size: f32) -> Self {
+        self.vertical_size = vertical_size;
+        self
+    }
+
     /// Sets desired near projection plane.
     pub fn with_z_near(mut self, z_near: f32) -> Self {
         self.z_near = z_near;
@@ -364,13 +830,33 @@ impl CameraBuilder {
         self
     }
 
+    /// Sets desired exposure.
+    pub fn with_exposure(mut self, exposure: Exposure) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Sets desired tone mapping operator.
+    pub fn with_tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Sets desired post effects.
+    pub fn with_post_effects(mut self, post_effects: PostEffects) -> Self {
+        self.post_effects = post_effects;
+        self
+    }
+
     /// Creates new instance of camera node. Do not forget to add node to scene,
     /// otherwise it is useless.
     pub fn build(self) -> Camera {
         Camera {
             enabled: self.enabled,
             base: self.base_builder.build(),
+            projection: self.projection,
             fov: self.fov,
+            vertical_size: self.vertical_size,
             z_near: self.z_near,
             z_far: self.z_far,
             viewport: self.viewport,
@@ -381,6 +867,9 @@ impl CameraBuilder {
             visibility_cache: Default::default(),
             skybox: self.skybox,
             environment: self.environment,
+            exposure: self.exposure,
+            tone_mapping: self.tone_mapping,
+            post_effects: self.post_effects,
         }
     }
 
@@ -440,3 +929,61 @@ impl Visit for SkyBox {
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::{Vector2, Vector3},
+        scene::{
+            base::BaseBuilder,
+            camera::{Camera, CameraBuilder, Exposure, Projection, ToneMapping},
+        },
+        utils::testing::visit_round_trip,
+    };
+
+    #[test]
+    fn orthographic_projection() {
+        let mut camera = CameraBuilder::new(BaseBuilder::new())
+            .with_projection(Projection::Orthographic)
+            .with_vertical_size(4.0)
+            .build();
+        let screen_size = Vector2::new(200.0, 100.0);
+        camera.calculate_matrices(screen_size);
+
+        // Visible area is 8x4 units, so upper right corner of the screen is at (-4; 2), because
+        // camera looks along +Z axis.
+        let screen_pos = camera
+            .project(Vector3::new(-4.0, 2.0, 10.0), screen_size)
+            .unwrap();
+        assert!((screen_pos - Vector2::new(200.0, 0.0)).norm() < 0.001);
+
+        // All rays must be parallel to the look vector.
+        for &screen_pos in &[Vector2::new(0.0, 0.0), Vector2::new(150.0, 75.0)] {
+            let ray = camera.make_ray(screen_pos, screen_size);
+            assert!((ray.dir.normalize() - Vector3::z()).norm() < 0.001);
+        }
+        let ray = camera.make_ray(Vector2::new(200.0, 0.0), screen_size);
+        assert!((ray.origin.xy() - Vector2::new(-4.0, 2.0)).norm() < 0.001);
+    }
+
+    #[test]
+    fn exposure_and_tone_mapping_visit() {
+        let exposure = Exposure::Auto {
+            key_value: 0.3,
+            min_luminance: 0.1,
+            max_luminance: 8.0,
+            adaptation_speed: 2.0,
+        };
+
+        let mut source = CameraBuilder::new(BaseBuilder::new())
+            .with_exposure(exposure)
+            .with_tone_mapping(ToneMapping::Reinhard)
+            .build();
+        let mut camera = Camera::default();
+        camera.set_exposure(Exposure::Manual(2.0));
+        visit_round_trip("Camera", &mut source, &mut camera);
+
+        assert_eq!(camera.exposure(), exposure);
+        assert_eq!(camera.tone_mapping(), ToneMapping::Reinhard);
+    }
+}
@@ -0,0 +1,811 @@
+//! Contains all structures and methods to create and manage undoable scene edits.
+//!
+//! Every modification of a scene that must be undoable is wrapped into a command. A command
+//! knows how to apply itself to a scene and how to revert changes it did. Commands are executed
+//! through a [`CommandStack`](struct.CommandStack.html) which keeps history of changes and
+//! allows to undo and redo them.
+//!
+//! # Handles
+//!
+//! Commands never invalidate handles of nodes they touch: when a node is "removed" by a command
+//! its handle is only reserved, and when the command is reverted the node is put back at the very
+//! same handle. This means that any other command in the stack can safely keep handles to nodes.
+//! Reserved handles are released when a command is dropped from the stack forever, see
+//! [`Command::finalize`](trait.Command.html#method.finalize).
+//!
+//! # Example
+//!
+//! ```
+//! use rg3d::scene::{
+//!     Scene,
+//!     base::BaseBuilder,
+//!     command::{AddNodeCommand, CommandStack},
+//! };
+//!
+//! let mut scene = Scene::new();
+//! let mut stack = CommandStack::new();
+//!
+//! stack.do_command(Box::new(AddNodeCommand::new(BaseBuilder::new().build_node())), &mut scene);
+//! assert_eq!(scene.graph.linear_iter().count(), 2);
+//!
+//! stack.undo(&mut scene);
+//! assert_eq!(scene.graph.linear_iter().count(), 1);
+//!
+//! stack.redo(&mut scene);
+//! assert_eq!(scene.graph.linear_iter().count(), 2);
+//! ```
+
+use crate::{
+    animation::Animation,
+    core::pool::{Handle, Ticket},
+    physics::{dynamics::RigidBody, geometry::Collider},
+    scene::{graph::SubGraph, node::Node, transform::Transform, Scene},
+};
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+};
+
+/// Command is a single undoable modification of a scene.
+pub trait Command: Any {
+    /// Returns human-readable name of the command, it can be used to show history of changes
+    /// in an editor.
+    fn name(&self) -> String;
+
+    /// Applies the command to the scene. Will be called again on redo.
+    fn execute(&mut self, scene: &mut Scene);
+
+    /// Reverts every change that was done by `execute`.
+    fn revert(&mut self, scene: &mut Scene);
+
+    /// Called when the command is dropped from the command stack forever, it is the last chance
+    /// to release resources held by the command (for example reserved handles of nodes).
+    fn finalize(&mut self, _scene: &mut Scene) {}
+
+    /// Tries to merge other, already executed, command into self. Should return `true` if merge
+    /// was successful, in this case other command will be discarded and self will represent
+    /// both changes. This is used to collapse series of small changes (like dragging of a node)
+    /// into a single entry in history.
+    fn try_merge(&mut self, _other: &dyn Command) -> bool {
+        false
+    }
+
+    /// Returns self as `Any`, it is used to downcast commands when merging.
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Adds a node (with all its descendants) to a scene.
+#[derive(Debug)]
+pub struct AddNodeCommand {
+    handle: Handle<Node>,
+    parent: Handle<Node>,
+    node: Option<Node>,
+    sub_graph: Option<SubGraph>,
+}
+
+impl AddNodeCommand {
+    /// Creates new command that will add given node to the root of the graph.
+    pub fn new(node: Node) -> Self {
+        Self {
+            handle: Handle::NONE,
+            parent: Handle::NONE,
+            node: Some(node),
+            sub_graph: None,
+        }
+    }
+
+    /// Sets desired parent of the node. If parent is not set, node will be attached to the
+    /// root of the graph.
+    pub fn with_parent(mut self, parent: Handle<Node>) -> Self {
+        self.parent = parent;
+        self
+    }
+
+    /// Returns handle of added node. It will be [`Handle::NONE`] until the command is executed
+    /// for the first time, after that it remains the same across undo/redo.
+    pub fn handle(&self) -> Handle<Node> {
+        self.handle
+    }
+}
+
+impl Command for AddNodeCommand {
+    fn name(&self) -> String {
+        "Add Node".to_owned()
+    }
+
+    fn execute(&mut self, scene: &mut Scene) {
+        if let Some(node) = self.node.take() {
+            self.handle = scene.graph.add_node(node);
+        } else if let Some(sub_graph) = self.sub_graph.take() {
+            self.handle = scene.graph.put_sub_graph_back(sub_graph);
+        }
+        if self.parent.is_some() {
+            scene.graph.link_nodes(self.handle, self.parent);
+        }
+    }
+
+    fn revert(&mut self, scene: &mut Scene) {
+        self.parent = scene.graph[self.handle].parent();
+        self.sub_graph = Some(scene.graph.take_reserve_sub_graph(self.handle));
+    }
+
+    fn finalize(&mut self, scene: &mut Scene) {
+        if let Some(sub_graph) = self.sub_graph.take() {
+            scene.graph.forget_sub_graph(sub_graph);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+struct RemovedBody {
+    node: Handle<Node>,
+    body: RigidBody,
+    colliders: Vec<Collider>,
+}
+
+impl Debug for RemovedBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "RemovedBody {:?}", self.node)
+    }
+}
+
+/// Removes a node with all its descendants from a scene. Rigid bodies bound to removed nodes
+/// are removed from the physics world too and animations that are animating removed nodes
+/// are taken out from the scene, everything is restored on revert.
+///
+/// # Notes
+///
+/// Joints attached to removed rigid bodies are *not* restored.
+#[derive(Debug)]
+pub struct RemoveNodeCommand {
+    handle: Handle<Node>,
+    parent: Handle<Node>,
+    sub_graph: Option<SubGraph>,
+    bodies: Vec<RemovedBody>,
+    animations: Vec<(Ticket<Animation>, Animation)>,
+}
+
+impl RemoveNodeCommand {
+    /// Creates new command that will remove given node.
+    pub fn new(handle: Handle<Node>) -> Self {
+        Self {
+            handle,
+            parent: Handle::NONE,
+            sub_graph: None,
+            bodies: Default::default(),
+            animations: Default::default(),
+        }
+    }
+}
+
+impl Command for RemoveNodeCommand {
+    fn name(&self) -> String {
+        "Remove Node".to_owned()
+    }
+
+    fn execute(&mut self, scene: &mut Scene) {
+        self.parent = scene.graph[self.handle].parent();
+
+        let descendants = scene
+            .graph
+            .traverse_handle_iter(self.handle)
+            .collect::<Vec<_>>();
+
+        for &descendant in descendants.iter() {
+            if let Some(body_handle) = scene.physics_binder.unbind(descendant) {
+                let physics = &mut scene.physics;
+                let colliders = physics
+                    .bodies
+                    .get(body_handle.into())
+                    .map(|body| {
+                        body.colliders()
+                            .iter()
+                            .filter_map(|&c| physics.colliders.get(c).cloned())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                if let Some(body) = physics.remove_body(body_handle) {
+                    self.bodies.push(RemovedBody {
+                        node: descendant,
+                        body,
+                        colliders,
+                    });
+                }
+            }
+        }
+
+        let animations = scene
+            .animations
+            .pair_iter()
+            .filter(|(_, animation)| {
+                animation
+                    .get_tracks()
+                    .iter()
+                    .any(|track| descendants.contains(&track.get_node()))
+            })
+            .map(|(handle, _)| handle)
+            .collect::<Vec<_>>();
+        for animation in animations {
+            self.animations
+                .push(scene.animations.take_reserve(animation));
+        }
+
+        self.sub_graph = Some(scene.graph.take_reserve_sub_graph(self.handle));
+    }
+
+    fn revert(&mut self, scene: &mut Scene) {
+        if let Some(sub_graph) = self.sub_graph.take() {
+            self.handle = scene.graph.put_sub_graph_back(sub_graph);
+            if self.parent.is_some() {
+                scene.graph.link_nodes(self.handle, self.parent);
+            }
+        }
+
+        for removed in self.bodies.drain(..) {
+            let body = scene.physics.add_body(removed.body);
+            for collider in removed.colliders {
+                scene.physics.add_collider(collider, body);
+            }
+            scene.physics_binder.bind(removed.node, body);
+        }
+
+        for (ticket, animation) in self.animations.drain(..) {
+            scene.animations.put_back(ticket, animation);
+        }
+    }
+
+    fn finalize(&mut self, scene: &mut Scene) {
+        if let Some(sub_graph) = self.sub_graph.take() {
+            scene.graph.forget_sub_graph(sub_graph);
+        }
+        for (ticket, _) in self.animations.drain(..) {
+            scene.animations.forget_ticket(ticket);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Attaches a node to a new parent. Local transform of the node is left untouched.
+#[derive(Debug)]
+pub struct LinkNodesCommand {
+    child: Handle<Node>,
+    parent: Handle<Node>,
+}
+
+impl LinkNodesCommand {
+    /// Creates new command that will attach `child` to `parent`.
+    pub fn new(child: Handle<Node>, parent: Handle<Node>) -> Self {
+        Self { child, parent }
+    }
+
+    fn swap(&mut self, scene: &mut Scene) {
+        let old_parent = scene.graph[self.child].parent();
+        scene.graph.link_nodes(self.child, self.parent);
+        self.parent = old_parent;
+    }
+}
+
+impl Command for LinkNodesCommand {
+    fn name(&self) -> String {
+        "Link Nodes".to_owned()
+    }
+
+    fn execute(&mut self, scene: &mut Scene) {
+        self.swap(scene);
+    }
+
+    fn revert(&mut self, scene: &mut Scene) {
+        self.swap(scene);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Sets new local transform of a node. Consecutive transform commands for the same node
+/// are merged together, so dragging a node with a mouse produces single entry in history.
+#[derive(Debug)]
+pub struct SetTransformCommand {
+    node: Handle<Node>,
+    transform: Transform,
+}
+
+impl SetTransformCommand {
+    /// Creates new command that will set given local transform to a node.
+    pub fn new(node: Handle<Node>, transform: Transform) -> Self {
+        Self { node, transform }
+    }
+
+    fn swap(&mut self, scene: &mut Scene) {
+        let node = &mut scene.graph[self.node];
+        let old = node.local_transform().clone();
+        node.set_local_transform(std::mem::replace(&mut self.transform, old));
+    }
+}
+
+impl Command for SetTransformCommand {
+    fn name(&self) -> String {
+        "Set Transform".to_owned()
+    }
+
+    fn execute(&mut self, scene: &mut Scene) {
+        self.swap(scene);
+    }
+
+    fn revert(&mut self, scene: &mut Scene) {
+        self.swap(scene);
+    }
+
+    fn try_merge(&mut self, other: &dyn Command) -> bool {
+        // Self already holds the transform before first change, so to merge we only need to
+        // ensure that other command modifies the same node.
+        other
+            .as_any()
+            .downcast_ref::<Self>()
+            .map_or(false, |other| other.node == self.node)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Sets an arbitrary property of a node using given setter. Setter must assign new value and
+/// return previous value of the property, this allows to use the same setter for both execute
+/// and revert.
+///
+/// # Example
+///
+/// ```
+/// use rg3d::scene::{command::SetPropertyCommand, node::Node};
+/// use rg3d::core::pool::Handle;
+///
+/// fn set_visibility(node: Handle<Node>, visibility: bool) -> SetPropertyCommand<bool> {
+///     SetPropertyCommand::new("Set Visibility", node, visibility, |node, value| {
+///         let old = node.visibility();
+///         node.set_visibility(value);
+///         old
+///     })
+/// }
+/// ```
+pub struct SetPropertyCommand<T> {
+    name: String,
+    node: Handle<Node>,
+    value: T,
+    setter: fn(&mut Node, T) -> T,
+}
+
+impl<T: Debug> Debug for SetPropertyCommand<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} {:?} {:?}", self.name, self.node, self.value)
+    }
+}
+
+impl<T: Clone> SetPropertyCommand<T> {
+    /// Creates new command that will set given value to a property of a node using setter.
+    pub fn new<N: AsRef<str>>(
+        name: N,
+        node: Handle<Node>,
+        value: T,
+        setter: fn(&mut Node, T) -> T,
+    ) -> Self {
+        Self {
+            name: name.as_ref().to_owned(),
+            node,
+            value,
+            setter,
+        }
+    }
+
+    fn swap(&mut self, scene: &mut Scene) {
+        self.value = (self.setter)(&mut scene.graph[self.node], self.value.clone());
+    }
+}
+
+impl<T: Clone + 'static> Command for SetPropertyCommand<T> {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn execute(&mut self, scene: &mut Scene) {
+        self.swap(scene);
+    }
+
+    fn revert(&mut self, scene: &mut Scene) {
+        self.swap(scene);
+    }
+
+    fn try_merge(&mut self, other: &dyn Command) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<Self>()
+            .map_or(false, |other| other.node == self.node && other.name == self.name)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Group of commands that are executed and reverted as a single command. Commands are
+/// executed in order of addition, and reverted in reverse order.
+pub struct CommandGroup {
+    name: String,
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl CommandGroup {
+    /// Creates new empty group with given name.
+    pub fn new<N: AsRef<str>>(name: N) -> Self {
+        Self {
+            name: name.as_ref().to_owned(),
+            commands: Default::default(),
+        }
+    }
+
+    /// Adds new command to the group. Command will be executed only when the group is executed.
+    pub fn with_command(mut self, command: Box<dyn Command>) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Returns true if group has no commands.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Adds already executed command to the group, tries to merge it with last command first.
+    fn push_executed(&mut self, scene: &mut Scene, mut command: Box<dyn Command>) {
+        if let Some(last) = self.commands.last_mut() {
+            if last.try_merge(&*command) {
+                command.finalize(scene);
+                return;
+            }
+        }
+        self.commands.push(command);
+    }
+}
+
+impl Command for CommandGroup {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn execute(&mut self, scene: &mut Scene) {
+        for command in self.commands.iter_mut() {
+            command.execute(scene);
+        }
+    }
+
+    fn revert(&mut self, scene: &mut Scene) {
+        for command in self.commands.iter_mut().rev() {
+            command.revert(scene);
+        }
+    }
+
+    fn finalize(&mut self, scene: &mut Scene) {
+        for mut command in self.commands.drain(..) {
+            command.finalize(scene);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Command stack keeps history of commands applied to a scene and allows to undo and
+/// redo them. See module docs for more info.
+pub struct CommandStack {
+    commands: Vec<Box<dyn Command>>,
+    /// Amount of executed commands, every command at index >= top is reverted and will
+    /// be discarded as soon as new command is done.
+    top: usize,
+    transaction: Option<CommandGroup>,
+    sealed: bool,
+}
+
+impl Default for CommandStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandStack {
+    /// Creates new empty command stack.
+    pub fn new() -> Self {
+        Self {
+            commands: Default::default(),
+            top: 0,
+            transaction: None,
+            sealed: false,
+        }
+    }
+
+    /// Executes given command and puts it on top of the stack. Every previously undone command
+    /// will be discarded. If a transaction is in progress, command will become part of the
+    /// transaction. Command will be merged with the command on top of the stack, if possible.
+    pub fn do_command(&mut self, mut command: Box<dyn Command>, scene: &mut Scene) {
+        command.execute(scene);
+
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.push_executed(scene, command);
+            return;
+        }
+
+        self.discard_reverted(scene);
+
+        if !self.sealed {
+            if let Some(top) = self.commands.last_mut() {
+                if top.try_merge(&*command) {
+                    command.finalize(scene);
+                    return;
+                }
+            }
+        }
+
+        self.commands.push(command);
+        self.top = self.commands.len();
+        self.sealed = false;
+    }
+
+    /// Prevents next command from being merged with the command on top of the stack. For
+    /// example it should be called when user releases mouse button after dragging a node, so
+    /// next drag will have its own entry in history.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Begins new transaction, every command done until [`commit_transaction`] or
+    /// [`rollback_transaction`] will be grouped together and undone/redone at once.
+    /// Nested transactions are not supported, if a transaction already in progress
+    /// then this method does nothing.
+    ///
+    /// [`commit_transaction`]: struct.CommandStack.html#method.commit_transaction
+    /// [`rollback_transaction`]: struct.CommandStack.html#method.rollback_transaction
+    pub fn begin_transaction<N: AsRef<str>>(&mut self, name: N) {
+        if self.transaction.is_none() {
+            self.transaction = Some(CommandGroup::new(name));
+        }
+    }
+
+    /// Returns true if there is a transaction in progress.
+    pub fn is_in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Finishes current transaction and puts it on top of the stack as a single command.
+    /// Empty transactions are discarded.
+    pub fn commit_transaction(&mut self, scene: &mut Scene) {
+        if let Some(transaction) = self.transaction.take() {
+            if !transaction.is_empty() {
+                self.discard_reverted(scene);
+                self.commands.push(Box::new(transaction));
+                self.top = self.commands.len();
+                self.sealed = true;
+            }
+        }
+    }
+
+    /// Reverts every command done in current transaction and discards them.
+    pub fn rollback_transaction(&mut self, scene: &mut Scene) {
+        if let Some(mut transaction) = self.transaction.take() {
+            transaction.revert(scene);
+            transaction.finalize(scene);
+        }
+    }
+
+    /// Reverts command on top of the stack. Returns false if there is nothing to undo.
+    /// Transaction in progress will be committed first.
+    pub fn undo(&mut self, scene: &mut Scene) -> bool {
+        self.commit_transaction(scene);
+        self.sealed = true;
+        if self.top > 0 {
+            self.top -= 1;
+            self.commands[self.top].revert(scene);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Executes previously reverted command. Returns false if there is nothing to redo.
+    pub fn redo(&mut self, scene: &mut Scene) -> bool {
+        if self.transaction.is_some() {
+            return false;
+        }
+        self.sealed = true;
+        if self.top < self.commands.len() {
+            self.commands[self.top].execute(scene);
+            self.top += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if there is a command to undo.
+    pub fn can_undo(&self) -> bool {
+        self.top > 0 || self.transaction.as_ref().map_or(false, |t| !t.is_empty())
+    }
+
+    /// Returns true if there is a command to redo.
+    pub fn can_redo(&self) -> bool {
+        self.transaction.is_none() && self.top < self.commands.len()
+    }
+
+    /// Returns name of the command that will be reverted on next undo.
+    pub fn undo_name(&self) -> Option<String> {
+        self.top.checked_sub(1).map(|i| self.commands[i].name())
+    }
+
+    /// Returns name of the command that will be executed on next redo.
+    pub fn redo_name(&self) -> Option<String> {
+        self.commands.get(self.top).map(|c| c.name())
+    }
+
+    /// Returns last done command downcasted to given type. If a transaction is in progress,
+    /// last command of the transaction is returned. It can be used to fetch results of a
+    /// command, for example handle of a node added by [`AddNodeCommand`](struct.AddNodeCommand.html).
+    pub fn last_command_as<C: Command>(&self) -> Option<&C> {
+        let last = if let Some(transaction) = self.transaction.as_ref() {
+            transaction.commands.last()
+        } else {
+            self.top.checked_sub(1).map(|i| &self.commands[i])
+        };
+        last.and_then(|c| c.as_any().downcast_ref::<C>())
+    }
+
+    /// Returns names of every command in history, starting from oldest.
+    pub fn names(&self) -> Vec<String> {
+        self.commands.iter().map(|c| c.name()).collect()
+    }
+
+    /// Discards whole history. Transaction in progress will be rolled back.
+    pub fn clear(&mut self, scene: &mut Scene) {
+        self.rollback_transaction(scene);
+        for mut command in self.commands.drain(..) {
+            command.finalize(scene);
+        }
+        self.top = 0;
+        self.sealed = false;
+    }
+
+    fn discard_reverted(&mut self, scene: &mut Scene) {
+        for mut command in self.commands.drain(self.top..) {
+            command.finalize(scene);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Vector3, pool::Handle},
+        scene::{
+            base::BaseBuilder,
+            command::{
+                AddNodeCommand, CommandStack, LinkNodesCommand, RemoveNodeCommand,
+                SetTransformCommand,
+            },
+            node::Node,
+            transform::TransformBuilder,
+            Scene,
+        },
+    };
+
+    fn node_count(scene: &Scene) -> usize {
+        scene.graph.linear_iter().count()
+    }
+
+    fn translation(scene: &Scene, node: Handle<Node>) -> Vector3<f32> {
+        scene.graph[node].local_transform().position()
+    }
+
+    #[test]
+    fn add_remove_undo_redo() {
+        let mut scene = Scene::new();
+        let mut stack = CommandStack::new();
+
+        let node = scene.graph.add_node(BaseBuilder::new().build_node());
+        let child = scene.graph.add_node(BaseBuilder::new().build_node());
+        scene.graph.link_nodes(child, node);
+        assert_eq!(node_count(&scene), 3);
+
+        stack.do_command(Box::new(RemoveNodeCommand::new(node)), &mut scene);
+        assert!(!scene.graph.is_valid_handle(node));
+        assert_eq!(node_count(&scene), 1);
+
+        assert!(stack.undo(&mut scene));
+        assert!(scene.graph.is_valid_handle(node));
+        assert_eq!(scene.graph[node].parent(), scene.graph.get_root());
+        assert_eq!(node_count(&scene), 3);
+        assert_eq!(scene.graph[child].parent(), node);
+
+        assert!(stack.redo(&mut scene));
+        assert_eq!(node_count(&scene), 1);
+        assert!(!stack.redo(&mut scene));
+
+        stack.clear(&mut scene);
+        assert!(!stack.can_undo());
+    }
+
+    #[test]
+    fn transform_merge_and_seal() {
+        let mut scene = Scene::new();
+        let mut stack = CommandStack::new();
+        let node = scene.graph.add_node(BaseBuilder::new().build_node());
+
+        for i in 1..=3 {
+            let transform = TransformBuilder::new()
+                .with_local_position(Vector3::new(i as f32, 0.0, 0.0))
+                .build();
+            stack.do_command(
+                Box::new(SetTransformCommand::new(node, transform)),
+                &mut scene,
+            );
+        }
+        assert_eq!(stack.names().len(), 1);
+        assert_eq!(translation(&scene, node), Vector3::new(3.0, 0.0, 0.0));
+
+        stack.seal();
+        let transform = TransformBuilder::new()
+            .with_local_position(Vector3::new(4.0, 0.0, 0.0))
+            .build();
+        stack.do_command(
+            Box::new(SetTransformCommand::new(node, transform)),
+            &mut scene,
+        );
+        assert_eq!(stack.names().len(), 2);
+
+        stack.undo(&mut scene);
+        assert_eq!(translation(&scene, node), Vector3::new(3.0, 0.0, 0.0));
+        stack.undo(&mut scene);
+        assert_eq!(translation(&scene, node), Vector3::default());
+        stack.redo(&mut scene);
+        assert_eq!(translation(&scene, node), Vector3::new(3.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn transaction() {
+        let mut scene = Scene::new();
+        let mut stack = CommandStack::new();
+
+        stack.begin_transaction("Add And Link");
+        stack.do_command(
+            Box::new(AddNodeCommand::new(BaseBuilder::new().build_node())),
+            &mut scene,
+        );
+        let parent = stack.last_command_as::<AddNodeCommand>().unwrap().handle();
+        stack.do_command(
+            Box::new(AddNodeCommand::new(BaseBuilder::new().build_node())),
+            &mut scene,
+        );
+        let child = stack.last_command_as::<AddNodeCommand>().unwrap().handle();
+        stack.do_command(Box::new(LinkNodesCommand::new(child, parent)), &mut scene);
+        stack.commit_transaction(&mut scene);
+
+        assert_eq!(stack.names(), vec!["Add And Link".to_owned()]);
+        assert_eq!(scene.graph[child].parent(), parent);
+
+        stack.undo(&mut scene);
+        assert_eq!(node_count(&scene), 1);
+
+        stack.redo(&mut scene);
+        assert_eq!(node_count(&scene), 3);
+        assert_eq!(scene.graph[child].parent(), parent);
+
+        stack.begin_transaction("Rollback");
+        stack.do_command(Box::new(RemoveNodeCommand::new(parent)), &mut scene);
+        stack.rollback_transaction(&mut scene);
+        assert_eq!(node_count(&scene), 3);
+        assert_eq!(stack.names().len(), 1);
+    }
+}
@@ -0,0 +1,561 @@
+//! Contains all structures and methods to create and manage transform constraints.
+//!
+//! Constraint is a rule that modifies global transform of a node using global transform
+//! of some other node (target). Constraints allow you to rig turrets, eyes, attachment
+//! points and so on without writing per-frame game code. Constraints are attached to a
+//! node (see [`Base::constraints_mut`]) and evaluated in order of addition when graph
+//! calculates global transforms of nodes (see [`Graph::update_hierarchical_data`]).
+//! Descendants of a constrained node will use its constrained global transform.
+//!
+//! # Evaluation order
+//!
+//! Nodes are updated from root to leaves, so a constraint will use global transform of
+//! its target calculated in current frame only if the target was updated before the
+//! constrained node, otherwise global transform from previous frame will be used. Target
+//! must not be a descendant of a constrained node, this will produce one frame lag.
+//! Constraints with invalid target handles are ignored.
+//!
+//! # Notes
+//!
+//! Constraints affect global transform only, local transform of a node stays untouched.
+//! Methods like [`Graph::global_rotation`] calculate transforms using local transforms only
+//! and do not take constraints into account.
+//!
+//! [`Base::constraints_mut`]: ../base/struct.Base.html#method.constraints_mut
+//! [`Graph::update_hierarchical_data`]: ../graph/struct.Graph.html#method.update_hierarchical_data
+//! [`Graph::global_rotation`]: ../graph/struct.Graph.html#method.global_rotation
+
+use crate::{
+    core::{
+        algebra::{Matrix3, Matrix4, Rotation3, UnitQuaternion, Vector3},
+        math::Matrix4Ext,
+        pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{graph::Graph, node::Node},
+};
+use std::collections::HashMap;
+
+/// Rotates a node so its look vector (local Z axis) will point to the target.
+#[derive(Clone, Debug)]
+pub struct LookAtConstraint {
+    /// Node to look at.
+    pub target: Handle<Node>,
+    /// Up vector in world coordinates.
+    pub up: Vector3<f32>,
+    /// Influence of constraint in [0; 1] range.
+    pub weight: f32,
+}
+
+impl Default for LookAtConstraint {
+    fn default() -> Self {
+        Self::new(Handle::NONE)
+    }
+}
+
+impl LookAtConstraint {
+    /// Creates new look-at constraint with world Y axis as up vector.
+    pub fn new(target: Handle<Node>) -> Self {
+        Self {
+            target,
+            up: Vector3::y(),
+            weight: 1.0,
+        }
+    }
+}
+
+impl Visit for LookAtConstraint {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.target.visit("Target", visitor)?;
+        self.up.visit("Up", visitor)?;
+        self.weight.visit("Weight", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Rotates a node so its arbitrary local axis (aim axis) will point to the target. Rotation
+/// around aim axis is defined by local up axis which will be aligned with world up vector
+/// as close as possible. Useful for turrets, barrels, etc. which were modelled with non-Z
+/// forward axis.
+#[derive(Clone, Debug)]
+pub struct AimConstraint {
+    /// Node to aim at.
+    pub target: Handle<Node>,
+    /// Axis in local coordinates of the node that will point to the target.
+    pub aim_axis: Vector3<f32>,
+    /// Axis in local coordinates of the node that will be aligned with world up vector.
+    pub up_axis: Vector3<f32>,
+    /// Up vector in world coordinates.
+    pub world_up: Vector3<f32>,
+    /// Influence of constraint in [0; 1] range.
+    pub weight: f32,
+}
+
+impl Default for AimConstraint {
+    fn default() -> Self {
+        Self::new(Handle::NONE, Vector3::z())
+    }
+}
+
+impl AimConstraint {
+    /// Creates new aim constraint with local and world Y axes as up vectors.
+    pub fn new(target: Handle<Node>, aim_axis: Vector3<f32>) -> Self {
+        Self {
+            target,
+            aim_axis,
+            up_axis: Vector3::y(),
+            world_up: Vector3::y(),
+            weight: 1.0,
+        }
+    }
+}
+
+impl Visit for AimConstraint {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.target.visit("Target", visitor)?;
+        self.aim_axis.visit("AimAxis", visitor)?;
+        self.up_axis.visit("UpAxis", visitor)?;
+        self.world_up.visit("WorldUp", visitor)?;
+        self.weight.visit("Weight", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Copies position, rotation and scale of the target in world coordinates, each part has
+/// its own weight so you can copy only some parts of transform.
+#[derive(Clone, Debug)]
+pub struct CopyTransformConstraint {
+    /// Node to copy transform from.
+    pub target: Handle<Node>,
+    /// Influence of target position in [0; 1] range.
+    pub position_weight: f32,
+    /// Influence of target rotation in [0; 1] range.
+    pub rotation_weight: f32,
+    /// Influence of target scale in [0; 1] range.
+    pub scale_weight: f32,
+}
+
+impl Default for CopyTransformConstraint {
+    fn default() -> Self {
+        Self::new(Handle::NONE)
+    }
+}
+
+impl CopyTransformConstraint {
+    /// Creates new constraint that copies whole transform of the target.
+    pub fn new(target: Handle<Node>) -> Self {
+        Self {
+            target,
+            position_weight: 1.0,
+            rotation_weight: 1.0,
+            scale_weight: 1.0,
+        }
+    }
+}
+
+impl Visit for CopyTransformConstraint {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.target.visit("Target", visitor)?;
+        self.position_weight.visit("PositionWeight", visitor)?;
+        self.rotation_weight.visit("RotationWeight", visitor)?;
+        self.scale_weight.visit("ScaleWeight", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Makes a node behave like a child of the target without actually linking it to the target.
+/// This is useful for attachment points: a weapon can be "attached" to a hand of a character
+/// and detached later by removing the constraint or setting its weight to zero.
+#[derive(Clone, Debug)]
+pub struct ParentConstraint {
+    /// Node that will be used as parent.
+    pub target: Handle<Node>,
+    /// Transform of the node relative to the target.
+    pub offset: Matrix4<f32>,
+    /// Influence of constraint in [0; 1] range.
+    pub weight: f32,
+}
+
+impl Default for ParentConstraint {
+    fn default() -> Self {
+        Self::new(Handle::NONE, Matrix4::identity())
+    }
+}
+
+impl ParentConstraint {
+    /// Creates new parent constraint with given offset relative to the target. Use
+    /// [`Graph::parent_constraint_offset`](../graph/struct.Graph.html#method.parent_constraint_offset)
+    /// to keep current position of a node.
+    pub fn new(target: Handle<Node>, offset: Matrix4<f32>) -> Self {
+        Self {
+            target,
+            offset,
+            weight: 1.0,
+        }
+    }
+}
+
+impl Visit for ParentConstraint {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.target.visit("Target", visitor)?;
+        self.offset.visit("Offset", visitor)?;
+        self.weight.visit("Weight", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Limits rotation of a node relative to its parent. Limits are defined as Euler angles
+/// (in radians) around X, Y and Z axes.
+#[derive(Clone, Debug)]
+pub struct LimitRotationConstraint {
+    /// Minimal angles around X, Y, Z axes in radians.
+    pub min: Vector3<f32>,
+    /// Maximal angles around X, Y, Z axes in radians.
+    pub max: Vector3<f32>,
+    /// Influence of constraint in [0; 1] range.
+    pub weight: f32,
+}
+
+impl Default for LimitRotationConstraint {
+    fn default() -> Self {
+        Self::new(Vector3::default(), Vector3::default())
+    }
+}
+
+impl LimitRotationConstraint {
+    /// Creates new rotation limit.
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self {
+            min,
+            max,
+            weight: 1.0,
+        }
+    }
+}
+
+impl Visit for LimitRotationConstraint {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.min.visit("Min", visitor)?;
+        self.max.visit("Max", visitor)?;
+        self.weight.visit("Weight", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub enum Constraint {
+    /// See LookAtConstraint docs.
+    LookAt(LookAtConstraint),
+    /// See AimConstraint docs.
+    Aim(AimConstraint),
+    /// See CopyTransformConstraint docs.
+    CopyTransform(CopyTransformConstraint),
+    /// See ParentConstraint docs.
+    Parent(ParentConstraint),
+    /// See LimitRotationConstraint docs.
+    LimitRotation(LimitRotationConstraint),
+}
+
+impl Default for Constraint {
+    fn default() -> Self {
+        Self::LookAt(Default::default())
+    }
+}
+
+impl Constraint {
+    /// Creates new constraint from given id.
+    pub fn new(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::LookAt(Default::default())),
+            1 => Ok(Self::Aim(Default::default())),
+            2 => Ok(Self::CopyTransform(Default::default())),
+            3 => Ok(Self::Parent(Default::default())),
+            4 => Ok(Self::LimitRotation(Default::default())),
+            _ => Err(format!("Invalid constraint id {}", id)),
+        }
+    }
+
+    /// Returns id of current constraint kind.
+    pub fn id(&self) -> u32 {
+        match self {
+            Self::LookAt(_) => 0,
+            Self::Aim(_) => 1,
+            Self::CopyTransform(_) => 2,
+            Self::Parent(_) => 3,
+            Self::LimitRotation(_) => 4,
+        }
+    }
+
+    /// Returns handle of target node, limit rotation constraint has no target and
+    /// `Handle::NONE` will be returned for it.
+    pub fn target(&self) -> Handle<Node> {
+        match self {
+            Self::LookAt(v) => v.target,
+            Self::Aim(v) => v.target,
+            Self::CopyTransform(v) => v.target,
+            Self::Parent(v) => v.target,
+            Self::LimitRotation(_) => Handle::NONE,
+        }
+    }
+
+    /// Sets new target node, does nothing for limit rotation constraint.
+    pub fn set_target(&mut self, target: Handle<Node>) {
+        match self {
+            Self::LookAt(v) => v.target = target,
+            Self::Aim(v) => v.target = target,
+            Self::CopyTransform(v) => v.target = target,
+            Self::Parent(v) => v.target = target,
+            Self::LimitRotation(_) => (),
+        }
+    }
+
+    pub(in crate) fn remap(&mut self, old_new_map: &HashMap<Handle<Node>, Handle<Node>>) {
+        if let Some(&new_target) = old_new_map.get(&self.target()) {
+            self.set_target(new_target);
+        }
+    }
+
+    fn apply(&self, graph: &Graph, parent_rotation: UnitQuaternion<f32>, pose: &mut Pose) {
+        let target_transform = match self {
+            Self::LimitRotation(_) => Matrix4::identity(),
+            _ if graph.is_valid_handle(self.target()) => graph[self.target()].global_transform(),
+            _ => return,
+        };
+
+        match self {
+            Self::LookAt(look_at) => {
+                if let Some(rotation) = aim_rotation(
+                    target_transform.position() - pose.position,
+                    look_at.up,
+                    Vector3::z(),
+                    Vector3::y(),
+                ) {
+                    pose.rotation = blend_rotation(pose.rotation, rotation, look_at.weight);
+                }
+            }
+            Self::Aim(aim) => {
+                if let Some(rotation) = aim_rotation(
+                    target_transform.position() - pose.position,
+                    aim.world_up,
+                    aim.aim_axis,
+                    aim.up_axis,
+                ) {
+                    pose.rotation = blend_rotation(pose.rotation, rotation, aim.weight);
+                }
+            }
+            Self::CopyTransform(copy) => {
+                let target = Pose::from_matrix(&target_transform);
+                pose.position = pose.position.lerp(&target.position, copy.position_weight);
+                pose.rotation =
+                    blend_rotation(pose.rotation, target.rotation, copy.rotation_weight);
+                pose.scale = pose.scale.lerp(&target.scale, copy.scale_weight);
+            }
+            Self::Parent(parent) => {
+                let target = Pose::from_matrix(&(target_transform * parent.offset));
+                pose.position = pose.position.lerp(&target.position, parent.weight);
+                pose.rotation = blend_rotation(pose.rotation, target.rotation, parent.weight);
+                pose.scale = pose.scale.lerp(&target.scale, parent.weight);
+            }
+            Self::LimitRotation(limit) => {
+                let local_rotation = parent_rotation.inverse() * pose.rotation;
+                let (x, y, z) = local_rotation.euler_angles();
+                let clamp = |angle: f32, min: f32, max: f32| angle.max(min).min(max);
+                let limited = UnitQuaternion::from_euler_angles(
+                    clamp(x, limit.min.x, limit.max.x),
+                    clamp(y, limit.min.y, limit.max.y),
+                    clamp(z, limit.min.z, limit.max.z),
+                );
+                pose.rotation =
+                    blend_rotation(pose.rotation, parent_rotation * limited, limit.weight);
+            }
+        }
+    }
+}
+
+impl Visit for Constraint {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::new(id)?;
+        }
+
+        match self {
+            Self::LookAt(v) => v.visit("Data", visitor)?,
+            Self::Aim(v) => v.visit("Data", visitor)?,
+            Self::CopyTransform(v) => v.visit("Data", visitor)?,
+            Self::Parent(v) => v.visit("Data", visitor)?,
+            Self::LimitRotation(v) => v.visit("Data", visitor)?,
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Decomposed global transform.
+struct Pose {
+    position: Vector3<f32>,
+    rotation: UnitQuaternion<f32>,
+    scale: Vector3<f32>,
+}
+
+impl Pose {
+    fn from_matrix(matrix: &Matrix4<f32>) -> Self {
+        let basis = matrix.basis();
+        let mut scale = Vector3::new(
+            basis.column(0).norm(),
+            basis.column(1).norm(),
+            basis.column(2).norm(),
+        );
+        // Mirrored basis cannot be represented by rotation, so move reflection to scale.
+        if basis.determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+        let safe = |s: f32| if s.abs() > f32::EPSILON { s } else { 1.0 };
+        let rotation_matrix = Matrix3::from_columns(&[
+            basis.column(0) / safe(scale.x),
+            basis.column(1) / safe(scale.y),
+            basis.column(2) / safe(scale.z),
+        ]);
+        Self {
+            position: matrix.position(),
+            rotation: UnitQuaternion::from(Rotation3::from_matrix(&rotation_matrix)),
+            scale,
+        }
+    }
+
+    fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.position)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+}
+
+fn blend_rotation(
+    from: UnitQuaternion<f32>,
+    to: UnitQuaternion<f32>,
+    weight: f32,
+) -> UnitQuaternion<f32> {
+    let weight = weight.clamp(0.0, 1.0);
+    if weight >= 1.0 {
+        to
+    } else if weight <= 0.0 {
+        from
+    } else {
+        from.try_slerp(&to, weight, f32::EPSILON)
+            .unwrap_or(if weight < 0.5 { from } else { to })
+    }
+}
+
+/// Calculates rotation that maps `aim_axis` to `direction` and `up_axis` to `world_up` (as
+/// close as possible). Returns `None` if direction is degenerate.
+fn aim_rotation(
+    direction: Vector3<f32>,
+    world_up: Vector3<f32>,
+    aim_axis: Vector3<f32>,
+    up_axis: Vector3<f32>,
+) -> Option<UnitQuaternion<f32>> {
+    fn face_towards(dir: Vector3<f32>, up: Vector3<f32>) -> Option<UnitQuaternion<f32>> {
+        if dir.norm_squared() <= f32::EPSILON {
+            return None;
+        }
+        let up = if dir.cross(&up).norm_squared() > f32::EPSILON {
+            up
+        } else if dir.cross(&Vector3::x()).norm_squared() > f32::EPSILON {
+            // Direction is collinear with up vector, pick any other.
+            Vector3::x()
+        } else {
+            Vector3::z()
+        };
+        Some(UnitQuaternion::face_towards(&dir, &up))
+    }
+
+    let world = face_towards(direction, world_up)?;
+    let local = face_towards(aim_axis, up_axis)?;
+    Some(world * local.inverse())
+}
+
+/// Applies constraints to given global transform of a node. Returns constrained global
+/// transform.
+pub(in crate) fn apply_constraints(
+    constraints: &[Constraint],
+    graph: &Graph,
+    parent_transform: &Matrix4<f32>,
+    global_transform: Matrix4<f32>,
+) -> Matrix4<f32> {
+    let parent_rotation = Pose::from_matrix(parent_transform).rotation;
+    let mut pose = Pose::from_matrix(&global_transform);
+    for constraint in constraints {
+        constraint.apply(graph, parent_rotation, &mut pose);
+    }
+    pose.matrix()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{Matrix4, Vector3},
+            math::Matrix4Ext,
+        },
+        scene::{
+            base::BaseBuilder,
+            constraint::{Constraint, LookAtConstraint, ParentConstraint},
+            graph::Graph,
+            transform::TransformBuilder,
+        },
+    };
+
+    #[test]
+    fn look_at_and_parent_constraints() {
+        let mut graph = Graph::new();
+        let target = graph.add_node(
+            BaseBuilder::new()
+                .with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(Vector3::new(10.0, 0.0, 0.0))
+                        .build(),
+                )
+                .build_node(),
+        );
+        let eye = graph.add_node(
+            BaseBuilder::new()
+                .with_constraints(vec![Constraint::LookAt(LookAtConstraint::new(target))])
+                .build_node(),
+        );
+        let attachment = graph.add_node(
+            BaseBuilder::new()
+                .with_constraints(vec![Constraint::Parent(ParentConstraint::new(
+                    target,
+                    Matrix4::new_translation(&Vector3::new(0.0, 1.0, 0.0)),
+                ))])
+                .build_node(),
+        );
+
+        graph.update_hierarchical_data();
+
+        let look = graph[eye].global_transform().look();
+        assert!((look - Vector3::x()).norm() < 0.001);
+
+        let position = graph[attachment].global_position();
+        assert!((position - Vector3::new(10.0, 1.0, 0.0)).norm() < 0.001);
+    }
+}
@@ -0,0 +1,223 @@
+//! Contains all structures and methods to create and manage decals.
+//!
+//! Decal is a texture projected onto existing geometry, it can be used to add bullet
+//! holes, blood splatter, graffiti and so on without modifying meshes.
+//!
+//! # Projection
+//!
+//! Decal is an oriented box, its volume is a unit cube centered at origin of the node,
+//! scale of the node defines actual size of the box. Everything inside of the box gets
+//! decal texture projected along local -Y axis of the node, texture coordinates are
+//! taken from local X and Z axes.
+//!
+//! Decals are rendered directly into G-buffer after all geometry and before lighting,
+//! so they modify diffuse color and normals of the geometry and are lit correctly.
+//!
+//! # Layers
+//!
+//! Decal is projected onto everything inside its volume by default, this is not always
+//! desired - for example a blood splatter should be projected onto floor but not onto
+//! a character standing over it. Every mesh has decal layer index and every decal has
+//! layer index too, decal will be projected only onto meshes with same layer index.
+//! See [`Mesh::set_decal_layer_index`](../mesh/struct.Mesh.html#method.set_decal_layer_index).
+
+use crate::{
+    core::{
+        color::Color,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    resource::texture::Texture,
+    scene::{
+        base::{Base, BaseBuilder},
+        node::Node,
+    },
+};
+use std::ops::{Deref, DerefMut};
+
+/// See module docs.
+#[derive(Debug)]
+pub struct Decal {
+    base: Base,
+    diffuse_texture: Option<Texture>,
+    normal_texture: Option<Texture>,
+    color: Color,
+    layer: u8,
+}
+
+impl Deref for Decal {
+    type Target = Base;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for Decal {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Default for Decal {
+    fn default() -> Self {
+        DecalBuilder::new(BaseBuilder::new()).build()
+    }
+}
+
+impl Visit for Decal {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Base", visitor)?;
+        self.diffuse_texture.visit("DiffuseTexture", visitor)?;
+        self.normal_texture.visit("NormalTexture", visitor)?;
+        self.color.visit("Color", visitor)?;
+        self.layer.visit("Layer", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+impl Decal {
+    /// Creates a raw copy of a decal node.
+    pub fn raw_copy(&self) -> Self {
+        Self {
+            base: self.base.raw_copy(),
+            diffuse_texture: self.diffuse_texture.clone(),
+            normal_texture: self.normal_texture.clone(),
+            color: self.color,
+            layer: self.layer,
+        }
+    }
+
+    /// Sets new diffuse texture. Alpha channel of the texture defines how much of decal
+    /// is blended with underlying geometry.
+    pub fn set_diffuse_texture(&mut self, diffuse_texture: Option<Texture>) {
+        self.diffuse_texture = diffuse_texture;
+    }
+
+    /// Returns current diffuse texture.
+    pub fn diffuse_texture(&self) -> Option<Texture> {
+        self.diffuse_texture.clone()
+    }
+
+    /// Sets new normal texture. Decal without normal texture does not modify normals of
+    /// underlying geometry.
+    pub fn set_normal_texture(&mut self, normal_texture: Option<Texture>) {
+        self.normal_texture = normal_texture;
+    }
+
+    /// Returns current normal texture.
+    pub fn normal_texture(&self) -> Option<Texture> {
+        self.normal_texture.clone()
+    }
+
+    /// Sets new color of decal, it is multiplied with diffuse texture.
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    /// Returns current color of decal.
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// Sets new layer index, decal will be projected only onto meshes with same decal
+    /// layer index.
+    pub fn set_layer(&mut self, layer: u8) {
+        self.layer = layer;
+    }
+
+    /// Returns current layer index.
+    pub fn layer(&self) -> u8 {
+        self.layer
+    }
+}
+
+/// Decal builder allows you to construct decal in declarative manner.
+/// This is typical implementation of Builder pattern.
+pub struct DecalBuilder {
+    base_builder: BaseBuilder,
+    diffuse_texture: Option<Texture>,
+    normal_texture: Option<Texture>,
+    color: Color,
+    layer: u8,
+}
+
+impl DecalBuilder {
+    /// Creates new builder with default state (no textures, white opaque color, layer 0).
+    pub fn new(base_builder: BaseBuilder) -> Self {
+        Self {
+            base_builder,
+            diffuse_texture: None,
+            normal_texture: None,
+            color: Color::WHITE,
+            layer: 0,
+        }
+    }
+
+    /// Sets desired diffuse texture.
+    pub fn with_diffuse_texture(mut self, diffuse_texture: Texture) -> Self {
+        self.diffuse_texture = Some(diffuse_texture);
+        self
+    }
+
+    /// Sets desired normal texture.
+    pub fn with_normal_texture(mut self, normal_texture: Texture) -> Self {
+        self.normal_texture = Some(normal_texture);
+        self
+    }
+
+    /// Sets desired color.
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets desired layer index.
+    pub fn with_layer(mut self, layer: u8) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    /// Creates new decal instance.
+    pub fn build(self) -> Decal {
+        Decal {
+            base: self.base_builder.build(),
+            diffuse_texture: self.diffuse_texture,
+            normal_texture: self.normal_texture,
+            color: self.color,
+            layer: self.layer,
+        }
+    }
+
+    /// Creates new node instance.
+    pub fn build_node(self) -> Node {
+        Node::Decal(self.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::color::Color,
+        scene::{base::BaseBuilder, decal::DecalBuilder, node::Node},
+        utils::testing::visit_round_trip,
+    };
+
+    #[test]
+    fn decal_visit() {
+        let mut decal = DecalBuilder::new(BaseBuilder::new().with_name("Decal"))
+            .with_color(Color::opaque(255, 0, 0))
+            .with_layer(3)
+            .build_node();
+        let mut node = Node::default();
+        visit_round_trip("Node", &mut decal, &mut node);
+
+        let decal = node.as_decal();
+        assert_eq!(decal.name(), "Decal");
+        assert_eq!(decal.color(), Color::opaque(255, 0, 0));
+        assert_eq!(decal.layer(), 3);
+        assert!(decal.diffuse_texture().is_none());
+    }
+}
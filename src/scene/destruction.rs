@@ -0,0 +1,196 @@
+//! Contains all structures and methods to destroy scene nodes in deferred manner.
+//!
+//! Often it is not desirable to destroy a node right away: a projectile that hit a wall
+//! should live until the end of the frame so every system could handle the hit, a particle
+//! system of an explosion should be destroyed only when all its particles are gone and
+//! so on. Scene has a destroy queue for such cases, see [`Scene::destroy_node`]. Every node
+//! destroyed by the scene (including nodes with expired lifetime, see [`Base::set_lifetime`])
+//! is reported in [`Scene::destroyed_nodes`], so game code can clean up its own handles to
+//! destroyed nodes.
+//!
+//! [`Scene::destroy_node`]: ../struct.Scene.html#method.destroy_node
+//! [`Scene::destroyed_nodes`]: ../struct.Scene.html#method.destroyed_nodes
+//! [`Base::set_lifetime`]: ../base/struct.Base.html#method.set_lifetime
+
+use crate::{
+    core::{
+        pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{graph::Graph, node::Node},
+};
+use std::collections::HashMap;
+
+/// Defines when a node will be destroyed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DestroyCondition {
+    /// Node will be destroyed at the end of current scene update.
+    EndOfFrame,
+    /// Node will be destroyed after given amount of seconds.
+    Delay(f32),
+    /// Node will be destroyed when every particle system in its hierarchy (including the node
+    /// itself) has finished emitting and has no alive particles. Nodes without particle systems
+    /// will be destroyed at the end of frame.
+    ParticleSystemsFinished,
+}
+
+impl Default for DestroyCondition {
+    fn default() -> Self {
+        Self::EndOfFrame
+    }
+}
+
+impl DestroyCondition {
+    fn id(self) -> u32 {
+        match self {
+            Self::EndOfFrame => 0,
+            Self::Delay(_) => 1,
+            Self::ParticleSystemsFinished => 2,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::EndOfFrame),
+            1 => Ok(Self::Delay(0.0)),
+            2 => Ok(Self::ParticleSystemsFinished),
+            _ => Err(format!("Invalid destroy condition id {}", id)),
+        }
+    }
+}
+
+impl Visit for DestroyCondition {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+
+        if let Self::Delay(time) = self {
+            time.visit("Time", visitor)?;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct DestroyRequest {
+    node: Handle<Node>,
+    condition: DestroyCondition,
+}
+
+impl Visit for DestroyRequest {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.node.visit("Node", visitor)?;
+        self.condition.visit("Condition", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// A queue of nodes waiting for destruction.
+#[derive(Debug, Default, Clone)]
+pub(in crate) struct DestroyQueue {
+    requests: Vec<DestroyRequest>,
+}
+
+impl DestroyQueue {
+    pub fn push(&mut self, node: Handle<Node>, condition: DestroyCondition) {
+        self.requests.push(DestroyRequest { node, condition })
+    }
+
+    /// Advances delays and moves every node that must be destroyed right now to `ready` list.
+    /// Requests with invalid handles are discarded.
+    pub fn collect_ready(&mut self, graph: &Graph, dt: f32, ready: &mut Vec<Handle<Node>>) {
+        self.requests
+            .retain(|request| graph.is_valid_handle(request.node));
+
+        for request in self.requests.iter_mut() {
+            if let DestroyCondition::Delay(time) = &mut request.condition {
+                *time -= dt;
+            }
+        }
+
+        self.requests.retain(|request| {
+            let destroy = match request.condition {
+                DestroyCondition::EndOfFrame => true,
+                DestroyCondition::Delay(time) => time <= 0.0,
+                DestroyCondition::ParticleSystemsFinished => {
+                    graph.traverse_iter(request.node).all(|node| {
+                        if let Node::ParticleSystem(particle_system) = node {
+                            particle_system.is_finished()
+                        } else {
+                            true
+                        }
+                    })
+                }
+            };
+            if destroy {
+                ready.push(request.node);
+            }
+            !destroy
+        });
+    }
+
+    pub fn remap(&mut self, old_new_map: &HashMap<Handle<Node>, Handle<Node>>) {
+        self.requests
+            .retain(|request| old_new_map.contains_key(&request.node));
+        for request in self.requests.iter_mut() {
+            request.node = old_new_map[&request.node];
+        }
+    }
+}
+
+impl Visit for DestroyQueue {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.requests.visit("Requests", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::Vector2,
+        scene::{base::BaseBuilder, destruction::DestroyCondition, Scene},
+    };
+
+    #[test]
+    fn deferred_destruction() {
+        let mut scene = Scene::new();
+        let parent = scene.graph.add_node(BaseBuilder::new().build_node());
+        let child = scene.graph.add_node(BaseBuilder::new().build_node());
+        scene.graph.link_nodes(child, parent);
+        let expiring = scene
+            .graph
+            .add_node(BaseBuilder::new().with_lifetime(0.15).build_node());
+
+        scene.destroy_node(parent, DestroyCondition::Delay(0.25));
+
+        scene.update(Vector2::new(1.0, 1.0), 0.1);
+        assert!(scene.graph.is_valid_handle(parent));
+        assert!(scene.destroyed_nodes().is_empty());
+
+        scene.update(Vector2::new(1.0, 1.0), 0.1);
+        assert!(!scene.graph.is_valid_handle(expiring));
+        assert_eq!(scene.destroyed_nodes(), &[expiring]);
+
+        scene.update(Vector2::new(1.0, 1.0), 0.1);
+        assert!(!scene.graph.is_valid_handle(parent));
+        assert!(!scene.graph.is_valid_handle(child));
+        assert!(scene.destroyed_nodes().contains(&parent));
+        assert!(scene.destroyed_nodes().contains(&child));
+
+        scene.update(Vector2::new(1.0, 1.0), 0.1);
+        assert!(scene.destroyed_nodes().is_empty());
+    }
+}
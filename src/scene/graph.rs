@@ -34,7 +34,7 @@ use crate::{
         visitor::{Visit, VisitResult, Visitor},
     },
     resource::ResourceState,
-    scene::{node::Node, VisibilityCache},
+    scene::{constraint, node::Node, particle_system::Emitter, VisibilityCache},
     utils::log::Log,
 };
 use rapier3d::na::Rotation3;
@@ -49,6 +49,8 @@ pub struct Graph {
     root: Handle<Node>,
     pool: Pool<Node>,
     stack: Vec<Handle<Node>>,
+    /// Nodes with expired lifetime, they will be destroyed by scene.
+    pub(in crate) lifetime_expired: Vec<Handle<Node>>,
 }
 
 impl Default for Graph {
@@ -57,6 +59,7 @@ impl Default for Graph {
             root: Handle::NONE,
             pool: Pool::new(),
             stack: Vec::new(),
+            lifetime_expired: Vec::new(),
         }
     }
 }
@@ -87,6 +90,7 @@ impl Graph {
             stack: Vec::new(),
             root,
             pool,
+            lifetime_expired: Vec::new(),
         }
     }
 
@@ -257,16 +261,30 @@ impl Graph {
         let mut old_new_mapping = HashMap::new();
         let root_handle = self.copy_node_raw(node_handle, dest_graph, &mut old_new_mapping, filter);
 
-        // Iterate over instantiated nodes and remap bones handles.
+        // Iterate over instantiated nodes and remap bones handles and constraint targets.
         for (_, &new_node_handle) in old_new_mapping.iter() {
-            if let Node::Mesh(mesh) = &mut dest_graph.pool[new_node_handle] {
-                for surface in mesh.surfaces_mut() {
-                    for bone_handle in surface.bones.iter_mut() {
-                        if let Some(entry) = old_new_mapping.get(bone_handle) {
-                            *bone_handle = *entry;
+            let node = &mut dest_graph.pool[new_node_handle];
+            for constraint in node.constraints_mut() {
+                constraint.remap(&old_new_mapping);
+            }
+            match node {
+                Node::Mesh(mesh) => {
+                    for surface in mesh.surfaces_mut() {
+                        for bone_handle in surface.bones.iter_mut() {
+                            if let Some(entry) = old_new_mapping.get(bone_handle) {
+                                *bone_handle = *entry;
+                            }
+                        }
+                    }
+                }
+                Node::ParticleSystem(particle_system) => {
+                    for emitter in particle_system.emitters_mut() {
+                        if let Emitter::MeshSurface(emitter) = emitter {
+                            emitter.remap(&old_new_mapping);
                         }
                     }
                 }
+                _ => (),
             }
         }
 
@@ -284,6 +302,7 @@ impl Graph {
         clone.original = node_handle;
         clone.parent = Handle::NONE;
         clone.children.clear();
+        clone.constraints_mut().clear();
         if let Node::Mesh(ref mut mesh) = clone {
             for surface in mesh.surfaces_mut() {
                 surface.bones.clear();
@@ -432,6 +451,8 @@ impl Graph {
     /// on each frame. However there is one use case - when you setup complex hierarchy and
     /// need to know global transform of nodes before entering update loop, then you can call
     /// this method.
+    ///
+    /// Transform constraints of nodes are evaluated here too, see `constraint` module docs.
     pub fn update_hierarchical_data(&mut self) {
         fn update_recursively(graph: &Graph, node_handle: Handle<Node>) {
             let node = &graph.pool[node_handle];
@@ -443,8 +464,16 @@ impl Graph {
                     (Matrix4::identity(), true)
                 };
 
-            node.global_transform
-                .set(parent_global_transform * node.local_transform().matrix());
+            let mut global_transform = parent_global_transform * node.local_transform().matrix();
+            if !node.constraints().is_empty() {
+                global_transform = constraint::apply_constraints(
+                    node.constraints(),
+                    graph,
+                    &parent_global_transform,
+                    global_transform,
+                );
+            }
+            node.global_transform.set(global_transform);
             node.global_visibility
                 .set(parent_visibility && node.visibility());
 
@@ -461,21 +490,81 @@ impl Graph {
         self.pool.is_valid_handle(node_handle)
     }
 
+    /// Mesh surface emitters need read access to the graph to fetch current pose of source
+    /// meshes, so emitters are temporarily moved out of their particle systems.
+    fn update_mesh_surface_emitters(&mut self) {
+        for i in 0..self.pool.get_capacity() {
+            let (mut emitters, inv_transform) = match self.pool.at_mut(i) {
+                Some(Node::ParticleSystem(particle_system))
+                    if particle_system
+                        .emitters()
+                        .iter()
+                        .any(|emitter| matches!(emitter, Emitter::MeshSurface(_))) =>
+                {
+                    (
+                        std::mem::take(particle_system.emitters_mut()),
+                        particle_system
+                            .global_transform()
+                            .try_inverse()
+                            .unwrap_or_else(Matrix4::identity),
+                    )
+                }
+                _ => continue,
+            };
+
+            for emitter in emitters.iter_mut() {
+                if let Emitter::MeshSurface(emitter) = emitter {
+                    emitter.update_geometry(self, inv_transform);
+                }
+            }
+
+            if let Some(Node::ParticleSystem(particle_system)) = self.pool.at_mut(i) {
+                *particle_system.emitters_mut() = emitters;
+            }
+        }
+    }
+
     /// Updates nodes in graph using given delta time. There is no need to call it manually.
+    ///
+    /// # Notes
+    ///
+    /// Nodes with expired lifetime are not updated and removed from the graph.
     pub fn update_nodes(&mut self, frame_size: Vector2<f32>, dt: f32) {
+        self.update_nodes_deferred(frame_size, dt);
+        self.remove_expired_nodes();
+    }
+
+    /// Removes every node which lifetime has expired during last update. Nodes that were
+    /// already removed by other means are skipped.
+    pub fn remove_expired_nodes(&mut self) {
+        for handle in std::mem::take(&mut self.lifetime_expired) {
+            if self.is_valid_handle(handle) {
+                self.remove_node(handle);
+            }
+        }
+    }
+
+    /// Same as [`Self::update_nodes`], but nodes with expired lifetime are only collected so
+    /// the scene can destroy them at the end of the frame together with its destroy queue.
+    pub(in crate) fn update_nodes_deferred(&mut self, frame_size: Vector2<f32>, dt: f32) {
         self.update_hierarchical_data();
 
+        self.lifetime_expired.clear();
+
+        self.update_mesh_surface_emitters();
+
         for i in 0..self.pool.get_capacity() {
             if let Some(node) = self.pool.at_mut(i) {
-                let remove = if let Some(lifetime) = node.lifetime.as_mut() {
+                let expired = if let Some(lifetime) = node.lifetime.as_mut() {
                     *lifetime -= dt;
                     *lifetime <= 0.0
                 } else {
                     false
                 };
 
-                if remove {
-                    self.remove_node(self.pool.handle_from_index(i));
+                if expired {
+                    let handle = self.pool.handle_from_index(i);
+                    self.lifetime_expired.push(handle);
                 } else {
                     match node {
                         Node::Camera(camera) => {
@@ -499,6 +588,8 @@ impl Graph {
                                 .visibility_cache = new_cache;
                         }
                         Node::ParticleSystem(particle_system) => particle_system.update(dt),
+                        Node::Sprite(sprite) => sprite.update(dt),
+                        Node::Terrain(terrain) => terrain.update(),
                         _ => (),
                     }
                 }
@@ -717,6 +808,21 @@ impl Graph {
         (self.global_rotation(node), self[node].global_position())
     }
 
+    /// Returns offset for [`ParentConstraint`](../constraint/struct.ParentConstraint.html) that
+    /// keeps current global transform of a node relative to given target. Global transforms
+    /// must be up-to-date, see [`update_hierarchical_data`](#method.update_hierarchical_data).
+    pub fn parent_constraint_offset(
+        &self,
+        node: Handle<Node>,
+        target: Handle<Node>,
+    ) -> Matrix4<f32> {
+        self[target]
+            .global_transform()
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            * self[node].global_transform()
+    }
+
     /// Returns global scale of a node.
     pub fn global_scale(&self, node: Handle<Node>) -> Vector3<f32> {
         let m = self.global_scale_matrix(node);
@@ -802,8 +908,12 @@ impl Visit for Graph {
 #[cfg(test)]
 mod test {
     use crate::{
-        core::pool::Handle,
-        scene::{base::Base, graph::Graph, node::Node},
+        core::{algebra::Vector2, pool::Handle},
+        scene::{
+            base::{Base, BaseBuilder},
+            graph::Graph,
+            node::Node,
+        },
     };
 
     #[test]
@@ -821,4 +931,19 @@ mod test {
         graph.add_node(Node::Base(Base::default()));
         assert_eq!(graph.pool.alive_count(), 4);
     }
+
+    #[test]
+    fn graph_lifetime_test() {
+        let mut graph = Graph::new();
+        let short = graph.add_node(BaseBuilder::new().with_lifetime(0.5).build_node());
+        let long = graph.add_node(BaseBuilder::new().with_lifetime(2.0).build_node());
+        let endless = graph.add_node(BaseBuilder::new().build_node());
+
+        graph.update_nodes(Vector2::new(100.0, 100.0), 1.0);
+
+        assert!(!graph.is_valid_handle(short));
+        assert!(graph.is_valid_handle(long));
+        assert!(graph.is_valid_handle(endless));
+        assert!(graph.lifetime_expired.is_empty());
+    }
 }
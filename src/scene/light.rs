@@ -43,6 +43,10 @@ pub const DEFAULT_SCATTER_G: f32 = 0.03;
 /// significant value and you'll clearly see light volume with such settings.
 pub const DEFAULT_SCATTER_B: f32 = 0.03;
 
+/// Default shadow bias of directional light. Cascades of directional light shadow
+/// map cover large depth range, so bias is bigger than for other kinds of lights.
+pub const DEFAULT_DIRECTIONAL_SHADOW_BIAS: f32 = 0.0005;
+
 /// Spot light is can be imagined as flash light - it has direction and cone
 /// shape of light volume. It defined by two angles:
 /// 1) Hot spot inner angle - this is zone where intensity of light is max.
@@ -420,18 +424,33 @@ impl PointLightBuilder {
 /// excellent example in real life - Sun. It does not have position,
 /// only direction which defined by parent light scene node.
 ///
-/// # Notes
+/// # Shadows
 ///
-/// Current directional light does *not* support shadows, it is still
-/// on list of features that should be implemented.
-#[derive(Default, Debug)]
+/// Directional lights use cascaded shadow maps - view frustum of a camera is
+/// split into few slices and each slice gets its own shadow map. Amount of
+/// cascades, their splits and shadows distance are defined in renderer's
+/// `QualitySettings`.
+#[derive(Debug)]
 pub struct DirectionalLight {
     base_light: BaseLight,
+    shadow_bias: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            base_light: Default::default(),
+            shadow_bias: DEFAULT_DIRECTIONAL_SHADOW_BIAS,
+        }
+    }
 }
 
 impl From<BaseLight> for DirectionalLight {
     fn from(base_light: BaseLight) -> Self {
-        Self { base_light }
+        Self {
+            base_light,
+            shadow_bias: DEFAULT_DIRECTIONAL_SHADOW_BIAS,
+        }
     }
 }
 
@@ -454,16 +473,29 @@ impl Visit for DirectionalLight {
         visitor.enter_region(name)?;
 
         self.base_light.visit("BaseLight", visitor)?;
+        let _ = self.shadow_bias.visit("ShadowBias", visitor);
 
         visitor.leave_region()
     }
 }
 
 impl DirectionalLight {
+    /// Sets new shadow bias value. Bias will be used to offset fragment's depth before
+    /// compare it with shadow map value, it is used to remove "shadow acne".
+    pub fn set_shadow_bias(&mut self, bias: f32) {
+        self.shadow_bias = bias;
+    }
+
+    /// Returns current value of shadow bias.
+    pub fn shadow_bias(&self) -> f32 {
+        self.shadow_bias
+    }
+
     /// Creates a raw copy of a directional light node.
     pub fn raw_copy(&self) -> Self {
         Self {
             base_light: self.base_light.raw_copy(),
+            shadow_bias: self.shadow_bias,
         }
     }
 }
@@ -471,18 +503,29 @@ impl DirectionalLight {
 /// Allows you to build directional light in declarative manner.
 pub struct DirectionalLightBuilder {
     base_light_builder: BaseLightBuilder,
+    shadow_bias: f32,
 }
 
 impl DirectionalLightBuilder {
     /// Creates new builder instance.
     pub fn new(base_light_builder: BaseLightBuilder) -> Self {
-        Self { base_light_builder }
+        Self {
+            base_light_builder,
+            shadow_bias: DEFAULT_DIRECTIONAL_SHADOW_BIAS,
+        }
+    }
+
+    /// Sets desired shadow bias.
+    pub fn with_shadow_bias(mut self, bias: f32) -> Self {
+        self.shadow_bias = bias;
+        self
     }
 
     /// Builds new instance of directional light.
     pub fn build(self) -> DirectionalLight {
         DirectionalLight {
             base_light: self.base_light_builder.build(),
+            shadow_bias: self.shadow_bias,
         }
     }
 
@@ -34,6 +34,7 @@ pub struct Mesh {
     surfaces: Vec<Surface>,
     bounding_box: Cell<AxisAlignedBoundingBox>,
     bounding_box_dirty: Cell<bool>,
+    decal_layer_index: u8,
 }
 
 impl Default for Mesh {
@@ -43,6 +44,7 @@ impl Default for Mesh {
             surfaces: Default::default(),
             bounding_box: Default::default(),
             bounding_box_dirty: Cell::new(true),
+            decal_layer_index: 0,
         }
     }
 }
@@ -70,6 +72,7 @@ impl Visit for Mesh {
         // Serialize surfaces, but keep in mind that surfaces from resources will be automatically
         // recreated on resolve stage! Serialization of surfaces needed for procedural surfaces.
         self.surfaces.visit("Surfaces", visitor)?;
+        let _ = self.decal_layer_index.visit("DecalLayerIndex", visitor);
 
         visitor.leave_region()
     }
@@ -213,6 +216,19 @@ impl Mesh {
         false
     }
 
+    /// Sets new decal layer index. Decals will be projected onto the mesh only if their
+    /// layer index matches layer index of the mesh. Default is 0.
+    #[inline]
+    pub fn set_decal_layer_index(&mut self, index: u8) {
+        self.decal_layer_index = index;
+    }
+
+    /// Returns current decal layer index.
+    #[inline]
+    pub fn decal_layer_index(&self) -> u8 {
+        self.decal_layer_index
+    }
+
     /// Creates a raw copy of a mesh node.
     pub fn raw_copy(&self) -> Self {
         Self {
@@ -220,6 +236,7 @@ impl Mesh {
             surfaces: self.surfaces.clone(),
             bounding_box: self.bounding_box.clone(),
             bounding_box_dirty: self.bounding_box_dirty.clone(),
+            decal_layer_index: self.decal_layer_index,
         }
     }
 }
@@ -228,6 +245,7 @@ impl Mesh {
 pub struct MeshBuilder {
     base_builder: BaseBuilder,
     surfaces: Vec<Surface>,
+    decal_layer_index: u8,
 }
 
 impl MeshBuilder {
@@ -236,6 +254,7 @@ impl MeshBuilder {
         Self {
             base_builder,
             surfaces: Default::default(),
+            decal_layer_index: 0,
         }
     }
 
@@ -245,6 +264,12 @@ impl MeshBuilder {
         self
     }
 
+    /// Sets desired decal layer index.
+    pub fn with_decal_layer_index(mut self, index: u8) -> Self {
+        self.decal_layer_index = index;
+        self
+    }
+
     /// Creates new mesh.
     pub fn build(self) -> Mesh {
         Mesh {
@@ -252,6 +277,7 @@ impl MeshBuilder {
             surfaces: self.surfaces,
             bounding_box: Default::default(),
             bounding_box_dirty: Cell::new(true),
+            decal_layer_index: self.decal_layer_index,
         }
     }
 
@@ -6,34 +6,50 @@
 
 pub mod base;
 pub mod camera;
+pub mod command;
+pub mod constraint;
+pub mod decal;
+pub mod destruction;
 pub mod graph;
 pub mod light;
 pub mod mesh;
 pub mod node;
 pub mod particle_system;
 pub mod physics;
+pub mod pick;
 pub mod sprite;
+pub mod streaming;
+pub mod terrain;
 pub mod transform;
 
-use crate::renderer::framework::query::Query;
 use crate::{
     animation::AnimationContainer,
     core::{
         algebra::{Matrix4, Vector2, Vector3},
         color::Color,
-        math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, Matrix4Ext},
+        math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, ray::Ray, Matrix4Ext},
         pool::{Handle, Pool, PoolIterator, PoolIteratorMut},
         visitor::{Visit, VisitError, VisitResult, Visitor},
     },
     engine::resource_manager::ResourceManager,
-    resource::texture::Texture,
-    scene::{graph::Graph, node::Node, physics::Physics},
+    resource::{material::Material, texture::Texture, ResourceState},
+    scene::{
+        destruction::{DestroyCondition, DestroyQueue},
+        graph::Graph,
+        node::Node,
+        physics::Physics,
+        pick::PickResult,
+        streaming::Streamer,
+    },
     utils::{lightmap::Lightmap, log::Log},
 };
-use rapier3d::na::Point3;
+use rapier3d::{
+    dynamics::RigidBodyBuilder,
+    na::{Isometry3, Point3, Translation3},
+};
 use std::cell::RefCell;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ops::{Index, IndexMut},
     path::Path,
 };
@@ -566,9 +582,19 @@ pub struct Scene {
     /// Drawing context for simple graphics.
     pub drawing_context: SceneDrawingContext,
 
+    /// Streamer loads and unloads parts of the scene stored in separate files depending on
+    /// position of an observer. See `streaming` module docs for more info.
+    pub streamer: Streamer,
+
     lightmap: Option<Lightmap>,
 
-    pub occlusion_map: RefCell<HashMap<Handle<Node>, Vec<Query>>>,
+    destroy_queue: DestroyQueue,
+
+    destroyed_nodes: Vec<Handle<Node>>,
+
+    /// Meshes occluded by other objects for each camera (by handle) rendered in last frame,
+    /// filled by the renderer when occlusion culling is enabled in quality settings.
+    pub occlusion_map: RefCell<HashMap<Handle<Node>, HashSet<Handle<Node>>>>,
 }
 
 impl Default for Scene {
@@ -581,6 +607,9 @@ impl Default for Scene {
             render_target: None,
             lightmap: None,
             drawing_context: Default::default(),
+            streamer: Default::default(),
+            destroy_queue: Default::default(),
+            destroyed_nodes: Default::default(),
             occlusion_map: RefCell::new(HashMap::default()),
         }
     }
@@ -595,6 +624,22 @@ fn map_texture(tex: Option<Texture>, rm: ResourceManager) -> Option<Texture> {
     }
 }
 
+fn map_material(material: Option<Material>, rm: ResourceManager) -> Option<Material> {
+    material.map(|shallow_material| {
+        let path = shallow_material.state().path().to_path_buf();
+        if path.as_os_str().is_empty() {
+            // Material was created in code and it is stored in the scene, only its textures
+            // must be restored.
+            if let ResourceState::Ok(data) = &mut *shallow_material.state() {
+                data.resolve_textures(&rm);
+            }
+            shallow_material
+        } else {
+            rm.request_material(path)
+        }
+    })
+}
+
 impl Scene {
     /// Creates new scene with single root node.
     ///
@@ -613,6 +658,9 @@ impl Scene {
             render_target: None,
             lightmap: None,
             drawing_context: Default::default(),
+            streamer: Default::default(),
+            destroy_queue: Default::default(),
+            destroyed_nodes: Default::default(),
             occlusion_map: RefCell::new(HashMap::default()),
         }
     }
@@ -670,6 +718,26 @@ impl Scene {
                             resource_manager.clone(),
                         ));
 
+                        surface.set_metallic_texture(map_texture(
+                            surface.metallic_texture(),
+                            resource_manager.clone(),
+                        ));
+
+                        surface.set_ambient_occlusion_texture(map_texture(
+                            surface.ambient_occlusion_texture(),
+                            resource_manager.clone(),
+                        ));
+
+                        surface.set_emission_texture(map_texture(
+                            surface.emission_texture(),
+                            resource_manager.clone(),
+                        ));
+
+                        surface.set_material(map_material(
+                            surface.material(),
+                            resource_manager.clone(),
+                        ));
+
                         // Do not resolve lightmap texture here, it makes no sense anyway,
                         // it will be resolved below.
                     }
@@ -677,6 +745,28 @@ impl Scene {
                 Node::Sprite(sprite) => {
                     sprite.set_texture(map_texture(sprite.texture(), resource_manager.clone()));
                 }
+                Node::Decal(decal) => {
+                    decal.set_diffuse_texture(map_texture(
+                        decal.diffuse_texture(),
+                        resource_manager.clone(),
+                    ));
+                    decal.set_normal_texture(map_texture(
+                        decal.normal_texture(),
+                        resource_manager.clone(),
+                    ));
+                }
+                Node::Terrain(terrain) => {
+                    for layer in terrain.layers_mut() {
+                        layer.set_diffuse_texture(map_texture(
+                            layer.diffuse_texture(),
+                            resource_manager.clone(),
+                        ));
+                        layer.set_normal_texture(map_texture(
+                            layer.normal_texture(),
+                            resource_manager.clone(),
+                        ));
+                    }
+                }
                 Node::ParticleSystem(particle_system) => {
                     particle_system.set_texture(map_texture(
                         particle_system.texture(),
@@ -698,6 +788,17 @@ impl Scene {
                         skybox.front = map_texture(skybox.front.clone(), resource_manager.clone());
                         skybox.back = map_texture(skybox.back.clone(), resource_manager.clone());
                     }
+
+                    let post_effects = camera.post_effects_mut();
+                    post_effects.color_grading_lut = map_texture(
+                        post_effects.color_grading_lut.clone(),
+                        resource_manager.clone(),
+                    );
+                    post_effects.custom_passes = post_effects
+                        .custom_passes
+                        .drain(..)
+                        .filter_map(|pass| map_material(Some(pass), resource_manager.clone()))
+                        .collect();
                 }
                 _ => (),
             }
@@ -739,7 +840,9 @@ impl Scene {
         }
     }
 
-    /// Removes node from scene with all associated entities, like animations etc.
+    /// Removes node from scene with all associated entities, like animations, rigid bodies
+    /// bound to nodes, etc. Handles of removed nodes will be reported in
+    /// [`destroyed_nodes`](#method.destroyed_nodes).
     ///
     /// # Panics
     ///
@@ -755,11 +858,46 @@ impl Scene {
                 }
                 true
             });
+
+            // Remove associated rigid body.
+            if let Some(body) = self.physics_binder.unbind(descendant) {
+                self.physics.remove_body(body);
+            }
+
+            self.destroyed_nodes.push(descendant);
         }
 
         self.graph.remove_node(handle)
     }
 
+    /// Puts node in destroy queue, node will be removed (see [`remove_node`](#method.remove_node))
+    /// when given condition is met. Destroy queue is processed at the end of each scene update.
+    /// If node was removed by other means before the condition is met, the request is silently
+    /// discarded. See `destruction` module docs for more info.
+    pub fn destroy_node(&mut self, handle: Handle<Node>, condition: DestroyCondition) {
+        self.destroy_queue.push(handle, condition);
+    }
+
+    /// Returns handles of nodes that were removed from the scene since beginning of the last
+    /// scene update. This includes nodes from destroy queue, nodes with expired lifetime and
+    /// every node removed by [`remove_node`](#method.remove_node). The list contains every node
+    /// in removed hierarchy, not only its root. Can be used to clean up handles to destroyed
+    /// nodes in game code.
+    pub fn destroyed_nodes(&self) -> &[Handle<Node>] {
+        &self.destroyed_nodes
+    }
+
+    fn update_destroy_queue(&mut self, dt: f32) {
+        let mut ready = std::mem::take(&mut self.graph.lifetime_expired);
+        self.destroy_queue.collect_ready(&self.graph, dt, &mut ready);
+        for handle in ready {
+            // Node might be already destroyed as a descendant of other node.
+            if self.graph.is_valid_handle(handle) {
+                self.remove_node(handle);
+            }
+        }
+    }
+
     pub(in crate) fn resolve(&mut self) {
         Log::writeln("Starting resolve...".to_owned());
 
@@ -845,13 +983,134 @@ impl Scene {
         Ok(std::mem::replace(&mut self.lightmap, Some(lightmap)))
     }
 
+    /// Searches for the closest mesh, sprite or particle system under given screen position
+    /// as seen by given camera. Screen position is in pixels relative to top left corner of
+    /// the screen of given size, see [`Camera::make_ray`](camera/struct.Camera.html#method.make_ray).
+    /// Meshes are tested using their triangles, so no colliders are required. See `pick`
+    /// module docs for more info.
+    ///
+    /// # Panics
+    ///
+    /// Panics if given handle does not point to a camera.
+    pub fn pick(
+        &self,
+        camera: Handle<Node>,
+        screen_pos: Vector2<f32>,
+        screen_size: Vector2<f32>,
+    ) -> Option<PickResult> {
+        let ray = self.graph[camera]
+            .as_camera()
+            .make_ray(screen_pos, screen_size);
+        self.pick_ray(camera, &ray, |_, _| true)
+    }
+
+    /// Tests given ray against meshes, sprites and particle systems and returns the closest
+    /// intersection. Camera is needed to test camera-facing sprites. Filter allows you to
+    /// exclude some nodes from test (for example editor gizmos), it must return false for
+    /// such nodes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if given handle does not point to a camera.
+    pub fn pick_ray<F>(&self, camera: Handle<Node>, ray: &Ray, filter: F) -> Option<PickResult>
+    where
+        F: FnMut(Handle<Node>, &Node) -> bool,
+    {
+        let camera = self.graph[camera].as_camera();
+        let inv_view = camera.inv_view_matrix().unwrap_or_default();
+        let axes = pick::CameraAxes {
+            side: inv_view.side().try_normalize(f32::EPSILON)?,
+            up: inv_view.up().try_normalize(f32::EPSILON)?,
+            position: camera.global_position(),
+        };
+        pick::pick_ray(&self.graph, ray, &axes, filter)
+    }
+
+    /// Loads and unloads streaming cells depending on position of streamer's observer.
+    /// In most cases there is no need to call it directly, engine automatically updates
+    /// streaming of all available scenes.
+    pub fn update_streaming(&mut self, resource_manager: &ResourceManager) {
+        let mut streamer = std::mem::take(&mut self.streamer);
+        streamer.update(self, resource_manager);
+        self.streamer = streamer;
+    }
+
     /// Performs single update tick with given delta time from last frame. Internally
     /// it updates physics, animations, and each graph node. In most cases there is
     /// no need to call it directly, engine automatically updates all available scenes.
     pub fn update(&mut self, frame_size: Vector2<f32>, dt: f32) {
+        self.destroyed_nodes.clear();
         self.update_physics();
         self.animations.update_animations(dt);
-        self.graph.update_nodes(frame_size, dt);
+        self.graph.update_nodes_deferred(frame_size, dt);
+        self.update_terrain_colliders();
+        self.update_particle_collisions();
+        self.update_destroy_queue(dt);
+    }
+
+    // Creates static rigid body with heightfield collider for every terrain that does not
+    // have one yet and replaces heightfield of terrains with modified height maps.
+    fn update_terrain_colliders(&mut self) {
+        for i in 0..self.graph.capacity() {
+            let handle = self.graph.handle_from_index(i);
+            if !self.graph.is_valid_handle(handle) {
+                continue;
+            }
+
+            let collider = match &mut self.graph[handle] {
+                Node::Terrain(terrain) => {
+                    if terrain.take_collider_dirty() {
+                        terrain.make_heightfield_collider()
+                    } else {
+                        continue;
+                    }
+                }
+                _ => continue,
+            };
+
+            let body = match self.physics_binder.body_of(handle) {
+                Some(body) if self.physics.bodies.contains(body.into()) => body,
+                _ => {
+                    let transform = self.graph[handle].local_transform();
+                    let body = self.physics.add_body(
+                        RigidBodyBuilder::new_static()
+                            .position(Isometry3 {
+                                translation: Translation3::from(transform.position()),
+                                rotation: transform.rotation(),
+                            })
+                            .build(),
+                    );
+                    self.physics_binder.bind(handle, body);
+                    body
+                }
+            };
+
+            let heightfields = self.physics.bodies[body.into()]
+                .colliders()
+                .iter()
+                .filter(|&&collider| {
+                    self.physics.colliders[collider]
+                        .shape()
+                        .as_heightfield()
+                        .is_some()
+                })
+                .copied()
+                .collect::<Vec<_>>();
+            for heightfield in heightfields {
+                self.physics.remove_collider(heightfield.into());
+            }
+
+            self.physics.add_collider(collider, body);
+        }
+    }
+
+    fn update_particle_collisions(&mut self) {
+        let mut query_buffer = Vec::new();
+        for node in self.graph.linear_iter_mut() {
+            if let Node::ParticleSystem(particle_system) = node {
+                particle_system.resolve_collisions(&self.physics, &mut query_buffer);
+            }
+        }
     }
 
     /// Creates deep copy of a scene, filter predicate allows you to filter out nodes
@@ -870,6 +1129,10 @@ impl Scene {
                 track.set_node(old_new_map[&track.get_node()]);
             }
         }
+        let mut streamer = self.streamer.clone();
+        streamer.remap(&old_new_map);
+        let mut destroy_queue = self.destroy_queue.clone();
+        destroy_queue.remap(&old_new_map);
         // It is ok to use old binder here, because handles maps one-to-one.
         let physics = self.physics.deep_copy(&self.physics_binder, &graph);
         let mut physics_binder = PhysicsBinder::default();
@@ -892,7 +1155,12 @@ impl Scene {
                 render_target: Default::default(),
                 lightmap: self.lightmap.clone(),
                 drawing_context: self.drawing_context.clone(),
-                occlusion_map: RefCell::new(self.occlusion_map.borrow_mut().clone()),
+                streamer,
+                destroy_queue,
+                destroyed_nodes: Default::default(),
+                // Occlusion map is intentionally not copied, handles of nodes are different
+                // in a copy.
+                occlusion_map: Default::default(),
             },
             old_new_map,
         )
@@ -907,6 +1175,8 @@ impl Visit for Scene {
         self.animations.visit("Animations", visitor)?;
         self.physics.visit("Physics", visitor)?;
         let _ = self.lightmap.visit("Lightmap", visitor);
+        let _ = self.streamer.visit("Streamer", visitor);
+        let _ = self.destroy_queue.visit("DestroyQueue", visitor);
         visitor.leave_region()
     }
 }
@@ -6,8 +6,8 @@ use crate::{
     core::define_is_as,
     core::visitor::{Visit, VisitResult, Visitor},
     scene::{
-        base::Base, camera::Camera, light::Light, mesh::Mesh, particle_system::ParticleSystem,
-        sprite::Sprite,
+        base::Base, camera::Camera, decal::Decal, light::Light, mesh::Mesh,
+        particle_system::ParticleSystem, sprite::Sprite, terrain::Terrain,
     },
 };
 use std::ops::{Deref, DerefMut};
@@ -23,6 +23,8 @@ macro_rules! static_dispatch {
             Node::Light(v) => v.$func($($args),*),
             Node::ParticleSystem(v) => v.$func($($args),*),
             Node::Sprite(v) => v.$func($($args),*),
+            Node::Decal(v) => v.$func($($args),*),
+            Node::Terrain(v) => v.$func($($args),*),
         }
     };
 }
@@ -54,6 +56,10 @@ pub enum Node {
     Sprite(Sprite),
     /// See ParticleSystem node docs.
     ParticleSystem(ParticleSystem),
+    /// See Decal node docs.
+    Decal(Decal),
+    /// See Terrain node docs.
+    Terrain(Terrain),
 }
 
 macro_rules! static_dispatch_deref {
@@ -65,6 +71,8 @@ macro_rules! static_dispatch_deref {
             Node::Light(v) => v,
             Node::ParticleSystem(v) => v,
             Node::Sprite(v) => v,
+            Node::Decal(v) => v,
+            Node::Terrain(v) => v,
         }
     };
 }
@@ -99,6 +107,8 @@ impl Node {
             3 => Ok(Self::Mesh(Default::default())),
             4 => Ok(Self::Sprite(Default::default())),
             5 => Ok(Self::ParticleSystem(Default::default())),
+            6 => Ok(Self::Decal(Default::default())),
+            7 => Ok(Self::Terrain(Default::default())),
             _ => Err(format!("Invalid node kind {}", id)),
         }
     }
@@ -112,6 +122,8 @@ impl Node {
             Self::Mesh(_) => 3,
             Self::Sprite(_) => 4,
             Self::ParticleSystem(_) => 5,
+            Self::Decal(_) => 6,
+            Self::Terrain(_) => 7,
         }
     }
 
@@ -126,6 +138,8 @@ impl Node {
             Node::Mesh(v) => Node::Mesh(v.raw_copy()),
             Node::Sprite(v) => Node::Sprite(v.raw_copy()),
             Node::ParticleSystem(v) => Node::ParticleSystem(v.raw_copy()),
+            Node::Decal(v) => Node::Decal(v.raw_copy()),
+            Node::Terrain(v) => Node::Terrain(v.raw_copy()),
         }
     }
 
@@ -134,4 +148,6 @@ impl Node {
     define_is_as!(Node : Light -> ref Light => fn is_light, fn as_light, fn as_light_mut);
     define_is_as!(Node : ParticleSystem -> ref ParticleSystem => fn is_particle_system, fn as_particle_system, fn as_particle_system_mut);
     define_is_as!(Node : Sprite -> ref Sprite => fn is_sprite, fn as_sprite, fn as_sprite_mut);
+    define_is_as!(Node : Decal -> ref Decal => fn is_decal, fn as_decal, fn as_decal_mut);
+    define_is_as!(Node : Terrain -> ref Terrain => fn is_terrain, fn as_terrain, fn as_terrain_mut);
 }
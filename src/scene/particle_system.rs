@@ -11,6 +11,31 @@
 //!
 //! Particle system can contain multiple particle emitters, each emitter has its own
 //! set of properties and it defines law of change of particle parameters over time.
+//! There are built-in box, sphere, cylinder, cone, disk and mesh surface emitters, custom
+//! emitters can be added using [`CustomEmitterFactory`].
+//!
+//! # Simulation space
+//!
+//! By default particles are simulated in local coordinates of particle system, so they move
+//! together with the node. This can be changed using [`SimulationSpace`], for example smoke
+//! trail of a rocket should be simulated in world space.
+//!
+//! # Sub-emitters
+//!
+//! Emitter can trigger another emitter of the same particle system when its particles are
+//! born or die, see [`SubEmitter`].
+//!
+//! # Modules
+//!
+//! Behaviour of particles over their lifetime can be changed using modules: color gradient,
+//! size and rotation curves, velocity over lifetime, drag, turbulence and texture sheet
+//! (flipbook) animation. All modules are evaluated in [`ParticleSystem::update`].
+//!
+//! # Collisions
+//!
+//! By default particles pass through everything, optionally they can collide with colliders
+//! of scene physics, see [`ParticleCollision`]. Every impact is reported and can be fetched
+//! using [`ParticleSystem::impacts`] after scene update.
 //!
 //! # Performance
 //!
@@ -68,27 +93,37 @@
 //! }
 //! ```
 
-use crate::core::algebra::{Vector2, Vector3};
+use crate::core::algebra::{Matrix4, Point3, Vector2, Vector3, Vector4};
 use crate::rand::Rng;
 use crate::scene::node::Node;
 use crate::{
     core::{
         color::Color,
         color_gradient::ColorGradient,
-        math::TriangleDefinition,
+        curve::Curve,
+        math::{aabb::AxisAlignedBoundingBox, ray::Ray, TriangleDefinition},
         numeric_range::NumericRange,
+        pool::Handle,
         visitor::{Visit, VisitResult, Visitor},
     },
+    renderer::surface::SurfaceSharedData,
     resource::texture::Texture,
-    scene::base::{Base, BaseBuilder},
+    scene::{
+        base::{Base, BaseBuilder},
+        graph::Graph,
+        physics::{Intersection, Physics, RayCastOptions},
+        ColliderHandle,
+    },
 };
+use rapier3d::geometry::InteractionGroups;
 use std::{
     any::Any,
     cell::Cell,
     cmp::Ordering,
+    collections::HashMap,
     fmt::Debug,
     ops::{Deref, DerefMut},
-    sync::{LockResult, Mutex, MutexGuard},
+    sync::{Arc, LockResult, Mutex, MutexGuard, RwLock},
 };
 
 /// OpenGL expects this structure packed as in C.
@@ -100,6 +135,7 @@ pub struct Vertex {
     size: f32,
     rotation: f32,
     color: Color,
+    uv_rect: Vector4<f32>,
 }
 
 /// Particle system is "rendered" into special buffer, which contains vertices and faces.
@@ -159,6 +195,10 @@ pub struct Particle {
     pub color: Color,
     emitter_index: u32,
     sqr_distance_to_camera: Cell<f32>,
+    // Values below are evaluated by modules each update, they are not serialized.
+    offset: Vector3<f32>,
+    size_scale: f32,
+    frame: u32,
 }
 
 impl Default for Particle {
@@ -176,6 +216,9 @@ impl Default for Particle {
             emitter_index: 0,
             color: Color::WHITE,
             sqr_distance_to_camera: Cell::new(0.0),
+            offset: Default::default(),
+            size_scale: 1.0,
+            frame: 0,
         }
     }
 }
@@ -421,208 +464,1004 @@ impl SphereEmitterBuilder {
     }
 }
 
-/// Callback that creates emitter by its numeric identifier.
-pub type CustomEmitterFactoryCallback =
-    dyn Fn(i32) -> Result<Box<dyn CustomEmitter>, String> + Send + 'static;
+/// Cylinder emitter uniformly places particles in cylindrical volume, axis of the
+/// cylinder is parallel to Y axis of particle system. Can be used to create pillars
+/// of smoke, magic circles, etc.
+#[derive(Debug, Clone)]
+pub struct CylinderEmitter {
+    emitter: BaseEmitter,
+    radius: f32,
+    height: f32,
+}
 
-/// Custom emitter factory is used to be able to make your own emitters if none of
-/// predefined are not suits to your case.
-pub struct CustomEmitterFactory {
-    callback: Option<Box<CustomEmitterFactoryCallback>>,
+impl Deref for CylinderEmitter {
+    type Target = BaseEmitter;
+
+    fn deref(&self) -> &Self::Target {
+        &self.emitter
+    }
 }
 
-impl Default for CustomEmitterFactory {
+impl DerefMut for CylinderEmitter {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.emitter
+    }
+}
+
+impl Default for CylinderEmitter {
     fn default() -> Self {
-        Self { callback: None }
+        Self {
+            emitter: Default::default(),
+            radius: 0.5,
+            height: 1.0,
+        }
     }
 }
 
-impl CustomEmitterFactory {
-    /// Locks factory singleton and returns lock result.
-    pub fn get() -> LockResult<MutexGuard<'static, Self>> {
-        CUSTOM_EMITTER_FACTORY_INSTANCE.lock()
+impl CylinderEmitter {
+    /// Creates new cylinder emitter with given radius and height.
+    pub fn new(emitter: BaseEmitter, radius: f32, height: f32) -> Self {
+        Self {
+            emitter,
+            radius,
+            height,
+        }
     }
 
-    /// Sets new callback that will be used to create custom emitters.
-    pub fn set_callback(&mut self, callback: Box<CustomEmitterFactoryCallback>) {
-        self.callback = Some(callback);
+    /// Sets new radius of the cylinder.
+    pub fn set_radius(&mut self, radius: f32) -> &mut Self {
+        self.radius = radius;
+        self
     }
 
-    fn spawn(&self, kind: i32) -> Result<Box<dyn CustomEmitter>, String> {
-        match &self.callback {
-            Some(callback) => callback(kind),
-            None => Err(String::from("no callback specified")),
-        }
+    /// Returns radius of the cylinder.
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Sets new height of the cylinder.
+    pub fn set_height(&mut self, height: f32) -> &mut Self {
+        self.height = height;
+        self
+    }
+
+    /// Returns height of the cylinder.
+    pub fn height(&self) -> f32 {
+        self.height
     }
 }
 
-lazy_static! {
-    static ref CUSTOM_EMITTER_FACTORY_INSTANCE: Mutex<CustomEmitterFactory> =
-        Mutex::new(Default::default());
+impl Emit for CylinderEmitter {
+    fn emit(&self, _particle_system: &ParticleSystem, particle: &mut Particle) {
+        self.emitter.emit(particle);
+        let mut rng = crate::rand::thread_rng();
+        let (x, z) = random_point_on_disk(&mut rng, self.radius);
+        particle.position =
+            self.position + Vector3::new(x, (rng.gen::<f32>() - 0.5) * self.height, z);
+    }
 }
 
-/// Custom emitter allows you to make your own emitters. It can be implemented on serializable
-/// types only!
-///
-/// # Example
-///
-/// TODO
-pub trait CustomEmitter:
-    Any + Emit + Visit + Send + Debug + Deref<Target = BaseEmitter> + DerefMut
-{
-    /// Creates boxed copy of custom emitter.
-    fn box_clone(&self) -> Box<dyn CustomEmitter>;
+impl Visit for CylinderEmitter {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
 
-    /// Returns unique of custom emitter. Must never be negative!
-    /// Negative numbers reserved for built-in kinds.
-    fn get_kind(&self) -> i32;
+        self.emitter.visit("Emitter", visitor)?;
+        self.radius.visit("Radius", visitor)?;
+        self.height.visit("Height", visitor)?;
+
+        visitor.leave_region()
+    }
 }
 
-/// Emitter is an enum over all possible emitter types, they all must
-/// use BaseEmitter which contains base functionality.
-#[derive(Debug)]
-pub enum Emitter {
-    /// Unknown kind here is just to have ability to implement Default trait,
-    /// must not be used at runtime!
-    Unknown,
-    /// See BoxEmitter docs.
-    Box(BoxEmitter),
-    /// See SphereEmitter docs.
-    Sphere(SphereEmitter),
-    /// Custom emitter.
-    Custom(Box<dyn CustomEmitter>),
+/// Cylinder emitter builder allows you to construct cylinder emitter in declarative manner.
+/// This is typical implementation of Builder pattern.
+pub struct CylinderEmitterBuilder {
+    base: BaseEmitterBuilder,
+    radius: f32,
+    height: f32,
 }
 
-impl Emitter {
-    /// Creates new emitter from given id.
-    pub fn new(id: i32) -> Result<Self, String> {
-        match id {
-            -1 => Ok(Self::Unknown),
-            -2 => Ok(Self::Box(Default::default())),
-            -3 => Ok(Self::Sphere(Default::default())),
-            _ => match CustomEmitterFactory::get() {
-                Ok(factory) => Ok(Emitter::Custom(factory.spawn(id)?)),
-                Err(_) => Err(String::from("Failed get custom emitter factory!")),
-            },
+impl CylinderEmitterBuilder {
+    /// Creates new cylinder emitter builder with 0.5 radius and 1.0 height.
+    pub fn new(base: BaseEmitterBuilder) -> Self {
+        Self {
+            base,
+            radius: 0.5,
+            height: 1.0,
         }
     }
 
-    /// Returns id of current emitter kind.
-    pub fn id(&self) -> i32 {
-        match self {
-            Self::Unknown => -1,
-            Self::Box(_) => -2,
-            Self::Sphere(_) => -3,
-            Self::Custom(custom_emitter) => {
-                let id = custom_emitter.get_kind();
-                assert!(
-                    id >= 0,
-                    "Negative number for emitter kind are reserved for built-in types!"
-                );
-                id
-            }
-        }
+    /// Sets desired radius of the emitter.
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
     }
-}
-
-macro_rules! static_dispatch {
-    ($self:ident, $func:ident, $($args:expr),*) => {
-        match $self {
-            Emitter::Unknown => panic!("Unknown emitter must not be used!"),
-            Emitter::Box(v) => v.$func($($args),*),
-            Emitter::Sphere(v) => v.$func($($args),*),
-            Emitter::Custom(v) => v.$func($($args),*),
-        }
-    };
-}
 
-impl Emit for Emitter {
-    fn emit(&self, particle_system: &ParticleSystem, particle: &mut Particle) {
-        static_dispatch!(self, emit, particle_system, particle)
+    /// Sets desired height of the emitter.
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
     }
-}
 
-impl Clone for Emitter {
-    fn clone(&self) -> Self {
-        match self {
-            Self::Unknown => panic!("Unknown emitter kind is not supported"),
-            Self::Box(box_emitter) => Self::Box(box_emitter.clone()),
-            Self::Sphere(sphere_emitter) => Self::Sphere(sphere_emitter.clone()),
-            Self::Custom(custom_emitter) => Self::Custom(custom_emitter.box_clone()),
-        }
+    /// Creates new cylinder emitter.
+    pub fn build(self) -> Emitter {
+        Emitter::Cylinder(CylinderEmitter {
+            emitter: self.base.build(),
+            radius: self.radius,
+            height: self.height,
+        })
     }
 }
 
-impl Visit for Emitter {
-    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
-        let mut kind_id: i32 = self.id();
-        kind_id.visit("KindId", visitor)?;
-        if visitor.is_reading() {
-            *self = Emitter::new(kind_id)?;
-        }
-
-        static_dispatch!(self, visit, name, visitor)
-    }
+/// Cone emitter places particles in conical volume with apex at position of emitter and
+/// axis parallel to Y axis of particle system. Unlike other emitters it also changes
+/// direction of initial velocity of particles - each particle moves away from the apex,
+/// while speed is defined by length of initial velocity from base emitter. Can be used to
+/// create fountains, muzzle flashes, sparks, etc.
+#[derive(Debug, Clone)]
+pub struct ConeEmitter {
+    emitter: BaseEmitter,
+    angle: f32,
+    height: f32,
 }
 
-impl Deref for Emitter {
+impl Deref for ConeEmitter {
     type Target = BaseEmitter;
 
     fn deref(&self) -> &Self::Target {
-        static_dispatch!(self, deref,)
+        &self.emitter
     }
 }
 
-impl DerefMut for Emitter {
+impl DerefMut for ConeEmitter {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        static_dispatch!(self, deref_mut,)
+        &mut self.emitter
     }
 }
 
-impl Default for Emitter {
+impl Default for ConeEmitter {
     fn default() -> Self {
-        Self::Unknown
+        Self {
+            emitter: Default::default(),
+            angle: 30.0f32.to_radians(),
+            height: 0.0,
+        }
     }
 }
 
-/// Particle limit for emitter.
-#[derive(Copy, Clone, Debug)]
-pub enum ParticleLimit {
-    /// No limit in amount of particles.
-    Unlimited,
-    /// Strict limit in amount of particles.
-    Strict(u32),
+impl ConeEmitter {
+    /// Creates new cone emitter with given half-angle (in radians) and height. Zero height
+    /// means that all particles will be emitted from the apex.
+    pub fn new(emitter: BaseEmitter, angle: f32, height: f32) -> Self {
+        Self {
+            emitter,
+            angle,
+            height,
+        }
+    }
+
+    /// Sets new half-angle of the cone in radians.
+    pub fn set_angle(&mut self, angle: f32) -> &mut Self {
+        self.angle = angle;
+        self
+    }
+
+    /// Returns half-angle of the cone in radians.
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    /// Sets new height of the cone.
+    pub fn set_height(&mut self, height: f32) -> &mut Self {
+        self.height = height;
+        self
+    }
+
+    /// Returns height of the cone.
+    pub fn height(&self) -> f32 {
+        self.height
+    }
 }
 
-impl Visit for ParticleLimit {
+impl Emit for ConeEmitter {
+    fn emit(&self, _particle_system: &ParticleSystem, particle: &mut Particle) {
+        self.emitter.emit(particle);
+        let mut rng = crate::rand::thread_rng();
+        // Uniformly distributed direction within the cone.
+        let cos_angle = self.angle.min(std::f32::consts::PI).cos();
+        let cos_theta = 1.0 - rng.gen::<f32>() * (1.0 - cos_angle);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = rng.gen::<f32>() * 2.0 * std::f32::consts::PI;
+        let direction = Vector3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+        // Distance from apex along the direction, cube root gives uniform distribution
+        // in the volume.
+        let distance = rng.gen::<f32>().cbrt() * self.height / cos_theta.max(f32::EPSILON);
+        particle.position = self.position + direction.scale(distance);
+        particle.velocity = direction.scale(particle.velocity.norm());
+    }
+}
+
+impl Visit for ConeEmitter {
     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
         visitor.enter_region(name)?;
 
-        let mut amount = match self {
-            Self::Unlimited => -1,
-            Self::Strict(value) => *value as i32,
-        };
+        self.emitter.visit("Emitter", visitor)?;
+        self.angle.visit("Angle", visitor)?;
+        self.height.visit("Height", visitor)?;
 
-        amount.visit("Amount", visitor)?;
+        visitor.leave_region()
+    }
+}
 
-        if visitor.is_reading() {
-            *self = if amount < 0 {
-                Self::Unlimited
-            } else {
-                Self::Strict(amount as u32)
-            };
+/// Cone emitter builder allows you to construct cone emitter in declarative manner.
+/// This is typical implementation of Builder pattern.
+pub struct ConeEmitterBuilder {
+    base: BaseEmitterBuilder,
+    angle: f32,
+    height: f32,
+}
+
+impl ConeEmitterBuilder {
+    /// Creates new cone emitter builder with 30 degrees half-angle and zero height.
+    pub fn new(base: BaseEmitterBuilder) -> Self {
+        Self {
+            base,
+            angle: 30.0f32.to_radians(),
+            height: 0.0,
         }
+    }
 
-        visitor.leave_region()
+    /// Sets desired half-angle of the cone in radians.
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        self.angle = angle;
+        self
     }
-}
 
-/// Base emitter contains properties for all other "derived" emitters.
-#[derive(Debug)]
-pub struct BaseEmitter {
-    /// Offset from center of particle system.
-    position: Vector3<f32>,
-    /// Particle spawn rate in unit-per-second. If < 0, spawns `max_particles`,
+    /// Sets desired height of the cone.
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// Creates new cone emitter.
+    pub fn build(self) -> Emitter {
+        Emitter::Cone(ConeEmitter {
+            emitter: self.base.build(),
+            angle: self.angle,
+            height: self.height,
+        })
+    }
+}
+
+/// Disk emitter uniformly places particles on a flat disk in XZ plane of particle system.
+/// Can be used to create ground fog, dust around landing aircraft, shock waves, etc.
+#[derive(Debug, Clone)]
+pub struct DiskEmitter {
+    emitter: BaseEmitter,
+    radius: f32,
+}
+
+impl Deref for DiskEmitter {
+    type Target = BaseEmitter;
+
+    fn deref(&self) -> &Self::Target {
+        &self.emitter
+    }
+}
+
+impl DerefMut for DiskEmitter {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.emitter
+    }
+}
+
+impl Default for DiskEmitter {
+    fn default() -> Self {
+        Self {
+            emitter: Default::default(),
+            radius: 0.5,
+        }
+    }
+}
+
+impl DiskEmitter {
+    /// Creates new disk emitter with given radius.
+    pub fn new(emitter: BaseEmitter, radius: f32) -> Self {
+        Self { emitter, radius }
+    }
+
+    /// Sets new radius of the disk.
+    pub fn set_radius(&mut self, radius: f32) -> &mut Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Returns radius of the disk.
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+}
+
+impl Emit for DiskEmitter {
+    fn emit(&self, _particle_system: &ParticleSystem, particle: &mut Particle) {
+        self.emitter.emit(particle);
+        let mut rng = crate::rand::thread_rng();
+        let (x, z) = random_point_on_disk(&mut rng, self.radius);
+        particle.position = self.position + Vector3::new(x, 0.0, z);
+    }
+}
+
+impl Visit for DiskEmitter {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.emitter.visit("Emitter", visitor)?;
+        self.radius.visit("Radius", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Disk emitter builder allows you to construct disk emitter in declarative manner.
+/// This is typical implementation of Builder pattern.
+pub struct DiskEmitterBuilder {
+    base: BaseEmitterBuilder,
+    radius: f32,
+}
+
+impl DiskEmitterBuilder {
+    /// Creates new disk emitter builder with 0.5 radius.
+    pub fn new(base: BaseEmitterBuilder) -> Self {
+        Self { base, radius: 0.5 }
+    }
+
+    /// Sets desired radius of the disk.
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Creates new disk emitter.
+    pub fn build(self) -> Emitter {
+        Emitter::Disk(DiskEmitter {
+            emitter: self.base.build(),
+            radius: self.radius,
+        })
+    }
+}
+
+fn random_point_on_disk<R: Rng>(rng: &mut R, radius: f32) -> (f32, f32) {
+    // Square root gives uniform distribution over the area.
+    let r = radius * rng.gen::<f32>().sqrt();
+    let angle = rng.gen::<f32>() * 2.0 * std::f32::consts::PI;
+    (r * angle.cos(), r * angle.sin())
+}
+
+/// Defines source of geometry for mesh surface emitter.
+#[derive(Debug, Clone)]
+pub enum MeshSurfaceSource {
+    /// Static geometry, vertices are in local coordinates of particle system. Keep in mind
+    /// that only procedural surface data is serialized, see [`SurfaceSharedData`] docs.
+    Data(Arc<RwLock<SurfaceSharedData>>),
+    /// Surface of a mesh node in the same graph. Current pose of the surface is used, this
+    /// includes skinning, so particles can be emitted from animated characters.
+    Mesh {
+        /// Handle of mesh node.
+        mesh: Handle<Node>,
+        /// Index of surface of the mesh.
+        surface: usize,
+    },
+}
+
+impl Default for MeshSurfaceSource {
+    fn default() -> Self {
+        Self::Mesh {
+            mesh: Handle::NONE,
+            surface: 0,
+        }
+    }
+}
+
+impl Visit for MeshSurfaceSource {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id: u32 = match self {
+            Self::Data(_) => 0,
+            Self::Mesh { .. } => 1,
+        };
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = match id {
+                0 => Self::Data(Default::default()),
+                1 => Self::default(),
+                _ => return Err(format!("Invalid mesh surface source id {}", id).into()),
+            };
+        }
+        match self {
+            Self::Data(data) => data.visit("Data", visitor)?,
+            Self::Mesh { mesh, surface } => {
+                mesh.visit("Mesh", visitor)?;
+                let mut index = *surface as u32;
+                index.visit("Surface", visitor)?;
+                *surface = index as usize;
+            }
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Defines which parts of mesh surface are used to emit particles.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MeshEmissionMode {
+    /// Particles are emitted from random points on triangles, larger triangles emit
+    /// proportionally more particles.
+    Surface,
+    /// Particles are emitted from random points on edges of triangles, longer edges emit
+    /// proportionally more particles. Useful for outlines, electric arcs, etc.
+    Edges,
+}
+
+impl Default for MeshEmissionMode {
+    fn default() -> Self {
+        Self::Surface
+    }
+}
+
+impl Visit for MeshEmissionMode {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id: u32 = match self {
+            Self::Surface => 0,
+            Self::Edges => 1,
+        };
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = match id {
+                0 => Self::Surface,
+                1 => Self::Edges,
+                _ => return Err(format!("Invalid mesh emission mode id {}", id).into()),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// Mesh surface emitter places particles at random points on a surface (or its edges),
+/// distribution is uniform over the surface area (or total edge length). Geometry can be
+/// taken from static surface data or from a surface of a mesh node in its current pose,
+/// see [`MeshSurfaceSource`].
+#[derive(Debug, Clone, Default)]
+pub struct MeshSurfaceEmitter {
+    emitter: BaseEmitter,
+    source: MeshSurfaceSource,
+    mode: MeshEmissionMode,
+    // Cached sampling data in local coordinates of particle system, each primitive is either
+    // a triangle or an edge (third point is unused then).
+    primitives: Vec<[Vector3<f32>; 3]>,
+    cumulative_weights: Vec<f32>,
+    cache_valid: bool,
+}
+
+impl Deref for MeshSurfaceEmitter {
+    type Target = BaseEmitter;
+
+    fn deref(&self) -> &Self::Target {
+        &self.emitter
+    }
+}
+
+impl DerefMut for MeshSurfaceEmitter {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.emitter
+    }
+}
+
+impl MeshSurfaceEmitter {
+    /// Creates new mesh surface emitter.
+    pub fn new(emitter: BaseEmitter, source: MeshSurfaceSource, mode: MeshEmissionMode) -> Self {
+        Self {
+            emitter,
+            source,
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// Sets new source of geometry.
+    pub fn set_source(&mut self, source: MeshSurfaceSource) -> &mut Self {
+        self.source = source;
+        self.cache_valid = false;
+        self
+    }
+
+    /// Returns current source of geometry.
+    pub fn source(&self) -> &MeshSurfaceSource {
+        &self.source
+    }
+
+    /// Sets new emission mode.
+    pub fn set_mode(&mut self, mode: MeshEmissionMode) -> &mut Self {
+        self.mode = mode;
+        self.cache_valid = false;
+        self
+    }
+
+    /// Returns current emission mode.
+    pub fn mode(&self) -> MeshEmissionMode {
+        self.mode
+    }
+
+    /// Forces emitter to rebuild its sampling data, must be called if static surface data
+    /// was modified. Mesh sources are rebuilt every frame automatically.
+    pub fn invalidate(&mut self) {
+        self.cache_valid = false;
+    }
+
+    pub(in crate) fn remap(&mut self, old_new_map: &HashMap<Handle<Node>, Handle<Node>>) {
+        if let MeshSurfaceSource::Mesh { mesh, .. } = &mut self.source {
+            if let Some(&new_mesh) = old_new_map.get(mesh) {
+                *mesh = new_mesh;
+            }
+        }
+    }
+
+    /// Rebuilds sampling data using current state of graph. `inv_owner_transform` is
+    /// inverse global transform of particle system which owns the emitter.
+    pub(in crate) fn update_geometry(&mut self, graph: &Graph, inv_owner_transform: Matrix4<f32>) {
+        let (positions, triangles) = match &self.source {
+            MeshSurfaceSource::Data(data) => {
+                if self.cache_valid {
+                    return;
+                }
+                self.cache_valid = true;
+                let data = data.read().unwrap();
+                let positions = data
+                    .get_vertices()
+                    .iter()
+                    .map(|vertex| vertex.position)
+                    .collect::<Vec<_>>();
+                (positions, data.triangles().to_vec())
+            }
+            MeshSurfaceSource::Mesh { mesh, surface } => {
+                let surface = if graph.is_valid_handle(*mesh) {
+                    match &graph[*mesh] {
+                        Node::Mesh(mesh) => mesh.surfaces().get(*surface),
+                        _ => None,
+                    }
+                } else {
+                    None
+                };
+                let surface = match surface {
+                    Some(surface) => surface,
+                    None => {
+                        self.primitives.clear();
+                        self.cumulative_weights.clear();
+                        return;
+                    }
+                };
+                let mesh_transform = graph[*mesh].global_transform();
+                let mut positions = Vec::new();
+                if surface.bones().is_empty() {
+                    let transform = inv_owner_transform * mesh_transform;
+                    let data = surface.data();
+                    let data = data.read().unwrap();
+                    positions.extend(data.get_vertices().iter().map(|vertex| {
+                        transform
+                            .transform_point(&Point3::from(vertex.position))
+                            .coords
+                    }));
+                } else {
+                    surface.skinned_positions(graph, mesh_transform, &mut positions);
+                    for position in positions.iter_mut() {
+                        *position = inv_owner_transform
+                            .transform_point(&Point3::from(*position))
+                            .coords;
+                    }
+                }
+                let triangles = surface.data().read().unwrap().triangles().to_vec();
+                (positions, triangles)
+            }
+        };
+        self.rebuild(&positions, &triangles);
+    }
+
+    fn rebuild(&mut self, positions: &[Vector3<f32>], triangles: &[TriangleDefinition]) {
+        self.primitives.clear();
+        self.cumulative_weights.clear();
+        let mut total = 0.0;
+        for triangle in triangles {
+            let [a, b, c] = [
+                positions[triangle.0[0] as usize],
+                positions[triangle.0[1] as usize],
+                positions[triangle.0[2] as usize],
+            ];
+            match self.mode {
+                MeshEmissionMode::Surface => {
+                    total += (b - a).cross(&(c - a)).norm() * 0.5;
+                    self.primitives.push([a, b, c]);
+                    self.cumulative_weights.push(total);
+                }
+                MeshEmissionMode::Edges => {
+                    for &(begin, end) in &[(a, b), (b, c), (c, a)] {
+                        total += (end - begin).norm();
+                        self.primitives.push([begin, end, end]);
+                        self.cumulative_weights.push(total);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns random point on cached geometry.
+    fn random_point<R: Rng>(&self, rng: &mut R) -> Option<Vector3<f32>> {
+        let total = *self.cumulative_weights.last()?;
+        let value = rng.gen::<f32>() * total;
+        let index = self
+            .cumulative_weights
+            .partition_point(|&weight| weight < value)
+            .min(self.primitives.len() - 1);
+        let [a, b, c] = self.primitives[index];
+        Some(match self.mode {
+            MeshEmissionMode::Surface => {
+                let (mut u, mut v) = (rng.gen::<f32>(), rng.gen::<f32>());
+                if u + v > 1.0 {
+                    u = 1.0 - u;
+                    v = 1.0 - v;
+                }
+                a + (b - a).scale(u) + (c - a).scale(v)
+            }
+            MeshEmissionMode::Edges => a + (b - a).scale(rng.gen::<f32>()),
+        })
+    }
+}
+
+impl Emit for MeshSurfaceEmitter {
+    fn emit(&self, _particle_system: &ParticleSystem, particle: &mut Particle) {
+        self.emitter.emit(particle);
+        let mut rng = crate::rand::thread_rng();
+        particle.position = self.position + self.random_point(&mut rng).unwrap_or_default();
+    }
+}
+
+impl Visit for MeshSurfaceEmitter {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.emitter.visit("Emitter", visitor)?;
+        self.source.visit("Source", visitor)?;
+        self.mode.visit("Mode", visitor)?;
+        // Sampling data is not serialized, it will be rebuilt on next update.
+        self.cache_valid = false;
+
+        visitor.leave_region()
+    }
+}
+
+/// Mesh surface emitter builder allows you to construct mesh surface emitter in declarative
+/// manner. This is typical implementation of Builder pattern.
+pub struct MeshSurfaceEmitterBuilder {
+    base: BaseEmitterBuilder,
+    source: MeshSurfaceSource,
+    mode: MeshEmissionMode,
+}
+
+impl MeshSurfaceEmitterBuilder {
+    /// Creates new mesh surface emitter builder with given source of geometry, particles will
+    /// be emitted from surface.
+    pub fn new(base: BaseEmitterBuilder, source: MeshSurfaceSource) -> Self {
+        Self {
+            base,
+            source,
+            mode: MeshEmissionMode::Surface,
+        }
+    }
+
+    /// Sets desired emission mode.
+    pub fn with_mode(mut self, mode: MeshEmissionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Creates new mesh surface emitter.
+    pub fn build(self) -> Emitter {
+        Emitter::MeshSurface(MeshSurfaceEmitter::new(
+            self.base.build(),
+            self.source,
+            self.mode,
+        ))
+    }
+}
+
+/// Callback that creates emitter by its numeric identifier.
+pub type CustomEmitterFactoryCallback =
+    dyn Fn(i32) -> Result<Box<dyn CustomEmitter>, String> + Send + 'static;
+
+/// Custom emitter factory is used to be able to make your own emitters if none of
+/// predefined are not suits to your case.
+pub struct CustomEmitterFactory {
+    callback: Option<Box<CustomEmitterFactoryCallback>>,
+}
+
+impl Default for CustomEmitterFactory {
+    fn default() -> Self {
+        Self { callback: None }
+    }
+}
+
+impl CustomEmitterFactory {
+    /// Locks factory singleton and returns lock result.
+    pub fn get() -> LockResult<MutexGuard<'static, Self>> {
+        CUSTOM_EMITTER_FACTORY_INSTANCE.lock()
+    }
+
+    /// Sets new callback that will be used to create custom emitters.
+    pub fn set_callback(&mut self, callback: Box<CustomEmitterFactoryCallback>) {
+        self.callback = Some(callback);
+    }
+
+    fn spawn(&self, kind: i32) -> Result<Box<dyn CustomEmitter>, String> {
+        match &self.callback {
+            Some(callback) => callback(kind),
+            None => Err(String::from("no callback specified")),
+        }
+    }
+}
+
+lazy_static! {
+    static ref CUSTOM_EMITTER_FACTORY_INSTANCE: Mutex<CustomEmitterFactory> =
+        Mutex::new(Default::default());
+}
+
+/// Custom emitter allows you to make your own emitters. It can be implemented on serializable
+/// types only!
+///
+/// # Example
+///
+/// TODO
+pub trait CustomEmitter:
+    Any + Emit + Visit + Send + Debug + Deref<Target = BaseEmitter> + DerefMut
+{
+    /// Creates boxed copy of custom emitter.
+    fn box_clone(&self) -> Box<dyn CustomEmitter>;
+
+    /// Returns unique of custom emitter. Must never be negative!
+    /// Negative numbers reserved for built-in kinds.
+    fn get_kind(&self) -> i32;
+}
+
+/// Emitter is an enum over all possible emitter types, they all must
+/// use BaseEmitter which contains base functionality.
+#[derive(Debug)]
+pub enum Emitter {
+    /// Unknown kind here is just to have ability to implement Default trait,
+    /// must not be used at runtime!
+    Unknown,
+    /// See BoxEmitter docs.
+    Box(BoxEmitter),
+    /// See SphereEmitter docs.
+    Sphere(SphereEmitter),
+    /// See CylinderEmitter docs.
+    Cylinder(CylinderEmitter),
+    /// See ConeEmitter docs.
+    Cone(ConeEmitter),
+    /// See DiskEmitter docs.
+    Disk(DiskEmitter),
+    /// See MeshSurfaceEmitter docs.
+    MeshSurface(MeshSurfaceEmitter),
+    /// Custom emitter.
+    Custom(Box<dyn CustomEmitter>),
+}
+
+impl Emitter {
+    /// Creates new emitter from given id.
+    pub fn new(id: i32) -> Result<Self, String> {
+        match id {
+            -1 => Ok(Self::Unknown),
+            -2 => Ok(Self::Box(Default::default())),
+            -3 => Ok(Self::Sphere(Default::default())),
+            -4 => Ok(Self::Cylinder(Default::default())),
+            -5 => Ok(Self::Cone(Default::default())),
+            -6 => Ok(Self::Disk(Default::default())),
+            -7 => Ok(Self::MeshSurface(Default::default())),
+            _ => match CustomEmitterFactory::get() {
+                Ok(factory) => Ok(Emitter::Custom(factory.spawn(id)?)),
+                Err(_) => Err(String::from("Failed get custom emitter factory!")),
+            },
+        }
+    }
+
+    /// Returns id of current emitter kind.
+    pub fn id(&self) -> i32 {
+        match self {
+            Self::Unknown => -1,
+            Self::Box(_) => -2,
+            Self::Sphere(_) => -3,
+            Self::Cylinder(_) => -4,
+            Self::Cone(_) => -5,
+            Self::Disk(_) => -6,
+            Self::MeshSurface(_) => -7,
+            Self::Custom(custom_emitter) => {
+                let id = custom_emitter.get_kind();
+                assert!(
+                    id >= 0,
+                    "Negative number for emitter kind are reserved for built-in types!"
+                );
+                id
+            }
+        }
+    }
+}
+
+macro_rules! static_dispatch {
+    ($self:ident, $func:ident, $($args:expr),*) => {
+        match $self {
+            Emitter::Unknown => panic!("Unknown emitter must not be used!"),
+            Emitter::Box(v) => v.$func($($args),*),
+            Emitter::Sphere(v) => v.$func($($args),*),
+            Emitter::Cylinder(v) => v.$func($($args),*),
+            Emitter::Cone(v) => v.$func($($args),*),
+            Emitter::Disk(v) => v.$func($($args),*),
+            Emitter::MeshSurface(v) => v.$func($($args),*),
+            Emitter::Custom(v) => v.$func($($args),*),
+        }
+    };
+}
+
+impl Emit for Emitter {
+    fn emit(&self, particle_system: &ParticleSystem, particle: &mut Particle) {
+        static_dispatch!(self, emit, particle_system, particle)
+    }
+}
+
+impl Clone for Emitter {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Unknown => panic!("Unknown emitter kind is not supported"),
+            Self::Box(box_emitter) => Self::Box(box_emitter.clone()),
+            Self::Sphere(sphere_emitter) => Self::Sphere(sphere_emitter.clone()),
+            Self::Cylinder(cylinder_emitter) => Self::Cylinder(cylinder_emitter.clone()),
+            Self::Cone(cone_emitter) => Self::Cone(cone_emitter.clone()),
+            Self::Disk(disk_emitter) => Self::Disk(disk_emitter.clone()),
+            Self::MeshSurface(mesh_emitter) => Self::MeshSurface(mesh_emitter.clone()),
+            Self::Custom(custom_emitter) => Self::Custom(custom_emitter.box_clone()),
+        }
+    }
+}
+
+impl Visit for Emitter {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut kind_id: i32 = self.id();
+        kind_id.visit("KindId", visitor)?;
+        if visitor.is_reading() {
+            *self = Emitter::new(kind_id)?;
+        }
+
+        static_dispatch!(self, visit, name, visitor)
+    }
+}
+
+impl Deref for Emitter {
+    type Target = BaseEmitter;
+
+    fn deref(&self) -> &Self::Target {
+        static_dispatch!(self, deref,)
+    }
+}
+
+impl DerefMut for Emitter {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        static_dispatch!(self, deref_mut,)
+    }
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self::Unknown
+    }
+}
+
+/// Particle limit for emitter.
+#[derive(Copy, Clone, Debug)]
+pub enum ParticleLimit {
+    /// No limit in amount of particles.
+    Unlimited,
+    /// Strict limit in amount of particles.
+    Strict(u32),
+}
+
+impl Visit for ParticleLimit {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut amount = match self {
+            Self::Unlimited => -1,
+            Self::Strict(value) => *value as i32,
+        };
+
+        amount.visit("Amount", visitor)?;
+
+        if visitor.is_reading() {
+            *self = if amount < 0 {
+                Self::Unlimited
+            } else {
+                Self::Strict(amount as u32)
+            };
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Defines when sub-emitter spawns its particles.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SubEmitterTrigger {
+    /// Particles are spawned when particle of parent emitter is born.
+    Birth,
+    /// Particles are spawned when particle of parent emitter dies, either because of its
+    /// lifetime or because it was killed by collision.
+    Death,
+}
+
+impl Default for SubEmitterTrigger {
+    fn default() -> Self {
+        Self::Birth
+    }
+}
+
+impl Visit for SubEmitterTrigger {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id: u32 = match self {
+            Self::Birth => 0,
+            Self::Death => 1,
+        };
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = match id {
+                0 => Self::Birth,
+                1 => Self::Death,
+                _ => return Err(format!("Invalid sub-emitter trigger id {}", id).into()),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// Sub-emitter spawns particles using another emitter of the same particle system at the
+/// position of a particle of parent emitter when the particle is born or dies. It can be
+/// used to create fireworks, sparks on impacts, smoke trails of debris, etc.
+///
+/// Emitters that are used as sub-emitters do not spawn particles on their own, they only
+/// spawn particles when triggered. Particles spawned by sub-emitters do not trigger birth
+/// sub-emitters, this prevents infinite chains of spawning.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SubEmitter {
+    /// Index of emitter in particle system that will spawn particles.
+    pub emitter: u32,
+    /// Event that triggers spawning.
+    pub trigger: SubEmitterTrigger,
+    /// Amount of particles to spawn per event.
+    pub count: u32,
+}
+
+impl Visit for SubEmitter {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.emitter.visit("Emitter", visitor)?;
+        self.trigger.visit("Trigger", visitor)?;
+        self.count.visit("Count", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Base emitter contains properties for all other "derived" emitters.
+#[derive(Debug)]
+pub struct BaseEmitter {
+    /// Offset from center of particle system.
+    position: Vector3<f32>,
+    /// Particle spawn rate in unit-per-second. If < 0, spawns `max_particles`,
     /// spawns nothing if `max_particles` < 0
     particle_spawn_rate: u32,
     /// Maximum amount of particles emitter can emit. Unlimited if < 0
@@ -648,6 +1487,7 @@ pub struct BaseEmitter {
     particles_to_spawn: usize,
     resurrect_particles: bool,
     spawned_particles: u64,
+    sub_emitters: Vec<SubEmitter>,
 }
 
 /// Emitter builder allows you to construct emitter in declarative manner.
@@ -665,6 +1505,7 @@ pub struct BaseEmitterBuilder {
     rotation_speed: Option<NumericRange<f32>>,
     rotation: Option<NumericRange<f32>>,
     resurrect_particles: bool,
+    sub_emitters: Vec<SubEmitter>,
 }
 
 impl Default for BaseEmitterBuilder {
@@ -689,6 +1530,7 @@ impl BaseEmitterBuilder {
             rotation_speed: None,
             rotation: None,
             resurrect_particles: true,
+            sub_emitters: Default::default(),
         }
     }
 
@@ -764,6 +1606,12 @@ impl BaseEmitterBuilder {
         self
     }
 
+    /// Sets desired sub-emitters.
+    pub fn with_sub_emitters(mut self, sub_emitters: Vec<SubEmitter>) -> Self {
+        self.sub_emitters = sub_emitters;
+        self
+    }
+
     /// Creates new instance of emitter.
     pub fn build(self) -> BaseEmitter {
         BaseEmitter {
@@ -799,6 +1647,7 @@ impl BaseEmitterBuilder {
             particles_to_spawn: 0,
             resurrect_particles: self.resurrect_particles,
             spawned_particles: 0,
+            sub_emitters: self.sub_emitters,
         }
     }
 }
@@ -825,6 +1674,18 @@ impl BaseEmitter {
         self.spawned_particles += self.particles_to_spawn as u64;
     }
 
+    /// Returns true if emitter has spawned every particle it could and won't spawn any more.
+    /// Emitters with unlimited amount of particles or with enabled particle resurrection
+    /// never finish.
+    pub fn is_finished(&self) -> bool {
+        match self.max_particles {
+            ParticleLimit::Unlimited => false,
+            ParticleLimit::Strict(max_particles) => {
+                !self.resurrect_particles && self.spawned_particles > u64::from(max_particles)
+            }
+        }
+    }
+
     /// Initializes particle with new state. Every custom emitter must call this method,
     /// otherwise you will get weird behavior of emitted particles.
     pub fn emit(&self, particle: &mut Particle) {
@@ -842,6 +1703,22 @@ impl BaseEmitter {
         particle.rotation_speed = self.rotation_speed.random();
     }
 
+    /// Adds new sub-emitter.
+    pub fn add_sub_emitter(&mut self, sub_emitter: SubEmitter) -> &mut Self {
+        self.sub_emitters.push(sub_emitter);
+        self
+    }
+
+    /// Returns shared reference to list of sub-emitters.
+    pub fn sub_emitters(&self) -> &[SubEmitter] {
+        &self.sub_emitters
+    }
+
+    /// Returns mutable reference to list of sub-emitters.
+    pub fn sub_emitters_mut(&mut self) -> &mut Vec<SubEmitter> {
+        &mut self.sub_emitters
+    }
+
     /// Sets new position of emitter in local coordinates.
     pub fn set_position(&mut self, position: Vector3<f32>) -> &mut Self {
         self.position = position;
@@ -994,77 +1871,331 @@ impl BaseEmitter {
     }
 }
 
-impl Visit for BaseEmitter {
+impl Visit for BaseEmitter {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.position.visit("Position", visitor)?;
+        self.particle_spawn_rate.visit("SpawnRate", visitor)?;
+        self.max_particles.visit("MaxParticles", visitor)?;
+        self.lifetime.visit("LifeTime", visitor)?;
+        self.size.visit("Size", visitor)?;
+        self.size_modifier.visit("SizeModifier", visitor)?;
+        self.x_velocity.visit("XVelocity", visitor)?;
+        self.y_velocity.visit("YVelocity", visitor)?;
+        self.z_velocity.visit("ZVelocity", visitor)?;
+        self.rotation_speed.visit("RotationSpeed", visitor)?;
+        self.rotation.visit("Rotation", visitor)?;
+        self.alive_particles.visit("AliveParticles", visitor)?;
+        self.time.visit("Time", visitor)?;
+        self.resurrect_particles
+            .visit("ResurrectParticles", visitor)?;
+        self.spawned_particles.visit("SpawnedParticles", visitor)?;
+        let _ = self.sub_emitters.visit("SubEmitters", visitor);
+
+        visitor.leave_region()
+    }
+}
+
+impl Clone for BaseEmitter {
+    fn clone(&self) -> Self {
+        Self {
+            position: self.position,
+            particle_spawn_rate: self.particle_spawn_rate,
+            max_particles: self.max_particles,
+            lifetime: self.lifetime,
+            size: self.size,
+            size_modifier: self.size_modifier,
+            x_velocity: self.x_velocity,
+            y_velocity: self.y_velocity,
+            z_velocity: self.z_velocity,
+            rotation_speed: self.rotation_speed,
+            rotation: self.rotation,
+            alive_particles: self.alive_particles.clone(),
+            time: self.time,
+            particles_to_spawn: 0,
+            resurrect_particles: self.resurrect_particles,
+            spawned_particles: self.spawned_particles,
+            sub_emitters: self.sub_emitters.clone(),
+        }
+    }
+}
+
+impl Default for BaseEmitter {
+    fn default() -> Self {
+        Self {
+            position: Vector3::default(),
+            particle_spawn_rate: 0,
+            max_particles: ParticleLimit::Unlimited,
+            lifetime: NumericRange::new(5.0, 10.0),
+            size: NumericRange::new(0.125, 0.250),
+            size_modifier: NumericRange::new(0.0005, 0.0010),
+            x_velocity: NumericRange::new(-0.001, 0.001),
+            y_velocity: NumericRange::new(-0.001, 0.001),
+            z_velocity: NumericRange::new(-0.001, 0.001),
+            rotation_speed: NumericRange::new(-0.02, 0.02),
+            rotation: NumericRange::new(-std::f32::consts::PI, std::f32::consts::PI),
+            alive_particles: Cell::new(0),
+            time: 0.0,
+            particles_to_spawn: 0,
+            resurrect_particles: true,
+            spawned_particles: 0,
+            sub_emitters: Default::default(),
+        }
+    }
+}
+
+/// Changes velocity of particles over their lifetime.
+#[derive(Clone, Debug, Default)]
+pub struct VelocityOverLifetime {
+    /// Velocity in units per second in local coordinates of particle system, it is added to
+    /// velocity of every particle.
+    pub linear: Vector3<f32>,
+    /// Multiplier for velocity of particles over normalized lifetime, empty curve means 1.0.
+    pub speed_multiplier: Curve,
+}
+
+impl Visit for VelocityOverLifetime {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.linear.visit("Linear", visitor)?;
+        self.speed_multiplier.visit("SpeedMultiplier", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Turbulence is a smooth pseudo-random vector field that displaces particles. It is cheap
+/// analytic field (sum of sine waves), not a true noise, but it is enough to make smoke and
+/// dust look alive.
+#[derive(Clone, Debug)]
+pub struct Turbulence {
+    /// Maximum displacement speed in units per second.
+    pub strength: f32,
+    /// Spatial frequency of the field, larger values give smaller swirls.
+    pub frequency: f32,
+    /// How fast the field changes over time.
+    pub scroll_speed: f32,
+}
+
+impl Default for Turbulence {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            frequency: 1.0,
+            scroll_speed: 1.0,
+        }
+    }
+}
+
+impl Turbulence {
+    /// Returns velocity of the field at given point and time.
+    pub fn sample(&self, position: Vector3<f32>, time: f32) -> Vector3<f32> {
+        let p = position.scale(self.frequency);
+        let t = time * self.scroll_speed;
+        Vector3::new(
+            (p.y + t).sin() + (p.z * 1.7 - t * 0.6).cos(),
+            (p.z + t * 1.3).sin() + (p.x * 1.3 + t * 0.8).cos(),
+            (p.x - t * 0.9).sin() + (p.y * 1.9 + t).cos(),
+        )
+        .scale(0.5 * self.strength)
+    }
+}
+
+impl Visit for Turbulence {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.strength.visit("Strength", visitor)?;
+        self.frequency.visit("Frequency", visitor)?;
+        self.scroll_speed.visit("ScrollSpeed", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Flipbook animation for particles, texture of particle system is treated as a grid of
+/// frames which are played over lifetime of each particle. Frames go from left to right,
+/// from top to bottom.
+#[derive(Clone, Debug)]
+pub struct TextureSheetAnimation {
+    /// Amount of columns in the texture.
+    pub columns: u32,
+    /// Amount of rows in the texture.
+    pub rows: u32,
+    /// Amount of frames, can be less than columns * rows if last row is not full.
+    pub frame_count: u32,
+    /// How many times animation will be played over lifetime of a particle.
+    pub cycles: f32,
+}
+
+impl Default for TextureSheetAnimation {
+    fn default() -> Self {
+        Self {
+            columns: 1,
+            rows: 1,
+            frame_count: 1,
+            cycles: 1.0,
+        }
+    }
+}
+
+impl TextureSheetAnimation {
+    /// Creates new animation for given grid, all frames of the grid are used and played
+    /// once over lifetime.
+    pub fn new(columns: u32, rows: u32) -> Self {
+        Self {
+            columns,
+            rows,
+            frame_count: columns * rows,
+            cycles: 1.0,
+        }
+    }
+
+    fn frame_count(&self) -> u32 {
+        self.frame_count.min(self.columns * self.rows).max(1)
+    }
+
+    /// Returns frame index at given normalized lifetime.
+    pub fn frame_at(&self, k: f32) -> u32 {
+        let frame_count = self.frame_count();
+        let frame = (k * self.cycles).fract() * frame_count as f32;
+        (frame as u32).min(frame_count - 1)
+    }
+
+    /// Returns texture coordinates rectangle of given frame, x and y are offset, z and w are
+    /// size of the rectangle.
+    pub fn frame_uv_rect(&self, frame: u32) -> Vector4<f32> {
+        let columns = self.columns.max(1);
+        let rows = self.rows.max(1);
+        let (w, h) = (1.0 / columns as f32, 1.0 / rows as f32);
+        Vector4::new(
+            (frame % columns) as f32 * w,
+            (frame / columns) as f32 * h,
+            w,
+            h,
+        )
+    }
+}
+
+impl Visit for TextureSheetAnimation {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.columns.visit("Columns", visitor)?;
+        self.rows.visit("Rows", visitor)?;
+        self.frame_count.visit("FrameCount", visitor)?;
+        self.cycles.visit("Cycles", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Defines how particles collide with colliders of scene physics. Particles are treated as
+/// points, their movement during update tick is tested against colliders using ray casting,
+/// so even fast particles will not pass through thin walls.
+#[derive(Clone, Debug)]
+pub struct ParticleCollision {
+    /// Fraction of normal velocity that will be kept after impact, 0.0 - particle will stick
+    /// to a surface, 1.0 - perfectly elastic bounce.
+    pub restitution: f32,
+    /// Fraction of tangential velocity that will be lost after impact, 0.0 - particle will
+    /// slide freely, 1.0 - particle will lose all tangential velocity.
+    pub friction: f32,
+    /// Whether particle should be destroyed on first impact or not.
+    pub kill_on_hit: bool,
+    /// Collision groups to test particles against.
+    pub groups: InteractionGroups,
+}
+
+impl Default for ParticleCollision {
+    fn default() -> Self {
+        Self {
+            restitution: 0.5,
+            friction: 0.1,
+            kill_on_hit: false,
+            groups: InteractionGroups::all(),
+        }
+    }
+}
+
+impl Visit for ParticleCollision {
     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
         visitor.enter_region(name)?;
 
-        self.position.visit("Position", visitor)?;
-        self.particle_spawn_rate.visit("SpawnRate", visitor)?;
-        self.max_particles.visit("MaxParticles", visitor)?;
-        self.lifetime.visit("LifeTime", visitor)?;
-        self.size.visit("Size", visitor)?;
-        self.size_modifier.visit("SizeModifier", visitor)?;
-        self.x_velocity.visit("XVelocity", visitor)?;
-        self.y_velocity.visit("YVelocity", visitor)?;
-        self.z_velocity.visit("ZVelocity", visitor)?;
-        self.rotation_speed.visit("RotationSpeed", visitor)?;
-        self.rotation.visit("Rotation", visitor)?;
-        self.alive_particles.visit("AliveParticles", visitor)?;
-        self.time.visit("Time", visitor)?;
-        self.resurrect_particles
-            .visit("ResurrectParticles", visitor)?;
-        self.spawned_particles.visit("SpawnedParticles", visitor)?;
+        self.restitution.visit("Restitution", visitor)?;
+        self.friction.visit("Friction", visitor)?;
+        self.kill_on_hit.visit("KillOnHit", visitor)?;
+        let mut groups = self.groups.0;
+        groups.visit("Groups", visitor)?;
+        self.groups = InteractionGroups(groups);
 
         visitor.leave_region()
     }
 }
 
-impl Clone for BaseEmitter {
-    fn clone(&self) -> Self {
-        Self {
-            position: self.position,
-            particle_spawn_rate: self.particle_spawn_rate,
-            max_particles: self.max_particles,
-            lifetime: self.lifetime,
-            size: self.size,
-            size_modifier: self.size_modifier,
-            x_velocity: self.x_velocity,
-            y_velocity: self.y_velocity,
-            z_velocity: self.z_velocity,
-            rotation_speed: self.rotation_speed,
-            rotation: self.rotation,
-            alive_particles: self.alive_particles.clone(),
-            time: self.time,
-            particles_to_spawn: 0,
-            resurrect_particles: self.resurrect_particles,
-            spawned_particles: self.spawned_particles,
-        }
-    }
+/// Information about particle impact with a collider, can be used to spawn decals, play sounds
+/// and so on. See [`ParticleSystem::impacts`].
+#[derive(Clone, Debug)]
+pub struct ParticleImpact {
+    /// Collider with which particle has collided.
+    pub collider: ColliderHandle,
+    /// Impact position in world coordinates.
+    pub position: Vector3<f32>,
+    /// Surface normal at impact position in world coordinates.
+    pub normal: Vector3<f32>,
+    /// Velocity of particle right before impact in world coordinates, units are the same as
+    /// for [`Particle::velocity`].
+    pub velocity: Vector3<f32>,
+    /// Whether particle was destroyed by impact or not.
+    pub killed: bool,
 }
 
-impl Default for BaseEmitter {
+/// Defines coordinate system in which particles are simulated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SimulationSpace {
+    /// Particles are simulated in local coordinates of particle system, so they move together
+    /// with the node.
+    Local,
+    /// Particles are simulated in world coordinates, emitted particles stay where they were
+    /// emitted even if the node moves. Useful for smoke trails, exhaust, etc.
+    World,
+}
+
+impl Default for SimulationSpace {
     fn default() -> Self {
-        Self {
-            position: Vector3::default(),
-            particle_spawn_rate: 0,
-            max_particles: ParticleLimit::Unlimited,
-            lifetime: NumericRange::new(5.0, 10.0),
-            size: NumericRange::new(0.125, 0.250),
-            size_modifier: NumericRange::new(0.0005, 0.0010),
-            x_velocity: NumericRange::new(-0.001, 0.001),
-            y_velocity: NumericRange::new(-0.001, 0.001),
-            z_velocity: NumericRange::new(-0.001, 0.001),
-            rotation_speed: NumericRange::new(-0.02, 0.02),
-            rotation: NumericRange::new(-std::f32::consts::PI, std::f32::consts::PI),
-            alive_particles: Cell::new(0),
-            time: 0.0,
-            particles_to_spawn: 0,
-            resurrect_particles: true,
-            spawned_particles: 0,
+        Self::Local
+    }
+}
+
+impl Visit for SimulationSpace {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id: u32 = match self {
+            Self::Local => 0,
+            Self::World => 1,
+        };
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = match id {
+                0 => Self::Local,
+                1 => Self::World,
+                _ => return Err(format!("Invalid simulation space id {}", id).into()),
+            };
         }
+        Ok(())
     }
 }
 
+struct SubEmission {
+    emitter: usize,
+    position: Vector3<f32>,
+    trigger: SubEmitterTrigger,
+}
+
+/// Time step used to prewarm particle systems.
+const PREWARM_STEP: f32 = 1.0 / 30.0;
+
 /// See module docs.
 #[derive(Debug)]
 pub struct ParticleSystem {
@@ -1075,6 +2206,18 @@ pub struct ParticleSystem {
     texture: Option<Texture>,
     acceleration: Vector3<f32>,
     color_over_lifetime: Option<ColorGradient>,
+    size_over_lifetime: Option<Curve>,
+    velocity_over_lifetime: Option<VelocityOverLifetime>,
+    drag: f32,
+    turbulence: Option<Turbulence>,
+    rotation_over_lifetime: Option<Curve>,
+    texture_sheet_animation: Option<TextureSheetAnimation>,
+    collision: Option<ParticleCollision>,
+    impacts: Vec<ParticleImpact>,
+    time: f32,
+    simulation_space: SimulationSpace,
+    prewarm_time: f32,
+    prewarmed: bool,
 }
 
 impl Deref for ParticleSystem {
@@ -1102,6 +2245,18 @@ impl ParticleSystem {
             texture: self.texture.clone(),
             acceleration: self.acceleration,
             color_over_lifetime: self.color_over_lifetime.clone(),
+            size_over_lifetime: self.size_over_lifetime.clone(),
+            velocity_over_lifetime: self.velocity_over_lifetime.clone(),
+            drag: self.drag,
+            turbulence: self.turbulence.clone(),
+            rotation_over_lifetime: self.rotation_over_lifetime.clone(),
+            texture_sheet_animation: self.texture_sheet_animation.clone(),
+            collision: self.collision.clone(),
+            impacts: Default::default(),
+            time: self.time,
+            simulation_space: self.simulation_space,
+            prewarm_time: self.prewarm_time,
+            prewarmed: self.prewarmed,
         }
     }
 
@@ -1110,6 +2265,16 @@ impl ParticleSystem {
         self.emitters.push(emitter)
     }
 
+    /// Returns shared reference to list of emitters.
+    pub fn emitters(&self) -> &[Emitter] {
+        &self.emitters
+    }
+
+    /// Returns mutable reference to list of emitters.
+    pub fn emitters_mut(&mut self) -> &mut Vec<Emitter> {
+        &mut self.emitters
+    }
+
     /// Returns current acceleration for particles in particle system.
     pub fn acceleration(&self) -> Vector3<f32> {
         self.acceleration
@@ -1126,54 +2291,307 @@ impl ParticleSystem {
         self.color_over_lifetime = Some(gradient)
     }
 
+    /// Sets new size curve, it defines multiplier for size of particles over normalized
+    /// lifetime.
+    pub fn set_size_over_lifetime(&mut self, curve: Option<Curve>) {
+        self.size_over_lifetime = curve;
+    }
+
+    /// Returns current size curve.
+    pub fn size_over_lifetime(&self) -> Option<&Curve> {
+        self.size_over_lifetime.as_ref()
+    }
+
+    /// Sets new velocity over lifetime module.
+    pub fn set_velocity_over_lifetime(&mut self, velocity: Option<VelocityOverLifetime>) {
+        self.velocity_over_lifetime = velocity;
+    }
+
+    /// Returns current velocity over lifetime module.
+    pub fn velocity_over_lifetime(&self) -> Option<&VelocityOverLifetime> {
+        self.velocity_over_lifetime.as_ref()
+    }
+
+    /// Sets new drag coefficient, velocity of particles is exponentially decreased by this
+    /// amount per second. Zero means no drag.
+    pub fn set_drag(&mut self, drag: f32) {
+        self.drag = drag.max(0.0);
+    }
+
+    /// Returns current drag coefficient.
+    pub fn drag(&self) -> f32 {
+        self.drag
+    }
+
+    /// Sets new turbulence module.
+    pub fn set_turbulence(&mut self, turbulence: Option<Turbulence>) {
+        self.turbulence = turbulence;
+    }
+
+    /// Returns current turbulence module.
+    pub fn turbulence(&self) -> Option<&Turbulence> {
+        self.turbulence.as_ref()
+    }
+
+    /// Sets new rotation curve, it defines multiplier for rotation speed of particles over
+    /// normalized lifetime.
+    pub fn set_rotation_over_lifetime(&mut self, curve: Option<Curve>) {
+        self.rotation_over_lifetime = curve;
+    }
+
+    /// Returns current rotation curve.
+    pub fn rotation_over_lifetime(&self) -> Option<&Curve> {
+        self.rotation_over_lifetime.as_ref()
+    }
+
+    /// Sets new texture sheet animation.
+    pub fn set_texture_sheet_animation(&mut self, animation: Option<TextureSheetAnimation>) {
+        self.texture_sheet_animation = animation;
+    }
+
+    /// Returns current texture sheet animation.
+    pub fn texture_sheet_animation(&self) -> Option<&TextureSheetAnimation> {
+        self.texture_sheet_animation.as_ref()
+    }
+
+    /// Enables or disables collisions of particles with colliders of scene physics.
+    pub fn set_collision(&mut self, collision: Option<ParticleCollision>) {
+        self.collision = collision;
+    }
+
+    /// Returns current collision settings, `None` means that collisions are disabled.
+    pub fn collision(&self) -> Option<&ParticleCollision> {
+        self.collision.as_ref()
+    }
+
+    /// Returns impacts of particles with colliders that happened during last update. Impacts
+    /// are collected only if collisions are enabled.
+    pub fn impacts(&self) -> &[ParticleImpact] {
+        &self.impacts
+    }
+
+    /// Sets new simulation space. Alive particles are converted to new space, so they keep
+    /// their world positions.
+    pub fn set_simulation_space(&mut self, simulation_space: SimulationSpace) {
+        if self.simulation_space == simulation_space {
+            return;
+        }
+        let global_transform = self.global_transform();
+        let transform = match simulation_space {
+            SimulationSpace::Local => global_transform
+                .try_inverse()
+                .unwrap_or_else(Matrix4::identity),
+            SimulationSpace::World => global_transform,
+        };
+        for particle in self.particles.iter_mut() {
+            particle.position = transform
+                .transform_point(&Point3::from(particle.position))
+                .coords;
+            particle.velocity = transform.transform_vector(&particle.velocity);
+            particle.offset = transform.transform_vector(&particle.offset);
+        }
+        self.simulation_space = simulation_space;
+    }
+
+    /// Returns current simulation space.
+    pub fn simulation_space(&self) -> SimulationSpace {
+        self.simulation_space
+    }
+
+    /// Returns matrix that transforms particles from simulation space to world space.
+    pub fn simulation_transform(&self) -> Matrix4<f32> {
+        match self.simulation_space {
+            SimulationSpace::Local => self.global_transform(),
+            SimulationSpace::World => Matrix4::identity(),
+        }
+    }
+
+    /// Returns matrix that transforms emitted particles from local coordinates of particle
+    /// system to simulation space.
+    fn emission_transform(&self) -> Matrix4<f32> {
+        match self.simulation_space {
+            SimulationSpace::Local => Matrix4::identity(),
+            SimulationSpace::World => self.global_transform(),
+        }
+    }
+
+    /// Sets amount of time (in seconds) that will be simulated at once on first update, so
+    /// particle system will look like it was working for a while when it appears. Prewarm is
+    /// done only once.
+    pub fn set_prewarm_time(&mut self, time: f32) {
+        self.prewarm_time = time.max(0.0);
+    }
+
+    /// Returns current prewarm time.
+    pub fn prewarm_time(&self) -> f32 {
+        self.prewarm_time
+    }
+
     /// Updates state of particle system, this means that it moves particles,
     /// changes their color, size, rotation, etc. This method should not be
     /// used directly, it will be automatically called by scene update.
     pub fn update(&mut self, dt: f32) {
+        if !self.prewarmed {
+            self.prewarmed = true;
+            let mut time = self.prewarm_time;
+            while time > 0.0 {
+                let step = time.min(PREWARM_STEP);
+                self.simulate(step);
+                time -= step;
+            }
+        }
+
+        self.simulate(dt);
+    }
+
+    fn is_sub_emitter(&self, index: usize) -> bool {
+        self.emitters.iter().any(|emitter| {
+            emitter
+                .sub_emitters
+                .iter()
+                .any(|sub_emitter| sub_emitter.emitter as usize == index)
+        })
+    }
+
+    /// Creates new particle using given emitter, if origin is specified, particle will be
+    /// emitted relative to it (origin must be in simulation space).
+    fn emit_particle(
+        &self,
+        emitter_index: usize,
+        transform: &Matrix4<f32>,
+        origin: Option<Vector3<f32>>,
+    ) -> Particle {
+        let emitter = &self.emitters[emitter_index];
+        let mut particle = Particle::default();
+        particle.emitter_index = emitter_index as u32;
+        emitter
+            .alive_particles
+            .set(emitter.alive_particles.get() + 1);
+        emitter.emit(self, &mut particle);
+        particle.position = match origin {
+            Some(origin) => {
+                origin + transform.transform_vector(&(particle.position - emitter.position))
+            }
+            None => {
+                transform
+                    .transform_point(&Point3::from(particle.position))
+                    .coords
+            }
+        };
+        particle.velocity = transform.transform_vector(&particle.velocity);
+        particle
+    }
+
+    fn add_particle(&mut self, particle: Particle) {
+        if let Some(free_index) = self.free_particles.pop() {
+            self.particles[free_index as usize] = particle;
+        } else {
+            self.particles.push(particle);
+        }
+    }
+
+    fn spawn_sub_emitter_particles(&mut self, sub_emissions: Vec<SubEmission>) {
+        if sub_emissions.is_empty() {
+            return;
+        }
+        let transform = self.emission_transform();
+        for sub_emission in sub_emissions {
+            for i in 0..self.emitters[sub_emission.emitter].sub_emitters.len() {
+                let sub_emitter = self.emitters[sub_emission.emitter].sub_emitters[i];
+                let emitter_index = sub_emitter.emitter as usize;
+                if sub_emitter.trigger != sub_emission.trigger
+                    || emitter_index >= self.emitters.len()
+                {
+                    continue;
+                }
+                for _ in 0..sub_emitter.count {
+                    let emitter = &self.emitters[emitter_index];
+                    if let ParticleLimit::Strict(max_particles) = emitter.max_particles {
+                        if emitter.alive_particles.get() >= max_particles {
+                            break;
+                        }
+                    }
+                    let particle =
+                        self.emit_particle(emitter_index, &transform, Some(sub_emission.position));
+                    self.add_particle(particle);
+                }
+            }
+        }
+    }
+
+    fn simulate(&mut self, dt: f32) {
+        self.impacts.clear();
+        self.time += dt;
+
         for emitter in self.emitters.iter_mut() {
             emitter.tick(dt);
         }
 
-        for (i, emitter) in self.emitters.iter().enumerate() {
-            for _ in 0..emitter.particles_to_spawn {
-                let mut particle = Particle::default();
-                particle.emitter_index = i as u32;
-                emitter
-                    .alive_particles
-                    .set(emitter.alive_particles.get() + 1);
-                emitter.emit(self, &mut particle);
-                if let Some(free_index) = self.free_particles.pop() {
-                    self.particles[free_index as usize] = particle;
-                } else {
-                    self.particles.push(particle);
+        let transform = self.emission_transform();
+        let mut sub_emissions = Vec::new();
+
+        for i in 0..self.emitters.len() {
+            if self.is_sub_emitter(i) {
+                continue;
+            }
+            for _ in 0..self.emitters[i].particles_to_spawn {
+                let particle = self.emit_particle(i, &transform, None);
+                if !self.emitters[i].sub_emitters.is_empty() {
+                    sub_emissions.push(SubEmission {
+                        emitter: i,
+                        position: particle.position,
+                        trigger: SubEmitterTrigger::Birth,
+                    });
                 }
+                self.add_particle(particle);
             }
         }
 
         let acceleration_offset = self.acceleration.scale(dt * dt);
+        let drag_factor = (-self.drag * dt).exp();
 
         for (i, particle) in self.particles.iter_mut().enumerate() {
             if particle.alive {
                 particle.lifetime += dt;
                 if particle.lifetime >= particle.initial_lifetime {
-                    self.free_particles.push(i as u32);
-                    if let Some(emitter) = self.emitters.get(particle.emitter_index as usize) {
-                        emitter
-                            .alive_particles
-                            .set(emitter.alive_particles.get() - 1);
-                    }
-                    particle.alive = false;
-                    particle.lifetime = particle.initial_lifetime;
+                    kill_particle(
+                        particle,
+                        i,
+                        &mut self.free_particles,
+                        &self.emitters,
+                        &mut sub_emissions,
+                    );
                 } else {
+                    let k = particle.lifetime / particle.initial_lifetime;
                     particle.velocity += acceleration_offset;
-                    particle.position += particle.velocity;
+                    particle.velocity = particle.velocity.scale(drag_factor);
+                    let mut offset = particle.velocity;
+                    if let Some(velocity_over_lifetime) = &self.velocity_over_lifetime {
+                        offset = offset.scale(velocity_over_lifetime.speed_multiplier.value_at(k))
+                            + velocity_over_lifetime.linear.scale(dt);
+                    }
+                    if let Some(turbulence) = &self.turbulence {
+                        offset += turbulence.sample(particle.position, self.time).scale(dt);
+                    }
+                    particle.offset = offset;
+                    particle.position += offset;
                     particle.size += particle.size_modifier * dt;
                     if particle.size < 0.0 {
                         particle.size = 0.0;
                     }
-                    particle.rotation += particle.rotation_speed * dt;
+                    particle.size_scale = self
+                        .size_over_lifetime
+                        .as_ref()
+                        .map_or(1.0, |curve| curve.value_at(k));
+                    let rotation_multiplier = self
+                        .rotation_over_lifetime
+                        .as_ref()
+                        .map_or(1.0, |curve| curve.value_at(k));
+                    particle.rotation += particle.rotation_speed * rotation_multiplier * dt;
+                    if let Some(animation) = &self.texture_sheet_animation {
+                        particle.frame = animation.frame_at(k);
+                    }
                     if let Some(color_over_lifetime) = &self.color_over_lifetime {
-                        let k = particle.lifetime / particle.initial_lifetime;
                         particle.color = color_over_lifetime.get_color(k);
                     } else {
                         particle.color = Color::WHITE;
@@ -1181,6 +2599,148 @@ impl ParticleSystem {
                 }
             }
         }
+
+        self.spawn_sub_emitter_particles(sub_emissions);
+    }
+
+    /// Tests movement of particles during last update against colliders and resolves
+    /// collisions. Called by scene right after graph update, position of particle before
+    /// update is restored using its movement during the update.
+    pub(in crate) fn resolve_collisions(
+        &mut self,
+        physics: &Physics,
+        query_buffer: &mut Vec<Intersection>,
+    ) {
+        let collision = match self.collision.as_ref() {
+            Some(collision) => collision,
+            None => return,
+        };
+
+        let transform = self.simulation_transform();
+        let inv_transform = match transform.try_inverse() {
+            Some(inv_transform) => inv_transform,
+            None => return,
+        };
+        let mut sub_emissions = Vec::new();
+
+        for (i, particle) in self.particles.iter_mut().enumerate() {
+            if !particle.alive {
+                continue;
+            }
+
+            let end = transform
+                .transform_point(&Point3::from(particle.position))
+                .coords;
+            let velocity = transform.transform_vector(&particle.velocity);
+            let movement = transform.transform_vector(&particle.offset);
+            let begin = end - movement;
+            let distance = movement.norm();
+            if distance <= f32::EPSILON {
+                continue;
+            }
+
+            physics.cast_ray(
+                RayCastOptions {
+                    ray: Ray {
+                        origin: begin,
+                        dir: movement,
+                    },
+                    max_len: distance,
+                    groups: collision.groups,
+                    sort_results: true,
+                },
+                query_buffer,
+            );
+
+            let intersection = match query_buffer.first() {
+                Some(intersection) => intersection,
+                None => continue,
+            };
+
+            // Make sure that normal faces towards particle.
+            let mut normal = intersection.normal;
+            if normal.dot(&movement) > 0.0 {
+                normal = -normal;
+            }
+
+            self.impacts.push(ParticleImpact {
+                collider: intersection.collider,
+                position: intersection.position.coords,
+                normal,
+                velocity,
+                killed: collision.kill_on_hit,
+            });
+
+            if collision.kill_on_hit {
+                // Death sub-emitters must spawn at the point of impact, not behind the collider.
+                particle.position = inv_transform.transform_point(&intersection.position).coords;
+                kill_particle(
+                    particle,
+                    i,
+                    &mut self.free_particles,
+                    &self.emitters,
+                    &mut sub_emissions,
+                );
+            } else {
+                let normal_velocity = normal.scale(velocity.dot(&normal));
+                let tangent_velocity = velocity - normal_velocity;
+                let new_velocity = tangent_velocity.scale(1.0 - collision.friction)
+                    - normal_velocity.scale(collision.restitution);
+                // Small offset prevents particle from being stuck in the surface.
+                let new_position = intersection.position.coords + normal.scale(0.001);
+                particle.position = inv_transform
+                    .transform_point(&Point3::from(new_position))
+                    .coords;
+                particle.velocity = inv_transform.transform_vector(&new_velocity);
+            }
+        }
+
+        self.spawn_sub_emitter_particles(sub_emissions);
+    }
+
+    /// Calculates bounding box of alive particles in *simulation space* (local coordinates
+    /// by default, see [`SimulationSpace`]), size of particles
+    /// is taken into account. Bounding box is not cached, so this method is relatively heavy.
+    pub fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        let mut bounding_box = AxisAlignedBoundingBox::default();
+        for particle in self.particles.iter().filter(|particle| particle.alive) {
+            // Particles are camera-facing quads which can be rotated, so use radius of
+            // circumscribed sphere.
+            let radius = particle.size * particle.size_scale * std::f32::consts::SQRT_2;
+            let extent = Vector3::new(radius, radius, radius);
+            bounding_box.add_point(particle.position - extent);
+            bounding_box.add_point(particle.position + extent);
+        }
+        bounding_box
+    }
+
+    /// Calculates bounding box of alive particles in *world coordinates*. If there are no
+    /// alive particles, returned box will be invalid (min > max).
+    pub fn world_bounding_box(&self) -> AxisAlignedBoundingBox {
+        let local_bounding_box = self.bounding_box();
+        if self.particles.iter().any(|particle| particle.alive) {
+            let global_transform = self.simulation_transform();
+            AxisAlignedBoundingBox::from_points(
+                &local_bounding_box
+                    .corners()
+                    .iter()
+                    .map(|&corner| {
+                        global_transform
+                            .transform_point(&Point3::from(corner))
+                            .coords
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        } else {
+            local_bounding_box
+        }
+    }
+
+    /// Returns true if every emitter of particle system has finished emitting and there are
+    /// no alive particles left. See [`BaseEmitter::is_finished`] for more info.
+    pub fn is_finished(&self) -> bool {
+        self.emitters.iter().all(|emitter| emitter.is_finished())
+            && self.particles.iter().all(|particle| !particle.alive)
     }
 
     /// Generates new draw data for current frame. Should not be used directly, unless you
@@ -1192,9 +2752,12 @@ impl ParticleSystem {
         camera_pos: &Vector3<f32>,
     ) {
         sorted_particles.clear();
+        let transform = self.simulation_transform();
         for (i, particle) in self.particles.iter().enumerate() {
             if particle.alive {
-                let actual_position = particle.position + self.base.global_position();
+                let actual_position = transform
+                    .transform_point(&Point3::from(particle.position))
+                    .coords;
                 particle
                     .sqr_distance_to_camera
                     .set((camera_pos - actual_position).norm_squared());
@@ -1222,37 +2785,48 @@ impl ParticleSystem {
 
         for (i, particle_index) in sorted_particles.iter().enumerate() {
             let particle = self.particles.get(*particle_index as usize).unwrap();
+            let size = particle.size * particle.size_scale;
+            let uv_rect = self
+                .texture_sheet_animation
+                .as_ref()
+                .map_or(Vector4::new(0.0, 0.0, 1.0, 1.0), |animation| {
+                    animation.frame_uv_rect(particle.frame)
+                });
 
             draw_data.vertices.push(Vertex {
                 position: particle.position,
                 tex_coord: Vector2::default(),
-                size: particle.size,
+                size,
                 rotation: particle.rotation,
                 color: particle.color,
+                uv_rect,
             });
 
             draw_data.vertices.push(Vertex {
                 position: particle.position,
                 tex_coord: Vector2::new(1.0, 0.0),
-                size: particle.size,
+                size,
                 rotation: particle.rotation,
                 color: particle.color,
+                uv_rect,
             });
 
             draw_data.vertices.push(Vertex {
                 position: particle.position,
                 tex_coord: Vector2::new(1.0, 1.0),
-                size: particle.size,
+                size,
                 rotation: particle.rotation,
                 color: particle.color,
+                uv_rect,
             });
 
             draw_data.vertices.push(Vertex {
                 position: particle.position,
                 tex_coord: Vector2::new(0.0, 1.0),
-                size: particle.size,
+                size,
                 rotation: particle.rotation,
                 color: particle.color,
+                uv_rect,
             });
 
             let base_index = (i * 4) as u32;
@@ -1281,6 +2855,30 @@ impl ParticleSystem {
     }
 }
 
+fn kill_particle(
+    particle: &mut Particle,
+    index: usize,
+    free_particles: &mut Vec<u32>,
+    emitters: &[Emitter],
+    sub_emissions: &mut Vec<SubEmission>,
+) {
+    free_particles.push(index as u32);
+    if let Some(emitter) = emitters.get(particle.emitter_index as usize) {
+        emitter
+            .alive_particles
+            .set(emitter.alive_particles.get() - 1);
+        if !emitter.sub_emitters.is_empty() {
+            sub_emissions.push(SubEmission {
+                emitter: particle.emitter_index as usize,
+                position: particle.position,
+                trigger: SubEmitterTrigger::Death,
+            });
+        }
+    }
+    particle.alive = false;
+    particle.lifetime = particle.initial_lifetime;
+}
+
 impl Visit for ParticleSystem {
     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
         visitor.enter_region(name)?;
@@ -1292,6 +2890,25 @@ impl Visit for ParticleSystem {
         self.acceleration.visit("Acceleration", visitor)?;
         self.color_over_lifetime.visit("ColorGradient", visitor)?;
         self.base.visit("Base", visitor)?;
+        let _ = self.collision.visit("Collision", visitor);
+        let _ = self.size_over_lifetime.visit("SizeOverLifetime", visitor);
+        let _ = self
+            .velocity_over_lifetime
+            .visit("VelocityOverLifetime", visitor);
+        let _ = self.drag.visit("Drag", visitor);
+        let _ = self.turbulence.visit("Turbulence", visitor);
+        let _ = self
+            .rotation_over_lifetime
+            .visit("RotationOverLifetime", visitor);
+        let _ = self
+            .texture_sheet_animation
+            .visit("TextureSheetAnimation", visitor);
+        let _ = self.simulation_space.visit("SimulationSpace", visitor);
+        let _ = self.prewarm_time.visit("PrewarmTime", visitor);
+        if self.prewarmed.visit("Prewarmed", visitor).is_err() {
+            // Old scenes have no prewarm, nothing to simulate.
+            self.prewarmed = true;
+        }
 
         visitor.leave_region()
     }
@@ -1311,6 +2928,15 @@ pub struct ParticleSystemBuilder {
     texture: Option<Texture>,
     acceleration: Vector3<f32>,
     color_over_lifetime: Option<ColorGradient>,
+    size_over_lifetime: Option<Curve>,
+    velocity_over_lifetime: Option<VelocityOverLifetime>,
+    drag: f32,
+    turbulence: Option<Turbulence>,
+    rotation_over_lifetime: Option<Curve>,
+    texture_sheet_animation: Option<TextureSheetAnimation>,
+    collision: Option<ParticleCollision>,
+    simulation_space: SimulationSpace,
+    prewarm_time: f32,
 }
 
 impl ParticleSystemBuilder {
@@ -1322,6 +2948,15 @@ impl ParticleSystemBuilder {
             texture: None,
             acceleration: Vector3::new(0.0, -9.81, 0.0),
             color_over_lifetime: None,
+            size_over_lifetime: None,
+            velocity_over_lifetime: None,
+            drag: 0.0,
+            turbulence: None,
+            rotation_over_lifetime: None,
+            texture_sheet_animation: None,
+            collision: None,
+            simulation_space: SimulationSpace::Local,
+            prewarm_time: 0.0,
         }
     }
 
@@ -1355,6 +2990,60 @@ impl ParticleSystemBuilder {
         self
     }
 
+    /// Sets size curve over lifetime for particle system.
+    pub fn with_size_over_lifetime(mut self, curve: Curve) -> Self {
+        self.size_over_lifetime = Some(curve);
+        self
+    }
+
+    /// Sets velocity over lifetime module for particle system.
+    pub fn with_velocity_over_lifetime(mut self, velocity: VelocityOverLifetime) -> Self {
+        self.velocity_over_lifetime = Some(velocity);
+        self
+    }
+
+    /// Sets drag coefficient for particle system.
+    pub fn with_drag(mut self, drag: f32) -> Self {
+        self.drag = drag.max(0.0);
+        self
+    }
+
+    /// Sets turbulence module for particle system.
+    pub fn with_turbulence(mut self, turbulence: Turbulence) -> Self {
+        self.turbulence = Some(turbulence);
+        self
+    }
+
+    /// Sets rotation curve over lifetime for particle system.
+    pub fn with_rotation_over_lifetime(mut self, curve: Curve) -> Self {
+        self.rotation_over_lifetime = Some(curve);
+        self
+    }
+
+    /// Sets texture sheet animation for particle system.
+    pub fn with_texture_sheet_animation(mut self, animation: TextureSheetAnimation) -> Self {
+        self.texture_sheet_animation = Some(animation);
+        self
+    }
+
+    /// Sets desired simulation space.
+    pub fn with_simulation_space(mut self, simulation_space: SimulationSpace) -> Self {
+        self.simulation_space = simulation_space;
+        self
+    }
+
+    /// Sets desired prewarm time in seconds.
+    pub fn with_prewarm_time(mut self, time: f32) -> Self {
+        self.prewarm_time = time.max(0.0);
+        self
+    }
+
+    /// Enables collisions of particles with colliders of scene physics.
+    pub fn with_collision(mut self, collision: ParticleCollision) -> Self {
+        self.collision = Some(collision);
+        self
+    }
+
     /// Creates new instance of particle system.
     pub fn build(self) -> ParticleSystem {
         ParticleSystem {
@@ -1365,6 +3054,18 @@ impl ParticleSystemBuilder {
             texture: self.texture.clone(),
             acceleration: self.acceleration,
             color_over_lifetime: self.color_over_lifetime,
+            size_over_lifetime: self.size_over_lifetime,
+            velocity_over_lifetime: self.velocity_over_lifetime,
+            drag: self.drag,
+            turbulence: self.turbulence,
+            rotation_over_lifetime: self.rotation_over_lifetime,
+            texture_sheet_animation: self.texture_sheet_animation,
+            collision: self.collision,
+            impacts: Default::default(),
+            time: 0.0,
+            simulation_space: self.simulation_space,
+            prewarm_time: self.prewarm_time,
+            prewarmed: false,
         }
     }
 
@@ -1373,3 +3074,296 @@ impl ParticleSystemBuilder {
         Node::ParticleSystem(self.build())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{Matrix4, Vector2, Vector3, Vector4},
+            curve::Curve,
+            numeric_range::NumericRange,
+        },
+        renderer::surface::{SurfaceBuilder, SurfaceSharedData},
+        scene::{
+            base::BaseBuilder,
+            graph::Graph,
+            mesh::MeshBuilder,
+            node::Node,
+            particle_system::{
+                BaseEmitterBuilder, DiskEmitterBuilder, Emitter, MeshEmissionMode,
+                MeshSurfaceEmitterBuilder, MeshSurfaceSource, Particle, ParticleCollision,
+                ParticleSystemBuilder, SimulationSpace, SubEmitter, SubEmitterTrigger,
+                TextureSheetAnimation, VelocityOverLifetime,
+            },
+            transform::TransformBuilder,
+            Scene,
+        },
+    };
+    use rapier3d::{dynamics::RigidBodyBuilder, geometry::ColliderBuilder};
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn mesh_surface_emitter() {
+        let quad = Arc::new(RwLock::new(SurfaceSharedData::make_quad(
+            Matrix4::identity(),
+        )));
+
+        let mut graph = Graph::new();
+        let mesh = MeshBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(10.0, 0.0, 0.0))
+                    .build(),
+            ),
+        )
+        .with_surfaces(vec![SurfaceBuilder::new(quad.clone()).build()])
+        .build_node();
+        let mesh = graph.add_node(mesh);
+        let particle_system = ParticleSystemBuilder::new(BaseBuilder::new())
+            .with_emitters(vec![
+                MeshSurfaceEmitterBuilder::new(
+                    BaseEmitterBuilder::new(),
+                    MeshSurfaceSource::Mesh { mesh, surface: 0 },
+                )
+                .build(),
+                MeshSurfaceEmitterBuilder::new(
+                    BaseEmitterBuilder::new(),
+                    MeshSurfaceSource::Data(quad),
+                )
+                .with_mode(MeshEmissionMode::Edges)
+                .build(),
+            ])
+            .build_node();
+        let particle_system = graph.add_node(particle_system);
+
+        graph.update_nodes(Vector2::new(100.0, 100.0), 0.0);
+
+        let emitters = graph[particle_system].as_particle_system().emitters();
+        let mut rng = crate::rand::thread_rng();
+
+        // Surface of the mesh is a unit quad in XZ plane, moved by the mesh transform.
+        if let Emitter::MeshSurface(emitter) = &emitters[0] {
+            assert!((emitter.cumulative_weights.last().unwrap() - 1.0).abs() < 0.001);
+            for _ in 0..100 {
+                let point = emitter.random_point(&mut rng).unwrap();
+                assert!((point.x - 10.0).abs() <= 0.5);
+                assert!(point.y.abs() <= 0.001);
+                assert!(point.z.abs() <= 0.5);
+            }
+        } else {
+            unreachable!();
+        }
+
+        // Edges are weighted by length: four sides and diagonal shared by two triangles.
+        if let Emitter::MeshSurface(emitter) = &emitters[1] {
+            let expected = 4.0 + 2.0 * 2.0f32.sqrt();
+            assert!((emitter.cumulative_weights.last().unwrap() - expected).abs() < 0.001);
+            for _ in 0..100 {
+                let point = emitter.random_point(&mut rng).unwrap();
+                let on_side =
+                    (point.x.abs() - 0.5).abs() < 0.001 || (point.z.abs() - 0.5).abs() < 0.001;
+                let on_diagonal = (point.x.abs() - point.z.abs()).abs() < 0.001;
+                assert!(on_side || on_diagonal);
+            }
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn particle_collision() {
+        let mut scene = Scene::new();
+        let ground = scene
+            .physics
+            .add_body(RigidBodyBuilder::new_static().build());
+        let collider = scene
+            .physics
+            .add_collider(ColliderBuilder::cuboid(10.0, 0.1, 10.0).build(), ground);
+
+        let mut particle_system = ParticleSystemBuilder::new(BaseBuilder::new())
+            .with_acceleration(Vector3::default())
+            .with_collision(ParticleCollision {
+                restitution: 0.5,
+                friction: 0.0,
+                ..Default::default()
+            })
+            .build();
+        particle_system.particles.push(Particle {
+            position: Vector3::new(0.0, 1.0, 0.0),
+            velocity: Vector3::new(0.0, -2.0, 0.0),
+            initial_lifetime: 10.0,
+            ..Default::default()
+        });
+        let particle_system = scene.graph.add_node(Node::ParticleSystem(particle_system));
+
+        scene.update(Vector2::new(100.0, 100.0), 0.1);
+
+        let particle_system = scene.graph[particle_system].as_particle_system();
+        let impacts = particle_system.impacts();
+        assert_eq!(impacts.len(), 1);
+        assert_eq!(impacts[0].collider, collider);
+        assert!((impacts[0].position - Vector3::new(0.0, 0.1, 0.0)).norm() < 0.001);
+        assert!((impacts[0].normal - Vector3::y()).norm() < 0.001);
+
+        // Particle bounced off the ground.
+        let particle = &particle_system.particles[0];
+        assert!((particle.position.y - 0.1).abs() < 0.01);
+        assert!((particle.velocity - Vector3::new(0.0, 1.0, 0.0)).norm() < 0.001);
+    }
+
+    #[test]
+    fn particle_collision_kill_spawns_death_sub_emitter() {
+        let mut scene = Scene::new();
+        let ground = scene
+            .physics
+            .add_body(RigidBodyBuilder::new_static().build());
+        scene
+            .physics
+            .add_collider(ColliderBuilder::cuboid(10.0, 0.1, 10.0).build(), ground);
+
+        let mut particle_system = ParticleSystemBuilder::new(BaseBuilder::new())
+            .with_acceleration(Vector3::default())
+            .with_collision(ParticleCollision {
+                kill_on_hit: true,
+                ..Default::default()
+            })
+            .with_emitters(vec![
+                DiskEmitterBuilder::new(
+                    BaseEmitterBuilder::new()
+                        .with_spawn_rate(0)
+                        .with_sub_emitters(vec![SubEmitter {
+                            emitter: 1,
+                            trigger: SubEmitterTrigger::Death,
+                            count: 3,
+                        }]),
+                )
+                .with_radius(0.0)
+                .build(),
+                DiskEmitterBuilder::new(BaseEmitterBuilder::new())
+                    .with_radius(0.0)
+                    .build(),
+            ])
+            .build();
+        particle_system.emitters[0].alive_particles.set(1);
+        particle_system.particles.push(Particle {
+            position: Vector3::new(0.0, 1.0, 0.0),
+            velocity: Vector3::new(0.0, -2.0, 0.0),
+            initial_lifetime: 10.0,
+            ..Default::default()
+        });
+        let particle_system = scene.graph.add_node(Node::ParticleSystem(particle_system));
+
+        scene.update(Vector2::new(100.0, 100.0), 0.1);
+
+        let particle_system = scene.graph[particle_system].as_particle_system();
+        assert_eq!(particle_system.impacts().len(), 1);
+        assert!(particle_system.impacts()[0].killed);
+        assert_eq!(particle_system.emitters()[0].alive_particles.get(), 0);
+
+        // Death sub-emitter spawned its particles at the point of impact.
+        let spawned = particle_system
+            .particles
+            .iter()
+            .filter(|p| p.alive && p.emitter_index == 1)
+            .collect::<Vec<_>>();
+        assert_eq!(spawned.len(), 3);
+        for particle in spawned {
+            assert!((particle.position - Vector3::new(0.0, 0.1, 0.0)).norm() < 0.001);
+        }
+    }
+
+    #[test]
+    fn over_lifetime_modules() {
+        let mut particle_system = ParticleSystemBuilder::new(BaseBuilder::new())
+            .with_acceleration(Vector3::default())
+            .with_drag(2.0)
+            .with_size_over_lifetime(Curve::linear(1.0, 0.0))
+            .with_velocity_over_lifetime(VelocityOverLifetime {
+                linear: Vector3::new(0.0, 2.0, 0.0),
+                speed_multiplier: Curve::linear(2.0, 2.0),
+            })
+            .with_texture_sheet_animation(TextureSheetAnimation::new(2, 2))
+            .build();
+        particle_system.particles.push(Particle {
+            velocity: Vector3::new(1.0, 0.0, 0.0),
+            initial_lifetime: 1.0,
+            ..Default::default()
+        });
+
+        particle_system.update(0.5);
+
+        let particle = &particle_system.particles[0];
+        let damped = (-1.0f32).exp();
+        assert!((particle.velocity.x - damped).abs() < 0.001);
+        assert!((particle.position - Vector3::new(2.0 * damped, 1.0, 0.0)).norm() < 0.001);
+        assert!((particle.size_scale - 0.5).abs() < 0.001);
+        assert_eq!(particle.frame, 2);
+
+        let animation = particle_system.texture_sheet_animation().unwrap();
+        assert_eq!(
+            animation.frame_uv_rect(particle.frame),
+            Vector4::new(0.0, 0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn world_space_sub_emitters_and_prewarm() {
+        let mut graph = Graph::new();
+        let particle_system = ParticleSystemBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(10.0, 0.0, 0.0))
+                    .build(),
+            ),
+        )
+        .with_acceleration(Vector3::default())
+        .with_simulation_space(SimulationSpace::World)
+        .with_prewarm_time(1.0)
+        .with_emitters(vec![
+            DiskEmitterBuilder::new(
+                BaseEmitterBuilder::new()
+                    .with_spawn_rate(10)
+                    .with_lifetime_range(NumericRange::new(0.25, 0.3))
+                    .with_sub_emitters(vec![SubEmitter {
+                        emitter: 1,
+                        trigger: SubEmitterTrigger::Death,
+                        count: 2,
+                    }]),
+            )
+            .with_radius(0.0)
+            .build(),
+            DiskEmitterBuilder::new(
+                BaseEmitterBuilder::new()
+                    .with_spawn_rate(1000)
+                    .with_lifetime_range(NumericRange::new(5.0, 6.0)),
+            )
+            .with_radius(0.0)
+            .build(),
+        ])
+        .build_node();
+        let particle_system = graph.add_node(particle_system);
+
+        // Prewarm is done on first update.
+        graph.update_nodes(Vector2::new(100.0, 100.0), 0.0);
+
+        let check = |graph: &Graph| {
+            let particle_system = graph[particle_system].as_particle_system();
+            let emitters = particle_system.emitters();
+            let deaths = emitters[0].spawned_particles - emitters[0].alive_particles.get() as u64;
+            assert!(deaths > 0);
+            // Second emitter spawns particles only when particles of first emitter die.
+            assert_eq!(emitters[1].alive_particles.get() as u64, 2 * deaths);
+            for particle in particle_system.particles.iter().filter(|p| p.alive) {
+                assert!((particle.position - Vector3::new(10.0, 0.0, 0.0)).norm() < 0.1);
+            }
+        };
+        check(&graph);
+
+        // Particles stay in place in world space when node moves.
+        graph[particle_system]
+            .local_transform_mut()
+            .set_position(Vector3::default());
+        graph.update_nodes(Vector2::new(100.0, 100.0), 0.0);
+        check(&graph);
+    }
+}
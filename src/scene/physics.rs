@@ -4,7 +4,7 @@ use crate::{
     core::{
         color::Color,
         math::ray::Ray,
-        visitor::{Visit, VisitResult, Visitor},
+        visitor::{Data, Visit, VisitResult, Visitor},
     },
     physics::math::AngVector,
     scene::{
@@ -764,18 +764,44 @@ impl Visit for TrimeshDesc {
     }
 }
 
-#[derive(Default, Copy, Clone, Debug)]
+// Unlike trimeshes, heightfields store their data, heights are stored in column-major order
+// (the same order as in DMatrix storage), rows go along Z axis, columns - along X axis.
+#[derive(Default, Clone, Debug)]
 #[doc(hidden)]
-pub struct HeightfieldDesc;
+pub struct HeightfieldDesc {
+    pub row_count: u32,
+    pub column_count: u32,
+    pub heights: Vec<f32>,
+    pub scale: Vector3<f32>,
+}
 
 impl Visit for HeightfieldDesc {
     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
         visitor.enter_region(name)?;
+
+        // Old versions did not store any data for heightfields.
+        let _ = self.row_count.visit("RowCount", visitor);
+        let _ = self.column_count.visit("ColumnCount", visitor);
+        let _ = self.scale.visit("Scale", visitor);
+
+        let mut bytes = self
+            .heights
+            .iter()
+            .flat_map(|h| h.to_le_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        let _ = Data::new(&mut bytes).visit("Heights", visitor);
+        if visitor.is_reading() {
+            self.heights = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+        }
+
         visitor.leave_region()
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 #[doc(hidden)]
 pub enum ColliderShapeDesc {
     Ball(BallDesc),
@@ -874,8 +900,13 @@ impl ColliderShapeDesc {
             })
         } else if shape.as_trimesh().is_some() {
             ColliderShapeDesc::Trimesh(TrimeshDesc)
-        } else if shape.as_heightfield().is_some() {
-            ColliderShapeDesc::Heightfield(HeightfieldDesc)
+        } else if let Some(heightfield) = shape.as_heightfield() {
+            ColliderShapeDesc::Heightfield(HeightfieldDesc {
+                row_count: heightfield.heights().nrows() as u32,
+                column_count: heightfield.heights().ncols() as u32,
+                heights: heightfield.heights().as_slice().to_vec(),
+                scale: *heightfield.scale(),
+            })
         } else {
             unreachable!()
         }
@@ -914,14 +945,32 @@ impl ColliderShapeDesc {
                 let c = Point3::new(1.0, 0.0, 0.0);
                 ColliderShape::trimesh(vec![a, b, c], vec![Point3::new(0, 1, 2)])
             }
-            ColliderShapeDesc::Heightfield(_) => ColliderShape::heightfield(
-                DMatrix::from_data(VecStorage::new(
-                    Dynamic::new(2),
-                    Dynamic::new(2),
-                    vec![0.0, 1.0, 0.0, 0.0],
-                )),
-                Default::default(),
-            ),
+            ColliderShapeDesc::Heightfield(heightfield) => {
+                let (rows, columns) = (
+                    heightfield.row_count as usize,
+                    heightfield.column_count as usize,
+                );
+                if rows > 1 && columns > 1 && heightfield.heights.len() == rows * columns {
+                    ColliderShape::heightfield(
+                        DMatrix::from_data(VecStorage::new(
+                            Dynamic::new(rows),
+                            Dynamic::new(columns),
+                            heightfield.heights,
+                        )),
+                        heightfield.scale,
+                    )
+                } else {
+                    // Heightfield was saved by old version which did not store any data.
+                    ColliderShape::heightfield(
+                        DMatrix::from_data(VecStorage::new(
+                            Dynamic::new(2),
+                            Dynamic::new(2),
+                            vec![0.0, 1.0, 0.0, 0.0],
+                        )),
+                        Default::default(),
+                    )
+                }
+            }
         }
     }
 }
@@ -0,0 +1,353 @@
+//! Contains all structures and methods to pick scene nodes using rays.
+//!
+//! Unlike ray casting in physics, picking does not require colliders, it tests ray against
+//! rendered geometry directly:
+//!
+//! - Meshes are tested triangle-by-triangle, skinned surfaces are tested in their current
+//!   (animated) pose.
+//! - Sprites are tested as camera-facing quads, so result depends on camera orientation.
+//! - Particle systems are tested using bounding box of alive particles.
+//!
+//! Picking is relatively heavy operation, it should not be used every frame for lots of
+//! rays, it is intended to be used for mouse picking in editors, strategy games and so on.
+//! See [`Scene::pick`](../struct.Scene.html#method.pick) and
+//! [`Scene::pick_ray`](../struct.Scene.html#method.pick_ray).
+
+use crate::{
+    core::{
+        algebra::{Point3, Vector3},
+        math::{aabb::AxisAlignedBoundingBox, ray::Ray, Matrix4Ext},
+        pool::Handle,
+    },
+    scene::{graph::Graph, mesh::Mesh, node::Node, sprite::Sprite},
+};
+
+/// Result of successful picking.
+#[derive(Clone, Debug)]
+pub struct PickResult {
+    /// Handle of picked node.
+    pub node: Handle<Node>,
+    /// Index of picked surface, only meshes have surfaces so for other nodes it will be
+    /// `None`.
+    pub surface: Option<usize>,
+    /// Intersection point in world coordinates.
+    pub position: Vector3<f32>,
+    /// Normal at intersection point in world coordinates. Normal always faces towards
+    /// origin of the ray.
+    pub normal: Vector3<f32>,
+    /// Distance from origin of the ray to intersection point.
+    pub distance: f32,
+}
+
+/// Camera parameters needed to test camera-facing sprites, side and up vectors must be
+/// normalized.
+pub(in crate) struct CameraAxes {
+    pub side: Vector3<f32>,
+    pub up: Vector3<f32>,
+    pub position: Vector3<f32>,
+}
+
+struct Hit {
+    /// Parameter of ray equation.
+    t: f32,
+    surface: Option<usize>,
+    normal: Vector3<f32>,
+}
+
+/// Möller-Trumbore ray-triangle intersection test, returns parameter of ray equation.
+/// Back-faces are not culled.
+fn ray_triangle(
+    origin: &Vector3<f32>,
+    dir: &Vector3<f32>,
+    triangle: &[Vector3<f32>; 3],
+) -> Option<f32> {
+    let ab = triangle[1] - triangle[0];
+    let ac = triangle[2] - triangle[0];
+    let p = dir.cross(&ac);
+    let det = ab.dot(&p);
+    if det.abs() <= f32::EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = origin - triangle[0];
+    let u = s.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&ab);
+    let v = dir.dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(&q) * inv_det;
+    if t >= 0.0 {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+fn pick_mesh(graph: &Graph, mesh: &Mesh, ray: &Ray) -> Option<Hit> {
+    let global_transform = mesh.global_transform();
+    let inv_global_transform = global_transform.try_inverse()?;
+
+    // Ray in local coordinates of the mesh, parameter of ray equation is the same in both
+    // spaces, so results of tests in different spaces can be compared.
+    let local_origin = inv_global_transform
+        .transform_point(&Point3::from(ray.origin))
+        .coords;
+    let local_dir = inv_global_transform.transform_vector(&ray.dir);
+
+    let mut closest: Option<Hit> = None;
+    let mut positions = Vec::new();
+
+    for (surface_index, surface) in mesh.surfaces().iter().enumerate() {
+        // Positions are in world space for skinned surfaces and in local space otherwise.
+        let skinned = !surface.bones().is_empty();
+        if skinned {
+            // Must be done before locking data of the surface, helper locks it too.
+            surface.skinned_positions(graph, global_transform, &mut positions);
+        }
+
+        let data = surface.data();
+        let data = data.read().unwrap();
+
+        let (origin, dir) = if skinned {
+            (ray.origin, ray.dir)
+        } else {
+            let mut bounds = AxisAlignedBoundingBox::default();
+            for vertex in data.get_vertices() {
+                bounds.add_point(vertex.position);
+            }
+            let local_ray = Ray {
+                origin: local_origin,
+                dir: local_dir,
+            };
+            if local_ray.aabb_intersection(&bounds).is_none() {
+                continue;
+            }
+
+            positions.clear();
+            positions.extend(data.get_vertices().iter().map(|vertex| vertex.position));
+
+            (local_origin, local_dir)
+        };
+
+        for triangle in data.triangles() {
+            let vertices = [
+                positions[triangle.0[0] as usize],
+                positions[triangle.0[1] as usize],
+                positions[triangle.0[2] as usize],
+            ];
+
+            if let Some(t) = ray_triangle(&origin, &dir, &vertices) {
+                if closest.as_ref().map_or(true, |hit| t < hit.t) {
+                    let mut normal =
+                        (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0]));
+                    if !skinned {
+                        // Normals must be transformed using inverse-transpose matrix to
+                        // handle non-uniform scale correctly.
+                        normal = inv_global_transform.basis().transpose() * normal;
+                    }
+                    closest = Some(Hit {
+                        t,
+                        surface: Some(surface_index),
+                        normal,
+                    });
+                }
+            }
+        }
+    }
+
+    closest
+}
+
+fn pick_sprite(sprite: &Sprite, ray: &Ray, camera: &CameraAxes) -> Option<Hit> {
+    let center = sprite.global_position();
+    let (side, up) = sprite.billboard_axes(camera.side, camera.up, camera.position);
+    let normal = side.cross(&up);
+    let denominator = normal.dot(&ray.dir);
+    if denominator.abs() <= f32::EPSILON {
+        return None;
+    }
+    let t = normal.dot(&(center - ray.origin)) / denominator;
+    if t < 0.0 {
+        return None;
+    }
+
+    // Sprites are rotated in camera plane by renderer, so do inverse rotation of the
+    // intersection point to check if it is inside of the quad.
+    let offset = ray.get_point(t) - center;
+    let (x, y) = (offset.dot(&side), offset.dot(&up));
+    let (sin, cos) = sprite.rotation().sin_cos();
+    let dimensions = sprite.dimensions();
+    if (cos * x - sin * y).abs() <= dimensions.x && (sin * x + cos * y).abs() <= dimensions.y {
+        Some(Hit {
+            t,
+            surface: None,
+            normal,
+        })
+    } else {
+        None
+    }
+}
+
+fn pick_aabb(bounds: &AxisAlignedBoundingBox, ray: &Ray) -> Option<Hit> {
+    let result = ray.aabb_intersection(bounds)?;
+    if result.max < 0.0 {
+        return None;
+    }
+    let t = result.min.max(0.0);
+
+    // Normal of the face the ray enters the box through.
+    let local = ray.get_point(t) - bounds.center();
+    let half_extents = bounds.half_extents();
+    let safe = |v: f32| {
+        if v.abs() > f32::EPSILON {
+            v
+        } else {
+            f32::EPSILON
+        }
+    };
+    let k = Vector3::new(
+        local.x / safe(half_extents.x),
+        local.y / safe(half_extents.y),
+        local.z / safe(half_extents.z),
+    );
+    let normal = if k.x.abs() >= k.y.abs() && k.x.abs() >= k.z.abs() {
+        Vector3::new(k.x.signum(), 0.0, 0.0)
+    } else if k.y.abs() >= k.z.abs() {
+        Vector3::new(0.0, k.y.signum(), 0.0)
+    } else {
+        Vector3::new(0.0, 0.0, k.z.signum())
+    };
+
+    Some(Hit {
+        t,
+        surface: None,
+        normal,
+    })
+}
+
+/// Tests given ray against every visible mesh, sprite and particle system in the graph
+/// and returns closest intersection. Filter allows to exclude some nodes from test, it
+/// must return false for such nodes.
+pub(in crate) fn pick_ray<F>(
+    graph: &Graph,
+    ray: &Ray,
+    camera: &CameraAxes,
+    mut filter: F,
+) -> Option<PickResult>
+where
+    F: FnMut(Handle<Node>, &Node) -> bool,
+{
+    let mut closest: Option<(Handle<Node>, Hit)> = None;
+
+    for (handle, node) in graph.pair_iter() {
+        if !node.global_visibility() || !filter(handle, node) {
+            continue;
+        }
+
+        let hit = match node {
+            Node::Mesh(mesh) => pick_mesh(graph, mesh, ray),
+            Node::Sprite(sprite) => pick_sprite(sprite, ray, camera),
+            Node::ParticleSystem(particle_system) => {
+                pick_aabb(&particle_system.world_bounding_box(), ray)
+            }
+            _ => None,
+        };
+
+        if let Some(hit) = hit {
+            if closest
+                .as_ref()
+                .map_or(true, |(_, closest)| hit.t < closest.t)
+            {
+                closest = Some((handle, hit));
+            }
+        }
+    }
+
+    closest.map(|(node, hit)| {
+        let position = ray.get_point(hit.t);
+        let mut normal = hit.normal.try_normalize(f32::EPSILON).unwrap_or_default();
+        if normal.dot(&ray.dir) > 0.0 {
+            normal = -normal;
+        }
+        PickResult {
+            node,
+            surface: hit.surface,
+            position,
+            normal,
+            distance: (position - ray.origin).norm(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::{Matrix4, Vector2, Vector3},
+        renderer::surface::{SurfaceBuilder, SurfaceSharedData},
+        scene::{
+            base::BaseBuilder, camera::CameraBuilder, mesh::MeshBuilder, sprite::SpriteBuilder,
+            transform::TransformBuilder, Scene,
+        },
+    };
+    use std::sync::{Arc, RwLock};
+
+    fn at(position: Vector3<f32>) -> BaseBuilder {
+        BaseBuilder::new().with_local_transform(
+            TransformBuilder::new()
+                .with_local_position(position)
+                .build(),
+        )
+    }
+
+    #[test]
+    fn pick_mesh_and_sprite() {
+        let mut scene = Scene::new();
+        let camera = CameraBuilder::new(BaseBuilder::new()).build_node();
+        let camera = scene.graph.add_node(camera);
+        let cube = MeshBuilder::new(at(Vector3::new(0.0, 0.0, 10.0)))
+            .with_surfaces(vec![SurfaceBuilder::new(Arc::new(RwLock::new(
+                SurfaceSharedData::make_cube(Matrix4::identity()),
+            )))
+            .build()])
+            .build_node();
+        let cube = scene.graph.add_node(cube);
+        let sprite = SpriteBuilder::new(at(Vector3::new(0.0, 0.0, 5.0)))
+            .with_size(0.1)
+            .build_node();
+        let sprite = scene.graph.add_node(sprite);
+
+        let screen_size = Vector2::new(100.0, 100.0);
+        scene.update(screen_size, 0.0);
+
+        // Sprite is in front of the cube.
+        let result = scene
+            .pick(camera, Vector2::new(50.0, 50.0), screen_size)
+            .unwrap();
+        assert_eq!(result.node, sprite);
+        assert!(result.surface.is_none());
+        assert!((result.position - Vector3::new(0.0, 0.0, 5.0)).norm() < 0.001);
+
+        let result = scene
+            .pick_ray(
+                camera,
+                &scene.graph[camera]
+                    .as_camera()
+                    .make_ray(Vector2::new(50.0, 50.0), screen_size),
+                |handle, _| handle != sprite,
+            )
+            .unwrap();
+        assert_eq!(result.node, cube);
+        assert_eq!(result.surface, Some(0));
+        assert!((result.position - Vector3::new(0.0, 0.0, 9.5)).norm() < 0.01);
+        assert!((result.normal - Vector3::new(0.0, 0.0, -1.0)).norm() < 0.001);
+
+        // Nothing at the corner of the screen.
+        assert!(scene
+            .pick(camera, Vector2::new(0.0, 0.0), screen_size)
+            .is_none());
+    }
+}
@@ -3,6 +3,20 @@
 //! Sprite is billboard which always faces towards camera. It can be used
 //! as a "model" for bullets, and so on.
 //!
+//! # Sprite sheets
+//!
+//! Sprite can use only a part of its texture defined by UV rectangle, this allows you
+//! to pack many sprites in a single texture (atlas). Sprite can also play flipbook
+//! animation using [`SpriteSheetAnimation`](struct.SpriteSheetAnimation.html) - each
+//! frame of such animation is a UV rectangle, this is useful for animated fire, muzzle
+//! flashes, explosions and so on.
+//!
+//! # Billboarding
+//!
+//! By default sprite always faces towards camera (spherical billboarding). Sprite can
+//! be locked to an axis (cylindrical billboarding), in this case it will rotate only
+//! around given axis. This is useful for trees, grass, fences, etc.
+//!
 //! # Performance
 //!
 //! Huge amount of sprites may cause performance issues, also uou should
@@ -11,7 +25,9 @@
 use crate::scene::node::Node;
 use crate::{
     core::{
+        algebra::{Vector2, Vector3},
         color::Color,
+        math::Rect,
         visitor::{Visit, VisitResult, Visitor},
     },
     resource::texture::Texture,
@@ -19,14 +35,275 @@ use crate::{
 };
 use std::ops::{Deref, DerefMut};
 
+/// Defines how frames of sprite sheet animation are played.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PlaybackMode {
+    /// Animation plays once and stops at last frame.
+    Once,
+    /// Animation starts over when last frame is reached.
+    Loop,
+    /// Animation plays forward and then backward, and so on.
+    PingPong,
+}
+
+impl Default for PlaybackMode {
+    fn default() -> Self {
+        Self::Loop
+    }
+}
+
+impl PlaybackMode {
+    fn id(self) -> u32 {
+        match self {
+            Self::Once => 0,
+            Self::Loop => 1,
+            Self::PingPong => 2,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::Once),
+            1 => Ok(Self::Loop),
+            2 => Ok(Self::PingPong),
+            _ => Err(format!("Invalid playback mode id {}", id)),
+        }
+    }
+}
+
+impl Visit for PlaybackMode {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id = self.id();
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Flipbook animation, each frame is a UV rectangle in texture of a sprite.
+#[derive(Clone, Debug)]
+pub struct SpriteSheetAnimation {
+    frames: Vec<Rect<f32>>,
+    fps: f32,
+    mode: PlaybackMode,
+    time: f32,
+    playing: bool,
+}
+
+impl Default for SpriteSheetAnimation {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl SpriteSheetAnimation {
+    /// Creates new looped animation from given list of frames with 30 frames per second.
+    /// Animation is playing by default.
+    pub fn new(frames: Vec<Rect<f32>>) -> Self {
+        Self {
+            frames,
+            fps: 30.0,
+            mode: PlaybackMode::Loop,
+            time: 0.0,
+            playing: true,
+        }
+    }
+
+    /// Creates new animation from a texture which has frames arranged in a grid of given
+    /// size. Frames are taken row by row starting from (0; 0) texture coordinates, `frame_count`
+    /// allows you to skip empty cells at the end of last row.
+    pub fn from_grid(columns: usize, rows: usize, frame_count: usize) -> Self {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        let (w, h) = (1.0 / columns as f32, 1.0 / rows as f32);
+        Self::new(
+            (0..frame_count.min(columns * rows))
+                .map(|i| Rect::new((i % columns) as f32 * w, (i / columns) as f32 * h, w, h))
+                .collect(),
+        )
+    }
+
+    /// Sets desired frames per second.
+    pub fn with_fps(mut self, fps: f32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    /// Sets desired playback mode.
+    pub fn with_mode(mut self, mode: PlaybackMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns shared reference to list of frames.
+    pub fn frames(&self) -> &[Rect<f32>] {
+        &self.frames
+    }
+
+    /// Returns mutable reference to list of frames.
+    pub fn frames_mut(&mut self) -> &mut Vec<Rect<f32>> {
+        &mut self.frames
+    }
+
+    /// Sets new playback speed in frames per second.
+    pub fn set_fps(&mut self, fps: f32) -> &mut Self {
+        self.fps = fps;
+        self
+    }
+
+    /// Returns playback speed in frames per second.
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    /// Sets new playback mode.
+    pub fn set_mode(&mut self, mode: PlaybackMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns current playback mode.
+    pub fn mode(&self) -> PlaybackMode {
+        self.mode
+    }
+
+    /// Starts or resumes playback.
+    pub fn play(&mut self) -> &mut Self {
+        self.playing = true;
+        self
+    }
+
+    /// Pauses playback, current frame is preserved.
+    pub fn stop(&mut self) -> &mut Self {
+        self.playing = false;
+        self
+    }
+
+    /// Moves animation to first frame.
+    pub fn rewind(&mut self) -> &mut Self {
+        self.time = 0.0;
+        self
+    }
+
+    /// Returns true if animation is playing.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Returns index of current frame.
+    pub fn current_frame(&self) -> usize {
+        let count = self.frames.len();
+        if count == 0 {
+            return 0;
+        }
+        let frame = (self.time * self.fps).max(0.0) as usize;
+        match self.mode {
+            PlaybackMode::Once => frame.min(count - 1),
+            PlaybackMode::Loop => frame % count,
+            PlaybackMode::PingPong => {
+                if count == 1 {
+                    0
+                } else {
+                    let period = 2 * count - 2;
+                    let frame = frame % period;
+                    if frame < count {
+                        frame
+                    } else {
+                        period - frame
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns UV rectangle of current frame, `None` if animation has no frames.
+    pub fn current_frame_uv_rect(&self) -> Option<Rect<f32>> {
+        self.frames.get(self.current_frame()).copied()
+    }
+
+    /// Advances animation by given amount of time. Animation with `Once` playback mode
+    /// stops when last frame was shown for full frame duration.
+    pub fn update(&mut self, dt: f32) {
+        if self.playing {
+            self.time += dt;
+            if self.mode == PlaybackMode::Once && self.time * self.fps >= self.frames.len() as f32 {
+                self.time = self.frames.len() as f32 / self.fps.max(f32::EPSILON);
+                self.playing = false;
+            }
+        }
+    }
+}
+
+impl Visit for SpriteSheetAnimation {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.frames.visit("Frames", visitor)?;
+        self.fps.visit("Fps", visitor)?;
+        self.mode.visit("Mode", visitor)?;
+        self.time.visit("Time", visitor)?;
+        self.playing.visit("Playing", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Defines how sprite is oriented towards camera.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BillboardMode {
+    /// Sprite always faces camera.
+    Spherical,
+    /// Sprite rotates only around given axis, axis is defined in local coordinates of
+    /// sprite node. Vertical side of sprite is always parallel to the axis.
+    Cylindrical {
+        /// Axis in local coordinates.
+        axis: Vector3<f32>,
+    },
+}
+
+impl Default for BillboardMode {
+    fn default() -> Self {
+        Self::Spherical
+    }
+}
+
+impl Visit for BillboardMode {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id: u32 = match self {
+            Self::Spherical => 0,
+            Self::Cylindrical { .. } => 1,
+        };
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = match id {
+                0 => Self::Spherical,
+                1 => Self::Cylindrical { axis: Vector3::y() },
+                _ => return Err(format!("Invalid billboard mode id {}", id).into()),
+            };
+        }
+        if let Self::Cylindrical { axis } = self {
+            axis.visit("Axis", visitor)?;
+        }
+
+        visitor.leave_region()
+    }
+}
+
 /// See module docs.
 #[derive(Debug)]
 pub struct Sprite {
     base: Base,
     texture: Option<Texture>,
     color: Color,
-    size: f32,
+    dimensions: Vector2<f32>,
     rotation: f32,
+    uv_rect: Rect<f32>,
+    billboard_mode: BillboardMode,
+    animation: Option<SpriteSheetAnimation>,
 }
 
 impl Deref for Sprite {
@@ -56,20 +333,33 @@ impl Sprite {
             base: self.base.raw_copy(),
             texture: self.texture.clone(),
             color: self.color,
-            size: self.size,
+            dimensions: self.dimensions,
             rotation: self.rotation,
+            uv_rect: self.uv_rect,
+            billboard_mode: self.billboard_mode,
+            animation: self.animation.clone(),
         }
     }
 
-    /// Sets new size of sprite. Since sprite is always square, size
-    /// defines half of width or height, so actual size will be doubled.    
+    /// Sets new size of sprite. Square sprite is assumed, size defines half
+    /// of width or height, so actual size will be doubled.
     pub fn set_size(&mut self, size: f32) {
-        self.size = size;
+        self.dimensions = Vector2::new(size, size);
     }
 
-    /// Returns current size of sprite.
+    /// Returns current size of sprite. For non-square sprites returns half of height.
     pub fn size(&self) -> f32 {
-        self.size
+        self.dimensions.y
+    }
+
+    /// Sets new half-width and half-height of sprite, allows to make non-square sprites.
+    pub fn set_dimensions(&mut self, dimensions: Vector2<f32>) {
+        self.dimensions = dimensions;
+    }
+
+    /// Returns half-width and half-height of sprite.
+    pub fn dimensions(&self) -> Vector2<f32> {
+        self.dimensions
     }
 
     /// Sets new color of sprite.
@@ -102,6 +392,91 @@ impl Sprite {
     pub fn texture(&self) -> Option<Texture> {
         self.texture.clone()
     }
+
+    /// Sets new rectangle in texture coordinates, only this part of texture will be
+    /// drawn. Ignored if sprite has animation.
+    pub fn set_uv_rect(&mut self, uv_rect: Rect<f32>) {
+        self.uv_rect = uv_rect;
+    }
+
+    /// Returns current rectangle in texture coordinates.
+    pub fn uv_rect(&self) -> Rect<f32> {
+        self.uv_rect
+    }
+
+    /// Returns rectangle in texture coordinates that will be used for rendering, it is
+    /// either current frame of animation or UV rectangle.
+    pub fn actual_uv_rect(&self) -> Rect<f32> {
+        self.animation
+            .as_ref()
+            .and_then(|animation| animation.current_frame_uv_rect())
+            .unwrap_or(self.uv_rect)
+    }
+
+    /// Sets new billboarding mode.
+    pub fn set_billboard_mode(&mut self, mode: BillboardMode) {
+        self.billboard_mode = mode;
+    }
+
+    /// Returns current billboarding mode.
+    pub fn billboard_mode(&self) -> BillboardMode {
+        self.billboard_mode
+    }
+
+    /// Sets new sprite sheet animation, returns previous one.
+    pub fn set_animation(
+        &mut self,
+        animation: Option<SpriteSheetAnimation>,
+    ) -> Option<SpriteSheetAnimation> {
+        std::mem::replace(&mut self.animation, animation)
+    }
+
+    /// Returns shared reference to current sprite sheet animation.
+    pub fn animation(&self) -> Option<&SpriteSheetAnimation> {
+        self.animation.as_ref()
+    }
+
+    /// Returns mutable reference to current sprite sheet animation.
+    pub fn animation_mut(&mut self) -> Option<&mut SpriteSheetAnimation> {
+        self.animation.as_mut()
+    }
+
+    /// Updates sprite sheet animation. This method should not be used directly, it will be
+    /// automatically called by scene update.
+    pub fn update(&mut self, dt: f32) {
+        if let Some(animation) = self.animation.as_mut() {
+            animation.update(dt);
+        }
+    }
+
+    /// Returns axis in world coordinates to which sprite is locked, `None` for spherical
+    /// billboarding.
+    pub fn world_billboard_axis(&self) -> Option<Vector3<f32>> {
+        match self.billboard_mode {
+            BillboardMode::Spherical => None,
+            BillboardMode::Cylindrical { axis } => self
+                .global_transform()
+                .transform_vector(&axis)
+                .try_normalize(f32::EPSILON),
+        }
+    }
+
+    /// Calculates side and up vectors of sprite quad in world coordinates for given camera
+    /// parameters. Must match calculations in sprite vertex shader.
+    pub(in crate) fn billboard_axes(
+        &self,
+        camera_side: Vector3<f32>,
+        camera_up: Vector3<f32>,
+        camera_position: Vector3<f32>,
+    ) -> (Vector3<f32>, Vector3<f32>) {
+        if let Some(axis) = self.world_billboard_axis() {
+            let to_camera = camera_position - self.global_position();
+            if let Some(side) = axis.cross(&to_camera).try_normalize(f32::EPSILON) {
+                return (side, axis);
+            }
+        }
+        (camera_side, camera_up)
+    }
 }
 
 impl Visit for Sprite {
@@ -110,9 +485,17 @@ impl Visit for Sprite {
 
         self.texture.visit("Texture", visitor)?;
         self.color.visit("Color", visitor)?;
-        self.size.visit("Size", visitor)?;
+        let mut size = self.dimensions.y;
+        size.visit("Size", visitor)?;
         self.rotation.visit("Rotation", visitor)?;
         self.base.visit("Base", visitor)?;
+        if self.dimensions.visit("Dimensions", visitor).is_err() && visitor.is_reading() {
+            // Old scenes have square sprites only.
+            self.dimensions = Vector2::new(size, size);
+        }
+        let _ = self.uv_rect.visit("UvRect", visitor);
+        let _ = self.billboard_mode.visit("BillboardMode", visitor);
+        let _ = self.animation.visit("Animation", visitor);
 
         visitor.leave_region()
     }
@@ -124,19 +507,26 @@ pub struct SpriteBuilder {
     base_builder: BaseBuilder,
     texture: Option<Texture>,
     color: Color,
-    size: f32,
+    dimensions: Vector2<f32>,
     rotation: f32,
+    uv_rect: Rect<f32>,
+    billboard_mode: BillboardMode,
+    animation: Option<SpriteSheetAnimation>,
 }
 
 impl SpriteBuilder {
-    /// Creates new builder with default state (white opaque color, 0.2 size, zero rotation).
+    /// Creates new builder with default state (white opaque color, 0.2 size, zero rotation,
+    /// full texture, spherical billboarding, no animation).
     pub fn new(base_builder: BaseBuilder) -> Self {
         Self {
             base_builder,
             texture: None,
             color: Color::WHITE,
-            size: 0.2,
+            dimensions: Vector2::new(0.2, 0.2),
             rotation: 0.0,
+            uv_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+            billboard_mode: BillboardMode::Spherical,
+            animation: None,
         }
     }
 
@@ -160,7 +550,13 @@ impl SpriteBuilder {
 
     /// Sets desired size.
     pub fn with_size(mut self, size: f32) -> Self {
-        self.size = size;
+        self.dimensions = Vector2::new(size, size);
+        self
+    }
+
+    /// Sets desired half-width and half-height.
+    pub fn with_dimensions(mut self, dimensions: Vector2<f32>) -> Self {
+        self.dimensions = dimensions;
         self
     }
 
@@ -170,14 +566,35 @@ impl SpriteBuilder {
         self
     }
 
+    /// Sets desired rectangle in texture coordinates.
+    pub fn with_uv_rect(mut self, uv_rect: Rect<f32>) -> Self {
+        self.uv_rect = uv_rect;
+        self
+    }
+
+    /// Sets desired billboarding mode.
+    pub fn with_billboard_mode(mut self, mode: BillboardMode) -> Self {
+        self.billboard_mode = mode;
+        self
+    }
+
+    /// Sets desired sprite sheet animation.
+    pub fn with_animation(mut self, animation: SpriteSheetAnimation) -> Self {
+        self.animation = Some(animation);
+        self
+    }
+
     /// Creates new sprite instance.
     pub fn build(self) -> Sprite {
         Sprite {
             base: self.base_builder.build(),
             texture: self.texture,
             color: self.color,
-            size: self.size,
+            dimensions: self.dimensions,
             rotation: self.rotation,
+            uv_rect: self.uv_rect,
+            billboard_mode: self.billboard_mode,
+            animation: self.animation,
         }
     }
 
@@ -186,3 +603,38 @@ impl SpriteBuilder {
         Node::Sprite(self.build())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::scene::sprite::{PlaybackMode, SpriteSheetAnimation};
+
+    #[test]
+    fn sprite_sheet_animation_playback() {
+        let mut animation = SpriteSheetAnimation::from_grid(2, 2, 3).with_fps(1.0);
+        assert_eq!(animation.frames().len(), 3);
+        assert_eq!(animation.frames()[2].x(), 0.0);
+        assert_eq!(animation.frames()[2].y(), 0.5);
+
+        let mut frames = Vec::new();
+        for mode in &[
+            PlaybackMode::Once,
+            PlaybackMode::Loop,
+            PlaybackMode::PingPong,
+        ] {
+            animation.set_mode(*mode).rewind().play();
+            frames.clear();
+            for _ in 0..6 {
+                frames.push(animation.current_frame());
+                animation.update(1.0);
+            }
+            match mode {
+                PlaybackMode::Once => {
+                    assert_eq!(frames, [0, 1, 2, 2, 2, 2]);
+                    assert!(!animation.is_playing());
+                }
+                PlaybackMode::Loop => assert_eq!(frames, [0, 1, 2, 0, 1, 2]),
+                PlaybackMode::PingPong => assert_eq!(frames, [0, 1, 2, 1, 0, 1]),
+            }
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! Contains all structures and methods to stream parts of a scene at runtime.
+//!
+//! Large worlds usually can't be loaded at once, instead they're split into cells where each
+//! cell is a separate scene file (`.rgs`) that covers some region of the world. Streamer keeps
+//! a list of such cells and loads cells that are close to an observer (usually an active camera)
+//! and removes cells that are far from it. Cells are loaded asynchronously using resource manager,
+//! so loading does not stall the game, once a cell is loaded its content (nodes, animations and
+//! rigid bodies bound to nodes) is merged into the scene.
+//!
+//! Streamer has two distances: load distance and unload distance. A cell starts loading when
+//! observer enters sphere of load distance around the cell bounds, and it will be unloaded when
+//! observer leaves sphere of unload distance. Unload distance should be larger than load distance
+//! to prevent cells from constantly loading and unloading when observer is near the border.
+//!
+//! # Limitations
+//!
+//! Only rigid bodies that are bound to nodes of a cell are streamed, joints are not streamed
+//! at all.
+//!
+//! # Example
+//!
+//! ```
+//! use rg3d::{
+//!     core::{algebra::Vector3, math::aabb::AxisAlignedBoundingBox},
+//!     scene::{node::Node, streaming::StreamingCell, Scene},
+//!     core::pool::Handle,
+//! };
+//!
+//! fn setup_streaming(scene: &mut Scene, camera: Handle<Node>) {
+//!     for i in 0..4 {
+//!         let min = Vector3::new(i as f32 * 100.0, -100.0, 0.0);
+//!         let max = min + Vector3::new(100.0, 200.0, 100.0);
+//!         scene.streamer.add_cell(StreamingCell::new(
+//!             format!("data/cells/cell{}.rgs", i),
+//!             AxisAlignedBoundingBox::from_min_max(min, max),
+//!         ));
+//!     }
+//!     scene.streamer.set_observer(camera);
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::aabb::AxisAlignedBoundingBox,
+        pool::{Handle, Pool, PoolIterator},
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    engine::resource_manager::ResourceManager,
+    resource::{model::Model, ResourceState},
+    scene::{node::Node, Scene},
+    utils::log::Log,
+};
+use std::{
+    collections::HashMap,
+    ops::{Index, IndexMut},
+    path::{Path, PathBuf},
+};
+
+/// Current state of a cell.
+#[derive(Debug, Clone)]
+pub enum CellState {
+    /// Content of the cell is not in the scene.
+    Unloaded,
+    /// Cell is loading.
+    Loading(Model),
+    /// Content of the cell is in the scene.
+    Loaded {
+        /// Handle of the root node of cell's content.
+        root: Handle<Node>,
+    },
+    /// Cell failed to load, it won't be loaded again until reset.
+    Failed,
+}
+
+impl Default for CellState {
+    fn default() -> Self {
+        Self::Unloaded
+    }
+}
+
+/// Streaming cell is a part of a world which is stored in a separate scene file and covers
+/// some region of the world.
+#[derive(Debug, Clone, Default)]
+pub struct StreamingCell {
+    path: PathBuf,
+    bounds: AxisAlignedBoundingBox,
+    state: CellState,
+}
+
+impl StreamingCell {
+    /// Creates new unloaded cell that will take content from given scene file and covers
+    /// given region.
+    pub fn new<P: AsRef<Path>>(path: P, bounds: AxisAlignedBoundingBox) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            bounds,
+            state: CellState::Unloaded,
+        }
+    }
+
+    /// Returns path to scene file of the cell.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns region covered by the cell.
+    pub fn bounds(&self) -> &AxisAlignedBoundingBox {
+        &self.bounds
+    }
+
+    /// Returns current state of the cell.
+    pub fn state(&self) -> &CellState {
+        &self.state
+    }
+
+    /// Returns handle of the root node of cell's content, or [`Handle::NONE`] if cell is not
+    /// loaded.
+    pub fn root(&self) -> Handle<Node> {
+        if let CellState::Loaded { root } = self.state {
+            root
+        } else {
+            Handle::NONE
+        }
+    }
+
+    /// Returns true if content of the cell is in the scene.
+    pub fn is_loaded(&self) -> bool {
+        matches!(self.state, CellState::Loaded { .. })
+    }
+
+    /// Allows the cell to be loaded again after a load failure.
+    pub fn reset_failure(&mut self) {
+        if let CellState::Failed = self.state {
+            self.state = CellState::Unloaded;
+        }
+    }
+}
+
+impl Visit for StreamingCell {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.path.visit("Path", visitor)?;
+        self.bounds.visit("Bounds", visitor)?;
+
+        // Content of a loaded cell is saved as part of the graph, so we only need to remember
+        // its root. Loading cells will be requested again.
+        let mut root = self.root();
+        root.visit("Root", visitor)?;
+        if visitor.is_reading() {
+            self.state = if root.is_some() {
+                CellState::Loaded { root }
+            } else {
+                CellState::Unloaded
+            };
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// See module docs.
+#[derive(Debug, Clone)]
+pub struct Streamer {
+    cells: Pool<StreamingCell>,
+    observer: Handle<Node>,
+    load_distance: f32,
+    unload_distance: f32,
+}
+
+impl Default for Streamer {
+    fn default() -> Self {
+        Self {
+            cells: Pool::new(),
+            observer: Handle::NONE,
+            load_distance: 100.0,
+            unload_distance: 150.0,
+        }
+    }
+}
+
+impl Streamer {
+    /// Adds new cell to the streamer. Cell will be loaded as soon as observer will be close
+    /// enough to it.
+    pub fn add_cell(&mut self, cell: StreamingCell) -> Handle<StreamingCell> {
+        self.cells.spawn(cell)
+    }
+
+    /// Removes a cell from the streamer and returns it. Content of the cell is *not* removed
+    /// from the scene, use [`Scene::remove_node`] with [`StreamingCell::root`] if needed.
+    pub fn remove_cell(&mut self, cell: Handle<StreamingCell>) -> StreamingCell {
+        self.cells.free(cell)
+    }
+
+    /// Returns an iterator over cells.
+    pub fn cells(&self) -> PoolIterator<'_, StreamingCell> {
+        self.cells.iter()
+    }
+
+    /// Sets a node around which cells will be loaded. Streaming is disabled if observer is not
+    /// set or was destroyed.
+    pub fn set_observer(&mut self, observer: Handle<Node>) {
+        self.observer = observer;
+    }
+
+    /// Returns current observer.
+    pub fn observer(&self) -> Handle<Node> {
+        self.observer
+    }
+
+    /// Sets distances to cell bounds at which cells will be loaded and unloaded. Unload
+    /// distance will be clamped so it is never less than load distance.
+    pub fn set_distances(&mut self, load_distance: f32, unload_distance: f32) {
+        self.load_distance = load_distance.max(0.0);
+        self.unload_distance = unload_distance.max(self.load_distance);
+    }
+
+    /// Returns distance to cell bounds at which cells will be loaded.
+    pub fn load_distance(&self) -> f32 {
+        self.load_distance
+    }
+
+    /// Returns distance to cell bounds at which cells will be unloaded.
+    pub fn unload_distance(&self) -> f32 {
+        self.unload_distance
+    }
+
+    /// Remaps roots of loaded cells using given old-to-new map. Cells which root is not in
+    /// the map become unloaded. Used when scene is copied.
+    pub(in crate) fn remap(&mut self, old_new_map: &HashMap<Handle<Node>, Handle<Node>>) {
+        self.observer = old_new_map.get(&self.observer).cloned().unwrap_or_default();
+        for cell in self.cells.iter_mut() {
+            if let CellState::Loaded { root } = cell.state {
+                cell.state = match old_new_map.get(&root) {
+                    Some(&root) => CellState::Loaded { root },
+                    None => CellState::Unloaded,
+                };
+            }
+        }
+    }
+
+    /// Checks distances from observer to every cell and requests cells to load or unload.
+    /// Also merges content of loaded cells into scene. Normally there is no need to call
+    /// this method directly, engine calls it automatically on each frame.
+    pub fn update(&mut self, scene: &mut Scene, resource_manager: &ResourceManager) {
+        if !scene.graph.is_valid_handle(self.observer) {
+            return;
+        }
+
+        let position = scene.graph[self.observer].global_position();
+
+        for cell in self.cells.iter_mut() {
+            let in_load_range = cell
+                .bounds
+                .is_intersects_sphere(position, self.load_distance);
+            let in_unload_range = cell
+                .bounds
+                .is_intersects_sphere(position, self.unload_distance);
+
+            cell.state = match std::mem::take(&mut cell.state) {
+                CellState::Unloaded => {
+                    if in_load_range {
+                        CellState::Loading(resource_manager.request_model(&cell.path))
+                    } else {
+                        CellState::Unloaded
+                    }
+                }
+                CellState::Loading(model) => {
+                    let state = model.state();
+                    match *state {
+                        ResourceState::Pending { .. } => {
+                            drop(state);
+                            CellState::Loading(model)
+                        }
+                        ResourceState::LoadError { .. } => {
+                            Log::writeln(format!("Unable to load streaming cell {:?}!", cell.path));
+                            CellState::Failed
+                        }
+                        ResourceState::Ok(_) => {
+                            drop(state);
+                            // Observer may have gone away while the cell was loading.
+                            if in_unload_range {
+                                CellState::Loaded {
+                                    root: merge_cell(&model, scene),
+                                }
+                            } else {
+                                CellState::Unloaded
+                            }
+                        }
+                    }
+                }
+                CellState::Loaded { root } => {
+                    if in_unload_range {
+                        CellState::Loaded { root }
+                    } else {
+                        unload_cell(root, scene);
+                        CellState::Unloaded
+                    }
+                }
+                CellState::Failed => CellState::Failed,
+            };
+        }
+    }
+}
+
+fn merge_cell(model: &Model, scene: &mut Scene) -> Handle<Node> {
+    let (root, old_new_mapping) = model.instantiate_geometry_with_mapping(scene);
+    model.retarget_animations(root, scene);
+
+    // Copy rigid bodies of nodes and bind them with copies of nodes.
+    let data = model.data_ref();
+    let cell_scene = data.get_scene();
+    for (node, &body) in cell_scene.physics_binder.node_rigid_body_map.iter() {
+        if let (Some(&new_node), Some(body)) = (
+            old_new_mapping.get(node),
+            cell_scene.physics.bodies.get(body.into()),
+        ) {
+            let new_body = scene.physics.add_body(body.clone());
+            for &collider in body.colliders() {
+                if let Some(collider) = cell_scene.physics.colliders.get(collider) {
+                    scene.physics.add_collider(collider.clone(), new_body);
+                }
+            }
+            scene.physics_binder.bind(new_node, new_body);
+        }
+    }
+
+    root
+}
+
+fn unload_cell(root: Handle<Node>, scene: &mut Scene) {
+    if !scene.graph.is_valid_handle(root) {
+        // Content was removed by someone else.
+        return;
+    }
+
+    // Bound rigid bodies will be removed too.
+    scene.remove_node(root);
+}
+
+impl Index<Handle<StreamingCell>> for Streamer {
+    type Output = StreamingCell;
+
+    fn index(&self, index: Handle<StreamingCell>) -> &Self::Output {
+        &self.cells[index]
+    }
+}
+
+impl IndexMut<Handle<StreamingCell>> for Streamer {
+    fn index_mut(&mut self, index: Handle<StreamingCell>) -> &mut Self::Output {
+        &mut self.cells[index]
+    }
+}
+
+impl Visit for Streamer {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.cells.visit("Cells", visitor)?;
+        self.observer.visit("Observer", visitor)?;
+        self.load_distance.visit("LoadDistance", visitor)?;
+        self.unload_distance.visit("UnloadDistance", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Vector3, math::aabb::AxisAlignedBoundingBox},
+        engine::resource_manager::ResourceManager,
+        scene::{
+            base::BaseBuilder,
+            streaming::{CellState, StreamingCell},
+            Scene,
+        },
+    };
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn streaming_cell_request() {
+        let resource_manager = ResourceManager::new();
+        let mut scene = Scene::new();
+        let observer = scene.graph.add_node(BaseBuilder::new().build_node());
+        scene.graph.update_hierarchical_data();
+
+        let near = scene.streamer.add_cell(StreamingCell::new(
+            "this/cell/does/not/exist.rgs",
+            AxisAlignedBoundingBox::from_min_max(
+                Vector3::new(10.0, -1.0, -1.0),
+                Vector3::new(20.0, 1.0, 1.0),
+            ),
+        ));
+        let far = scene.streamer.add_cell(StreamingCell::new(
+            "far.rgs",
+            AxisAlignedBoundingBox::from_min_max(
+                Vector3::new(1000.0, -1.0, -1.0),
+                Vector3::new(1010.0, 1.0, 1.0),
+            ),
+        ));
+
+        // No observer - nothing happens.
+        scene.update_streaming(&resource_manager);
+        assert!(matches!(scene.streamer[near].state(), CellState::Unloaded));
+
+        scene.streamer.set_observer(observer);
+        scene.streamer.set_distances(20.0, 40.0);
+        scene.update_streaming(&resource_manager);
+        assert!(matches!(scene.streamer[far].state(), CellState::Unloaded));
+
+        let begin = Instant::now();
+        while begin.elapsed() < Duration::from_secs(10) {
+            scene.update_streaming(&resource_manager);
+            if let CellState::Failed = scene.streamer[near].state() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(matches!(scene.streamer[near].state(), CellState::Failed));
+    }
+}
@@ -0,0 +1,1157 @@
+//! Contains all structures and methods to create and manage terrains.
+//!
+//! Terrain is a height field - a regular grid of points with a height in each point. Grid
+//! lies in XZ plane of local coordinate system of terrain node, its origin is at (0; 0)
+//! corner of the grid and it spans `width` along X axis and `length` along Z axis.
+//!
+//! # Geometry and LODs
+//!
+//! Terrain geometry is split into square chunks, each chunk has a set of levels of detail,
+//! level N uses every 2^N-th point of height map. Renderer selects level of detail of each
+//! chunk by its distance to camera, see [`Terrain::set_lod_distance`](struct.Terrain.html#method.set_lod_distance).
+//! Every chunk has a "skirt" - a vertical strip of triangles along its borders, skirts hide
+//! cracks between neighbour chunks with different levels of detail.
+//!
+//! # Layers
+//!
+//! Terrain can have any number of layers, each layer has its own diffuse and normal
+//! textures and a mask. Mask defines how much of a layer is visible at each point of height
+//! map. Layers are drawn in order, first layer is a base layer and it is always opaque (its
+//! mask is ignored), every next layer is blended over previous ones using its mask.
+//!
+//! # Editing
+//!
+//! Heights and masks can be modified using brushes, see [`Brush`](struct.Brush.html) and
+//! [`Terrain::draw`](struct.Terrain.html#method.draw). Geometry of modified chunks will be
+//! regenerated on next update of the scene.
+//!
+//! # Physics
+//!
+//! Scene automatically creates static rigid body with heightfield collider for every terrain
+//! and keeps heightfield in sync with height map. The body is bound to terrain node using
+//! physics binder, heightfield does not take scale of terrain node into account.
+
+use crate::{
+    core::{
+        algebra::{Point3, Vector2, Vector3, Vector4},
+        math::{aabb::AxisAlignedBoundingBox, lerpf, TriangleDefinition},
+        visitor::{Data, Visit, VisitResult, Visitor},
+    },
+    renderer::surface::{SurfaceSharedData, Vertex},
+    resource::texture::{
+        Texture, TextureData, TextureKind, TextureMagnificationFilter, TextureMinificationFilter,
+        TexturePixelKind, TextureState, TextureWrapMode,
+    },
+    scene::{
+        base::{Base, BaseBuilder},
+        node::Node,
+    },
+    utils::log::Log,
+};
+use rapier3d::{
+    geometry::{Collider, ColliderBuilder, ColliderShape},
+    na::{DMatrix, Isometry3, Translation3},
+};
+use std::ops::{Deref, DerefMut};
+
+/// Terrain layer, see module docs.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    diffuse_texture: Option<Texture>,
+    normal_texture: Option<Texture>,
+    tile_factor: Vector2<f32>,
+    mask: Vec<u8>,
+    mask_texture: Option<Texture>,
+    mask_dirty: bool,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Visit for Layer {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.diffuse_texture.visit("DiffuseTexture", visitor)?;
+        self.normal_texture.visit("NormalTexture", visitor)?;
+        self.tile_factor.visit("TileFactor", visitor)?;
+        Data::new(&mut self.mask).visit("Mask", visitor)?;
+
+        if visitor.is_reading() {
+            self.mask_dirty = true;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+impl Layer {
+    /// Creates new layer without textures and with 1.0 tile factor. Mask will be created
+    /// when layer is added to a terrain.
+    pub fn new() -> Self {
+        Self {
+            diffuse_texture: None,
+            normal_texture: None,
+            tile_factor: Vector2::new(1.0, 1.0),
+            mask: Default::default(),
+            mask_texture: None,
+            mask_dirty: true,
+        }
+    }
+
+    /// Sets desired diffuse texture.
+    pub fn with_diffuse_texture(mut self, texture: Texture) -> Self {
+        self.diffuse_texture = Some(texture);
+        self
+    }
+
+    /// Sets desired normal texture.
+    pub fn with_normal_texture(mut self, texture: Texture) -> Self {
+        self.normal_texture = Some(texture);
+        self
+    }
+
+    /// Sets desired tile factor.
+    pub fn with_tile_factor(mut self, tile_factor: Vector2<f32>) -> Self {
+        self.tile_factor = tile_factor;
+        self
+    }
+
+    /// Sets new diffuse texture.
+    pub fn set_diffuse_texture(&mut self, texture: Option<Texture>) {
+        self.diffuse_texture = texture;
+    }
+
+    /// Returns current diffuse texture.
+    pub fn diffuse_texture(&self) -> Option<Texture> {
+        self.diffuse_texture.clone()
+    }
+
+    /// Sets new normal texture.
+    pub fn set_normal_texture(&mut self, texture: Option<Texture>) {
+        self.normal_texture = texture;
+    }
+
+    /// Returns current normal texture.
+    pub fn normal_texture(&self) -> Option<Texture> {
+        self.normal_texture.clone()
+    }
+
+    /// Sets how many times textures of the layer are repeated over whole terrain along
+    /// X and Z axes.
+    pub fn set_tile_factor(&mut self, tile_factor: Vector2<f32>) {
+        self.tile_factor = tile_factor;
+    }
+
+    /// Returns current tile factor.
+    pub fn tile_factor(&self) -> Vector2<f32> {
+        self.tile_factor
+    }
+
+    /// Returns mask of the layer, it has one value per each point of height map, rows go
+    /// along X axis.
+    pub fn mask(&self) -> &[u8] {
+        &self.mask
+    }
+
+    /// Returns mutable reference to mask of the layer, mask texture will be updated on next
+    /// update of the scene.
+    pub fn mask_mut(&mut self) -> &mut [u8] {
+        self.mask_dirty = true;
+        &mut self.mask
+    }
+
+    /// Returns texture that is made from current mask, it is `None` until first update of
+    /// the scene after the layer was added to a terrain.
+    pub fn mask_texture(&self) -> Option<Texture> {
+        self.mask_texture.clone()
+    }
+
+    fn update_mask_texture(&mut self, width_point_count: u32, length_point_count: u32) {
+        if !self.mask_dirty {
+            return;
+        }
+        self.mask_dirty = false;
+
+        match TextureData::from_bytes(
+            TextureKind::Rectangle {
+                width: width_point_count,
+                height: length_point_count,
+            },
+            TexturePixelKind::R8,
+            self.mask.clone(),
+        ) {
+            Ok(mut data) => {
+                data.set_minification_filter(TextureMinificationFilter::Linear);
+                data.set_magnification_filter(TextureMagnificationFilter::Linear);
+                data.set_s_wrap_mode(TextureWrapMode::ClampToEdge);
+                data.set_t_wrap_mode(TextureWrapMode::ClampToEdge);
+                // Texture is re-created instead of modification of existing one, because
+                // renderer does not track changes of texture data.
+                self.mask_texture = Some(Texture::new(TextureState::Ok(data)));
+            }
+            Err(_) => {
+                Log::writeln("Unable to create mask texture of terrain layer, mask size does not match size of height map!".to_owned());
+                self.mask_texture = None;
+            }
+        }
+    }
+}
+
+/// Part of terrain geometry, see module docs.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    // Indices of first and last points of height map covered by the chunk.
+    x_range: (u32, u32),
+    z_range: (u32, u32),
+    lods: Vec<SurfaceSharedData>,
+    bounds: AxisAlignedBoundingBox,
+    dirty: bool,
+}
+
+impl Chunk {
+    /// Returns geometry of each level of detail, first level is the most detailed.
+    pub fn lods(&self) -> &[SurfaceSharedData] {
+        &self.lods
+    }
+
+    /// Returns bounding box of the chunk in local coordinates of terrain.
+    pub fn local_bounds(&self) -> AxisAlignedBoundingBox {
+        self.bounds
+    }
+}
+
+/// Shape of a brush.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BrushShape {
+    /// Circle with given radius, effect of the brush fades linearly from center to edge.
+    Circle {
+        /// Radius of the circle.
+        radius: f32,
+    },
+    /// Rectangle with given size, effect of the brush is the same in every point.
+    Rectangle {
+        /// Size along X axis.
+        width: f32,
+        /// Size along Z axis.
+        length: f32,
+    },
+}
+
+/// Defines what brush does with terrain.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BrushMode {
+    /// Adds given amount to heights, negative values lower terrain.
+    ModifyHeight {
+        /// Amount of change in the center of brush.
+        amount: f32,
+    },
+    /// Moves heights towards given height.
+    Flatten {
+        /// Desired height.
+        height: f32,
+    },
+    /// Adds given amount to mask of a layer, negative values erase the layer.
+    DrawOnMask {
+        /// Index of a layer.
+        layer: usize,
+        /// Amount of change in the center of brush, in [-1; 1] range.
+        alpha: f32,
+    },
+}
+
+/// Brush is used to modify heights and masks of terrain.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Brush {
+    /// Center of the brush in world coordinates, only X and Z coordinates (in local space of
+    /// terrain) are used.
+    pub center: Vector3<f32>,
+    /// Shape of the brush, sizes are defined in local coordinates of terrain.
+    pub shape: BrushShape,
+    /// What the brush does.
+    pub mode: BrushMode,
+}
+
+/// See module docs.
+#[derive(Debug)]
+pub struct Terrain {
+    base: Base,
+    width: f32,
+    length: f32,
+    width_point_count: u32,
+    length_point_count: u32,
+    heightmap: Vec<f32>,
+    layers: Vec<Layer>,
+    chunk_size: u32,
+    lod_distance: f32,
+    skirt_depth: f32,
+    chunks: Vec<Chunk>,
+    chunks_dirty: bool,
+    collider_dirty: bool,
+}
+
+impl Deref for Terrain {
+    type Target = Base;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for Terrain {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        TerrainBuilder::new(BaseBuilder::new()).build()
+    }
+}
+
+impl Visit for Terrain {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Base", visitor)?;
+        self.width.visit("Width", visitor)?;
+        self.length.visit("Length", visitor)?;
+        self.width_point_count.visit("WidthPointCount", visitor)?;
+        self.length_point_count.visit("LengthPointCount", visitor)?;
+
+        let mut bytes = self
+            .heightmap
+            .iter()
+            .flat_map(|h| h.to_le_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        Data::new(&mut bytes).visit("Heights", visitor)?;
+        if visitor.is_reading() {
+            self.heightmap = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+        }
+
+        self.layers.visit("Layers", visitor)?;
+        self.chunk_size.visit("ChunkSize", visitor)?;
+        self.lod_distance.visit("LodDistance", visitor)?;
+        self.skirt_depth.visit("SkirtDepth", visitor)?;
+
+        if visitor.is_reading() {
+            self.chunks_dirty = true;
+            self.collider_dirty = true;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+// Indices of points of a grid line from `begin` to `end` (inclusive) with given step, last
+// point is always included even if distance between it and previous one is less than step.
+fn grid_line(begin: u32, end: u32, step: u32) -> Vec<u32> {
+    let mut line = (begin..end).step_by(step as usize).collect::<Vec<_>>();
+    line.push(end);
+    line
+}
+
+impl Terrain {
+    /// Creates a raw copy of a terrain node.
+    pub fn raw_copy(&self) -> Self {
+        Self {
+            base: self.base.raw_copy(),
+            width: self.width,
+            length: self.length,
+            width_point_count: self.width_point_count,
+            length_point_count: self.length_point_count,
+            heightmap: self.heightmap.clone(),
+            layers: self.layers.clone(),
+            chunk_size: self.chunk_size,
+            lod_distance: self.lod_distance,
+            skirt_depth: self.skirt_depth,
+            // Geometry will be regenerated on next update.
+            chunks: Default::default(),
+            chunks_dirty: true,
+            collider_dirty: true,
+        }
+    }
+
+    /// Returns size of terrain along X axis.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Sets new size of terrain along X axis.
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width;
+        self.invalidate_all();
+    }
+
+    /// Returns size of terrain along Z axis.
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    /// Sets new size of terrain along Z axis.
+    pub fn set_length(&mut self, length: f32) {
+        self.length = length;
+        self.invalidate_all();
+    }
+
+    /// Returns amount of points of height map along X axis.
+    pub fn width_point_count(&self) -> u32 {
+        self.width_point_count
+    }
+
+    /// Returns amount of points of height map along Z axis.
+    pub fn length_point_count(&self) -> u32 {
+        self.length_point_count
+    }
+
+    /// Returns height map, rows go along X axis.
+    pub fn heightmap(&self) -> &[f32] {
+        &self.heightmap
+    }
+
+    /// Sets new height map, rows go along X axis. Masks of layers will be reset if
+    /// resolution of height map has changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are less than two points along any axis or if size of height map
+    /// does not match given point counts.
+    pub fn set_heightmap(
+        &mut self,
+        width_point_count: u32,
+        length_point_count: u32,
+        heightmap: Vec<f32>,
+    ) {
+        assert!(width_point_count > 1 && length_point_count > 1);
+        assert_eq!(
+            heightmap.len(),
+            (width_point_count * length_point_count) as usize
+        );
+        let resized = self.width_point_count != width_point_count
+            || self.length_point_count != length_point_count;
+        self.width_point_count = width_point_count;
+        self.length_point_count = length_point_count;
+        self.heightmap = heightmap;
+        if resized {
+            let point_count = self.heightmap.len();
+            for (i, layer) in self.layers.iter_mut().enumerate() {
+                layer.mask = vec![if i == 0 { 255 } else { 0 }; point_count];
+                layer.mask_dirty = true;
+            }
+        }
+        self.invalidate_all();
+    }
+
+    /// Returns height at given point of height map.
+    pub fn height(&self, x: u32, z: u32) -> Option<f32> {
+        if x < self.width_point_count && z < self.length_point_count {
+            Some(self.heightmap[(z * self.width_point_count + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Sets height at given point of height map, does nothing if point is out of bounds.
+    pub fn set_height(&mut self, x: u32, z: u32, height: f32) {
+        if x < self.width_point_count && z < self.length_point_count {
+            self.heightmap[(z * self.width_point_count + x) as usize] = height;
+            self.invalidate_region(x, z, x, z);
+        }
+    }
+
+    /// Returns height of terrain at given point in local coordinates using bilinear
+    /// interpolation of height map, returns `None` if point is outside of terrain.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        if x < 0.0 || z < 0.0 || x > self.width || z > self.length {
+            return None;
+        }
+        let (dx, dz) = self.cell_size();
+        let fx = (x / dx).min((self.width_point_count - 1) as f32);
+        let fz = (z / dz).min((self.length_point_count - 1) as f32);
+        let (x0, z0) = (fx.floor() as u32, fz.floor() as u32);
+        let (x1, z1) = (
+            (x0 + 1).min(self.width_point_count - 1),
+            (z0 + 1).min(self.length_point_count - 1),
+        );
+        let (kx, kz) = (fx.fract(), fz.fract());
+        let h = |x, z| self.heightmap[(z * self.width_point_count + x) as usize];
+        Some(lerpf(
+            lerpf(h(x0, z0), h(x1, z0), kx),
+            lerpf(h(x0, z1), h(x1, z1), kx),
+            kz,
+        ))
+    }
+
+    /// Returns shared reference to layers.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Returns mutable reference to layers.
+    pub fn layers_mut(&mut self) -> &mut [Layer] {
+        &mut self.layers
+    }
+
+    /// Adds new layer on top of other layers. If mask of the layer does not match size of
+    /// height map, it will be replaced with empty mask (or full mask for first layer).
+    pub fn add_layer(&mut self, mut layer: Layer) {
+        let point_count = self.heightmap.len();
+        if layer.mask.len() != point_count {
+            layer.mask = vec![if self.layers.is_empty() { 255 } else { 0 }; point_count];
+        }
+        layer.mask_dirty = true;
+        self.layers.push(layer);
+    }
+
+    /// Removes layer at given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if index is out of bounds.
+    pub fn remove_layer(&mut self, index: usize) -> Layer {
+        self.layers.remove(index)
+    }
+
+    /// Returns amount of height map cells in a side of a chunk.
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    /// Sets new size of chunks in height map cells, it will be rounded up to the next power
+    /// of two.
+    pub fn set_chunk_size(&mut self, chunk_size: u32) {
+        self.chunk_size = chunk_size.max(2).next_power_of_two();
+        self.chunks_dirty = true;
+    }
+
+    /// Returns current LOD distance.
+    pub fn lod_distance(&self) -> f32 {
+        self.lod_distance
+    }
+
+    /// Sets distance at which chunks will switch from first level of detail to second one,
+    /// every next level is used at twice the distance of previous one. Zero distance disables
+    /// LODs.
+    pub fn set_lod_distance(&mut self, lod_distance: f32) {
+        self.lod_distance = lod_distance.max(0.0);
+    }
+
+    /// Returns current depth of chunk skirts.
+    pub fn skirt_depth(&self) -> f32 {
+        self.skirt_depth
+    }
+
+    /// Sets new depth of chunk skirts.
+    pub fn set_skirt_depth(&mut self, skirt_depth: f32) {
+        self.skirt_depth = skirt_depth.max(0.0);
+        self.chunks_dirty = true;
+    }
+
+    /// Returns chunks of terrain, they are generated on update of the scene so it will be
+    /// empty for a newly created terrain.
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// Returns index of level of detail of given chunk that should be used for an observer
+    /// at given position in world coordinates.
+    pub fn select_lod(&self, chunk: &Chunk, observer: Vector3<f32>) -> usize {
+        if self.lod_distance <= 0.0 || chunk.lods.is_empty() {
+            return 0;
+        }
+        let center = self
+            .global_transform()
+            .transform_point(&Point3::from(chunk.bounds.center()))
+            .coords;
+        let k = center.metric_distance(&observer) / self.lod_distance;
+        let lod = if k < 1.0 {
+            0
+        } else {
+            k.log2().floor() as usize + 1
+        };
+        lod.min(chunk.lods.len() - 1)
+    }
+
+    /// Applies given brush to the terrain.
+    pub fn draw(&mut self, brush: &Brush) {
+        let center = match self.global_transform().try_inverse() {
+            Some(inv) => inv.transform_point(&Point3::from(brush.center)).coords,
+            None => return,
+        };
+        let (half_width, half_length) = match brush.shape {
+            BrushShape::Circle { radius } => (radius, radius),
+            BrushShape::Rectangle { width, length } => (width * 0.5, length * 0.5),
+        };
+        let (dx, dz) = self.cell_size();
+        let to_index = |v: f32, cell: f32, count: u32| ((v / cell).max(0.0) as u32).min(count - 1);
+        let (x_min, x_max) = (
+            to_index(center.x - half_width, dx, self.width_point_count),
+            to_index(center.x + half_width, dx, self.width_point_count),
+        );
+        let (z_min, z_max) = (
+            to_index(center.z - half_length, dz, self.length_point_count),
+            to_index(center.z + half_length, dz, self.length_point_count),
+        );
+
+        let mut modified = false;
+        for z in z_min..=z_max {
+            for x in x_min..=x_max {
+                let offset = Vector2::new(x as f32 * dx - center.x, z as f32 * dz - center.z);
+                let k = match brush.shape {
+                    BrushShape::Circle { radius } => {
+                        let distance = offset.norm();
+                        if distance > radius || radius <= 0.0 {
+                            continue;
+                        }
+                        1.0 - distance / radius
+                    }
+                    BrushShape::Rectangle { .. } => {
+                        if offset.x.abs() > half_width || offset.y.abs() > half_length {
+                            continue;
+                        }
+                        1.0
+                    }
+                };
+                let index = (z * self.width_point_count + x) as usize;
+                match brush.mode {
+                    BrushMode::ModifyHeight { amount } => self.heightmap[index] += amount * k,
+                    BrushMode::Flatten { height } => {
+                        self.heightmap[index] = lerpf(self.heightmap[index], height, k)
+                    }
+                    BrushMode::DrawOnMask { layer, alpha } => {
+                        if let Some(layer) = self.layers.get_mut(layer) {
+                            let value = layer.mask[index] as f32 + alpha * k * 255.0;
+                            layer.mask[index] = value.clamp(0.0, 255.0) as u8;
+                            layer.mask_dirty = true;
+                        }
+                    }
+                }
+                modified = true;
+            }
+        }
+
+        if modified {
+            if let BrushMode::DrawOnMask { .. } = brush.mode {
+                // Masks do not affect geometry.
+            } else {
+                self.invalidate_region(x_min, z_min, x_max, z_max);
+            }
+        }
+    }
+
+    /// Creates heightfield collider that matches height map of the terrain. Heightfield is
+    /// positioned relative to terrain node, so it must be attached to a rigid body that has
+    /// same position and rotation as terrain node.
+    pub fn make_heightfield_collider(&self) -> Collider {
+        // Rows of heightfield go along Z axis, columns - along X axis.
+        let heights = DMatrix::from_fn(
+            self.length_point_count as usize,
+            self.width_point_count as usize,
+            |row, column| self.heightmap[row * self.width_point_count as usize + column],
+        );
+        // Heightfield is centered at origin of its collider.
+        ColliderBuilder::new(ColliderShape::heightfield(
+            heights,
+            Vector3::new(self.width, 1.0, self.length),
+        ))
+        .position(Isometry3 {
+            translation: Translation3::new(self.width * 0.5, 0.0, self.length * 0.5),
+            rotation: Default::default(),
+        })
+        .build()
+    }
+
+    /// Returns true if heightfield collider must be re-created, and resets the flag.
+    pub(in crate) fn take_collider_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.collider_dirty, false)
+    }
+
+    /// Regenerates geometry of modified chunks and textures of modified masks. This method
+    /// should not be used directly, it will be automatically called by scene update.
+    pub fn update(&mut self) {
+        if self.chunks_dirty {
+            self.chunks_dirty = false;
+            self.rebuild_chunk_list();
+        }
+
+        for i in 0..self.chunks.len() {
+            if self.chunks[i].dirty {
+                let (x_range, z_range) = (self.chunks[i].x_range, self.chunks[i].z_range);
+                let lods = self.make_chunk_lods(x_range, z_range);
+                let chunk = &mut self.chunks[i];
+                chunk.bounds = AxisAlignedBoundingBox::default();
+                for vertex in lods[0].get_vertices() {
+                    chunk.bounds.add_point(vertex.position);
+                }
+                chunk.lods = lods;
+                chunk.dirty = false;
+            }
+        }
+
+        let (width_point_count, length_point_count) =
+            (self.width_point_count, self.length_point_count);
+        for layer in self.layers.iter_mut() {
+            layer.update_mask_texture(width_point_count, length_point_count);
+        }
+    }
+
+    fn cell_size(&self) -> (f32, f32) {
+        (
+            self.width / (self.width_point_count - 1) as f32,
+            self.length / (self.length_point_count - 1) as f32,
+        )
+    }
+
+    fn invalidate_all(&mut self) {
+        self.chunks_dirty = true;
+        self.collider_dirty = true;
+    }
+
+    // Marks chunks that contain given region of height map as dirty. Neighbour points are
+    // included too, because their normals depend on modified heights.
+    fn invalidate_region(&mut self, x_min: u32, z_min: u32, x_max: u32, z_max: u32) {
+        let (x_min, z_min) = (x_min.saturating_sub(1), z_min.saturating_sub(1));
+        let (x_max, z_max) = (x_max + 1, z_max + 1);
+        for chunk in self.chunks.iter_mut() {
+            if chunk.x_range.0 <= x_max
+                && chunk.x_range.1 >= x_min
+                && chunk.z_range.0 <= z_max
+                && chunk.z_range.1 >= z_min
+            {
+                chunk.dirty = true;
+            }
+        }
+        self.collider_dirty = true;
+    }
+
+    fn rebuild_chunk_list(&mut self) {
+        self.chunks.clear();
+        let (last_x, last_z) = (self.width_point_count - 1, self.length_point_count - 1);
+        let mut z = 0;
+        while z < last_z {
+            let mut x = 0;
+            while x < last_x {
+                self.chunks.push(Chunk {
+                    x_range: (x, (x + self.chunk_size).min(last_x)),
+                    z_range: (z, (z + self.chunk_size).min(last_z)),
+                    lods: Default::default(),
+                    bounds: Default::default(),
+                    dirty: true,
+                });
+                x += self.chunk_size;
+            }
+            z += self.chunk_size;
+        }
+    }
+
+    fn make_vertex(&self, x: u32, z: u32) -> Vertex {
+        let (dx, dz) = self.cell_size();
+        let h = |x: u32, z: u32| {
+            let x = x.min(self.width_point_count - 1);
+            let z = z.min(self.length_point_count - 1);
+            self.heightmap[(z * self.width_point_count + x) as usize]
+        };
+
+        let normal = Vector3::new(
+            (h(x.saturating_sub(1), z) - h(x + 1, z)) / (2.0 * dx),
+            1.0,
+            (h(x, z.saturating_sub(1)) - h(x, z + 1)) / (2.0 * dz),
+        )
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(Vector3::y);
+
+        // Texture coordinates go along X and Z axes, so tangent is X axis made orthogonal
+        // to the normal.
+        let tangent = (Vector3::x() - normal.scale(normal.x))
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::x);
+
+        Vertex {
+            position: Vector3::new(x as f32 * dx, h(x, z), z as f32 * dz),
+            tex_coord: Vector2::new(
+                x as f32 / (self.width_point_count - 1) as f32,
+                z as f32 / (self.length_point_count - 1) as f32,
+            ),
+            second_tex_coord: Default::default(),
+            normal,
+            tangent: Vector4::new(tangent.x, tangent.y, tangent.z, 1.0),
+            bone_weights: [0.0; 4],
+            bone_indices: Default::default(),
+        }
+    }
+
+    fn make_chunk_lods(&self, x_range: (u32, u32), z_range: (u32, u32)) -> Vec<SurfaceSharedData> {
+        let mut lods = Vec::new();
+        let mut step = 1;
+        while step <= self.chunk_size {
+            let xs = grid_line(x_range.0, x_range.1, step);
+            let zs = grid_line(z_range.0, z_range.1, step);
+            let row = xs.len() as u32;
+
+            let mut vertices = Vec::with_capacity(xs.len() * zs.len());
+            for &z in zs.iter() {
+                for &x in xs.iter() {
+                    vertices.push(self.make_vertex(x, z));
+                }
+            }
+
+            let mut triangles = Vec::new();
+            for j in 0..zs.len() as u32 - 1 {
+                for i in 0..row - 1 {
+                    let a = j * row + i;
+                    let b = a + row;
+                    triangles.push(TriangleDefinition([a, b, a + 1]));
+                    triangles.push(TriangleDefinition([a + 1, b, b + 1]));
+                }
+            }
+
+            // Skirts are double-sided so they hide cracks from any direction.
+            let column = zs.len() as u32;
+            let borders = [
+                (0..row).collect::<Vec<_>>(),
+                (0..row).map(|i| (column - 1) * row + i).collect(),
+                (0..column).map(|j| j * row).collect(),
+                (0..column).map(|j| j * row + row - 1).collect(),
+            ];
+            for border in borders.iter() {
+                let first_lowered = vertices.len() as u32;
+                for &index in border.iter() {
+                    let mut vertex = vertices[index as usize];
+                    vertex.position.y -= self.skirt_depth;
+                    vertices.push(vertex);
+                }
+                for k in 0..border.len() as u32 - 1 {
+                    let (a, b) = (border[k as usize], border[k as usize + 1]);
+                    let (la, lb) = (first_lowered + k, first_lowered + k + 1);
+                    triangles.push(TriangleDefinition([a, la, b]));
+                    triangles.push(TriangleDefinition([b, la, lb]));
+                    triangles.push(TriangleDefinition([b, la, a]));
+                    triangles.push(TriangleDefinition([lb, la, b]));
+                }
+            }
+
+            lods.push(SurfaceSharedData::new(vertices, triangles, true));
+
+            step *= 2;
+        }
+        lods
+    }
+}
+
+fn heights_from_texture(data: &TextureData, max_height: f32) -> Option<(u32, u32, Vec<f32>)> {
+    let (width, height) = if let TextureKind::Rectangle { width, height } = data.kind {
+        (width, height)
+    } else {
+        return None;
+    };
+    // Stride of a pixel, offset of red channel and size of a channel in bytes.
+    let (stride, offset, channel_size) = match data.pixel_kind {
+        TexturePixelKind::R8 => (1, 0, 1),
+        TexturePixelKind::RG8 => (2, 0, 1),
+        TexturePixelKind::RGB8 => (3, 0, 1),
+        TexturePixelKind::BGR8 => (3, 2, 1),
+        TexturePixelKind::RGBA8 => (4, 0, 1),
+        TexturePixelKind::BGRA8 => (4, 2, 1),
+        TexturePixelKind::R16 => (2, 0, 2),
+        TexturePixelKind::RG16 => (4, 0, 2),
+        TexturePixelKind::RGB16 => (6, 0, 2),
+        TexturePixelKind::RGBA16 => (8, 0, 2),
+        _ => return None,
+    };
+    if width < 2 || height < 2 || data.bytes.len() < (width * height) as usize * stride {
+        return None;
+    }
+    let heights = data
+        .bytes
+        .chunks_exact(stride)
+        .take((width * height) as usize)
+        .map(|pixel| {
+            let value = if channel_size == 1 {
+                pixel[offset] as f32 / 255.0
+            } else {
+                u16::from_ne_bytes([pixel[offset], pixel[offset + 1]]) as f32 / 65535.0
+            };
+            value * max_height
+        })
+        .collect();
+    Some((width, height, heights))
+}
+
+/// Terrain builder allows you to construct terrain in declarative manner.
+/// This is typical implementation of Builder pattern.
+pub struct TerrainBuilder {
+    base_builder: BaseBuilder,
+    width: f32,
+    length: f32,
+    width_point_count: u32,
+    length_point_count: u32,
+    heightmap: Option<Vec<f32>>,
+    height_map_texture: Option<(Texture, f32)>,
+    layers: Vec<Layer>,
+    chunk_size: u32,
+    lod_distance: f32,
+    skirt_depth: f32,
+}
+
+impl TerrainBuilder {
+    /// Creates new builder with default state (flat 64x64 terrain with 65x65 height map,
+    /// 32 cells in a chunk, 32.0 LOD distance, no layers).
+    pub fn new(base_builder: BaseBuilder) -> Self {
+        Self {
+            base_builder,
+            width: 64.0,
+            length: 64.0,
+            width_point_count: 65,
+            length_point_count: 65,
+            heightmap: None,
+            height_map_texture: None,
+            layers: Default::default(),
+            chunk_size: 32,
+            lod_distance: 32.0,
+            skirt_depth: 1.0,
+        }
+    }
+
+    /// Sets desired size of terrain along X axis.
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Sets desired size of terrain along Z axis.
+    pub fn with_length(mut self, length: f32) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Sets desired height map, rows go along X axis. Height map must have at least two
+    /// points along each axis and its size must match given point counts, otherwise flat
+    /// height map with given point counts will be used.
+    pub fn with_height_map(
+        mut self,
+        width_point_count: u32,
+        length_point_count: u32,
+        heightmap: Vec<f32>,
+    ) -> Self {
+        self.width_point_count = width_point_count.max(2);
+        self.length_point_count = length_point_count.max(2);
+        self.heightmap = Some(heightmap);
+        self
+    }
+
+    /// Sets desired height map texture, it must be loaded at the moment of building. Red
+    /// channel of each pixel defines height in [0; max_height] range, width of texture is
+    /// the amount of points along X axis, height of texture - along Z axis. Takes precedence
+    /// over height map.
+    pub fn with_height_map_texture(mut self, texture: Texture, max_height: f32) -> Self {
+        self.height_map_texture = Some((texture, max_height));
+        self
+    }
+
+    /// Sets desired layers.
+    pub fn with_layers(mut self, layers: Vec<Layer>) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Sets desired amount of height map cells in a side of a chunk.
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets desired LOD distance.
+    pub fn with_lod_distance(mut self, lod_distance: f32) -> Self {
+        self.lod_distance = lod_distance;
+        self
+    }
+
+    /// Sets desired depth of chunk skirts.
+    pub fn with_skirt_depth(mut self, skirt_depth: f32) -> Self {
+        self.skirt_depth = skirt_depth;
+        self
+    }
+
+    /// Creates new terrain instance.
+    pub fn build(self) -> Terrain {
+        let mut width_point_count = self.width_point_count;
+        let mut length_point_count = self.length_point_count;
+        let point_count = (width_point_count * length_point_count) as usize;
+        let mut heightmap = match self.heightmap {
+            Some(heightmap) if heightmap.len() == point_count => heightmap,
+            Some(_) => {
+                Log::writeln("Size of terrain height map does not match its point counts! Flat height map will be used.".to_owned());
+                vec![0.0; point_count]
+            }
+            None => vec![0.0; point_count],
+        };
+
+        if let Some((texture, max_height)) = self.height_map_texture {
+            let result = if let TextureState::Ok(data) = &*texture.state() {
+                heights_from_texture(data, max_height)
+            } else {
+                None
+            };
+            match result {
+                Some((width, length, heights)) => {
+                    width_point_count = width;
+                    length_point_count = length;
+                    heightmap = heights;
+                }
+                None => Log::writeln(
+                    "Unable to use texture as terrain height map, it is not loaded or has unsupported format!"
+                        .to_owned(),
+                ),
+            }
+        }
+
+        let mut terrain = Terrain {
+            base: self.base_builder.build(),
+            width: self.width,
+            length: self.length,
+            width_point_count,
+            length_point_count,
+            heightmap,
+            layers: Default::default(),
+            chunk_size: self.chunk_size.max(2).next_power_of_two(),
+            lod_distance: self.lod_distance.max(0.0),
+            skirt_depth: self.skirt_depth.max(0.0),
+            chunks: Default::default(),
+            chunks_dirty: true,
+            collider_dirty: true,
+        };
+        for layer in self.layers {
+            terrain.add_layer(layer);
+        }
+        terrain
+    }
+
+    /// Creates new node instance.
+    pub fn build_node(self) -> Node {
+        Node::Terrain(self.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::{Vector2, Vector3},
+        scene::{
+            base::BaseBuilder,
+            physics::Physics,
+            terrain::{Brush, BrushMode, BrushShape, Layer, TerrainBuilder},
+            RigidBodyHandle, Scene,
+        },
+        utils::testing::visit_round_trip,
+    };
+
+    #[test]
+    fn terrain_chunks_and_brush() {
+        let mut terrain = TerrainBuilder::new(BaseBuilder::new())
+            .with_width(16.0)
+            .with_length(8.0)
+            .with_height_map(17, 9, vec![0.0; 17 * 9])
+            .with_chunk_size(8)
+            .with_layers(vec![Layer::new(), Layer::new()])
+            .build();
+        terrain.update();
+
+        assert_eq!(terrain.chunks().len(), 2);
+        // Steps 1, 2, 4 and 8.
+        assert_eq!(terrain.chunks()[0].lods().len(), 4);
+        assert!(terrain.layers()[0].mask().iter().all(|&v| v == 255));
+        assert!(terrain.layers()[1].mask().iter().all(|&v| v == 0));
+        assert!(terrain.layers()[1].mask_texture().is_some());
+
+        terrain.draw(&Brush {
+            center: Vector3::new(4.0, 0.0, 4.0),
+            shape: BrushShape::Circle { radius: 2.0 },
+            mode: BrushMode::ModifyHeight { amount: 1.0 },
+        });
+        assert_eq!(terrain.height(4, 4), Some(1.0));
+        assert_eq!(terrain.height(5, 4), Some(0.5));
+        assert_eq!(terrain.height(12, 4), Some(0.0));
+        assert_eq!(terrain.height_at(4.5, 4.0), Some(0.75));
+        // Only first chunk is affected.
+        assert!(terrain.chunks()[0].dirty);
+        assert!(!terrain.chunks()[1].dirty);
+
+        terrain.draw(&Brush {
+            center: Vector3::new(12.0, 0.0, 4.0),
+            shape: BrushShape::Rectangle {
+                width: 2.0,
+                length: 2.0,
+            },
+            mode: BrushMode::DrawOnMask {
+                layer: 1,
+                alpha: 1.0,
+            },
+        });
+        assert_eq!(terrain.layers()[1].mask()[4 * 17 + 12], 255);
+        assert_eq!(terrain.layers()[1].mask()[4 * 17 + 14], 0);
+
+        terrain.update();
+        let lod = &terrain.chunks()[0].lods()[0];
+        assert!(lod
+            .get_vertices()
+            .iter()
+            .any(|v| v.position == Vector3::new(4.0, 1.0, 4.0)));
+
+        let collider = terrain.make_heightfield_collider();
+        let heightfield = collider.shape().as_heightfield().unwrap();
+        assert_eq!(heightfield.heights().nrows(), 9);
+        assert_eq!(heightfield.heights().ncols(), 17);
+        assert_eq!(heightfield.heights()[(4, 4)], 1.0);
+    }
+
+    #[test]
+    fn terrain_heightfield_collider() {
+        let mut scene = Scene::new();
+        let terrain = scene.graph.add_node(
+            TerrainBuilder::new(BaseBuilder::new())
+                .with_width(2.0)
+                .with_length(1.0)
+                .with_height_map(3, 2, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0])
+                .build_node(),
+        );
+
+        fn heights(physics: &Physics, body: RigidBodyHandle) -> Vec<f32> {
+            let colliders = physics.bodies[body.into()].colliders();
+            assert_eq!(colliders.len(), 1);
+            let heightfield = physics.colliders[colliders[0]]
+                .shape()
+                .as_heightfield()
+                .unwrap();
+            heightfield.heights().as_slice().to_vec()
+        }
+
+        scene.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+        let body = scene.physics_binder.body_of(terrain).unwrap();
+        // Column-major, rows go along Z axis.
+        assert_eq!(
+            heights(&scene.physics, body),
+            vec![0.0, 3.0, 1.0, 4.0, 2.0, 5.0]
+        );
+
+        scene.graph[terrain].as_terrain_mut().set_height(1, 1, 10.0);
+        scene.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+        assert_eq!(scene.physics_binder.body_of(terrain), Some(body));
+        assert_eq!(
+            heights(&scene.physics, body),
+            vec![0.0, 3.0, 1.0, 10.0, 2.0, 5.0]
+        );
+
+        // Heightfield must survive save/load through physics descriptors.
+        let mut physics = Physics::default();
+        visit_round_trip("Physics", &mut scene.physics, &mut physics);
+        physics.resolve(&scene.physics_binder, &scene.graph);
+        assert_eq!(heights(&physics, body), vec![0.0, 3.0, 1.0, 10.0, 2.0, 5.0]);
+    }
+}
@@ -7,6 +7,9 @@ pub mod lightmap;
 pub mod log;
 pub mod navmesh;
 pub mod raw_mesh;
+pub mod simplify;
+#[cfg(test)]
+pub(in crate) mod testing;
 pub mod uvgen;
 
 use crate::core::algebra::Vector2;
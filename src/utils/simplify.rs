@@ -0,0 +1,728 @@
+//! Mesh simplifier. Used to generate levels of detail for meshes.
+//!
+//! Simplification is done by series of half-edge collapses - a vertex is merged into one of
+//! its neighbours, the cost of each collapse is evaluated using quadric error metric.
+//! Vertices are never moved and never created, so every vertex of simplified mesh has exactly
+//! same attributes (texture coordinates, normals, skin weights, etc.) as in source mesh.
+//!
+//! UV seams (vertices with same position, but different attributes) and open borders of a mesh
+//! are preserved - a vertex on a seam can be collapsed only along the seam, a vertex on a border
+//! can be collapsed only along the border.
+
+use crate::{
+    core::{
+        algebra::Vector3,
+        math::{aabb::AxisAlignedBoundingBox, TriangleDefinition},
+        pool::Handle,
+    },
+    renderer::surface::{SurfaceBuilder, SurfaceSharedData, Vertex},
+    scene::{
+        base::{BaseBuilder, LevelOfDetail, LodGroup},
+        graph::Graph,
+        mesh::MeshBuilder,
+        node::Node,
+    },
+};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+// Edges on borders and seams are "glued" to their initial place by additional planes
+// perpendicular to faces, this coefficient defines how strong the glue is.
+const BORDER_WEIGHT: f64 = 10.0;
+
+#[derive(Copy, Clone, Default, Debug)]
+struct Quadric {
+    a2: f64,
+    ab: f64,
+    ac: f64,
+    ad: f64,
+    b2: f64,
+    bc: f64,
+    bd: f64,
+    c2: f64,
+    cd: f64,
+    d2: f64,
+}
+
+impl Quadric {
+    fn from_plane(normal: Vector3<f64>, point: Vector3<f64>, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        let d = -normal.dot(&point);
+        Self {
+            a2: a * a * weight,
+            ab: a * b * weight,
+            ac: a * c * weight,
+            ad: a * d * weight,
+            b2: b * b * weight,
+            bc: b * c * weight,
+            bd: b * d * weight,
+            c2: c * c * weight,
+            cd: c * d * weight,
+            d2: d * d * weight,
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        self.a2 += other.a2;
+        self.ab += other.ab;
+        self.ac += other.ac;
+        self.ad += other.ad;
+        self.b2 += other.b2;
+        self.bc += other.bc;
+        self.bd += other.bd;
+        self.c2 += other.c2;
+        self.cd += other.cd;
+        self.d2 += other.d2;
+    }
+
+    // Returns weighted sum of squared distances from given point to planes of the quadric.
+    fn error(&self, p: Vector3<f64>) -> f64 {
+        let (x, y, z) = (p.x, p.y, p.z);
+        (x * x * self.a2
+            + y * y * self.b2
+            + z * z * self.c2
+            + 2.0 * (x * y * self.ab + x * z * self.ac + y * z * self.bc)
+            + 2.0 * (x * self.ad + y * self.bd + z * self.cd)
+            + self.d2)
+            .abs()
+    }
+}
+
+fn vertex_key(v: &Vertex) -> [u32; 19] {
+    [
+        v.position.x.to_bits(),
+        v.position.y.to_bits(),
+        v.position.z.to_bits(),
+        v.tex_coord.x.to_bits(),
+        v.tex_coord.y.to_bits(),
+        v.second_tex_coord.x.to_bits(),
+        v.second_tex_coord.y.to_bits(),
+        v.normal.x.to_bits(),
+        v.normal.y.to_bits(),
+        v.normal.z.to_bits(),
+        v.tangent.x.to_bits(),
+        v.tangent.y.to_bits(),
+        v.tangent.z.to_bits(),
+        v.tangent.w.to_bits(),
+        v.bone_weights[0].to_bits(),
+        v.bone_weights[1].to_bits(),
+        v.bone_weights[2].to_bits(),
+        v.bone_weights[3].to_bits(),
+        u32::from_le_bytes(v.bone_indices),
+    ]
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+struct Simplifier {
+    // Positions of "geometric" vertices, each one has one or more attribute vertices (wedges).
+    positions: Vec<Vector3<f64>>,
+    // Index of geometric vertex for each attribute vertex.
+    position_of: Vec<usize>,
+    // Attribute vertex indices.
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    alive_count: usize,
+    // Triangles around each geometric vertex, may contain dead triangles.
+    adjacency: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+}
+
+impl Simplifier {
+    fn new(vertices: &[Vertex], triangles: &[[usize; 3]]) -> Self {
+        let mut position_map = HashMap::new();
+        let mut positions = Vec::new();
+        let position_of = vertices
+            .iter()
+            .map(|v| {
+                let key = [
+                    v.position.x.to_bits(),
+                    v.position.y.to_bits(),
+                    v.position.z.to_bits(),
+                ];
+                *position_map.entry(key).or_insert_with(|| {
+                    positions.push(Vector3::new(
+                        v.position.x as f64,
+                        v.position.y as f64,
+                        v.position.z as f64,
+                    ));
+                    positions.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Triangles that are already degenerated are thrown away.
+        let triangles = triangles
+            .iter()
+            .filter(|t| {
+                let (a, b, c) = (position_of[t[0]], position_of[t[1]], position_of[t[2]]);
+                a != b && b != c && c != a
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut adjacency = vec![Vec::new(); positions.len()];
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut attribute_edges = HashMap::new();
+        for (i, triangle) in triangles.iter().enumerate() {
+            let p = triangle.iter().map(|&v| position_of[v]).collect::<Vec<_>>();
+            let cross =
+                (positions[p[1]] - positions[p[0]]).cross(&(positions[p[2]] - positions[p[0]]));
+            let area = cross.norm() * 0.5;
+            if let Some(normal) = cross.try_normalize(f64::EPSILON) {
+                let quadric = Quadric::from_plane(normal, positions[p[0]], area);
+                for &k in p.iter() {
+                    quadrics[k].add(&quadric);
+                }
+            }
+            for k in 0..3 {
+                adjacency[p[k]].push(i);
+                *attribute_edges
+                    .entry(edge(triangle[k], triangle[(k + 1) % 3]))
+                    .or_insert(0) += 1;
+            }
+        }
+
+        // Edges that belong to a single triangle are either on a border or on a seam.
+        for triangle in triangles.iter() {
+            let p = triangle.iter().map(|&v| position_of[v]).collect::<Vec<_>>();
+            let face_normal = (positions[p[1]] - positions[p[0]])
+                .cross(&(positions[p[2]] - positions[p[0]]))
+                .try_normalize(f64::EPSILON);
+            for k in 0..3 {
+                if attribute_edges[&edge(triangle[k], triangle[(k + 1) % 3])] != 1 {
+                    continue;
+                }
+                let (a, b) = (p[k], p[(k + 1) % 3]);
+                let direction = positions[b] - positions[a];
+                if let Some(normal) =
+                    face_normal.and_then(|n| direction.cross(&n).try_normalize(f64::EPSILON))
+                {
+                    let quadric = Quadric::from_plane(
+                        normal,
+                        positions[a],
+                        direction.norm_squared() * BORDER_WEIGHT,
+                    );
+                    quadrics[a].add(&quadric);
+                    quadrics[b].add(&quadric);
+                }
+            }
+        }
+
+        Self {
+            positions,
+            position_of,
+            alive: vec![true; triangles.len()],
+            alive_count: triangles.len(),
+            triangles,
+            adjacency,
+            quadrics,
+        }
+    }
+
+    fn live_triangles(&self, position: usize) -> impl Iterator<Item = usize> + '_ {
+        self.adjacency[position]
+            .iter()
+            .cloned()
+            .filter(move |&t| self.alive[t])
+    }
+
+    fn neighbours(&self, position: usize) -> HashSet<usize> {
+        self.live_triangles(position)
+            .flat_map(|t| self.triangles[t].iter().map(|&v| self.position_of[v]))
+            .filter(|&p| p != position)
+            .collect()
+    }
+
+    // Checks whether geometric vertex `from` can be merged into `to` and returns pairs of
+    // attribute vertices (from -> to) that should be merged.
+    fn collapse_remap(&self, from: usize, to: usize) -> Option<Vec<(usize, usize)>> {
+        let mut remap: Vec<(usize, usize)> = Vec::new();
+        let mut shared_triangles = 0;
+        for t in self.live_triangles(from) {
+            let triangle = self.triangles[t];
+            let wedge_to = triangle
+                .iter()
+                .cloned()
+                .find(|&v| self.position_of[v] == to);
+            if let Some(wedge_to) = wedge_to {
+                shared_triangles += 1;
+                let wedge_from = triangle
+                    .iter()
+                    .cloned()
+                    .find(|&v| self.position_of[v] == from)
+                    .unwrap();
+                match remap.iter().find(|(a, _)| *a == wedge_from) {
+                    // Attribute vertex must have exactly one partner, otherwise the collapse
+                    // would tear or weld a seam.
+                    Some(&(_, b)) if b != wedge_to => return None,
+                    Some(_) => (),
+                    None => remap.push((wedge_from, wedge_to)),
+                }
+            }
+        }
+
+        // Every attribute vertex must have a partner.
+        for t in self.live_triangles(from) {
+            for &v in self.triangles[t].iter() {
+                if self.position_of[v] == from && !remap.iter().any(|(a, _)| *a == v) {
+                    return None;
+                }
+            }
+        }
+
+        // Link condition - vertices must not have common neighbours except the ones that form
+        // shared triangles, otherwise collapse will produce non-manifold geometry.
+        let common = self
+            .neighbours(from)
+            .intersection(&self.neighbours(to))
+            .count();
+        if shared_triangles == 0 || common != shared_triangles {
+            return None;
+        }
+
+        // Triangles must not flip.
+        for t in self.live_triangles(from) {
+            let p = self.triangles[t]
+                .iter()
+                .map(|&v| self.position_of[v])
+                .collect::<Vec<_>>();
+            if p.contains(&to) {
+                continue;
+            }
+            let normal = |p: &[usize], moved: Option<Vector3<f64>>| {
+                let pos = |k: usize| {
+                    if p[k] == from {
+                        moved.unwrap_or(self.positions[from])
+                    } else {
+                        self.positions[p[k]]
+                    }
+                };
+                (pos(1) - pos(0)).cross(&(pos(2) - pos(0)))
+            };
+            let old = normal(&p, None);
+            let new = normal(&p, Some(self.positions[to]));
+            if old.dot(&new) <= 0.0 {
+                return None;
+            }
+        }
+
+        Some(remap)
+    }
+
+    fn collapse(&mut self, from: usize, to: usize, remap: &[(usize, usize)]) {
+        let triangles = std::mem::take(&mut self.adjacency[from]);
+        for t in triangles {
+            if !self.alive[t] {
+                continue;
+            }
+            if self.triangles[t].iter().any(|&v| self.position_of[v] == to) {
+                self.alive[t] = false;
+                self.alive_count -= 1;
+            } else {
+                for v in self.triangles[t].iter_mut() {
+                    if let Some(&(_, b)) = remap.iter().find(|(a, _)| *a == *v) {
+                        *v = b;
+                    }
+                }
+                self.adjacency[to].push(t);
+            }
+        }
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+    }
+
+    fn run(&mut self, target_triangle_count: usize) {
+        while self.alive_count > target_triangle_count {
+            // Count how many triangles share each edge to find borders.
+            let mut edges = HashMap::new();
+            for (t, triangle) in self.triangles.iter().enumerate() {
+                if self.alive[t] {
+                    for k in 0..3 {
+                        let a = self.position_of[triangle[k]];
+                        let b = self.position_of[triangle[(k + 1) % 3]];
+                        *edges.entry(edge(a, b)).or_insert(0) += 1;
+                    }
+                }
+            }
+            let mut is_border = vec![false; self.positions.len()];
+            for (&(a, b), &count) in edges.iter() {
+                if count == 1 {
+                    is_border[a] = true;
+                    is_border[b] = true;
+                }
+            }
+
+            let mut candidates = Vec::with_capacity(edges.len());
+            for (&(a, b), &count) in edges.iter() {
+                let cost = |from: usize, to: usize| {
+                    // Border vertex can move only along border.
+                    if is_border[from] && count != 1 {
+                        f64::MAX
+                    } else {
+                        self.quadrics[from].error(self.positions[to])
+                    }
+                };
+                let (ab, ba) = (cost(a, b), cost(b, a));
+                if ab < ba {
+                    candidates.push((ab, a, b));
+                } else if ba < f64::MAX {
+                    candidates.push((ba, b, a));
+                }
+            }
+            // Degenerate input (NaN positions) gives NaN errors, such edges are collapsed in
+            // any order.
+            candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+            // Each collapse invalidates information about its neighbourhood, so such vertices
+            // are locked until next pass.
+            let mut locked = vec![false; self.positions.len()];
+            let mut collapsed = false;
+            for (_, from, to) in candidates {
+                if self.alive_count <= target_triangle_count {
+                    break;
+                }
+                if locked[from] || locked[to] {
+                    continue;
+                }
+                if let Some(remap) = self.collapse_remap(from, to) {
+                    self.collapse(from, to, &remap);
+                    locked[from] = true;
+                    locked[to] = true;
+                    for neighbour in self.neighbours(to) {
+                        locked[neighbour] = true;
+                    }
+                    collapsed = true;
+                }
+            }
+
+            if !collapsed {
+                break;
+            }
+        }
+    }
+}
+
+/// Simplifies given surface data until it has given amount of triangles or less. Simplification
+/// stops earlier if there is no collapse that preserves seams, borders and topology of the
+/// surface. Returns new procedural surface data, source data is left untouched.
+pub fn simplify(data: &SurfaceSharedData, target_triangle_count: usize) -> SurfaceSharedData {
+    // Weld duplicated vertices first, seam detection relies on shared vertices.
+    let mut vertex_map = HashMap::new();
+    let mut vertices = Vec::new();
+    let index_map = data
+        .vertices
+        .iter()
+        .map(|v| {
+            *vertex_map.entry(vertex_key(v)).or_insert_with(|| {
+                vertices.push(*v);
+                vertices.len() - 1
+            })
+        })
+        .collect::<Vec<_>>();
+    let triangles = data
+        .triangles
+        .iter()
+        .map(|t| {
+            [
+                index_map[t[0] as usize],
+                index_map[t[1] as usize],
+                index_map[t[2] as usize],
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut simplifier = Simplifier::new(&vertices, &triangles);
+    simplifier.run(target_triangle_count);
+
+    // Throw away unused vertices.
+    let mut new_index = vec![None; vertices.len()];
+    let mut new_vertices = Vec::new();
+    let mut new_triangles = Vec::with_capacity(simplifier.alive_count);
+    for (t, triangle) in simplifier.triangles.iter().enumerate() {
+        if !simplifier.alive[t] {
+            continue;
+        }
+        let mut indices = [0; 3];
+        for (index, &v) in indices.iter_mut().zip(triangle.iter()) {
+            *index = *new_index[v].get_or_insert_with(|| {
+                new_vertices.push(vertices[v]);
+                (new_vertices.len() - 1) as u32
+            });
+        }
+        new_triangles.push(TriangleDefinition(indices));
+    }
+
+    SurfaceSharedData::new(new_vertices, new_triangles, true)
+}
+
+/// Settings of a generated level of detail.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LodSettings {
+    /// Level of detail is used when object takes less than given part of screen height,
+    /// should be in (0; 1) range.
+    pub screen_size: f32,
+    /// Amount of triangles of the level relative to source mesh, should be in (0; 1) range.
+    pub triangle_ratio: f32,
+}
+
+/// Generates levels of detail for a mesh node and assigns new LOD group to it. Each level is a
+/// new mesh with simplified surfaces which is attached to the source mesh as a child, source
+/// mesh itself is the first level. Levels are sorted by screen size in descending order.
+///
+/// LOD group works with normalized distances, so screen sizes are converted into distances
+/// using given vertical field of view and far plane distance of a camera which will be used to
+/// render the mesh. Size of the mesh is taken from its world bounding box, so global transform
+/// of the mesh must be up-to-date.
+///
+/// Levels with non-finite settings are ignored. Returns handles of generated meshes.
+///
+/// # Panics
+///
+/// Panics if given handle is not a mesh.
+pub fn generate_lod_group(
+    graph: &mut Graph,
+    mesh_handle: Handle<Node>,
+    levels: &[LodSettings],
+    fov: f32,
+    z_far: f32,
+) -> Vec<Handle<Node>> {
+    let mut levels = levels
+        .iter()
+        .filter(|level| level.screen_size.is_finite() && level.triangle_ratio.is_finite())
+        .copied()
+        .collect::<Vec<_>>();
+    levels.sort_by(|a, b| {
+        b.screen_size
+            .partial_cmp(&a.screen_size)
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let mesh = graph[mesh_handle].as_mesh();
+
+    let bounds: AxisAlignedBoundingBox = mesh.world_bounding_box();
+    let radius = (bounds.max - bounds.min).norm() * 0.5;
+    let half_fov_tan = (fov * 0.5).tan();
+    let normalized_distance = |screen_size: f32| {
+        let distance = radius / (screen_size.max(f32::EPSILON) * half_fov_tan);
+        (distance / z_far).clamp(0.0, 1.0)
+    };
+
+    let name = mesh.name().to_owned();
+    let decal_layer_index = mesh.decal_layer_index();
+    let simplified = levels
+        .iter()
+        .map(|settings| {
+            mesh.surfaces()
+                .iter()
+                .map(|surface| {
+                    let data = surface.data();
+                    let data = data.read().unwrap();
+                    let target = (data.triangles().len() as f32
+                        * settings.triangle_ratio.clamp(0.0, 1.0))
+                        as usize;
+                    let mut builder =
+                        SurfaceBuilder::new(Arc::new(RwLock::new(simplify(&data, target))))
+                            .with_color(surface.color())
+                            .with_blend_mode(surface.blend_mode())
+                            .with_bones(surface.bones().to_vec());
+                    if let Some(texture) = surface.diffuse_texture() {
+                        builder = builder.with_diffuse_texture(texture);
+                    }
+                    if let Some(texture) = surface.normal_texture() {
+                        builder = builder.with_normal_texture(texture);
+                    }
+                    if let Some(texture) = surface.specular_texture() {
+                        builder = builder.with_specular_texture(texture);
+                    }
+                    if let Some(texture) = surface.roughness_texture() {
+                        builder = builder.with_roughness_texture(texture);
+                    }
+                    if let Some(texture) = surface.metallic_texture() {
+                        builder = builder.with_metallic_texture(texture);
+                    }
+                    if let Some(texture) = surface.ambient_occlusion_texture() {
+                        builder = builder.with_ambient_occlusion_texture(texture);
+                    }
+                    if let Some(texture) = surface.emission_texture() {
+                        builder = builder.with_emission_texture(texture);
+                    }
+                    if let Some(texture) = surface.lightmap_texture() {
+                        builder = builder.with_lightmap_texture(texture);
+                    }
+                    if let Some(material) = surface.material() {
+                        builder = builder.with_material(material);
+                    }
+                    builder.build()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut handles = Vec::with_capacity(levels.len());
+    for (i, surfaces) in simplified.into_iter().enumerate() {
+        let handle = graph.add_node(
+            MeshBuilder::new(BaseBuilder::new().with_name(format!("{}_LOD{}", name, i + 1)))
+                .with_surfaces(surfaces)
+                .with_decal_layer_index(decal_layer_index)
+                .build_node(),
+        );
+        graph.link_nodes(handle, mesh_handle);
+        handles.push(handle);
+    }
+
+    let mut lod_group = LodGroup::default();
+    let mut begin = 0.0;
+    for (i, settings) in levels.iter().enumerate() {
+        let end = normalized_distance(settings.screen_size);
+        let object = if i == 0 { mesh_handle } else { handles[i - 1] };
+        lod_group
+            .levels
+            .push(LevelOfDetail::new(begin, end, vec![object]));
+        begin = end;
+    }
+    if let Some(&last) = handles.last() {
+        lod_group
+            .levels
+            .push(LevelOfDetail::new(begin, 1.0, vec![last]));
+    }
+    graph[mesh_handle].set_lod_group(lod_group);
+
+    handles
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::Matrix4,
+        renderer::surface::{SurfaceBuilder, SurfaceSharedData},
+        scene::{base::BaseBuilder, graph::Graph, mesh::MeshBuilder},
+        utils::simplify::{generate_lod_group, simplify, LodSettings},
+    };
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn simplify_sphere() {
+        let data = SurfaceSharedData::make_sphere(16, 16, 1.0);
+        let source_count = data.triangles().len();
+        let simplified = simplify(&data, source_count / 4);
+
+        assert!(simplified.triangles().len() <= source_count / 4);
+        assert!(simplified.triangles().len() > source_count / 8);
+        // Vertices are never moved or modified.
+        for vertex in simplified.get_vertices() {
+            assert!(data
+                .get_vertices()
+                .iter()
+                .any(|v| v == vertex && v.second_tex_coord == vertex.second_tex_coord));
+        }
+    }
+
+    #[test]
+    fn simplify_preserves_seams() {
+        // Each corner of a cube has three vertices with different normals and UVs.
+        let data = SurfaceSharedData::make_cube(Matrix4::identity());
+        let simplified = simplify(&data, 0);
+        assert_eq!(simplified.triangles().len(), data.triangles().len());
+    }
+
+    #[test]
+    fn generate_lods() {
+        let mut graph = Graph::new();
+        let mesh = graph.add_node(
+            MeshBuilder::new(BaseBuilder::new().with_name("Sphere"))
+                .with_surfaces(vec![SurfaceBuilder::new(Arc::new(RwLock::new(
+                    SurfaceSharedData::make_sphere(16, 16, 1.0),
+                )))
+                .build()])
+                .build_node(),
+        );
+        graph.update_hierarchical_data();
+
+        let levels = generate_lod_group(
+            &mut graph,
+            mesh,
+            &[
+                LodSettings {
+                    screen_size: 0.1,
+                    triangle_ratio: 0.25,
+                },
+                LodSettings {
+                    screen_size: 0.5,
+                    triangle_ratio: 0.5,
+                },
+            ],
+            75.0f32.to_radians(),
+            100.0,
+        );
+        assert_eq!(levels.len(), 2);
+        assert_eq!(graph[levels[0]].name(), "Sphere_LOD1");
+        assert_eq!(graph[levels[0]].parent(), mesh);
+
+        let triangle_count = |handle| {
+            graph[handle].as_mesh().surfaces()[0]
+                .data()
+                .read()
+                .unwrap()
+                .triangles()
+                .len()
+        };
+        assert!(triangle_count(levels[0]) > triangle_count(levels[1]));
+        assert!(triangle_count(mesh) > triangle_count(levels[0]));
+
+        let lod_group = graph[mesh].lod_group().unwrap();
+        assert_eq!(lod_group.levels.len(), 3);
+        assert_eq!(lod_group.levels[0].objects, vec![mesh]);
+        assert_eq!(lod_group.levels[0].begin(), 0.0);
+        for pair in lod_group.levels.windows(2) {
+            assert_eq!(pair[0].end(), pair[1].begin());
+        }
+        assert_eq!(lod_group.levels[2].end(), 1.0);
+    }
+
+    #[test]
+    fn generate_lods_ignores_non_finite_settings() {
+        let mut graph = Graph::new();
+        let mesh = graph.add_node(
+            MeshBuilder::new(BaseBuilder::new())
+                .with_surfaces(vec![SurfaceBuilder::new(Arc::new(RwLock::new(
+                    SurfaceSharedData::make_sphere(8, 8, 1.0),
+                )))
+                .build()])
+                .build_node(),
+        );
+        graph.update_hierarchical_data();
+
+        let levels = generate_lod_group(
+            &mut graph,
+            mesh,
+            &[
+                LodSettings {
+                    screen_size: f32::NAN,
+                    triangle_ratio: 0.5,
+                },
+                LodSettings {
+                    screen_size: 0.5,
+                    triangle_ratio: f32::INFINITY,
+                },
+                LodSettings {
+                    screen_size: 0.25,
+                    triangle_ratio: 0.5,
+                },
+            ],
+            75.0f32.to_radians(),
+            100.0,
+        );
+        assert_eq!(levels.len(), 1);
+        assert_eq!(graph[mesh].lod_group().unwrap().levels.len(), 2);
+    }
+}
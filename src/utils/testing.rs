@@ -0,0 +1,50 @@
+//! Helpers shared by unit tests.
+
+use crate::core::visitor::{Visit, Visitor};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// File in temporary directory which is removed when dropped (even if test panics). Name of
+/// the file is unique for each instance, tests of concurrent `cargo test` runs don't collide.
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    pub fn new() -> Self {
+        Self {
+            path: std::env::temp_dir().join(format!(
+                "rg3d_test_{}_{}.bin",
+                std::process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            )),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Saves `source` into a temporary file and loads it back into `dest`, both are visited in a
+/// region with given name.
+pub fn visit_round_trip<S: Visit, D: Visit>(name: &str, source: &mut S, dest: &mut D) {
+    let file = TempFile::new();
+
+    let mut visitor = Visitor::new();
+    source.visit(name, &mut visitor).unwrap();
+    visitor.save_binary(file.path()).unwrap();
+
+    let mut visitor = Visitor::load_binary(file.path()).unwrap();
+    dest.visit(name, &mut visitor).unwrap();
+}